@@ -4,6 +4,7 @@ Executor: Coordinates workers and manages pipeline execution
 Provides Executor for orchestrating the entire data processing pipeline.
 """
 
+import math
 from collections.abc import Iterator
 from typing import Any
 
@@ -204,6 +205,7 @@ class Executor:
                         data_writer=worker_writer,
                         rejected_writer=rejected_writer,
                         collect_rejected=self.collect_rejected,
+                        num_threads=max(1, math.ceil(num_cpus)),
                     )
 
                     stage_workers.append(worker)
@@ -67,8 +67,15 @@ class MetricsAggregator:
                         p95_latency=op_stats.get("p95_latency", 0.0),
                         p99_latency=op_stats.get("p99_latency", 0.0),
                         throughput=op_stats.get("throughput", 0.0),
-                        error_count=0,
-                        custom_metrics={},
+                        error_count=op_stats.get("error_count", 0),
+                        custom_metrics=(
+                            {
+                                "errors_by_status": op_stats["errors_by_status"],
+                                "error_samples": op_stats.get("error_samples", []),
+                            }
+                            if op_stats.get("errors_by_status")
+                            else {}
+                        ),
                     )
 
                     all_metrics.append(metrics)
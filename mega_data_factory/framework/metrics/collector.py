@@ -263,6 +263,9 @@ class MetricsCollector:
                 self.input_records = stats.get("input_records", 0)
                 self.output_records = stats.get("output_records", 0)
                 self.error_count = stats.get("error_count", 0)
+                if stats.get("errors_by_status"):
+                    self.custom_metrics["errors_by_status"] = stats["errors_by_status"]
+                    self.custom_metrics["error_samples"] = stats.get("error_samples", [])
 
             def add_custom_metric(self, name: str, value: Any):
                 """Add custom metric for this operator."""
@@ -13,6 +13,9 @@ import pyarrow as pa
 
 from .backend import DedupBackend
 
+# Maximum number of (status, message) error samples kept per operator
+MAX_ERROR_SAMPLES = 20
+
 
 @dataclass
 class BatchResult:
@@ -43,6 +46,9 @@ class Operator(ABC):
             "min_latency": float("inf"),
             "max_latency": 0.0,
             "_latencies": [],
+            "error_count": 0,
+            "errors_by_status": {},
+            "error_samples": [],
         }
         self._collect_rejected = collect_rejected
         self._rejected_buffer: list[dict[str, Any]] = []
@@ -116,6 +122,22 @@ class Operator(ABC):
         """Internal batch processing implementation (subclasses implement this)."""
         pass
 
+    def record_item_errors(self, statuses: list[tuple[str, str | None]]) -> None:
+        """Count per-item failures reported by a batch backend.
+
+        Args:
+            statuses: One (status, message) tuple per item, as returned by the Rust batch
+                      functions with return_status=True. Items with status "ok" are ignored.
+        """
+        for status, message in statuses:
+            if status == "ok":
+                continue
+            self._stats["error_count"] += 1
+            by_status = self._stats["errors_by_status"]
+            by_status[status] = by_status.get(status, 0) + 1
+            if len(self._stats["error_samples"]) < MAX_ERROR_SAMPLES:
+                self._stats["error_samples"].append((status, message))
+
     def get_stats(self) -> dict[str, Any]:
         """Get performance statistics for this operator.
 
@@ -132,6 +154,9 @@ class Operator(ABC):
             - p95_latency: 95th percentile latency (seconds)
             - p99_latency: 99th percentile latency (seconds)
             - throughput: Records per second
+            - error_count: Number of items reported as failed via record_item_errors
+            - errors_by_status: Failed item counts per status
+            - error_samples: Up to MAX_ERROR_SAMPLES (status, message) tuples
         """
         stats = self._stats.copy()
         input_records = stats["input_records"]
@@ -150,6 +175,9 @@ class Operator(ABC):
                 "p95_latency": 0.0,
                 "p99_latency": 0.0,
                 "throughput": 0.0,
+                "error_count": stats["error_count"],
+                "errors_by_status": dict(stats["errors_by_status"]),
+                "error_samples": list(stats["error_samples"]),
             }
 
         pass_rate = 100.0 * output_records / input_records
@@ -181,6 +209,9 @@ class Operator(ABC):
             "p95_latency": p95,
             "p99_latency": p99,
             "throughput": throughput,
+            "error_count": stats["error_count"],
+            "errors_by_status": dict(stats["errors_by_status"]),
+            "error_samples": list(stats["error_samples"]),
         }
 
     def reset_stats(self):
@@ -192,6 +223,9 @@ class Operator(ABC):
             "min_latency": float("inf"),
             "max_latency": 0.0,
             "_latencies": [],
+            "error_count": 0,
+            "errors_by_status": {},
+            "error_samples": [],
         }
 
     def get_output_schema(self) -> dict[str, pa.DataType]:
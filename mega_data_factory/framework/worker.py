@@ -4,6 +4,7 @@ Worker: Executes operators on records
 Provides RayWorker for executing operators in batch processing.
 """
 
+import contextlib
 import logging
 from dataclasses import dataclass, field
 from typing import Any
@@ -13,6 +14,14 @@ import ray
 from .base import DataWriter
 from .operator import CombinedOperator, Operator
 
+# Rust operators use a dedicated thread pool per worker when the extension is available
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _RustExecutor = getattr(_rust_module, "RustExecutor", None)
+except ImportError:
+    _RustExecutor = None
+
 
 @dataclass
 class WorkerBatchResult:
@@ -42,6 +51,7 @@ class RayWorker:
         data_writer: DataWriter | None = None,
         rejected_writer: DataWriter | None = None,
         collect_rejected: bool = False,
+        num_threads: int | None = None,
     ):
         """Initialize Ray worker.
 
@@ -51,6 +61,8 @@ class RayWorker:
             data_writer: Data writer for writing results locally (optional)
             rejected_writer: Data writer for writing rejected samples (optional)
             collect_rejected: If True, collect rejected samples for deep dive analysis
+            num_threads: Threads for Rust operators (defaults to all cores). Set this to
+                the worker's CPU allocation so Rust parallelism stays within it.
         """
         self.name = name
         self.operators = operators
@@ -62,6 +74,12 @@ class RayWorker:
         self.logger = logging.getLogger(f"RayWorker.{name}")
         self.logger.setLevel(logging.INFO)
 
+        # Rust batch operators run in this pool instead of the node-wide global pool
+        if _RustExecutor is not None and num_threads is not None:
+            self.rust_executor = _RustExecutor(num_threads=num_threads)
+        else:
+            self.rust_executor = contextlib.nullcontext()
+
         # Initialize batch counters for progress tracking
         self.batch_count = 0
         self.record_count = 0
@@ -106,7 +124,8 @@ class RayWorker:
 
         # Process batch - use rejected collection if enabled
         if self.collect_rejected:
-            batch_result = self.operator.process_batch_with_rejected(records)
+            with self.rust_executor:
+                batch_result = self.operator.process_batch_with_rejected(records)
             processed = batch_result.passed
             rejected = batch_result.rejected
 
@@ -118,7 +137,8 @@ class RayWorker:
             self.rejected_count += len(rejected)
         else:
             # Standard processing without rejected collection
-            results = self.operator.process_batch(records)
+            with self.rust_executor:
+                results = self.operator.process_batch(records)
             processed = [r for r in results if r is not None]
 
         # Update counters
@@ -171,7 +191,8 @@ class RayWorker:
             return WorkerBatchResult(passed=[], rejected=[])
 
         # Process batch with rejected collection
-        batch_result = self.operator.process_batch_with_rejected(records)
+        with self.rust_executor:
+            batch_result = self.operator.process_batch_with_rejected(records)
         processed = batch_result.passed
         rejected = batch_result.rejected
 
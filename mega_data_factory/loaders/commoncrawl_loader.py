@@ -9,7 +9,6 @@ from collections.abc import Iterator
 from typing import Any
 
 import requests
-from warcio.archiveiterator import ArchiveIterator
 
 from mega_data_factory.framework import DataLoader
 
@@ -17,9 +16,14 @@ from mega_data_factory.framework import DataLoader
 class CommonCrawlLoader(DataLoader):
     """Streaming WARC loader with Rust text extraction.
 
-    Uses Python warcio for streaming WARC parsing (yields records as they're parsed)
+    Uses the Rust WARC reader for streaming WARC parsing (yields records batch by batch)
     and Rust for fast HTML text extraction. This enables true streaming where
     downstream stages can start processing before the entire file is parsed.
+
+    Pathological pages cannot stall or break a batch: a page whose extraction panics,
+    takes longer than `extract_timeout` seconds or has more than `max_html_elements`
+    elements is dropped. A page that times out still occupies one of a fixed number of
+    extraction threads until it finishes, so set `max_html_elements` as well.
     """
 
     def __init__(
@@ -28,11 +32,15 @@ class CommonCrawlLoader(DataLoader):
         base_url: str = "https://data.commoncrawl.org/",
         cache_dir: str | None = None,
         num_files: int | None = None,
+        extract_timeout: float | None = None,
+        max_html_elements: int | None = None,
     ):
         self.crawl_id = crawl_id
         self.base_url = base_url.rstrip("/") + "/"
         self.cache_dir = cache_dir or os.path.expanduser("~/.cache/commoncrawl")
         self.num_files = num_files
+        self.extract_timeout = extract_timeout
+        self.max_html_elements = max_html_elements
         self._file_list: list[str] | None = None
 
     def get_file_list(self, max_samples: int | None = None, num_workers: int = 1) -> list[str]:
@@ -71,11 +79,12 @@ class CommonCrawlLoader(DataLoader):
     ) -> Iterator[dict[str, Any]]:
         """Load WARC files and yield records with extracted text.
 
-        TRUE STREAMING: Uses Python warcio for streaming WARC parsing, yielding
-        records as they're parsed. Rust is used only for HTML text extraction.
-        This enables downstream stages to start processing immediately.
+        TRUE STREAMING: Uses the Rust WarcTextExtractor, which parses the WARC file,
+        filters HTML responses and runs readability extraction in parallel batch by
+        batch without holding the GIL. This enables downstream stages to start
+        processing immediately.
         """
-        from mega_data_factory.rust_operators import html_extract_text
+        from mega_data_factory.rust_operators import WarcTextExtractor
 
         label = f"W{worker_id}" if worker_id is not None else "L"
         skip = checkpoint.get("records_processed", 0) if checkpoint else 0
@@ -87,46 +96,16 @@ class CommonCrawlLoader(DataLoader):
             local_path = self._download(warc_path)
             print(f"[{label}] File ready, opening: {local_path.split('/')[-1]}")
 
-            # Stream WARC records using Python warcio - yields as it parses!
-            with open(local_path, "rb") as f:
-                print(f"[{label}] Starting ArchiveIterator...")
-                record_count = 0
-                for record in ArchiveIterator(f):
-                    record_count += 1
-                    if record_count == 1:
-                        print(f"[{label}] First record received from ArchiveIterator")
-                    # Skip non-response records
-                    if record.rec_type != "response":
-                        continue
-
-                    # Get URL and date from headers
-                    url = record.rec_headers.get_header("WARC-Target-URI", "")
-                    warc_date = record.rec_headers.get_header("WARC-Date", "")
-
-                    # Check content type
-                    content_type = record.http_headers.get_header("Content-Type", "") if record.http_headers else ""
-                    if "text/html" not in content_type.lower():
-                        continue
-
-                    # Read HTML content
-                    try:
-                        html_content = record.content_stream().read().decode("utf-8", errors="ignore")
-                    except Exception:
-                        continue
-
-                    if not html_content or len(html_content) < 100:
-                        continue
-
-                    count += 1
-                    if count <= skip:
-                        continue
-
-                    # 🦀 Rust: Extract text from HTML (fast!)
-                    result = html_extract_text(html_content)
-                    if result is None:
-                        continue
-
-                    title, text, text_length = result
+            # 🦀 Rust: Parse WARC + extract text from HTML in parallel (fast!)
+            print(f"[{label}] Starting WarcTextExtractor...")
+            extractor = WarcTextExtractor(
+                local_path,
+                skip_records=max(skip - count, 0),
+                timeout=self.extract_timeout,
+                max_elements=self.max_html_elements,
+            )
+            for batch in extractor:
+                for record in batch:
                     yielded += 1
 
                     if yielded == 1:
@@ -135,12 +114,9 @@ class CommonCrawlLoader(DataLoader):
                     yield {
                         "crawl_id": self.crawl_id,
                         "warc_path": warc_path,
-                        "url": url,
-                        "warc_date": warc_date,
-                        "title": title,
-                        "text": text,
-                        "text_length": text_length,
+                        **record,
                     }
+            count += extractor.records_parsed
 
         print(f"[{label}] Streaming: {count} HTML records parsed, {yielded} yielded")
 
@@ -16,10 +16,12 @@ from mega_data_factory.framework import Deduplicator
 # Try to load Rust phash batch function (faster)
 RUST_PHASH_AVAILABLE = False
 _compute_phash_batch_rust = None
+_DecodeLimits = None
 
 try:
-    from mega_data_factory import rust_accelerated_ops as _rust_module  # type: ignore
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
 
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
     _compute_phash_batch_rust = getattr(_rust_module, "image_compute_phash_batch", None)
     if _compute_phash_batch_rust is not None:
         RUST_PHASH_AVAILABLE = True
@@ -31,11 +33,25 @@ class ImagePhashDeduplicator(Deduplicator):
     """Deduplicates records based on perceptual hash.
 
     Auto-uses Rust backend (faster) if available, otherwise falls back to imagehash.
+    Both backends produce the same `imagehash.phash` hex strings, so keys are stable
+    regardless of which one ran.
     """
 
-    def __init__(self, hash_size: int = 16):
+    def __init__(self, hash_size: int = 16, decode_limits: dict[str, Any] | None = None):
+        """Initialize phash deduplicator.
+
+        Args:
+            hash_size: Perceptual hash size.
+            decode_limits: Limits applied by the Rust backend before decoding each image,
+                e.g. {"max_pixels": 100_000_000, "max_input_bytes": 50_000_000,
+                "allowed_formats": ["jpeg", "png", "webp"]}. Keys: max_pixels,
+                max_alloc_bytes, max_input_bytes, allowed_formats.
+                Rejected images fall back to their record id as key.
+        """
         super().__init__()
         self.hash_size = hash_size
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
 
     def get_dedup_keys_batch(self, records: list[dict[str, Any]]) -> list[str]:
         """Extract perceptual hashes from a batch of records."""
@@ -54,7 +70,13 @@ class ImagePhashDeduplicator(Deduplicator):
         computed_phashes = []
         if image_bytes_list and RUST_PHASH_AVAILABLE and _compute_phash_batch_rust:
             try:
-                computed_phashes = _compute_phash_batch_rust(image_bytes_list, self.hash_size)
+                computed_phashes = _compute_phash_batch_rust(
+                    image_bytes_list,
+                    self.hash_size,
+                    algorithm="phash",
+                    encoding="hex",
+                    limits=self._rust_limits,
+                )
             except Exception:
                 computed_phashes = []
 
@@ -11,8 +11,17 @@ from mega_data_factory.framework import Filter
 # Import field name constants from refiners
 from mega_data_factory.operators.refiners.image_metadata import FIELD_HEIGHT, FIELD_WIDTH
 from mega_data_factory.operators.refiners.image_technical_quality import (
+    FIELD_BORDER_FRACTION,
+    FIELD_COLORFULNESS,
     FIELD_COMPRESSION_ARTIFACTS,
+    FIELD_HIGHLIGHT_CLIP_FRACTION,
     FIELD_INFORMATION_ENTROPY,
+    FIELD_LAPLACIAN_VARIANCE,
+    FIELD_NOISE_SIGMA,
+    FIELD_PLACEHOLDER_CATEGORY,
+    FIELD_PLACEHOLDER_CONFIDENCE,
+    FIELD_RMS_CONTRAST,
+    FIELD_SHADOW_CLIP_FRACTION,
 )
 
 
@@ -22,6 +31,18 @@ class ImageQualityFilter(Filter):
     Uses fields from ImageMetadataRefiner and ImageTechnicalQualityRefiner:
     - image_width, image_height
     - image_compression_artifacts, image_information_entropy
+    - image_laplacian_variance (only with min_laplacian_variance)
+    - image_noise_sigma (only with max_noise)
+    - image_rms_contrast, image_highlight_clip_fraction, image_shadow_clip_fraction,
+      image_colorfulness (only with the matching threshold)
+    - image_placeholder_category, image_placeholder_confidence (only with
+      reject_placeholder_categories)
+    - image_border_fraction (only with max_border_fraction)
+
+    Records without the compression artifact and entropy fields are rejected, so a
+    skipped or failed ImageTechnicalQualityRefiner never lets images through. With
+    require_quality_fields=False they are kept instead, so the filter can run right
+    after ImageMetadataRefiner to reject undersized images before any image is decoded.
     """
 
     def __init__(
@@ -30,12 +51,43 @@ class ImageQualityFilter(Filter):
         min_height: int = 256,
         max_compression_artifacts: float = 0.8,
         min_information_entropy: float = 3.0,
+        min_laplacian_variance: float | None = None,
+        max_noise: float | None = None,
+        min_rms_contrast: float | None = None,
+        max_highlight_clip_fraction: float | None = None,
+        max_shadow_clip_fraction: float | None = None,
+        min_colorfulness: float | None = None,
+        reject_placeholder_categories: list[str] | None = None,
+        min_placeholder_confidence: float = 0.5,
+        max_border_fraction: float | None = None,
+        require_quality_fields: bool = True,
     ):
         super().__init__()
         self.min_width = min_width
         self.min_height = min_height
         self.max_compression_artifacts = max_compression_artifacts
         self.min_information_entropy = min_information_entropy
+        self.min_laplacian_variance = min_laplacian_variance
+        self.max_noise = max_noise
+        self.min_rms_contrast = min_rms_contrast
+        self.max_highlight_clip_fraction = max_highlight_clip_fraction
+        self.max_shadow_clip_fraction = max_shadow_clip_fraction
+        self.min_colorfulness = min_colorfulness
+        self.reject_placeholder_categories = set(reject_placeholder_categories or [])
+        self.min_placeholder_confidence = min_placeholder_confidence
+        self.max_border_fraction = max_border_fraction
+        self.require_quality_fields = require_quality_fields
+
+        # Optional (field, minimum, maximum) bounds, checked only when set and the field is present
+        self.optional_bounds = [
+            (FIELD_LAPLACIAN_VARIANCE, min_laplacian_variance, None),
+            (FIELD_NOISE_SIGMA, None, max_noise),
+            (FIELD_RMS_CONTRAST, min_rms_contrast, None),
+            (FIELD_HIGHLIGHT_CLIP_FRACTION, None, max_highlight_clip_fraction),
+            (FIELD_SHADOW_CLIP_FRACTION, None, max_shadow_clip_fraction),
+            (FIELD_COLORFULNESS, min_colorfulness, None),
+            (FIELD_BORDER_FRACTION, None, max_border_fraction),
+        ]
 
     def should_keep_batch(self, records: list[dict[str, Any]]) -> list[bool]:
         """Determine which records meet quality criteria."""
@@ -43,14 +95,49 @@ class ImageQualityFilter(Filter):
         for record in records:
             width = record.get(FIELD_WIDTH, 0)
             height = record.get(FIELD_HEIGHT, 0)
+            if width < self.min_width or height < self.min_height:
+                results.append(False)
+                continue
+
+            if FIELD_COMPRESSION_ARTIFACTS not in record and FIELD_INFORMATION_ENTROPY not in record:
+                # Size-only filtering before ImageTechnicalQualityRefiner has run
+                results.append(not self.require_quality_fields)
+                continue
+
             compression_artifacts = record.get(FIELD_COMPRESSION_ARTIFACTS, 0.0)
             information_entropy = record.get(FIELD_INFORMATION_ENTROPY, 0.0)
 
+            # Quality metrics are None when the image failed to decode
+            if compression_artifacts is None or information_entropy is None:
+                results.append(False)
+                continue
+
             keep = (
-                width >= self.min_width
-                and height >= self.min_height
-                and compression_artifacts <= self.max_compression_artifacts
+                compression_artifacts <= self.max_compression_artifacts
                 and information_entropy >= self.min_information_entropy
             )
-            results.append(keep)
+            results.append(keep and self._within_optional_bounds(record) and not self._is_placeholder(record))
         return results
+
+    def _is_placeholder(self, record: dict[str, Any]) -> bool:
+        """Whether the record is a rejected placeholder category with enough confidence."""
+        if not self.reject_placeholder_categories or FIELD_PLACEHOLDER_CATEGORY not in record:
+            return False
+        return (
+            record[FIELD_PLACEHOLDER_CATEGORY] in self.reject_placeholder_categories
+            and (record.get(FIELD_PLACEHOLDER_CONFIDENCE) or 0.0) >= self.min_placeholder_confidence
+        )
+
+    def _within_optional_bounds(self, record: dict[str, Any]) -> bool:
+        """Check the optional thresholds whose fields the record has."""
+        for field, minimum, maximum in self.optional_bounds:
+            if (minimum is None and maximum is None) or field not in record:
+                continue
+            value = record[field]
+            if value is None:
+                return False
+            if minimum is not None and value < minimum:
+                return False
+            if maximum is not None and value > maximum:
+                return False
+        return True
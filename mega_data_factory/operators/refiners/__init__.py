@@ -7,19 +7,27 @@ def _register_image_refiners():
     """Lazy register image refiners that depend on PIL/torch."""
     from .image_aesthetic_quality import ImageAestheticQualityRefiner
     from .image_aigc_detector import ImageAIGCDetectorRefiner
+    from .image_aspect_bucket import ImageAspectBucketRefiner
+    from .image_border_crop import ImageBorderCropRefiner
     from .image_clip_embedding import ImageClipEmbeddingRefiner
+    from .image_embedded_metadata import ImageEmbeddedMetadataRefiner
     from .image_metadata import ImageMetadataRefiner
     from .image_siglip_embedding import ImageSigLIPEmbeddingRefiner
     from .image_technical_quality import ImageTechnicalQualityRefiner
+    from .image_transcode import ImageTranscodeRefiner
     from .image_visual_degradations import ImageVisualDegradationsRefiner
 
     OperatorRegistry.register("ImageMetadataRefiner", ImageMetadataRefiner)
+    OperatorRegistry.register("ImageEmbeddedMetadataRefiner", ImageEmbeddedMetadataRefiner)
     OperatorRegistry.register("ImageTechnicalQualityRefiner", ImageTechnicalQualityRefiner)
+    OperatorRegistry.register("ImageBorderCropRefiner", ImageBorderCropRefiner)
+    OperatorRegistry.register("ImageAspectBucketRefiner", ImageAspectBucketRefiner)
     OperatorRegistry.register("ImageVisualDegradationsRefiner", ImageVisualDegradationsRefiner)
     OperatorRegistry.register("ImageClipEmbeddingRefiner", ImageClipEmbeddingRefiner)
     OperatorRegistry.register("ImageSigLIPEmbeddingRefiner", ImageSigLIPEmbeddingRefiner)
     OperatorRegistry.register("ImageAestheticQualityRefiner", ImageAestheticQualityRefiner)
     OperatorRegistry.register("ImageAIGCDetectorRefiner", ImageAIGCDetectorRefiner)
+    OperatorRegistry.register("ImageTranscodeRefiner", ImageTranscodeRefiner)
 
 
 try:
@@ -0,0 +1,307 @@
+"""
+Image Aspect Bucket Refiner
+
+Assigns images to aspect-ratio buckets of a fixed pixel budget, as used for multi-resolution
+training, and plans the resize and center crop that fit each image to its bucket.
+This is a Refiner that enriches records with bucket fields and can replace the image with
+its resized, cropped version.
+
+Automatically uses the Rust backend if available. Without `encode`, bucketing reuses the
+`ImageMetadataRefiner` fields when present, so no image is decoded.
+"""
+
+import math
+from io import BytesIO
+from typing import Any
+
+import pyarrow as pa
+from PIL import Image, ImageOps
+
+from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_metadata import FIELD_HEIGHT, FIELD_ORIENTATION, FIELD_WIDTH
+from mega_data_factory.operators.refiners.image_technical_quality import output_format_for_source
+
+# Field name constants
+FIELD_BUCKET_WIDTH = "image_bucket_width"
+FIELD_BUCKET_HEIGHT = "image_bucket_height"
+FIELD_BUCKET_RESIZE_WIDTH = "image_bucket_resize_width"
+FIELD_BUCKET_RESIZE_HEIGHT = "image_bucket_resize_height"
+FIELD_BUCKET_CROP_BOX = "image_bucket_crop_box"
+FIELD_BUCKET_SCALE = "image_bucket_scale"
+
+OUTPUT_FIELDS = [
+    FIELD_BUCKET_WIDTH,
+    FIELD_BUCKET_HEIGHT,
+    FIELD_BUCKET_RESIZE_WIDTH,
+    FIELD_BUCKET_RESIZE_HEIGHT,
+    FIELD_BUCKET_CROP_BOX,
+    FIELD_BUCKET_SCALE,
+]
+
+# Defaults (match the Rust backend)
+DEFAULT_TARGET_AREA = 1024 * 1024
+DEFAULT_STEP = 64
+DEFAULT_MAX_ASPECT_RATIO = 4.0
+DEFAULT_QUALITY = 90
+MAX_BUCKET_WIDTHS = 1 << 16  # most candidate widths tried when generating buckets
+
+# Pillow resampling filter and save format for each name
+_PIL_RESIZE_FILTERS = {
+    "nearest": Image.Resampling.NEAREST,
+    "bilinear": Image.Resampling.BILINEAR,
+    "bicubic": Image.Resampling.BICUBIC,
+    "lanczos": Image.Resampling.LANCZOS,
+}
+_PIL_SAVE_FORMATS = {"jpeg": "JPEG", "png": "PNG", "webp": "WEBP"}
+
+# Try to load Rust extension (auto-acceleration)
+RUST_BACKEND_AVAILABLE = False
+_plan_buckets_rust = None
+_bucket_batch_rust = None
+_DecodeLimits = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
+    _plan_buckets_rust = getattr(_rust_module, "image_plan_buckets", None)
+    _bucket_batch_rust = getattr(_rust_module, "image_bucket_batch", None)
+    if _plan_buckets_rust is not None and _bucket_batch_rust is not None:
+        RUST_BACKEND_AVAILABLE = True
+except ImportError:
+    pass
+
+
+def make_buckets(
+    target_area: int = DEFAULT_TARGET_AREA,
+    step: int = DEFAULT_STEP,
+    ratios: list[float] | None = None,
+    max_aspect_ratio: float = DEFAULT_MAX_ASPECT_RATIO,
+) -> list[tuple[int, int]]:
+    """Build (width, height) buckets (Python version of the Rust backend's bucket spec)."""
+    if step <= 0 or step * step > target_area:
+        raise ValueError("step must be positive and step * step must not exceed target_area")
+    if not max_aspect_ratio >= 1.0:
+        raise ValueError("max_aspect_ratio must be at least 1")
+    if ratios is not None:
+        if not ratios or any(not (math.isfinite(r) and r > 0) for r in ratios):
+            raise ValueError("ratios must be a non-empty list of positive numbers")
+
+        def to_step(side: float) -> int:
+            return max(1, math.floor(side / step + 0.5)) * step
+
+        buckets = {(to_step(math.sqrt(target_area * r)), to_step(math.sqrt(target_area / r))) for r in ratios}
+    else:
+        # A bucket of width w is at least w^2 / target_area wide
+        max_widths = target_area // step // step
+        widest = math.sqrt(target_area * max_aspect_ratio) / step
+        if widest < max_widths:
+            max_widths = math.floor(widest) + 1
+        if max_widths > MAX_BUCKET_WIDTHS:
+            raise ValueError(
+                f"target_area, step and max_aspect_ratio allow {max_widths} bucket widths "
+                f"(at most {MAX_BUCKET_WIDTHS}); increase step or lower max_aspect_ratio"
+            )
+        buckets = set()
+        for width in range(step, max_widths * step + 1, step):
+            height = target_area // width // step * step
+            if height and max(width / height, height / width) <= max_aspect_ratio:
+                buckets.add((width, height))
+    if not buckets:
+        raise ValueError("no bucket fits target_area, step and max_aspect_ratio")
+    return sorted(buckets)
+
+
+def plan_bucket(buckets: list[tuple[int, int]], width: int, height: int) -> dict[str, Any]:
+    """Assign a size to the closest-ratio bucket and plan its cover resize and center crop."""
+    log_ratio = math.log(width / height)
+    bucket = min(buckets, key=lambda b: abs(math.log(b[0] / b[1]) - log_ratio))
+    scale = max(bucket[0] / width, bucket[1] / height)
+    resized = (max(bucket[0], math.floor(width * scale + 0.5)), max(bucket[1], math.floor(height * scale + 0.5)))
+    left = (resized[0] - bucket[0]) // 2
+    top = (resized[1] - bucket[1]) // 2
+    return {
+        "bucket": bucket,
+        "resize": resized,
+        "crop_box": (left, top, left + bucket[0], top + bucket[1]),
+        "scale": scale,
+    }
+
+
+class ImageAspectBucketRefiner(Refiner):
+    """Refiner that assigns images to aspect-ratio buckets.
+
+    Output fields:
+    - image_bucket_width, image_bucket_height: Size of the assigned bucket
+    - image_bucket_resize_width, image_bucket_resize_height: Aspect-preserving size that
+      covers the bucket
+    - image_bucket_crop_box: [left, top, right, bottom] of the centered bucket-sized crop
+      within the resized image
+    - image_bucket_scale: Resize factor (> 1 means the image is upscaled)
+
+    Sizes are taken after applying the EXIF orientation. With encode=True, the image
+    bytes are replaced by the resized and cropped image and `image_width` /
+    `image_height` (if present) are updated to the bucket size. Images that cannot be
+    read get None fields and are counted in the operator's error stats.
+    """
+
+    def __init__(
+        self,
+        target_area: int = DEFAULT_TARGET_AREA,
+        step: int = DEFAULT_STEP,
+        ratios: list[float] | None = None,
+        max_aspect_ratio: float = DEFAULT_MAX_ASPECT_RATIO,
+        encode: bool = False,
+        output_format: str | None = None,
+        quality: int = DEFAULT_QUALITY,
+        resize_filter: str = "bicubic",
+        decode_limits: dict[str, Any] | None = None,
+    ):
+        """Initialize aspect bucket refiner.
+
+        Args:
+            target_area: Pixel budget of each bucket (e.g. 1024 * 1024).
+            step: Bucket sides are multiples of this many pixels.
+            ratios: Bucket aspect ratios (width / height); None generates one bucket per
+                width that is a multiple of `step`, up to `max_aspect_ratio`.
+            max_aspect_ratio: Widest (and, inverted, tallest) generated bucket.
+            encode: Replace the image with its resized, cropped and re-encoded version.
+            output_format: Format of re-encoded images ("jpeg", "png" or "webp"); None writes
+                JPEG and opaque WebP sources as JPEG and everything else as PNG.
+            quality: JPEG quality of re-encoded images (1-100).
+            resize_filter: "bicubic", "bilinear", "lanczos" or "nearest".
+            decode_limits: Limits applied by the Rust backend before decoding each image
+                (same keys as ImageTechnicalQualityRefiner).
+        """
+        super().__init__()
+        if output_format is not None and output_format.lower() not in _PIL_SAVE_FORMATS:
+            raise ValueError(f"unsupported output_format: {output_format}")
+        if resize_filter not in _PIL_RESIZE_FILTERS:
+            raise ValueError(f"unknown resize_filter: {resize_filter}")
+        self.target_area = target_area
+        self.step = step
+        self.ratios = ratios
+        self.max_aspect_ratio = max_aspect_ratio
+        self.encode = encode
+        self.output_format = output_format.lower() if output_format else None
+        self.quality = quality
+        self.resize_filter = resize_filter
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+        self.buckets = make_buckets(target_area, step, ratios, max_aspect_ratio)
+
+    def _bucket_spec(self) -> dict[str, Any]:
+        return {
+            "target_area": self.target_area,
+            "step": self.step,
+            "ratios": self.ratios,
+            "max_aspect_ratio": self.max_aspect_ratio,
+        }
+
+    def refine_batch(self, records: list[dict[str, Any]]) -> None:
+        """Assign a batch of records to buckets (inplace)."""
+        if not records:
+            return
+
+        if RUST_BACKEND_AVAILABLE:
+            try:
+                if not self.encode and all(FIELD_WIDTH in r and FIELD_HEIGHT in r for r in records):
+                    sizes = [self._oriented_size(record) for record in records]
+                    plans = _plan_buckets_rust(sizes, **self._bucket_spec())
+                    for record, plan in zip(records, plans, strict=False):
+                        self._apply(record, plan, None)
+                    return
+
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in records
+                ]
+                results, statuses = _bucket_batch_rust(
+                    image_bytes_list,
+                    return_status=True,
+                    encode=self.encode,
+                    output_format=self.output_format,
+                    quality=self.quality,
+                    resize_filter=self.resize_filter,
+                    **self._bucket_spec(),
+                    limits=self._rust_limits,
+                )
+                self.record_item_errors(statuses)
+                for record, result in zip(records, results, strict=False):
+                    self._apply(record, result, result.get("image") if result else None)
+                return
+            except Exception:
+                pass  # Fallback to Python
+
+        for record in records:
+            if not self.encode and FIELD_WIDTH in record and FIELD_HEIGHT in record:
+                width, height = self._oriented_size(record)
+                plan = plan_bucket(self.buckets, width, height) if width and height else None
+                self._apply(record, plan, None)
+                continue
+
+            img_obj = record.get("image", {})
+            if not (isinstance(img_obj, dict) and "bytes" in img_obj):
+                self._apply(record, None, None)
+                continue
+            try:
+                img = ImageOps.exif_transpose(Image.open(BytesIO(img_obj["bytes"])))
+                plan = plan_bucket(self.buckets, *img.size)
+                encoded = self._encode(img, plan) if self.encode else None
+                self._apply(record, plan, encoded)
+            except Exception as e:
+                self._apply(record, None, None)
+                self.record_item_errors([("decode_error", str(e))])
+
+    @staticmethod
+    def _oriented_size(record: dict[str, Any]) -> tuple[int, int]:
+        """Width and height from ImageMetadataRefiner fields, after the EXIF orientation."""
+        width, height = record.get(FIELD_WIDTH) or 0, record.get(FIELD_HEIGHT) or 0
+        if (record.get(FIELD_ORIENTATION) or 1) >= 5:
+            return height, width
+        return width, height
+
+    @staticmethod
+    def _apply(record: dict[str, Any], plan: dict[str, Any] | None, encoded: bytes | None) -> None:
+        """Store a bucket plan and swap in the re-encoded image."""
+        if plan is None:
+            for field in OUTPUT_FIELDS:
+                record[field] = None
+            return
+        record[FIELD_BUCKET_WIDTH], record[FIELD_BUCKET_HEIGHT] = plan["bucket"]
+        record[FIELD_BUCKET_RESIZE_WIDTH], record[FIELD_BUCKET_RESIZE_HEIGHT] = plan["resize"]
+        record[FIELD_BUCKET_CROP_BOX] = list(plan["crop_box"])
+        record[FIELD_BUCKET_SCALE] = plan["scale"]
+        if encoded is None:
+            return
+        record["image"]["bytes"] = encoded
+        if FIELD_WIDTH in record:
+            record[FIELD_WIDTH], record[FIELD_HEIGHT] = plan["bucket"]
+        if FIELD_ORIENTATION in record:
+            record[FIELD_ORIENTATION] = 1  # re-encoded upright, without EXIF
+
+    def _encode(self, img: Image.Image, plan: dict[str, Any]) -> bytes:
+        """Resize, crop and re-encode an upright image (Python fallback)."""
+        output_format = self.output_format
+        if output_format is None:
+            output_format = output_format_for_source(img.format, img)
+        bucketed = img.resize(plan["resize"], _PIL_RESIZE_FILTERS[self.resize_filter]).crop(plan["crop_box"])
+        if output_format == "jpeg" and bucketed.mode not in ("RGB", "L"):
+            bucketed = bucketed.convert("RGB")
+        buffer = BytesIO()
+        if output_format == "webp":
+            bucketed.save(buffer, format="WEBP", lossless=True)
+        else:
+            bucketed.save(buffer, format=_PIL_SAVE_FORMATS[output_format], quality=self.quality)
+        return buffer.getvalue()
+
+    def get_output_schema(self) -> dict[str, pa.DataType]:
+        """Return output schema for new fields added by this refiner."""
+        return {
+            FIELD_BUCKET_WIDTH: pa.int32(),
+            FIELD_BUCKET_HEIGHT: pa.int32(),
+            FIELD_BUCKET_RESIZE_WIDTH: pa.int32(),
+            FIELD_BUCKET_RESIZE_HEIGHT: pa.int32(),
+            FIELD_BUCKET_CROP_BOX: pa.list_(pa.int32()),
+            FIELD_BUCKET_SCALE: pa.float32(),
+        }
@@ -0,0 +1,192 @@
+"""
+Image Border Crop Refiner
+
+Detects uniform borders (letterboxing, padding) and replaces the image with its content
+region, so downstream stages see the cropped image.
+This is a Refiner that modifies the image bytes and records where the content was.
+
+Automatically uses the Rust backend if available, which detects, crops and re-encodes
+each batch in parallel.
+"""
+
+from io import BytesIO
+from typing import Any
+
+import pyarrow as pa
+from PIL import Image
+
+from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_metadata import FIELD_HEIGHT, FIELD_WIDTH
+from mega_data_factory.operators.refiners.image_technical_quality import (
+    BORDER_MIN_WIDTH,
+    BORDER_TOLERANCE,
+    FIELD_BORDER_FRACTION,
+    detect_borders,
+    output_format_for_source,
+)
+
+# Field name constants
+FIELD_CONTENT_BOX = "image_content_box"
+
+# Default JPEG / WebP quality of the re-encoded crop (matches the Rust backend)
+DEFAULT_CROP_QUALITY = 90
+
+# Pillow save format for each output format name
+_PIL_SAVE_FORMATS = {"jpeg": "JPEG", "png": "PNG", "webp": "WEBP"}
+
+# Try to load Rust extension (auto-acceleration)
+RUST_BACKEND_AVAILABLE = False
+_detect_borders_batch_rust = None
+_DecodeLimits = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
+    _detect_borders_batch_rust = getattr(_rust_module, "image_detect_borders_batch", None)
+    if _detect_borders_batch_rust is not None:
+        RUST_BACKEND_AVAILABLE = True
+except ImportError:
+    pass
+
+
+class ImageBorderCropRefiner(Refiner):
+    """Refiner that crops uniform borders off images.
+
+    Output fields:
+    - image_border_fraction: Fraction of the original image area in borders (0-1)
+    - image_content_box: [left, top, right, bottom] of the content in the original image
+      (right/bottom exclusive), or None if the whole image is uniform
+
+    Images with a border get `record["image"]["bytes"]` replaced by the re-encoded
+    content; `image_width` / `image_height` are updated when present. Images without a
+    border, uniform images and images that fail to decode are left unchanged (failures
+    get None fields and are counted in the operator's error stats).
+    """
+
+    def __init__(
+        self,
+        tolerance: int = BORDER_TOLERANCE,
+        min_border: int = BORDER_MIN_WIDTH,
+        any_color: bool = False,
+        output_format: str | None = None,
+        quality: int = DEFAULT_CROP_QUALITY,
+        decode_limits: dict[str, Any] | None = None,
+    ):
+        """Initialize border crop refiner.
+
+        Args:
+            tolerance: Per-channel deviation from the border color still counted as border.
+            min_border: Minimum border width in pixels; thinner borders are ignored.
+            any_color: Accept uniform borders of any color, not just black, white or
+                transparent (may also crop flat skies or studio backdrops).
+            output_format: Format of the cropped image ("jpeg", "png" or "webp"); None writes
+                JPEG and opaque WebP sources as JPEG and everything else as PNG.
+            quality: JPEG quality of the cropped image (1-100).
+            decode_limits: Limits applied by the Rust backend before decoding each image
+                (same keys as ImageTechnicalQualityRefiner).
+        """
+        super().__init__()
+        if output_format is not None and output_format.lower() not in _PIL_SAVE_FORMATS:
+            raise ValueError(f"unsupported output_format: {output_format}")
+        self.tolerance = tolerance
+        self.min_border = min_border
+        self.any_color = any_color
+        self.output_format = output_format.lower() if output_format else None
+        self.quality = quality
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+
+    def refine_batch(self, records: list[dict[str, Any]]) -> None:
+        """Detect and crop borders for a batch of records (inplace)."""
+        if not records:
+            return
+
+        if RUST_BACKEND_AVAILABLE and _detect_borders_batch_rust:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in records
+                ]
+                results, statuses = _detect_borders_batch_rust(
+                    image_bytes_list,
+                    return_status=True,
+                    tolerance=self.tolerance,
+                    min_border=self.min_border,
+                    any_color=self.any_color,
+                    crop=True,
+                    crop_format=self.output_format,
+                    crop_quality=self.quality,
+                    limits=self._rust_limits,
+                )
+                self.record_item_errors(statuses)
+                for record, result in zip(records, results, strict=False):
+                    if result is None:
+                        self._set_error(record)
+                        continue
+                    self._apply(record, result["content_box"], result["border_fraction"], result["cropped"])
+                return
+            except Exception:
+                pass  # Fallback to Python
+
+        for record in records:
+            img_obj = record.get("image", {})
+            if not (isinstance(img_obj, dict) and "bytes" in img_obj):
+                self._set_error(record)
+                continue
+            try:
+                img = Image.open(BytesIO(img_obj["bytes"]))
+                content_box, border_fraction = detect_borders(img, self.tolerance, self.min_border, self.any_color)
+                cropped = None
+                if content_box is not None and border_fraction > 0.0:
+                    cropped = self._encode(img.crop(content_box), img.format)
+                self._apply(record, content_box, border_fraction, cropped)
+            except Exception as e:
+                self._set_error(record)
+                self.record_item_errors([("decode_error", str(e))])
+
+    @staticmethod
+    def _apply(
+        record: dict[str, Any],
+        content_box: tuple[int, int, int, int] | None,
+        border_fraction: float,
+        cropped: bytes | None,
+    ) -> None:
+        """Store the detection result and swap in the cropped image."""
+        record[FIELD_BORDER_FRACTION] = border_fraction
+        record[FIELD_CONTENT_BOX] = list(content_box) if content_box is not None else None
+        if cropped is None:
+            return
+        record["image"]["bytes"] = cropped
+        left, top, right, bottom = content_box
+        if FIELD_WIDTH in record:
+            record[FIELD_WIDTH] = right - left
+        if FIELD_HEIGHT in record:
+            record[FIELD_HEIGHT] = bottom - top
+
+    def _encode(self, img: Image.Image, source_format: str | None) -> bytes:
+        """Re-encode a cropped image (Python fallback)."""
+        output_format = self.output_format
+        if output_format is None:
+            output_format = output_format_for_source(source_format, img)
+        if output_format == "jpeg" and img.mode not in ("RGB", "L"):
+            img = img.convert("RGB")
+        buffer = BytesIO()
+        if output_format == "webp":
+            img.save(buffer, format="WEBP", lossless=True)
+        else:
+            img.save(buffer, format=_PIL_SAVE_FORMATS[output_format], quality=self.quality)
+        return buffer.getvalue()
+
+    @staticmethod
+    def _set_error(record: dict[str, Any]) -> None:
+        """Fill border fields for an image that could not be read."""
+        record[FIELD_BORDER_FRACTION] = None
+        record[FIELD_CONTENT_BOX] = None
+
+    def get_output_schema(self) -> dict[str, pa.DataType]:
+        """Return output schema for new fields added by this refiner."""
+        return {
+            FIELD_BORDER_FRACTION: pa.float32(),
+            FIELD_CONTENT_BOX: pa.list_(pa.int32()),
+        }
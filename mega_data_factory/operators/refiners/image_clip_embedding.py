@@ -3,6 +3,10 @@ Image CLIP Embedding Refiner
 
 Extracts image embeddings using OpenCLIP models.
 This refiner enriches records with CLIP embedding features for semantic analysis.
+
+Images are preprocessed by the Rust backend if available (and the model's transform
+is a plain resize / center-crop / normalize), otherwise by OpenCLIP's transform in
+Python threads.
 """
 
 from concurrent.futures import ThreadPoolExecutor
@@ -13,8 +17,67 @@ import open_clip
 import pyarrow as pa
 import torch
 from PIL import Image
+from torchvision.transforms import CenterCrop, InterpolationMode, Normalize, Resize, ToTensor
 
 from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_technical_quality import convert_to_srgb, flatten_alpha
+
+# Try to load Rust extension (auto-acceleration)
+_preprocess_batch_rust = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _preprocess_batch_rust = getattr(_rust_module, "image_preprocess_batch", None)
+except ImportError:
+    pass
+
+# Rust resize filter for each torchvision interpolation mode
+_RUST_RESIZE_FILTERS = {
+    InterpolationMode.BICUBIC: "bicubic",
+    InterpolationMode.BILINEAR: "bilinear",
+    InterpolationMode.LANCZOS: "lanczos",
+    InterpolationMode.NEAREST: "nearest",
+}
+
+
+def _rust_preprocess_config(preprocess: Any) -> dict[str, Any] | None:
+    """Arguments that make the Rust image_preprocess_batch match an OpenCLIP transform.
+
+    Returns None when the transform does anything else than RGB conversion, resize,
+    center crop, ToTensor and Normalize, so the Python transform is used instead.
+    """
+    resize_size = crop_size = None
+    config: dict[str, Any] = {}
+    for transform in getattr(preprocess, "transforms", []):
+        if isinstance(transform, Resize):
+            if transform.interpolation not in _RUST_RESIZE_FILTERS:
+                return None
+            config["resize_filter"] = _RUST_RESIZE_FILTERS[transform.interpolation]
+            resize_size = transform.size
+        elif isinstance(transform, CenterCrop):
+            crop_size = tuple(transform.size)
+        elif isinstance(transform, Normalize):
+            config["mean"] = list(transform.mean)
+            config["std"] = list(transform.std)
+        elif not (isinstance(transform, ToTensor) or getattr(transform, "__name__", "") == "_convert_to_rgb"):
+            return None
+
+    if isinstance(resize_size, int) or (isinstance(resize_size, list | tuple) and len(resize_size) == 1):
+        # Shortest side resized to `side`, then a square center crop of the same size
+        side = resize_size if isinstance(resize_size, int) else resize_size[0]
+        if crop_size != (side, side):
+            return None
+        config.update(size=side, resize_mode="center_crop")
+    elif isinstance(resize_size, list | tuple) and len(resize_size) == 2:
+        if crop_size not in (None, tuple(resize_size)):
+            return None
+        config.update(size=tuple(resize_size), resize_mode="squash")
+    else:
+        return None
+    if "mean" not in config:
+        return None
+    return config
 
 
 class ImageClipEmbeddingRefiner(Refiner):
@@ -103,6 +166,9 @@ class ImageClipEmbeddingRefiner(Refiner):
         # Thread pool initialized lazily (can't pickle ThreadPoolExecutor for Ray)
         self._executor = None
 
+        # Rust preprocessing arguments (None = use the OpenCLIP transform)
+        self.rust_preprocess = _rust_preprocess_config(self.preprocess) if _preprocess_batch_rust else None
+
         print(f"Model loaded (visual tower only). Embedding dim: {self.embedding_dim}")
         print(f"Output field: {self.feature_field_name}")
 
@@ -111,14 +177,51 @@ class ImageClipEmbeddingRefiner(Refiner):
         img_obj = record.get("image", {})
         if isinstance(img_obj, dict) and "bytes" in img_obj:
             try:
-                img = Image.open(BytesIO(img_obj["bytes"]))
-                if img.mode != "RGB":
-                    img = img.convert("RGB")
+                img = flatten_alpha(convert_to_srgb(Image.open(BytesIO(img_obj["bytes"]))))
                 return self.preprocess(img)
             except Exception:
                 pass
         return None
 
+    def _preprocess_batch(self, batch_records: list[dict[str, Any]]) -> tuple[torch.Tensor | None, list[int]]:
+        """Preprocess a mini-batch into a stacked tensor plus the indices of valid images."""
+        if self.rust_preprocess is not None:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in batch_records
+                ]
+                tensor, valid = _preprocess_batch_rust(
+                    image_bytes_list, dtype="float16" if self.use_fp16 else "float32", **self.rust_preprocess
+                )
+                valid_indices = valid.nonzero()[0].tolist()
+                if not valid_indices:
+                    return None, []
+                if len(valid_indices) < len(batch_records):
+                    tensor = tensor[valid]
+                return torch.from_numpy(tensor), valid_indices
+            except Exception:
+                pass  # Fallback to Python
+
+        # Lazy init thread pool (can't pickle for Ray serialization)
+        if self._executor is None:
+            self._executor = ThreadPoolExecutor(max_workers=self.preprocess_workers)
+
+        # Parallel preprocess using thread pool
+        results = list(self._executor.map(self._preprocess_image, batch_records))
+
+        # Collect valid tensors and indices
+        tensors = []
+        valid_indices = []
+        for i, tensor in enumerate(results):
+            if tensor is not None:
+                tensors.append(tensor)
+                valid_indices.append(i)
+
+        if not tensors:
+            return None, []
+        return torch.stack(tensors), valid_indices
+
     def refine_batch(self, records: list[dict[str, Any]]) -> None:
         """Extract CLIP embeddings from a batch of images inplace (GPU batch inference)."""
         if not records:
@@ -135,27 +238,13 @@ class ImageClipEmbeddingRefiner(Refiner):
             batch_end = min(batch_start + self.inference_batch_size, len(records))
             batch_records = records[batch_start:batch_end]
 
-            # Lazy init thread pool (can't pickle for Ray serialization)
-            if self._executor is None:
-                self._executor = ThreadPoolExecutor(max_workers=self.preprocess_workers)
-
-            # Parallel preprocess using thread pool
-            results = list(self._executor.map(self._preprocess_image, batch_records))
-
-            # Collect valid tensors and indices
-            tensors = []
-            valid_indices = []
-            for i, tensor in enumerate(results):
-                if tensor is not None:
-                    tensors.append(tensor)
-                    valid_indices.append(i)
-
-            if not tensors:
+            batch_tensor, valid_indices = self._preprocess_batch(batch_records)
+            if batch_tensor is None:
                 continue
 
             # Batch inference
             try:
-                batch_tensor = torch.stack(tensors).to(self.device, dtype=self.dtype)
+                batch_tensor = batch_tensor.to(self.device, dtype=self.dtype)
                 with torch.inference_mode():
                     features = self.visual(batch_tensor)
                     if self.normalize:
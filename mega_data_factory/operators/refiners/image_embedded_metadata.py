@@ -0,0 +1,330 @@
+"""
+Image Embedded Metadata Refiner
+
+Extracts the metadata embedded in image files: EXIF camera, software and capture time,
+GPS presence, XMP creator tool, IPTC digital source type, C2PA manifests and the ICC
+profile name. The digital source type and C2PA manifest carry AI-generation declarations
+(e.g. "trainedAlgorithmicMedia") that provenance-aware filters can use.
+This is a Refiner that enriches records, and can strip GPS data or all metadata from
+the image bytes for privacy compliance.
+
+Automatically uses the Rust backend if available, which reads metadata without decoding
+pixels and strips it without re-encoding the image.
+"""
+
+import re
+import struct
+from io import BytesIO
+from typing import Any
+
+import pyarrow as pa
+from PIL import Image
+
+from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_metadata import FIELD_FILE_SIZE
+
+# Field name constants
+FIELD_HAS_EXIF = "image_has_exif"
+FIELD_CAMERA_MAKE = "image_camera_make"
+FIELD_CAMERA_MODEL = "image_camera_model"
+FIELD_SOFTWARE = "image_software"
+FIELD_CAPTURE_TIME = "image_capture_time"
+FIELD_HAS_GPS = "image_has_gps"
+FIELD_HAS_XMP = "image_has_xmp"
+FIELD_DIGITAL_SOURCE_TYPE = "image_digital_source_type"
+FIELD_HAS_C2PA = "image_has_c2pa"
+FIELD_DECLARED_AI_GENERATED = "image_declared_ai_generated"
+FIELD_ICC_PROFILE_NAME = "image_icc_profile_name"
+FIELD_METADATA_STRIPPED = "image_metadata_stripped"
+
+STRIP_MODES = ("gps", "all")
+
+# EXIF tags
+_EXIF_MAKE = 0x010F
+_EXIF_MODEL = 0x0110
+_EXIF_SOFTWARE = 0x0131
+_EXIF_DATETIME = 0x0132
+_EXIF_IFD = 0x8769
+_EXIF_GPS_IFD = 0x8825
+_EXIF_DATETIME_ORIGINAL = 0x9003
+
+# IPTC digital source types that declare generative-AI content
+AI_GENERATED_SOURCE_TYPES = {"trainedAlgorithmicMedia", "compositeWithTrainedAlgorithmicMedia"}
+
+# IPTC digital source type codes, longest first so prefixes do not shadow longer codes
+_DIGITAL_SOURCE_TYPES = sorted(
+    [
+        "digitalCapture",
+        "computationalCapture",
+        "negativeFilm",
+        "positiveFilm",
+        "print",
+        "minorHumanEdits",
+        "humanEdits",
+        "compositeCapture",
+        "algorithmicallyEnhanced",
+        "dataDrivenMedia",
+        "digitalArt",
+        "virtualRecording",
+        "compositeSynthetic",
+        "trainedAlgorithmicMedia",
+        "compositeWithTrainedAlgorithmicMedia",
+        "algorithmicMedia",
+        "screenCapture",
+        "digitalCreation",
+    ],
+    key=len,
+    reverse=True,
+)
+_C2PA_SOURCE_TYPE_RE = re.compile(rb"digitalsourcetype/(" + "|".join(_DIGITAL_SOURCE_TYPES).encode() + rb")")
+
+# Pillow save formats the fallback can strip metadata from
+_PIL_STRIP_FORMATS = {"JPEG", "PNG", "WEBP"}
+
+# Try to load Rust extension (auto-acceleration)
+RUST_BACKEND_AVAILABLE = False
+_embedded_metadata_batch_rust = None
+_DecodeLimits = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
+    _embedded_metadata_batch_rust = getattr(_rust_module, "image_embedded_metadata_batch", None)
+    if _embedded_metadata_batch_rust is not None:
+        RUST_BACKEND_AVAILABLE = True
+except ImportError:
+    pass
+
+
+def _xmp_property(xmp: str | None, name: str) -> str | None:
+    """Value of a simple XMP property in attribute or element form."""
+    if not xmp:
+        return None
+    match = re.search(rf"""[\s<]{re.escape(name)}\s*=\s*(["'])(.*?)\1""", xmp, re.S) or re.search(
+        rf"<{re.escape(name)}(?:\s[^>]*)?>(.*?)</{re.escape(name)}", xmp, re.S
+    )
+    if not match:
+        return None
+    value = re.sub(r"<[^>]*>", "", match.group(match.lastindex)).strip()
+    for entity, char in (("&lt;", "<"), ("&gt;", ">"), ("&quot;", '"'), ("&apos;", "'"), ("&amp;", "&")):
+        value = value.replace(entity, char)
+    return value or None
+
+
+def _icc_profile_name(icc: bytes | None) -> str | None:
+    """Description of an ICC profile (v2 `desc` or v4 `mluc` tag)."""
+    try:
+        (tag_count,) = struct.unpack_from(">I", icc, 128)
+        for i in range(tag_count):
+            signature, offset, size = struct.unpack_from(">4sII", icc, 132 + 12 * i)
+            if signature != b"desc":
+                continue
+            tag = icc[offset : offset + size]
+            if tag[:4] == b"desc":
+                (length,) = struct.unpack_from(">I", tag, 8)
+                name = tag[12 : 12 + length].split(b"\0")[0].decode("latin-1")
+            elif tag[:4] == b"mluc":
+                length, start = struct.unpack_from(">II", tag, 20)
+                name = tag[start : start + length].decode("utf-16-be", "replace")
+            else:
+                return None
+            return name.strip("\0 ") or None
+    except (struct.error, TypeError):
+        pass
+    return None
+
+
+def _text(value: Any) -> str | None:
+    """EXIF ASCII value, trimmed; None if empty."""
+    if isinstance(value, bytes):
+        value = value.decode("utf-8", "replace")
+    if not isinstance(value, str):
+        return None
+    return value.split("\0")[0].strip() or None
+
+
+class ImageEmbeddedMetadataRefiner(Refiner):
+    """Refiner for EXIF/XMP/ICC/C2PA metadata embedded in images.
+
+    Output fields:
+    - image_has_exif: EXIF data is present
+    - image_camera_make / image_camera_model: EXIF camera make and model
+    - image_software: EXIF Software, falling back to XMP CreatorTool
+    - image_capture_time: EXIF DateTimeOriginal (else DateTime), "YYYY:MM:DD HH:MM:SS"
+    - image_has_gps: EXIF or XMP carry GPS coordinates
+    - image_has_xmp: An XMP packet is present
+    - image_digital_source_type: IPTC digital source type code (XMP, else C2PA)
+    - image_has_c2pa: A C2PA manifest store is embedded
+    - image_declared_ai_generated: XMP or C2PA declare generative-AI content
+    - image_icc_profile_name: ICC profile description
+    - image_metadata_stripped: Whether `image.bytes` was replaced by the stripped image
+
+    Fields describe the original image. Unreadable images get empty fields and are
+    counted in the operator's error stats; with `strip` set they keep their bytes.
+    """
+
+    def __init__(self, strip: str | None = None, decode_limits: dict[str, Any] | None = None):
+        """Initialize embedded metadata refiner.
+
+        Args:
+            strip: "gps" removes GPS data (the EXIF GPS IFD and XMP packets with GPS
+                properties; EXIF blocks too malformed to locate the GPS IFD in are dropped
+                whole); "all" removes EXIF, XMP, IPTC, C2PA, comments and text chunks.
+                ICC profiles are always kept. None leaves the bytes unchanged.
+            decode_limits: Limits applied by the Rust backend before reading each image
+                (same keys as ImageTechnicalQualityRefiner).
+        """
+        super().__init__()
+        if strip is not None and strip not in STRIP_MODES:
+            raise ValueError(f"unsupported strip mode: {strip} (expected 'gps' or 'all')")
+        self.strip = strip
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+
+    def refine_batch(self, records: list[dict[str, Any]]) -> None:
+        """Extract (and optionally strip) embedded metadata for a batch of records (inplace)."""
+        if not records:
+            return
+
+        if RUST_BACKEND_AVAILABLE and _embedded_metadata_batch_rust:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in records
+                ]
+                results, statuses = _embedded_metadata_batch_rust(
+                    image_bytes_list, return_status=True, strip=self.strip, limits=self._rust_limits
+                )
+                self.record_item_errors(statuses)
+                for record, result in zip(records, results, strict=False):
+                    self._apply(record, result)
+                return
+            except Exception:
+                pass  # Fallback to Python
+
+        for record in records:
+            img_obj = record.get("image", {})
+            if not (isinstance(img_obj, dict) and "bytes" in img_obj):
+                self._apply(record, None)
+                continue
+            try:
+                self._apply(record, self._extract(img_obj["bytes"]))
+            except Exception as e:
+                self._apply(record, None)
+                self.record_item_errors([("decode_error", str(e))])
+
+    @staticmethod
+    def _apply(record: dict[str, Any], result: dict[str, Any] | None) -> None:
+        """Set output fields from a backend result dict and swap in stripped bytes."""
+        result = result or {}
+        record[FIELD_HAS_EXIF] = result.get("has_exif", False)
+        record[FIELD_CAMERA_MAKE] = result.get("camera_make")
+        record[FIELD_CAMERA_MODEL] = result.get("camera_model")
+        record[FIELD_SOFTWARE] = result.get("software") or result.get("creator_tool")
+        record[FIELD_CAPTURE_TIME] = result.get("capture_time")
+        record[FIELD_HAS_GPS] = result.get("has_gps", False)
+        record[FIELD_HAS_XMP] = result.get("has_xmp", False)
+        record[FIELD_DIGITAL_SOURCE_TYPE] = result.get("digital_source_type")
+        record[FIELD_HAS_C2PA] = result.get("has_c2pa", False)
+        record[FIELD_DECLARED_AI_GENERATED] = result.get("declared_ai_generated", False)
+        record[FIELD_ICC_PROFILE_NAME] = result.get("icc_profile_name")
+        stripped = result.get("image")
+        record[FIELD_METADATA_STRIPPED] = stripped is not None
+        if stripped is not None:
+            record["image"]["bytes"] = stripped
+            if FIELD_FILE_SIZE in record:
+                record[FIELD_FILE_SIZE] = len(stripped)
+
+    def _extract(self, image_bytes: bytes) -> dict[str, Any]:
+        """Read (and optionally strip) embedded metadata of one image (Python fallback)."""
+        img = Image.open(BytesIO(image_bytes))
+        exif = img.getexif()
+        exif_ifd = exif.get_ifd(_EXIF_IFD)
+        xmp = img.info.get("xmp") or img.info.get("XML:com.adobe.xmp")
+        if isinstance(xmp, bytes):
+            xmp = xmp.decode("utf-8", "replace")
+        icc_profile = img.info.get("icc_profile")
+
+        c2pa_sources = [m.decode() for m in _C2PA_SOURCE_TYPE_RE.findall(image_bytes)]
+        has_c2pa = b"jumb" in image_bytes and b"c2pa" in image_bytes
+        xmp_source = _xmp_property(xmp, "Iptc4xmpExt:DigitalSourceType")
+        xmp_source = xmp_source.rstrip("/").rsplit("/", 1)[-1] if xmp_source else None
+        has_exif_gps = _EXIF_GPS_IFD in exif and bool(exif.get_ifd(_EXIF_GPS_IFD))
+        has_xmp_gps = bool(xmp) and "exif:GPS" in xmp
+
+        result = {
+            "has_exif": len(exif) > 0,
+            "camera_make": _text(exif.get(_EXIF_MAKE)),
+            "camera_model": _text(exif.get(_EXIF_MODEL)),
+            "software": _text(exif.get(_EXIF_SOFTWARE)),
+            "capture_time": _text(exif_ifd.get(_EXIF_DATETIME_ORIGINAL)) or _text(exif.get(_EXIF_DATETIME)),
+            "has_gps": has_exif_gps or has_xmp_gps,
+            "has_xmp": bool(xmp),
+            "creator_tool": _xmp_property(xmp, "xmp:CreatorTool"),
+            "digital_source_type": xmp_source or (c2pa_sources[0] if has_c2pa and c2pa_sources else None),
+            "has_c2pa": has_c2pa,
+            "declared_ai_generated": xmp_source in AI_GENERATED_SOURCE_TYPES
+            or (has_c2pa and any(source in AI_GENERATED_SOURCE_TYPES for source in c2pa_sources)),
+            "icc_profile_name": _icc_profile_name(icc_profile),
+        }
+        if self.strip is not None:
+            result["image"] = self._strip(img, image_bytes, exif, xmp, has_xmp_gps, result)
+        return result
+
+    def _strip(
+        self,
+        img: Image.Image,
+        image_bytes: bytes,
+        exif: Image.Exif,
+        xmp: str | None,
+        has_xmp_gps: bool,
+        result: dict[str, Any],
+    ) -> bytes:
+        """Re-save one image without the selected metadata (Python fallback).
+
+        Unlike the Rust backend this re-encodes the image: JPEGs keep their quantization
+        tables, PNG and WebP are written losslessly.
+        """
+        if self.strip == "gps":
+            has_target = result["has_gps"]
+        else:
+            has_target = result["has_exif"] or result["has_xmp"] or result["has_c2pa"]
+        if not has_target:
+            return image_bytes
+        if img.format not in _PIL_STRIP_FORMATS:
+            raise ValueError(f"metadata stripping does not support {img.format}")
+
+        options: dict[str, Any] = {}
+        if img.info.get("icc_profile"):
+            options["icc_profile"] = img.info["icc_profile"]
+        if self.strip == "gps":
+            exif.pop(_EXIF_GPS_IFD, None)
+            options["exif"] = exif.tobytes()
+            if xmp and not has_xmp_gps:
+                options["xmp"] = xmp.encode()
+        if img.format == "JPEG":
+            options["quality"] = "keep"
+        elif img.format == "WEBP":
+            options["lossless"] = True
+
+        buffer = BytesIO()
+        img.save(buffer, format=img.format, **options)
+        return buffer.getvalue()
+
+    def get_output_schema(self) -> dict[str, pa.DataType]:
+        """Return output schema for new fields added by this refiner."""
+        return {
+            FIELD_HAS_EXIF: pa.bool_(),
+            FIELD_CAMERA_MAKE: pa.string(),
+            FIELD_CAMERA_MODEL: pa.string(),
+            FIELD_SOFTWARE: pa.string(),
+            FIELD_CAPTURE_TIME: pa.string(),
+            FIELD_HAS_GPS: pa.bool_(),
+            FIELD_HAS_XMP: pa.bool_(),
+            FIELD_DIGITAL_SOURCE_TYPE: pa.string(),
+            FIELD_HAS_C2PA: pa.bool_(),
+            FIELD_DECLARED_AI_GENERATED: pa.bool_(),
+            FIELD_ICC_PROFILE_NAME: pa.string(),
+            FIELD_METADATA_STRIPPED: pa.bool_(),
+        }
@@ -1,10 +1,15 @@
 """
 Image Metadata Refiner
 
-Extracts basic image metadata: width, height, file size, format.
+Extracts basic image metadata: width, height, file size, format, color type,
+bit depth, frame count, EXIF orientation and color space.
 This is a Refiner that enriches records with metadata information.
+
+Automatically uses the Rust backend if available, which reads only the image header
+instead of decoding the full image.
 """
 
+import struct
 from io import BytesIO
 from typing import Any
 
@@ -18,8 +23,54 @@ FIELD_WIDTH = "image_width"
 FIELD_HEIGHT = "image_height"
 FIELD_FILE_SIZE = "image_file_size_bytes"
 FIELD_FORMAT = "image_format"
+FIELD_COLOR_TYPE = "image_color_type"
+FIELD_BIT_DEPTH = "image_bit_depth"
+FIELD_FRAME_COUNT = "image_frame_count"
+FIELD_ORIENTATION = "image_orientation"
+FIELD_COLOR_SPACE = "image_color_space"
+
+OUTPUT_FIELDS = [
+    FIELD_WIDTH,
+    FIELD_HEIGHT,
+    FIELD_FILE_SIZE,
+    FIELD_FORMAT,
+    FIELD_COLOR_TYPE,
+    FIELD_BIT_DEPTH,
+    FIELD_FRAME_COUNT,
+    FIELD_ORIENTATION,
+    FIELD_COLOR_SPACE,
+]
+
+# Bits per channel of Pillow modes that are not 8-bit
+_PIL_MODE_BIT_DEPTH = {"1": 1, "I;16": 16, "I;16B": 16, "I;16L": 16, "I": 32, "F": 32}
+
+# EXIF tag holding the orientation
+_EXIF_ORIENTATION_TAG = 0x0112
+
+# D50 colorants (rXYZ, gXYZ, bXYZ) of the RGB spaces ICC profiles are matched against
+_RGB_SPACE_COLORANTS = {
+    "srgb": ((0.4358, 0.2224, 0.0139), (0.3853, 0.7170, 0.0972), (0.1431, 0.0606, 0.7141)),
+    "display_p3": ((0.5150, 0.2411, -0.0010), (0.2921, 0.6923, 0.0419), (0.1571, 0.0666, 0.7843)),
+    "adobe_rgb": ((0.6096, 0.3110, 0.0195), (0.2054, 0.6258, 0.0609), (0.1492, 0.0632, 0.7448)),
+    "prophoto_rgb": ((0.7975, 0.2880, 0.0000), (0.1353, 0.7119, 0.0000), (0.0314, 0.0001, 0.8252)),
+    "rec2020": ((0.6733, 0.2790, -0.0019), (0.1658, 0.6754, 0.0300), (0.1251, 0.0456, 0.7971)),
+}
+_COLORANT_TOLERANCE = 0.005
+
+# Try to load Rust extension (auto-acceleration)
+RUST_BACKEND_AVAILABLE = False
+_probe_metadata_batch_rust = None
+_DecodeLimits = None
 
-OUTPUT_FIELDS = [FIELD_WIDTH, FIELD_HEIGHT, FIELD_FILE_SIZE, FIELD_FORMAT]
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
+    _probe_metadata_batch_rust = getattr(_rust_module, "image_probe_metadata_batch", None)
+    if _probe_metadata_batch_rust is not None:
+        RUST_BACKEND_AVAILABLE = True
+except ImportError:
+    pass
 
 
 class ImageMetadataRefiner(Refiner):
@@ -30,10 +81,64 @@ class ImageMetadataRefiner(Refiner):
     - image_height: Image height in pixels
     - image_file_size_bytes: File size in bytes
     - image_format: Image format (JPEG, PNG, etc.)
+    - image_color_type: Color mode (L, LA, P, RGB, RGBA, CMYK)
+    - image_bit_depth: Bits per channel (or per palette index)
+    - image_frame_count: Number of frames (> 1 for animated GIF/APNG/WebP)
+    - image_orientation: EXIF orientation (1-8, 1 when absent)
+    - image_color_space: Encoded color space (srgb, display_p3, adobe_rgb, prophoto_rgb,
+      rec2020, other_rgb, gray, cmyk or other), from the ICC profile when there is one
+
+    Images over `decode_limits` are treated like unreadable ones and counted in the
+    operator's error stats under their status.
     """
 
+    def __init__(self, decode_limits: dict[str, Any] | None = None):
+        """Initialize metadata refiner.
+
+        Args:
+            decode_limits: Limits applied by the Rust backend before decoding each image,
+                e.g. {"max_pixels": 100_000_000, "max_input_bytes": 50_000_000,
+                "allowed_formats": ["jpeg", "png", "webp"]}. Keys: max_pixels,
+                max_alloc_bytes, max_input_bytes, allowed_formats.
+        """
+        super().__init__()
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+
     def refine_batch(self, records: list[dict[str, Any]]) -> None:
         """Extract basic image metadata for a batch of records (inplace)."""
+        if not records:
+            return
+
+        # Use Rust header-only probing if available (no full decode)
+        if RUST_BACKEND_AVAILABLE and _probe_metadata_batch_rust:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in records
+                ]
+                probes, statuses = _probe_metadata_batch_rust(
+                    image_bytes_list, return_status=True, limits=self._rust_limits
+                )
+                self.record_item_errors(statuses)
+
+                for record, image_bytes, metadata in zip(records, image_bytes_list, probes, strict=False):
+                    if metadata is None:
+                        self._set_error(record, len(image_bytes or b""))
+                        continue
+                    record[FIELD_WIDTH] = metadata["width"]
+                    record[FIELD_HEIGHT] = metadata["height"]
+                    record[FIELD_FILE_SIZE] = len(image_bytes)
+                    record[FIELD_FORMAT] = metadata["format"]
+                    record[FIELD_COLOR_TYPE] = metadata["color_type"]
+                    record[FIELD_BIT_DEPTH] = metadata["bit_depth"]
+                    record[FIELD_FRAME_COUNT] = metadata["frame_count"]
+                    record[FIELD_ORIENTATION] = metadata["orientation"]
+                    record[FIELD_COLOR_SPACE] = metadata["color_space"]
+                return
+            except Exception:
+                pass  # Fallback to Python
+
         for record in records:
             img_obj = record.get("image", {})
 
@@ -46,16 +151,28 @@ class ImageMetadataRefiner(Refiner):
                     record[FIELD_HEIGHT] = h
                     record[FIELD_FILE_SIZE] = len(image_bytes)
                     record[FIELD_FORMAT] = img.format or "UNKNOWN"
+                    record[FIELD_COLOR_TYPE] = img.mode
+                    record[FIELD_BIT_DEPTH] = _PIL_MODE_BIT_DEPTH.get(img.mode, 8)
+                    record[FIELD_FRAME_COUNT] = getattr(img, "n_frames", 1)
+                    record[FIELD_ORIENTATION] = img.getexif().get(_EXIF_ORIENTATION_TAG, 1)
+                    record[FIELD_COLOR_SPACE] = _color_space(img.info.get("icc_profile"), img.mode)
                 except Exception:
-                    record[FIELD_WIDTH] = 0
-                    record[FIELD_HEIGHT] = 0
-                    record[FIELD_FILE_SIZE] = len(image_bytes)
-                    record[FIELD_FORMAT] = "ERROR"
+                    self._set_error(record, len(image_bytes))
             else:
-                record[FIELD_WIDTH] = 0
-                record[FIELD_HEIGHT] = 0
-                record[FIELD_FILE_SIZE] = 0
-                record[FIELD_FORMAT] = "ERROR"
+                self._set_error(record, 0)
+
+    @staticmethod
+    def _set_error(record: dict[str, Any], file_size: int) -> None:
+        """Fill metadata fields for an image that could not be read."""
+        record[FIELD_WIDTH] = 0
+        record[FIELD_HEIGHT] = 0
+        record[FIELD_FILE_SIZE] = file_size
+        record[FIELD_FORMAT] = "ERROR"
+        record[FIELD_COLOR_TYPE] = None
+        record[FIELD_BIT_DEPTH] = 0
+        record[FIELD_FRAME_COUNT] = 0
+        record[FIELD_ORIENTATION] = 1
+        record[FIELD_COLOR_SPACE] = None
 
     def get_output_schema(self) -> dict[str, pa.DataType]:
         """Return output schema for new fields added by this refiner."""
@@ -64,4 +181,56 @@ class ImageMetadataRefiner(Refiner):
             FIELD_HEIGHT: pa.int32(),
             FIELD_FILE_SIZE: pa.int64(),
             FIELD_FORMAT: pa.string(),
+            FIELD_COLOR_TYPE: pa.string(),
+            FIELD_BIT_DEPTH: pa.int8(),
+            FIELD_FRAME_COUNT: pa.int32(),
+            FIELD_ORIENTATION: pa.int8(),
+            FIELD_COLOR_SPACE: pa.string(),
         }
+
+
+def _icc_colorants(icc: bytes) -> list[tuple[float, float, float]] | None:
+    """Read the rXYZ, gXYZ and bXYZ tags of an ICC profile (None if missing or malformed)."""
+    try:
+        (count,) = struct.unpack_from(">I", icc, 128)
+        tags = {}
+        for i in range(count):
+            signature, offset, _ = struct.unpack_from(">4sII", icc, 132 + 12 * i)
+            tags[signature] = offset
+        return [
+            tuple(v / 65536 for v in struct.unpack_from(">3i", icc, tags[signature] + 8))
+            for signature in (b"rXYZ", b"gXYZ", b"bXYZ")
+        ]
+    except (struct.error, KeyError):
+        return None
+
+
+def _color_space(icc: bytes | None, mode: str) -> str:
+    """Encoded color space from an ICC profile, else from the Pillow mode.
+
+    Mirrors the Rust backend: RGB profiles are matched by primaries, and unreadable
+    profiles are ignored.
+    """
+    data_space = icc[16:20] if icc and len(icc) >= 132 else None
+    if data_space == b"GRAY":
+        return "gray"
+    if data_space == b"CMYK":
+        return "cmyk"
+    if data_space == b"RGB ":
+        colorants = _icc_colorants(icc)
+        if colorants is not None:
+            for name, reference in _RGB_SPACE_COLORANTS.items():
+                if all(
+                    abs(a - b) <= _COLORANT_TOLERANCE
+                    for colorant, expected in zip(colorants, reference, strict=True)
+                    for a, b in zip(colorant, expected, strict=True)
+                ):
+                    return name
+            return "other_rgb"
+    elif data_space is not None:
+        return "other"
+    if mode in ("1", "L", "LA", "I", "I;16", "I;16B", "I;16L", "F"):
+        return "gray"
+    if mode == "CMYK":
+        return "cmyk"
+    return "srgb"
@@ -14,6 +14,9 @@ Available models:
 - google/siglip2-so400m-patch14-384 (1152-dim, recommended)
 - google/siglip2-base-patch16-224 (768-dim, faster)
 - google/siglip2-large-patch16-256 (1024-dim)
+
+Fixed-resolution models are preprocessed by the Rust backend if available, otherwise by
+the HuggingFace image processor.
 """
 
 from concurrent.futures import ThreadPoolExecutor
@@ -26,6 +29,45 @@ from PIL import Image
 from transformers import AutoModel, AutoProcessor
 
 from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_technical_quality import convert_to_srgb, flatten_alpha
+
+# Try to load Rust extension (auto-acceleration)
+_preprocess_batch_rust = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _preprocess_batch_rust = getattr(_rust_module, "image_preprocess_batch", None)
+except ImportError:
+    pass
+
+# Rust resize filter for each PIL resampling filter
+_RUST_RESIZE_FILTERS = {0: "nearest", 1: "lanczos", 2: "bilinear", 3: "bicubic"}
+
+
+def _rust_preprocess_config(image_processor: Any) -> dict[str, Any] | None:
+    """Arguments that make the Rust image_preprocess_batch match a SigLIP image processor.
+
+    Returns None for processors it cannot reproduce (e.g. NaFlex variable-resolution
+    SigLIP2), so the HuggingFace processor is used instead.
+    """
+    if type(image_processor).__name__ not in ("SiglipImageProcessor", "SiglipImageProcessorFast"):
+        return None
+    if not (image_processor.do_resize and image_processor.do_rescale and image_processor.do_normalize):
+        return None
+    if abs(image_processor.rescale_factor - 1 / 255) > 1e-9:
+        return None
+    resize_filter = _RUST_RESIZE_FILTERS.get(int(image_processor.resample))
+    size = image_processor.size
+    if resize_filter is None or "height" not in size or "width" not in size:
+        return None
+    return {
+        "size": (size["height"], size["width"]),
+        "mean": list(image_processor.image_mean),
+        "std": list(image_processor.image_std),
+        "resize_mode": "squash",
+        "resize_filter": resize_filter,
+    }
 
 
 class ImageSigLIPEmbeddingRefiner(Refiner):
@@ -127,6 +169,11 @@ class ImageSigLIPEmbeddingRefiner(Refiner):
         # Thread pool initialized lazily (can't pickle ThreadPoolExecutor for Ray)
         self._executor = None
 
+        # Rust preprocessing arguments (None = use the HuggingFace processor)
+        self.rust_preprocess = (
+            _rust_preprocess_config(self.processor.image_processor) if _preprocess_batch_rust else None
+        )
+
         print(f"Model loaded (visual tower only). Embedding dim: {self.embedding_dim}")
         print(f"Output field: {self.feature_field_name}")
 
@@ -135,14 +182,53 @@ class ImageSigLIPEmbeddingRefiner(Refiner):
         img_obj = record.get("image", {})
         if isinstance(img_obj, dict) and "bytes" in img_obj:
             try:
-                img = Image.open(BytesIO(img_obj["bytes"]))
-                if img.mode != "RGB":
-                    img = img.convert("RGB")
+                img = flatten_alpha(convert_to_srgb(Image.open(BytesIO(img_obj["bytes"]))))
                 return img
             except Exception:
                 pass
         return None
 
+    def _preprocess_batch(self, batch_records: list[dict[str, Any]]) -> tuple[dict[str, Any] | None, list[int]]:
+        """Preprocess a mini-batch into model inputs plus the indices of valid images."""
+        if self.rust_preprocess is not None:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in batch_records
+                ]
+                pixel_values, valid = _preprocess_batch_rust(
+                    image_bytes_list, dtype="float16" if self.use_fp16 else "float32", **self.rust_preprocess
+                )
+                valid_indices = valid.nonzero()[0].tolist()
+                if not valid_indices:
+                    return None, []
+                if len(valid_indices) < len(batch_records):
+                    pixel_values = pixel_values[valid]
+                return {"pixel_values": torch.from_numpy(pixel_values)}, valid_indices
+            except Exception:
+                pass  # Fallback to the HuggingFace processor
+
+        # Lazy init thread pool (can't pickle for Ray serialization)
+        if self._executor is None:
+            self._executor = ThreadPoolExecutor(max_workers=self.preprocess_workers)
+
+        # Parallel preprocess using thread pool
+        images = list(self._executor.map(self._preprocess_image, batch_records))
+
+        # Collect valid images and indices
+        valid_images = []
+        valid_indices = []
+        for i, img in enumerate(images):
+            if img is not None:
+                valid_images.append(img)
+                valid_indices.append(i)
+
+        if not valid_images:
+            return None, []
+
+        # Process images through SigLIP2 processor
+        return self.processor(images=valid_images, return_tensors="pt"), valid_indices
+
     def refine_batch(self, records: list[dict[str, Any]]) -> None:
         """Extract SigLIP embeddings from a batch of images inplace (GPU batch inference)."""
         if not records:
@@ -159,28 +245,12 @@ class ImageSigLIPEmbeddingRefiner(Refiner):
             batch_end = min(batch_start + self.inference_batch_size, len(records))
             batch_records = records[batch_start:batch_end]
 
-            # Lazy init thread pool (can't pickle for Ray serialization)
-            if self._executor is None:
-                self._executor = ThreadPoolExecutor(max_workers=self.preprocess_workers)
-
-            # Parallel preprocess using thread pool
-            images = list(self._executor.map(self._preprocess_image, batch_records))
-
-            # Collect valid images and indices
-            valid_images = []
-            valid_indices = []
-            for i, img in enumerate(images):
-                if img is not None:
-                    valid_images.append(img)
-                    valid_indices.append(i)
-
-            if not valid_images:
-                continue
-
             # Batch inference
             try:
-                # Process images through SigLIP2 processor
-                inputs = self.processor(images=valid_images, return_tensors="pt")
+                inputs, valid_indices = self._preprocess_batch(batch_records)
+                if inputs is None:
+                    continue
+
                 inputs = {
                     k: v.to(self.device, dtype=self.dtype if k != "input_ids" else v.dtype) for k, v in inputs.items()
                 }
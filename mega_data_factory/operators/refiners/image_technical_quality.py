@@ -4,6 +4,13 @@ Technical Quality Refiner (Auto-accelerated with Rust if available)
 Assesses technical quality metrics:
 - Compression artifacts detection
 - Information entropy calculation
+- Sharpness / blur (Laplacian variance, Tenengrad, fraction of sharp tiles)
+- Noise level (Immerkær's noise sigma estimate)
+- Exposure, contrast and color statistics (clipping, RMS contrast, colorfulness, saturation)
+- Blank / solid-color / placeholder classification
+- Uniform border / letterbox detection
+Pixels are converted to sRGB through the embedded ICC profile and transparent images
+are composited onto a background color before the color metrics are computed.
 This is a Refiner that enriches records with quality metrics.
 
 Automatically uses Rust backend (3-10x faster) if available, otherwise falls back to Python implementation.
@@ -14,7 +21,7 @@ from typing import Any
 
 import numpy as np
 import pyarrow as pa
-from PIL import Image
+from PIL import Image, ImageCms
 
 from mega_data_factory.framework import Refiner
 
@@ -22,32 +29,287 @@ from mega_data_factory.framework import Refiner
 FIELD_COMPRESSION_ARTIFACTS = "image_compression_artifacts"
 FIELD_INFORMATION_ENTROPY = "image_information_entropy"
 
+FIELD_LAPLACIAN_VARIANCE = "image_laplacian_variance"
+FIELD_TENENGRAD = "image_tenengrad"
+FIELD_SHARP_TILE_FRACTION = "image_sharp_tile_fraction"
+FIELD_NOISE_SIGMA = "image_noise_sigma"
+FIELD_LUMA_MEAN = "image_luma_mean"
+FIELD_LUMA_STD = "image_luma_std"
+FIELD_HIGHLIGHT_CLIP_FRACTION = "image_highlight_clip_fraction"
+FIELD_SHADOW_CLIP_FRACTION = "image_shadow_clip_fraction"
+FIELD_RMS_CONTRAST = "image_rms_contrast"
+FIELD_COLORFULNESS = "image_colorfulness"
+FIELD_SATURATION_MEAN = "image_saturation_mean"
+FIELD_PLACEHOLDER_CATEGORY = "image_placeholder_category"
+FIELD_PLACEHOLDER_CONFIDENCE = "image_placeholder_confidence"
+FIELD_BORDER_FRACTION = "image_border_fraction"
+
 OUTPUT_FIELDS = [FIELD_COMPRESSION_ARTIFACTS, FIELD_INFORMATION_ENTROPY]
 
+# Sharpness fields and the image_analyze_batch metric each one comes from
+SHARPNESS_FIELDS = {
+    FIELD_LAPLACIAN_VARIANCE: "laplacian_variance",
+    FIELD_TENENGRAD: "tenengrad",
+    FIELD_SHARP_TILE_FRACTION: "sharp_tile_fraction",
+}
+
+# Exposure fields and the image_analyze_batch metric each one comes from
+EXPOSURE_FIELDS = {
+    FIELD_LUMA_MEAN: "luma_mean",
+    FIELD_LUMA_STD: "luma_std",
+    FIELD_HIGHLIGHT_CLIP_FRACTION: "highlight_clip_fraction",
+    FIELD_SHADOW_CLIP_FRACTION: "shadow_clip_fraction",
+    FIELD_RMS_CONTRAST: "rms_contrast",
+    FIELD_COLORFULNESS: "colorfulness",
+    FIELD_SATURATION_MEAN: "saturation_mean",
+}
+
+# Luma clipping thresholds (match the Rust backend)
+HIGHLIGHT_CLIP_LUMA = 250.0
+SHADOW_CLIP_LUMA = 5.0
+
+# Placeholder detection thresholds (match the Rust backend)
+PLACEHOLDER_MIN_SIDE = 8
+PLACEHOLDER_THUMBNAIL_SIDE = 32
+VISIBLE_ALPHA = 16
+TRANSPARENT_MAX_COVERAGE = 0.01
+MAX_COUNTED_COLORS = 1024
+NEAR_SOLID_MAX_STD = 4.0
+NEAR_SOLID_MIN_DOMINANT = 0.95
+GRADIENT_MAX_LAPLACIAN = 2.0
+
+# Border detection defaults and thresholds (match the Rust backend)
+BORDER_TOLERANCE = 16
+BORDER_MIN_WIDTH = 2
+BORDER_LINE_MIN_MATCH = 0.98
+BORDER_BLACK_MAX = 40
+BORDER_WHITE_MIN = 215
+
+# Tile grid and per-tile Laplacian variance threshold (match the Rust backend)
+SHARPNESS_TILE_GRID = 8
+SHARP_TILE_THRESHOLD = 100.0
+
+# Color transparent images are composited onto (matches the Rust backend)
+DEFAULT_BACKGROUND = (255, 255, 255)
+
+# Field reused by ImagePhashDeduplicator when present
+FIELD_PHASH = "phash"
+
+# Resolution scale the metrics were computed at (only with analysis_max_side)
+FIELD_ANALYSIS_SCALE = "image_analysis_scale"
+
 # Try to load Rust extension (auto-acceleration)
 RUST_BACKEND_AVAILABLE = False
 _assess_quality_batch_rust = None
+_analyze_batch_rust = None
+_DecodeLimits = None
 
 try:
     from mega_data_factory import rust_operators as _rust_module  # type: ignore
 
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
     _assess_quality_batch_rust = getattr(_rust_module, "image_assess_quality_batch", None)
+    _analyze_batch_rust = getattr(_rust_module, "image_analyze_batch", None)
     if _assess_quality_batch_rust is not None:
         RUST_BACKEND_AVAILABLE = True
 except ImportError:
     pass
 
 
+def convert_to_srgb(img: Image.Image, icc_profile: bytes | None = None) -> Image.Image:
+    """Apply the embedded ICC profile, converting to sRGB (Python version of the Rust color management).
+
+    `icc_profile` overrides `img.info["icc_profile"]`. CMYK images become RGB; images
+    without a usable RGB or CMYK profile keep their pixels.
+    """
+    icc_profile = icc_profile or img.info.get("icc_profile")
+    if icc_profile and img.mode in ("RGB", "RGBA", "CMYK"):
+        try:
+            return ImageCms.profileToProfile(
+                img,
+                ImageCms.ImageCmsProfile(BytesIO(icc_profile)),
+                ImageCms.createProfile("sRGB"),
+                outputMode="RGBA" if img.mode == "RGBA" else "RGB",
+            )
+        except (ImageCms.PyCMSError, OSError):
+            pass  # Unusable profile: treat the pixels as sRGB
+    return img.convert("RGB") if img.mode == "CMYK" else img
+
+
+def luma(img: Image.Image) -> np.ndarray:
+    """Rec. 709 luma (0-255), truncated like the Rust backend's grayscale conversion.
+
+    PIL's convert("L") uses Rec. 601 weights, which would make the sharpness and noise
+    fields of color images differ between backends.
+    """
+    rgb = np.asarray(img.convert("RGB"), dtype=np.int32)
+    return (2126 * rgb[:, :, 0] + 7152 * rgb[:, :, 1] + 722 * rgb[:, :, 2]) // 10000
+
+
+def has_alpha(img: Image.Image) -> bool:
+    """Whether an image has an alpha channel or a transparent color."""
+    return img.mode in ("RGBA", "LA", "PA") or "transparency" in img.info
+
+
+def output_format_for_source(source_format: str | None, img: Image.Image) -> str:
+    """Format to re-encode an image in when none is configured (matches the Rust backend).
+
+    JPEG is kept and WebP becomes JPEG (PNG if it has alpha), since WebP output is
+    lossless and would usually be several times larger than a lossy source; anything
+    else becomes PNG.
+    """
+    if source_format == "JPEG" or (source_format == "WEBP" and not has_alpha(img)):
+        return "jpeg"
+    return "png"
+
+
+def flatten_alpha(img: Image.Image, background: tuple[int, int, int] = DEFAULT_BACKGROUND) -> Image.Image:
+    """Composite a transparent image onto a solid background color, returning RGB."""
+    if has_alpha(img):
+        canvas = Image.new("RGBA", img.size, (*background, 255))
+        canvas.alpha_composite(img.convert("RGBA"))
+        return canvas.convert("RGB")
+    return img if img.mode == "RGB" else img.convert("RGB")
+
+
+def _border_width(lines: np.ndarray, tolerance: int, any_color: bool) -> int:
+    """Count consecutive border lines from the start of `lines` (shape: lines x pixels x RGBA)."""
+    if len(lines) == 0:
+        return 0
+    color = lines[0].mean(axis=0).astype(np.int32)
+    r, g, b, a = color
+    if not (any_color or a == 0 or max(r, g, b) <= BORDER_BLACK_MAX or min(r, g, b) >= BORDER_WHITE_MIN):
+        return 0
+    matches = (np.abs(lines - color) <= tolerance).all(axis=2).mean(axis=1) >= BORDER_LINE_MIN_MATCH
+    if not matches[0]:
+        return 0
+    return int(np.argmin(matches)) if not matches.all() else len(lines)
+
+
+def detect_borders(
+    img: Image.Image,
+    tolerance: int = BORDER_TOLERANCE,
+    min_border: int = BORDER_MIN_WIDTH,
+    any_color: bool = False,
+) -> tuple[tuple[int, int, int, int] | None, float]:
+    """Find uniform borders (Python version of the Rust backend's border detection).
+
+    Returns the (left, top, right, bottom) content box, or None if the whole image is
+    border, and the fraction of the image area outside it.
+    """
+    rgba = np.asarray(img.convert("RGBA"), dtype=np.int32).copy()
+    rgba[rgba[:, :, 3] < VISIBLE_ALPHA] = 0  # any transparent color matches
+    height, width = rgba.shape[:2]
+
+    top = _border_width(rgba, tolerance, any_color)
+    if top == height:
+        return None, 1.0
+    bottom = _border_width(rgba[top:][::-1], tolerance, any_color)
+    content_rows = rgba[top : height - bottom].transpose(1, 0, 2)
+    left = _border_width(content_rows, tolerance, any_color)
+    if left == width:
+        return None, 1.0
+    right = _border_width(content_rows[left:][::-1], tolerance, any_color)
+
+    left, top, right, bottom = (side if side >= min_border else 0 for side in (left, top, right, bottom))
+    box = (left, top, width - right, height - bottom)
+    content_area = (box[2] - box[0]) * (box[3] - box[1])
+    return box, 1.0 - content_area / (width * height)
+
+
 class ImageTechnicalQualityRefiner(Refiner):
-    """Refiner for image technical quality assessment (compression artifacts, entropy).
+    """Refiner for image technical quality assessment (artifacts, entropy, sharpness, noise, exposure).
 
     Automatically uses Rust backend (3-10x faster) if available, otherwise falls back to Python.
 
     Output fields:
     - image_compression_artifacts: Compression artifact score (0-1, higher = more artifacts)
     - image_information_entropy: Shannon entropy (higher = more information/detail)
+    - image_laplacian_variance: Variance of the luma Laplacian (lower = blurrier)
+    - image_tenengrad: Mean squared Sobel gradient magnitude (lower = blurrier)
+    - image_sharp_tile_fraction: Fraction of an 8x8 tile grid with Laplacian variance >= 100
+      (low = blurry, or sharp only in a small region)
+    - image_noise_sigma: Estimated noise standard deviation in 8-bit luma units (higher = noisier)
+    - image_luma_mean, image_luma_std: Rec. 709 luma mean and standard deviation (0-255)
+    - image_highlight_clip_fraction, image_shadow_clip_fraction: Fraction of pixels with
+      luma >= 250 / <= 5
+    - image_rms_contrast: Luma standard deviation normalized to 0-1 (low = washed out)
+    - image_colorfulness: Hasler-Süsstrunk colorfulness (0 = grayscale)
+    - image_saturation_mean: Mean HSV saturation (0-1)
+    - image_placeholder_category: "tiny", "transparent", "solid", "near_solid", "gradient"
+      or "content"
+    - image_placeholder_confidence: Confidence of the category (0.5 at a threshold, up to 1.0)
+    - image_border_fraction: Fraction of the image area in uniform black, white or
+      transparent borders (letterboxing, padding)
+
+    Only the compression artifact and entropy fields are computed by default; the
+    other groups are enabled with their compute_* flags.
+
+    Images that fail to decode get None for all fields and are counted in the
+    operator's error stats (error_count, errors_by_status, error_samples).
+
+    With compute_phash=True (Rust backend only), the same decode also produces the
+    `phash` field in ImagePhashDeduplicator's format, so the dedup stage does not
+    decode the image again.
+
+    Images over `decode_limits` are rejected before decoding and counted under their
+    status (too_many_pixels, alloc_limit, input_too_large, format_not_allowed).
+
+    With analysis_max_side (Rust backend only), large images are reduced and the metrics
+    are computed at that resolution; the scale used is stored in `image_analysis_scale`.
+    Baseline and progressive JPEGs are decoded straight to 1/2, 1/4 or 1/8 scale, so the
+    decode limits apply at that size; other images are reduced after a full decode.
+
+    Images are converted to sRGB through their embedded ICC profile (CMYK included), and
+    transparent images are composited onto `background` for the artifact, entropy,
+    sharpness, noise and exposure fields.
     """
 
+    def __init__(
+        self,
+        compute_phash: bool = False,
+        compute_sharpness: bool = False,
+        compute_noise: bool = False,
+        compute_exposure: bool = False,
+        compute_placeholder: bool = False,
+        compute_border: bool = False,
+        hash_size: int = 16,
+        decode_limits: dict[str, Any] | None = None,
+        analysis_max_side: int | None = None,
+        background: tuple[int, int, int] = DEFAULT_BACKGROUND,
+    ):
+        """Initialize technical quality refiner.
+
+        Args:
+            compute_phash: Also compute the `phash` field from the same decode.
+            compute_sharpness: Compute the sharpness fields (Laplacian variance, Tenengrad,
+                sharp tile fraction).
+            compute_noise: Compute the `image_noise_sigma` field.
+            compute_exposure: Compute the exposure, contrast and color fields.
+            compute_placeholder: Compute the placeholder category and confidence fields.
+            compute_border: Compute the `image_border_fraction` field.
+            hash_size: Perceptual hash size; must match ImagePhashDeduplicator's hash_size.
+            decode_limits: Limits applied by the Rust backend before decoding each image,
+                e.g. {"max_pixels": 100_000_000, "max_input_bytes": 50_000_000,
+                "allowed_formats": ["jpeg", "png", "webp"]}. Keys: max_pixels,
+                max_alloc_bytes, max_input_bytes, allowed_formats.
+            analysis_max_side: Compute metrics on a copy reduced so its long side is about
+                this many pixels (None = full resolution). JPEGs are decoded at the reduced size.
+            background: RGB color transparent images are composited onto.
+        """
+        super().__init__()
+        self.compute_phash = compute_phash
+        self.compute_sharpness = compute_sharpness
+        self.compute_noise = compute_noise
+        self.compute_exposure = compute_exposure
+        self.compute_placeholder = compute_placeholder
+        self.compute_border = compute_border
+        self.hash_size = hash_size
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+        self.analysis_max_side = analysis_max_side
+        self.background = tuple(background)
+
     def refine_batch(self, records: list[dict[str, Any]]) -> None:
         """Refine a batch of records inplace (optimized with Rust batch processing)."""
         if not records:
@@ -60,11 +322,70 @@ class ImageTechnicalQualityRefiner(Refiner):
                     record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
                     for record in records
                 ]
-                batch_results = _assess_quality_batch_rust(image_bytes_list)
-
-                for record, (ca, ent) in zip(records, batch_results, strict=False):
-                    record[FIELD_COMPRESSION_ARTIFACTS] = float(ca)
-                    record[FIELD_INFORMATION_ENTROPY] = float(ent)
+                if self._needs_analyze() and _analyze_batch_rust:
+                    metrics = ["compression_artifacts", "entropy"]
+                    if self.compute_sharpness:
+                        metrics.extend(SHARPNESS_FIELDS.values())
+                    if self.compute_noise:
+                        metrics.append("noise_sigma")
+                    if self.compute_exposure:
+                        metrics.extend(EXPOSURE_FIELDS.values())
+                    if self.compute_placeholder:
+                        metrics.extend(["placeholder_category", "placeholder_confidence"])
+                    if self.compute_border:
+                        metrics.append("border_fraction")
+                    if self.compute_phash:
+                        metrics.append("phash")
+                    analyses, statuses = _analyze_batch_rust(
+                        image_bytes_list,
+                        metrics,
+                        hash_size=self.hash_size,
+                        hash_algorithm="phash",
+                        hash_encoding="hex",
+                        return_status=True,
+                        analysis_max_side=self.analysis_max_side,
+                        background=self.background,
+                        limits=self._rust_limits,
+                    )
+                    for record, analysis in zip(records, analyses, strict=False):
+                        ok = analysis is not None
+                        record[FIELD_COMPRESSION_ARTIFACTS] = analysis["compression_artifacts"] if ok else None
+                        record[FIELD_INFORMATION_ENTROPY] = analysis["entropy"] if ok else None
+                        if self.compute_sharpness:
+                            for field, metric in SHARPNESS_FIELDS.items():
+                                record[field] = analysis[metric] if ok else None
+                        if self.compute_noise:
+                            record[FIELD_NOISE_SIGMA] = analysis["noise_sigma"] if ok else None
+                        if self.compute_exposure:
+                            for field, metric in EXPOSURE_FIELDS.items():
+                                record[field] = analysis[metric] if ok else None
+                        if self.compute_placeholder:
+                            record[FIELD_PLACEHOLDER_CATEGORY] = analysis["placeholder_category"] if ok else None
+                            record[FIELD_PLACEHOLDER_CONFIDENCE] = analysis["placeholder_confidence"] if ok else None
+                        if self.compute_border:
+                            record[FIELD_BORDER_FRACTION] = analysis["border_fraction"] if ok else None
+                        if ok and self.compute_phash:
+                            record[FIELD_PHASH] = analysis["phash"]
+                        if self.analysis_max_side is not None:
+                            record[FIELD_ANALYSIS_SCALE] = analysis["analysis_scale"] if ok else None
+                    self.record_item_errors(statuses)
+                    return
+
+                batch_results, statuses = _assess_quality_batch_rust(
+                    image_bytes_list,
+                    return_status=True,
+                    analysis_max_side=self.analysis_max_side,
+                    background=self.background,
+                    limits=self._rust_limits,
+                )
+
+                for record, result, (status, _) in zip(records, batch_results, statuses, strict=False):
+                    ok = status == "ok"
+                    record[FIELD_COMPRESSION_ARTIFACTS] = float(result[0]) if ok else None
+                    record[FIELD_INFORMATION_ENTROPY] = float(result[1]) if ok else None
+                    if self.analysis_max_side is not None:
+                        record[FIELD_ANALYSIS_SCALE] = float(result[2]) if ok else None
+                self.record_item_errors(statuses)
                 return
             except Exception:
                 pass  # Fallback to Python
@@ -77,23 +398,94 @@ class ImageTechnicalQualityRefiner(Refiner):
                     result = self._refine_python(img_obj["bytes"])
                     record[FIELD_COMPRESSION_ARTIFACTS] = result[FIELD_COMPRESSION_ARTIFACTS]
                     record[FIELD_INFORMATION_ENTROPY] = result[FIELD_INFORMATION_ENTROPY]
-                except Exception:
-                    record[FIELD_COMPRESSION_ARTIFACTS] = 0.0
-                    record[FIELD_INFORMATION_ENTROPY] = 0.0
+                    if self.compute_sharpness:
+                        for field in SHARPNESS_FIELDS:
+                            record[field] = result[field]
+                    if self.compute_noise:
+                        record[FIELD_NOISE_SIGMA] = result[FIELD_NOISE_SIGMA]
+                    if self.compute_exposure:
+                        for field in EXPOSURE_FIELDS:
+                            record[field] = result[field]
+                    if self.compute_placeholder:
+                        record[FIELD_PLACEHOLDER_CATEGORY] = result[FIELD_PLACEHOLDER_CATEGORY]
+                        record[FIELD_PLACEHOLDER_CONFIDENCE] = result[FIELD_PLACEHOLDER_CONFIDENCE]
+                    if self.compute_border:
+                        record[FIELD_BORDER_FRACTION] = result[FIELD_BORDER_FRACTION]
+                    if self.analysis_max_side is not None:
+                        record[FIELD_ANALYSIS_SCALE] = 1.0  # Python backend always analyzes at full resolution
+                except Exception as e:
+                    record[FIELD_COMPRESSION_ARTIFACTS] = None
+                    record[FIELD_INFORMATION_ENTROPY] = None
+                    if self.compute_sharpness:
+                        for field in SHARPNESS_FIELDS:
+                            record[field] = None
+                    if self.compute_noise:
+                        record[FIELD_NOISE_SIGMA] = None
+                    if self.compute_exposure:
+                        for field in EXPOSURE_FIELDS:
+                            record[field] = None
+                    if self.compute_placeholder:
+                        record[FIELD_PLACEHOLDER_CATEGORY] = None
+                        record[FIELD_PLACEHOLDER_CONFIDENCE] = None
+                    if self.compute_border:
+                        record[FIELD_BORDER_FRACTION] = None
+                    if self.analysis_max_side is not None:
+                        record[FIELD_ANALYSIS_SCALE] = None
+                    self.record_item_errors([("decode_error", str(e))])
             else:
                 record[FIELD_COMPRESSION_ARTIFACTS] = 0.0
                 record[FIELD_INFORMATION_ENTROPY] = 0.0
+                if self.compute_sharpness:
+                    for field in SHARPNESS_FIELDS:
+                        record[field] = 0.0
+                if self.compute_noise:
+                    record[FIELD_NOISE_SIGMA] = 0.0
+                if self.compute_exposure:
+                    for field in EXPOSURE_FIELDS:
+                        record[field] = 0.0
+                if self.compute_placeholder:
+                    record[FIELD_PLACEHOLDER_CATEGORY] = None
+                    record[FIELD_PLACEHOLDER_CONFIDENCE] = None
+                if self.compute_border:
+                    record[FIELD_BORDER_FRACTION] = None
+                if self.analysis_max_side is not None:
+                    record[FIELD_ANALYSIS_SCALE] = None
+
+    def _needs_analyze(self) -> bool:
+        """Whether the Rust backend needs image_analyze_batch rather than the quality-only batch."""
+        return (
+            self.compute_phash
+            or self.compute_sharpness
+            or self.compute_noise
+            or self.compute_exposure
+            or self.compute_placeholder
+            or self.compute_border
+        )
 
     def _refine_python(self, image_bytes: bytes) -> dict[str, Any]:
         """Python fallback implementation (slower but always available)."""
-        img = Image.open(BytesIO(image_bytes))
-        compression_artifacts = self._detect_compression_artifacts(img, image_bytes)
-        entropy = self._calculate_entropy(img)
+        img = convert_to_srgb(Image.open(BytesIO(image_bytes)))
+        rgb = flatten_alpha(img, self.background)
+        compression_artifacts = self._detect_compression_artifacts(rgb, image_bytes)
+        entropy = self._calculate_entropy(rgb)
 
-        return {
+        result = {
             FIELD_COMPRESSION_ARTIFACTS: compression_artifacts,
             FIELD_INFORMATION_ENTROPY: entropy,
         }
+        if self.compute_sharpness:
+            result.update(self._calculate_sharpness(rgb))
+        if self.compute_noise:
+            result[FIELD_NOISE_SIGMA] = self._estimate_noise(rgb)
+        if self.compute_exposure:
+            result.update(self._calculate_exposure(rgb))
+        if self.compute_placeholder:
+            category, confidence = self._classify_placeholder(img)
+            result[FIELD_PLACEHOLDER_CATEGORY] = category
+            result[FIELD_PLACEHOLDER_CONFIDENCE] = confidence
+        if self.compute_border:
+            _, result[FIELD_BORDER_FRACTION] = detect_borders(img)
+        return result
 
     def _detect_compression_artifacts(
         self,
@@ -167,6 +559,125 @@ class ImageTechnicalQualityRefiner(Refiner):
 
         return overall_entropy
 
+    def _calculate_sharpness(self, img: Image.Image) -> dict[str, float]:
+        """Calculate Laplacian variance, Tenengrad and sharp tile fraction on luma."""
+        gray = luma(img)
+        h, w = gray.shape
+        if h < 3 or w < 3:
+            return dict.fromkeys(SHARPNESS_FIELDS, 0.0)
+
+        center = gray[1:-1, 1:-1]
+        laplacian = gray[1:-1, :-2] + gray[1:-1, 2:] + gray[:-2, 1:-1] + gray[2:, 1:-1] - 4 * center
+        laplacian = laplacian.astype(np.float64)
+        right = gray[:-2, 2:] + 2 * gray[1:-1, 2:] + gray[2:, 2:]
+        left = gray[:-2, :-2] + 2 * gray[1:-1, :-2] + gray[2:, :-2]
+        bottom = gray[2:, :-2] + 2 * gray[2:, 1:-1] + gray[2:, 2:]
+        top = gray[:-2, :-2] + 2 * gray[:-2, 1:-1] + gray[:-2, 2:]
+        gx = (right - left).astype(np.float64)
+        gy = (bottom - top).astype(np.float64)
+        tenengrad = float(np.mean(gx**2 + gy**2))
+
+        # Tile of each interior pixel, as in the Rust backend
+        tile_rows = np.arange(1, h - 1) * SHARPNESS_TILE_GRID // h
+        tile_cols = np.arange(1, w - 1) * SHARPNESS_TILE_GRID // w
+        tiles = (tile_rows[:, None] * SHARPNESS_TILE_GRID + tile_cols[None, :]).ravel()
+        n_tiles = SHARPNESS_TILE_GRID * SHARPNESS_TILE_GRID
+        counts = np.bincount(tiles, minlength=n_tiles)
+        sums = np.bincount(tiles, weights=laplacian.ravel(), minlength=n_tiles)
+        sums_sq = np.bincount(tiles, weights=laplacian.ravel() ** 2, minlength=n_tiles)
+        populated = counts > 0
+        means = sums[populated] / counts[populated]
+        variances = np.maximum(sums_sq[populated] / counts[populated] - means**2, 0.0)
+
+        return {
+            FIELD_LAPLACIAN_VARIANCE: float(laplacian.var()),
+            FIELD_TENENGRAD: tenengrad,
+            FIELD_SHARP_TILE_FRACTION: float(np.mean(variances >= SHARP_TILE_THRESHOLD)),
+        }
+
+    def _estimate_noise(self, img: Image.Image) -> float:
+        """Estimate the luma noise sigma with Immerkær's method."""
+        gray = luma(img)
+        h, w = gray.shape
+        if h < 3 or w < 3:
+            return 0.0
+
+        corners = gray[:-2, :-2] + gray[:-2, 2:] + gray[2:, :-2] + gray[2:, 2:]
+        edges = gray[:-2, 1:-1] + gray[1:-1, :-2] + gray[1:-1, 2:] + gray[2:, 1:-1]
+        response = np.abs(corners - 2 * edges + 4 * gray[1:-1, 1:-1])
+        return float(np.sqrt(np.pi / 2) * response.sum(dtype=np.float64) / (6 * (w - 2) * (h - 2)))
+
+    def _calculate_exposure(self, img: Image.Image) -> dict[str, float]:
+        """Calculate luma, clipping, contrast, colorfulness and saturation statistics."""
+        rgb = np.asarray(img.convert("RGB"), dtype=np.float64).reshape(-1, 3)
+        if rgb.size == 0:
+            return dict.fromkeys(EXPOSURE_FIELDS, 0.0)
+
+        r, g, b = rgb[:, 0], rgb[:, 1], rgb[:, 2]
+        luma = 0.2126 * r + 0.7152 * g + 0.0722 * b
+        rg = r - g
+        yb = 0.5 * (r + g) - b
+        colorfulness = np.hypot(rg.std(), yb.std()) + 0.3 * np.hypot(rg.mean(), yb.mean())
+        max_channel = rgb.max(axis=1)
+        chroma = max_channel - rgb.min(axis=1)
+        saturation = np.divide(chroma, max_channel, out=np.zeros_like(chroma), where=max_channel > 0)
+
+        return {
+            FIELD_LUMA_MEAN: float(luma.mean()),
+            FIELD_LUMA_STD: float(luma.std()),
+            FIELD_HIGHLIGHT_CLIP_FRACTION: float(np.mean(luma >= HIGHLIGHT_CLIP_LUMA)),
+            FIELD_SHADOW_CLIP_FRACTION: float(np.mean(luma <= SHADOW_CLIP_LUMA)),
+            FIELD_RMS_CONTRAST: float(luma.std() / 255.0),
+            FIELD_COLORFULNESS: float(colorfulness),
+            FIELD_SATURATION_MEAN: float(saturation.mean()),
+        }
+
+    def _classify_placeholder(self, img: Image.Image) -> tuple[str, float]:
+        """Classify an image as tiny, transparent, solid, near_solid, gradient or content."""
+        if min(img.size) < PLACEHOLDER_MIN_SIDE:
+            return "tiny", 1.0
+
+        def confidence(value: float) -> float:
+            return float(min(1.0, max(0.5, value)))
+
+        rgba = np.asarray(img.convert("RGBA")).reshape(-1, 4)
+        visible = rgba[rgba[:, 3] >= VISIBLE_ALPHA, :3]
+        coverage = len(visible) / len(rgba)
+        if coverage < TRANSPARENT_MAX_COVERAGE:
+            return "transparent", confidence(1.0 - 0.5 * coverage / TRANSPARENT_MAX_COVERAGE)
+
+        _, counts = np.unique(visible, axis=0, return_counts=True)
+        if len(counts) == 1:
+            return "solid", 1.0
+        dominant = counts.max() / len(visible) if len(counts) <= MAX_COUNTED_COLORS else 0.0
+
+        thumbnail = img.convert("RGB")
+        thumbnail.thumbnail((PLACEHOLDER_THUMBNAIL_SIDE, PLACEHOLDER_THUMBNAIL_SIDE))
+        thumb = np.asarray(thumbnail, dtype=np.int32)
+        std = float(thumb.reshape(-1, 3).std(axis=0).max())
+        if thumb.shape[0] >= 3 and thumb.shape[1] >= 3:
+            lap = (
+                thumb[1:-1, :-2] + thumb[1:-1, 2:] + thumb[:-2, 1:-1] + thumb[2:, 1:-1] - 4 * thumb[1:-1, 1:-1]
+            )
+            laplacian = float(np.abs(lap).mean())
+        else:
+            laplacian = 0.0
+
+        if std <= NEAR_SOLID_MAX_STD or dominant >= NEAR_SOLID_MIN_DOMINANT:
+            flatness = 1.0 - 0.5 * std / NEAR_SOLID_MAX_STD
+            dominance = 0.5 + 0.5 * (dominant - NEAR_SOLID_MIN_DOMINANT) / (1.0 - NEAR_SOLID_MIN_DOMINANT)
+            return "near_solid", confidence(max(flatness, dominance))
+        if laplacian <= GRADIENT_MAX_LAPLACIAN:
+            return "gradient", confidence(1.0 - 0.5 * laplacian / GRADIENT_MAX_LAPLACIAN)
+
+        margin = min(
+            std / NEAR_SOLID_MAX_STD,
+            laplacian / GRADIENT_MAX_LAPLACIAN,
+            (1.0 - dominant) / (1.0 - NEAR_SOLID_MIN_DOMINANT),
+            coverage / TRANSPARENT_MAX_COVERAGE,
+        )
+        return "content", confidence(1.0 - 0.5 / margin)
+
     def _channel_entropy(self, channel: np.ndarray) -> float:
         """Calculate Shannon entropy for a single channel (optimized)."""
         # Use bincount for uint8 channels (faster than histogram)
@@ -187,7 +698,25 @@ class ImageTechnicalQualityRefiner(Refiner):
 
     def get_output_schema(self) -> dict[str, pa.DataType]:
         """Return output schema for new fields added by this refiner."""
-        return {
+        schema = {
             FIELD_COMPRESSION_ARTIFACTS: pa.float32(),
             FIELD_INFORMATION_ENTROPY: pa.float32(),
         }
+        if self.compute_sharpness:
+            for field in SHARPNESS_FIELDS:
+                schema[field] = pa.float32()
+        if self.compute_noise:
+            schema[FIELD_NOISE_SIGMA] = pa.float32()
+        if self.compute_exposure:
+            for field in EXPOSURE_FIELDS:
+                schema[field] = pa.float32()
+        if self.compute_placeholder:
+            schema[FIELD_PLACEHOLDER_CATEGORY] = pa.string()
+            schema[FIELD_PLACEHOLDER_CONFIDENCE] = pa.float32()
+        if self.compute_border:
+            schema[FIELD_BORDER_FRACTION] = pa.float32()
+        if self.compute_phash:
+            schema[FIELD_PHASH] = pa.string()
+        if self.analysis_max_side is not None:
+            schema[FIELD_ANALYSIS_SCALE] = pa.float32()
+        return schema
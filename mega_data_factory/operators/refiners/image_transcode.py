@@ -0,0 +1,209 @@
+"""
+Image Transcode Refiner
+
+Re-encodes images to a canonical format before they are written out: upright, no
+larger than a maximum side, in one format and quality, with metadata stripped.
+This is a Refiner that replaces the image bytes, so output shards are consistent and
+usually much smaller than the source bytes.
+
+Automatically uses the Rust backend if available, which decodes, resizes and
+re-encodes each batch in parallel.
+"""
+
+from io import BytesIO
+from typing import Any
+
+import pyarrow as pa
+from PIL import Image, ImageOps
+
+from mega_data_factory.framework import Refiner
+from mega_data_factory.operators.refiners.image_metadata import FIELD_WIDTH, ImageMetadataRefiner
+from mega_data_factory.operators.refiners.image_technical_quality import (
+    DEFAULT_BACKGROUND,
+    convert_to_srgb,
+    flatten_alpha,
+    output_format_for_source,
+)
+
+# Field name constants
+FIELD_TRANSCODED = "image_transcoded"
+
+# Defaults (match the Rust backend)
+DEFAULT_QUALITY = 90
+DEFAULT_CHROMA_SUBSAMPLING = "4:2:0"
+
+# Pillow save format, resampling filter and JPEG subsampling for each name
+_PIL_SAVE_FORMATS = {"jpeg": "JPEG", "png": "PNG", "webp": "WEBP"}
+_PIL_RESIZE_FILTERS = {
+    "nearest": Image.Resampling.NEAREST,
+    "bilinear": Image.Resampling.BILINEAR,
+    "bicubic": Image.Resampling.BICUBIC,
+    "lanczos": Image.Resampling.LANCZOS,
+}
+_PIL_SUBSAMPLING = {"4:4:4": 0, "4:2:2": 1, "4:2:0": 2}
+
+# Try to load Rust extension (auto-acceleration)
+RUST_BACKEND_AVAILABLE = False
+_transcode_batch_rust = None
+_DecodeLimits = None
+
+try:
+    from mega_data_factory import rust_operators as _rust_module  # type: ignore
+
+    _DecodeLimits = getattr(_rust_module, "DecodeLimits", None)
+    _transcode_batch_rust = getattr(_rust_module, "image_transcode_batch", None)
+    if _transcode_batch_rust is not None:
+        RUST_BACKEND_AVAILABLE = True
+except ImportError:
+    pass
+
+
+class ImageTranscodeRefiner(Refiner):
+    """Refiner that re-encodes images to a canonical format.
+
+    Output fields:
+    - image_transcoded: Whether `image.bytes` was replaced by the re-encoded image
+
+    Images that fail to decode keep their original bytes and are counted in the
+    operator's error stats. If `ImageMetadataRefiner` already ran, its fields are
+    refreshed for the re-encoded images.
+
+    Pixels are converted to sRGB through the embedded ICC profile (CMYK included), so
+    the output carries no ICC profile.
+    """
+
+    def __init__(
+        self,
+        output_format: str | None = "jpeg",
+        quality: int | None = None,
+        chroma_subsampling: str = DEFAULT_CHROMA_SUBSAMPLING,
+        max_side: int | None = None,
+        resize_filter: str = "lanczos",
+        strip_metadata: bool = True,
+        decode_limits: dict[str, Any] | None = None,
+        background: tuple[int, int, int] = DEFAULT_BACKGROUND,
+    ):
+        """Initialize transcode refiner.
+
+        Args:
+            output_format: "jpeg", "png" or "webp"; None writes JPEG and opaque WebP
+                sources as JPEG and everything else as PNG. WebP output is lossless, so it
+                is usually larger than a lossy source.
+            quality: JPEG quality (1-100, default 90). Not accepted with
+                output_format="webp", which has no quality setting.
+            chroma_subsampling: JPEG chroma subsampling ("4:2:0", "4:2:2" or "4:4:4").
+            max_side: Downscale images whose longer side exceeds this many pixels.
+            resize_filter: "lanczos", "bicubic", "bilinear" or "nearest".
+            strip_metadata: Drop all metadata; otherwise the EXIF data is kept (with the
+                EXIF orientation reset, as the pixels are rotated upright).
+            decode_limits: Limits applied by the Rust backend before decoding each image
+                (same keys as ImageTechnicalQualityRefiner).
+            background: RGB color transparent images are composited onto for JPEG output.
+        """
+        super().__init__()
+        if output_format is not None and output_format.lower() not in _PIL_SAVE_FORMATS:
+            raise ValueError(f"unsupported output_format: {output_format}")
+        if output_format is not None and output_format.lower() == "webp" and quality is not None:
+            raise ValueError("quality does not apply to output_format='webp', which is lossless")
+        if chroma_subsampling not in _PIL_SUBSAMPLING:
+            raise ValueError(f"unsupported chroma_subsampling: {chroma_subsampling}")
+        if resize_filter not in _PIL_RESIZE_FILTERS:
+            raise ValueError(f"unknown resize_filter: {resize_filter}")
+        self.output_format = output_format.lower() if output_format else None
+        self.quality = quality
+        self.chroma_subsampling = chroma_subsampling
+        self.max_side = max_side
+        self.resize_filter = resize_filter
+        self.strip_metadata = strip_metadata
+        self.decode_limits = decode_limits or {}
+        self._rust_limits = _DecodeLimits(**self.decode_limits) if _DecodeLimits else None
+        self.background = tuple(background)
+        self._metadata_refiner = ImageMetadataRefiner()
+
+    def refine_batch(self, records: list[dict[str, Any]]) -> None:
+        """Re-encode a batch of records (inplace)."""
+        if not records:
+            return
+
+        if RUST_BACKEND_AVAILABLE and _transcode_batch_rust:
+            try:
+                image_bytes_list = [
+                    record.get("image", {}).get("bytes", b"") if isinstance(record.get("image"), dict) else b""
+                    for record in records
+                ]
+                results, statuses = _transcode_batch_rust(
+                    image_bytes_list,
+                    return_status=True,
+                    output_format=self.output_format,
+                    quality=self.quality,
+                    chroma_subsampling=self.chroma_subsampling,
+                    max_side=self.max_side,
+                    resize_filter=self.resize_filter,
+                    strip_metadata=self.strip_metadata,
+                    background=self.background,
+                    limits=self._rust_limits,
+                )
+                self.record_item_errors(statuses)
+                for record, result in zip(records, results, strict=False):
+                    self._apply(record, result["image"] if result else None)
+                self._refresh_metadata(records)
+                return
+            except Exception:
+                pass  # Fallback to Python
+
+        for record in records:
+            img_obj = record.get("image", {})
+            if not (isinstance(img_obj, dict) and "bytes" in img_obj):
+                self._apply(record, None)
+                continue
+            try:
+                self._apply(record, self._transcode(img_obj["bytes"]))
+            except Exception as e:
+                self._apply(record, None)
+                self.record_item_errors([("decode_error", str(e))])
+        self._refresh_metadata(records)
+
+    @staticmethod
+    def _apply(record: dict[str, Any], transcoded: bytes | None) -> None:
+        """Swap in the re-encoded image."""
+        record[FIELD_TRANSCODED] = transcoded is not None
+        if transcoded is not None:
+            record["image"]["bytes"] = transcoded
+
+    def _refresh_metadata(self, records: list[dict[str, Any]]) -> None:
+        """Re-read ImageMetadataRefiner fields of re-encoded images that have them."""
+        stale = [record for record in records if record[FIELD_TRANSCODED] and FIELD_WIDTH in record]
+        if stale:
+            self._metadata_refiner.refine_batch(stale)
+
+    def _transcode(self, image_bytes: bytes) -> bytes:
+        """Decode, downscale and re-encode one image (Python fallback)."""
+        source = Image.open(BytesIO(image_bytes))
+        icc_profile = source.info.get("icc_profile")
+        exif = source.getexif()
+        img = convert_to_srgb(ImageOps.exif_transpose(source), icc_profile)
+        if self.max_side and max(img.size) > self.max_side:
+            img.thumbnail((self.max_side, self.max_side), _PIL_RESIZE_FILTERS[self.resize_filter])
+
+        output_format = self.output_format
+        if output_format is None:
+            output_format = output_format_for_source(source.format, img)
+        options: dict[str, Any] = {}
+        if not self.strip_metadata:
+            exif.pop(0x0112, None)  # orientation is applied to the pixels
+            options["exif"] = exif.tobytes()
+        if output_format == "jpeg":
+            if img.mode not in ("RGB", "L"):
+                img = flatten_alpha(img, self.background)
+            quality = DEFAULT_QUALITY if self.quality is None else self.quality
+            options.update(quality=quality, subsampling=_PIL_SUBSAMPLING[self.chroma_subsampling])
+        elif output_format == "webp":
+            options["lossless"] = True
+
+        buffer = BytesIO()
+        img.save(buffer, format=_PIL_SAVE_FORMATS[output_format], **options)
+        return buffer.getvalue()
+
+    def get_output_schema(self) -> dict[str, pa.DataType]:
+        """Return output schema for new fields added by this refiner."""
+        return {FIELD_TRANSCODED: pa.bool_()}
@@ -0,0 +1,699 @@
+//! Zero-copy Arrow input/output for batch operators
+//!
+//! Implements the parts of the Arrow C data interface and its PyCapsule protocol that
+//! the batch operators need, so payloads are shared with pyarrow instead of copied:
+//! - `ArrowBinaryInput`: Borrows a binary/large_binary/string/large_string array
+//! - `ArrowColumn`: Owned column buffers, exported as a `pyarrow.RecordBatch`
+//! - `record_batch_output`: Build an `*_arrow` function's return value
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr::{self, NonNull};
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyCapsule;
+
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+// ============================================================================
+// C Data Interface Structs
+// ============================================================================
+
+/// `struct ArrowSchema` of the Arrow C data interface
+#[repr(C)]
+struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+/// `struct ArrowArray` of the Arrow C data interface
+#[repr(C)]
+struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+/// Field may contain nulls
+const ARROW_FLAG_NULLABLE: i64 = 2;
+
+const SCHEMA_CAPSULE_NAME: &CStr = c"arrow_schema";
+const ARRAY_CAPSULE_NAME: &CStr = c"arrow_array";
+
+// ============================================================================
+// Input
+// ============================================================================
+
+/// A pyarrow binary or string array borrowed through `__arrow_c_array__`
+///
+/// The capsules own the imported array and release it when dropped, so element
+/// slices borrow from `self` and are valid without copying.
+pub(crate) struct ArrowBinaryInput<'py> {
+    _schema: Bound<'py, PyCapsule>,
+    _array: Bound<'py, PyCapsule>,
+    array: NonNull<ArrowArray>,
+    large_offsets: bool,
+}
+
+impl<'py> ArrowBinaryInput<'py> {
+    /// Import an object implementing the Arrow PyCapsule interface (e.g. `pyarrow.Array`)
+    pub fn import(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if !obj.hasattr("__arrow_c_array__")? {
+            return Err(PyTypeError::new_err(
+                "expected an Arrow array implementing __arrow_c_array__ (e.g. pyarrow.Array; \
+                 call combine_chunks() on a ChunkedArray)",
+            ));
+        }
+        let (schema, array): (Bound<'py, PyCapsule>, Bound<'py, PyCapsule>) =
+            obj.call_method0("__arrow_c_array__")?.extract()?;
+        let schema_ptr = schema
+            .pointer_checked(Some(SCHEMA_CAPSULE_NAME))?
+            .cast::<ArrowSchema>();
+        let array_ptr = array
+            .pointer_checked(Some(ARRAY_CAPSULE_NAME))?
+            .cast::<ArrowArray>();
+
+        // SAFETY: per the PyCapsule interface both capsules hold initialized structs,
+        // which stay valid while the capsules are alive
+        let (format, array_ref) = unsafe {
+            (
+                CStr::from_ptr(schema_ptr.as_ref().format),
+                array_ptr.as_ref(),
+            )
+        };
+        let large_offsets = check_binary_layout(format.to_bytes(), array_ref)?;
+
+        Ok(Self {
+            _schema: schema,
+            _array: array,
+            array: array_ptr,
+            large_offsets,
+        })
+    }
+
+    /// Borrow every element; null elements are `None`
+    pub fn values(&self) -> Vec<Option<&[u8]>> {
+        // SAFETY: `import` checked the layout, and the buffers live as long as `self`
+        unsafe { binary_values(self.array.as_ref(), self.large_offsets) }
+    }
+}
+
+/// Check that an imported array is a binary or string array; returns whether it has
+/// 64-bit offsets
+fn check_binary_layout(format: &[u8], array: &ArrowArray) -> PyResult<bool> {
+    let large_offsets = match format {
+        b"z" | b"u" => false,
+        b"Z" | b"U" => true,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "unsupported Arrow type (format {:?}); expected binary, large_binary, \
+                 string or large_string",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+    if array.release.is_none() {
+        return Err(PyValueError::new_err(
+            "Arrow array has already been released",
+        ));
+    }
+    if array.n_buffers != 3 || array.length < 0 || array.offset < 0 {
+        return Err(PyValueError::new_err("malformed Arrow binary array"));
+    }
+    Ok(large_offsets)
+}
+
+/// Borrow the elements of a binary array checked by `check_binary_layout`
+///
+/// # Safety
+/// The buffers must follow the Arrow binary layout (validity bitmap, offsets, data)
+/// with the given offset width, and outlive the returned slices.
+unsafe fn binary_values(array: &ArrowArray, large_offsets: bool) -> Vec<Option<&[u8]>> {
+    let length = array.length as usize;
+    let offset = array.offset as usize;
+    if length == 0 {
+        return Vec::new();
+    }
+    let buffers = std::slice::from_raw_parts(array.buffers, 3);
+    let validity = buffers[0].cast::<u8>();
+    let data = buffers[2].cast::<u8>();
+
+    (offset..offset + length)
+        .map(|pos| {
+            if array.null_count != 0
+                && !validity.is_null()
+                && *validity.add(pos / 8) & (1 << (pos % 8)) == 0
+            {
+                return None;
+            }
+            let (start, end) = if large_offsets {
+                let offsets = buffers[1].cast::<i64>();
+                (*offsets.add(pos) as usize, *offsets.add(pos + 1) as usize)
+            } else {
+                let offsets = buffers[1].cast::<i32>();
+                (*offsets.add(pos) as usize, *offsets.add(pos + 1) as usize)
+            };
+            if end <= start {
+                return Some(&[][..]);
+            }
+            Some(std::slice::from_raw_parts(data.add(start), end - start))
+        })
+        .collect()
+}
+
+/// Error for a null element of an input array
+pub(crate) fn null_input() -> ItemError {
+    ItemError::new(ItemStatus::DecodeError, "null input")
+}
+
+// ============================================================================
+// Output
+// ============================================================================
+
+/// Owned buffer with the alignment of its element type
+pub(crate) enum Buffer {
+    U8(Vec<u8>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    F64(Vec<f64>),
+}
+
+impl Buffer {
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            Self::U8(values) => values.as_ptr().cast(),
+            Self::U32(values) => values.as_ptr().cast(),
+            Self::I32(values) => values.as_ptr().cast(),
+            Self::I64(values) => values.as_ptr().cast(),
+            Self::U64(values) => values.as_ptr().cast(),
+            Self::F64(values) => values.as_ptr().cast(),
+        }
+    }
+}
+
+/// Primitive types that can be exported as fixed-width Arrow columns
+pub(crate) trait ArrowNative: Copy + Default {
+    const FORMAT: &'static CStr;
+    fn into_buffer(values: Vec<Self>) -> Buffer;
+}
+
+impl ArrowNative for u8 {
+    const FORMAT: &'static CStr = c"C";
+    fn into_buffer(values: Vec<Self>) -> Buffer {
+        Buffer::U8(values)
+    }
+}
+
+impl ArrowNative for u32 {
+    const FORMAT: &'static CStr = c"I";
+    fn into_buffer(values: Vec<Self>) -> Buffer {
+        Buffer::U32(values)
+    }
+}
+
+impl ArrowNative for u64 {
+    const FORMAT: &'static CStr = c"L";
+    fn into_buffer(values: Vec<Self>) -> Buffer {
+        Buffer::U64(values)
+    }
+}
+
+impl ArrowNative for f64 {
+    const FORMAT: &'static CStr = c"g";
+    fn into_buffer(values: Vec<Self>) -> Buffer {
+        Buffer::F64(values)
+    }
+}
+
+/// A named, nullable column ready to be exported
+pub(crate) struct ArrowColumn {
+    name: CString,
+    format: &'static CStr,
+    length: usize,
+    null_count: usize,
+    validity: Option<Vec<u8>>,
+    buffers: Vec<Buffer>,
+}
+
+/// Validity bitmap for `valid`, or `None` when every element is valid
+fn validity_bitmap(valid: &[bool]) -> (Option<Vec<u8>>, usize) {
+    let null_count = valid.iter().filter(|&&v| !v).count();
+    if null_count == 0 {
+        return (None, 0);
+    }
+    let mut bitmap = vec![0u8; valid.len().div_ceil(8)];
+    for (i, _) in valid.iter().enumerate().filter(|(_, &v)| v) {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+    (Some(bitmap), null_count)
+}
+
+impl ArrowColumn {
+    /// Fixed-width column; `None` values become nulls
+    pub fn primitive<T: ArrowNative>(
+        name: &str,
+        values: impl IntoIterator<Item = Option<T>>,
+    ) -> Self {
+        let (values, valid): (Vec<T>, Vec<bool>) = values
+            .into_iter()
+            .map(|value| (value.unwrap_or_default(), value.is_some()))
+            .unzip();
+        let (validity, null_count) = validity_bitmap(&valid);
+        Self {
+            name: column_name(name),
+            format: T::FORMAT,
+            length: values.len(),
+            null_count,
+            validity,
+            buffers: vec![T::into_buffer(values)],
+        }
+    }
+
+    /// Boolean (bit-packed) column; `None` values become nulls
+    pub fn boolean(name: &str, values: impl IntoIterator<Item = Option<bool>>) -> Self {
+        let (values, valid): (Vec<bool>, Vec<bool>) = values
+            .into_iter()
+            .map(|value| (value.unwrap_or_default(), value.is_some()))
+            .unzip();
+        let mut bits = vec![0u8; values.len().div_ceil(8)];
+        for (i, _) in values.iter().enumerate().filter(|(_, &v)| v) {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+        let (validity, null_count) = validity_bitmap(&valid);
+        Self {
+            name: column_name(name),
+            format: c"b",
+            length: values.len(),
+            null_count,
+            validity,
+            buffers: vec![Buffer::U8(bits)],
+        }
+    }
+
+    /// UTF-8 string column; `None` values become nulls
+    ///
+    /// Uses `large_string` when the data does not fit 32-bit offsets.
+    pub fn utf8<S: AsRef<str>>(name: &str, values: impl IntoIterator<Item = Option<S>>) -> Self {
+        Self::variable_width(name, values, |v| v.as_ref().as_bytes(), (c"u", c"U"))
+    }
+
+    /// Binary column; `None` values become nulls
+    ///
+    /// Uses `large_binary` when the data does not fit 32-bit offsets.
+    pub fn binary<B: AsRef<[u8]>>(name: &str, values: impl IntoIterator<Item = Option<B>>) -> Self {
+        Self::variable_width(name, values, |v| v.as_ref(), (c"z", c"Z"))
+    }
+
+    /// Offsets + data column with the given (32-bit, 64-bit offset) formats
+    fn variable_width<T>(
+        name: &str,
+        values: impl IntoIterator<Item = Option<T>>,
+        as_bytes: impl Fn(&T) -> &[u8],
+        formats: (&'static CStr, &'static CStr),
+    ) -> Self {
+        let mut data = Vec::new();
+        let mut offsets = vec![0i64];
+        let mut valid = Vec::new();
+        for value in values {
+            if let Some(value) = &value {
+                data.extend_from_slice(as_bytes(value));
+            }
+            offsets.push(data.len() as i64);
+            valid.push(value.is_some());
+        }
+        let (validity, null_count) = validity_bitmap(&valid);
+        let (format, offsets) = if data.len() <= i32::MAX as usize {
+            let offsets = offsets.into_iter().map(|o| o as i32).collect();
+            (formats.0, Buffer::I32(offsets))
+        } else {
+            (formats.1, Buffer::I64(offsets))
+        };
+        Self {
+            name: column_name(name),
+            format,
+            length: valid.len(),
+            null_count,
+            validity,
+            buffers: vec![offsets, Buffer::U8(data)],
+        }
+    }
+}
+
+/// Column names are static identifiers without interior NULs
+fn column_name(name: &str) -> CString {
+    CString::new(name).expect("column name contains a NUL byte")
+}
+
+/// Keeps everything an exported `ArrowSchema` points to alive
+struct SchemaPrivate {
+    format: CString,
+    name: CString,
+    children: Vec<*mut ArrowSchema>,
+}
+
+/// Keeps everything an exported `ArrowArray` points to alive
+struct ArrayPrivate {
+    _buffers: Vec<Buffer>,
+    buffer_ptrs: Vec<*const c_void>,
+    children: Vec<*mut ArrowArray>,
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    let Some(schema) = schema.as_mut() else {
+        return;
+    };
+    let mut private = Box::from_raw(schema.private_data.cast::<SchemaPrivate>());
+    for child in private.children.drain(..) {
+        if let Some(release) = (*child).release {
+            release(child);
+        }
+        drop(Box::from_raw(child));
+    }
+    schema.release = None;
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    let Some(array) = array.as_mut() else {
+        return;
+    };
+    let mut private = Box::from_raw(array.private_data.cast::<ArrayPrivate>());
+    for child in private.children.drain(..) {
+        if let Some(release) = (*child).release {
+            release(child);
+        }
+        drop(Box::from_raw(child));
+    }
+    array.release = None;
+}
+
+/// Build an exported schema; `children` become owned by it
+fn export_schema(
+    format: CString,
+    name: CString,
+    flags: i64,
+    children: Vec<ArrowSchema>,
+) -> ArrowSchema {
+    let mut private = Box::new(SchemaPrivate {
+        format,
+        name,
+        children: children
+            .into_iter()
+            .map(|c| Box::into_raw(Box::new(c)))
+            .collect(),
+    });
+    ArrowSchema {
+        format: private.format.as_ptr(),
+        name: private.name.as_ptr(),
+        metadata: ptr::null(),
+        flags,
+        n_children: private.children.len() as i64,
+        children: private.children.as_mut_ptr(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_schema),
+        private_data: Box::into_raw(private).cast(),
+    }
+}
+
+/// Build an exported array; `validity` is the first buffer, `children` become owned by it
+fn export_array(
+    length: usize,
+    null_count: usize,
+    validity: Option<Vec<u8>>,
+    buffers: Vec<Buffer>,
+    children: Vec<ArrowArray>,
+) -> ArrowArray {
+    let validity_ptr = validity.as_ref().map_or(ptr::null(), |v| v.as_ptr().cast());
+    let mut buffer_ptrs = vec![validity_ptr];
+    buffer_ptrs.extend(buffers.iter().map(Buffer::as_ptr));
+    let mut owned = buffers;
+    owned.extend(validity.map(Buffer::U8));
+
+    let mut private = Box::new(ArrayPrivate {
+        _buffers: owned,
+        buffer_ptrs,
+        children: children
+            .into_iter()
+            .map(|c| Box::into_raw(Box::new(c)))
+            .collect(),
+    });
+    ArrowArray {
+        length: length as i64,
+        null_count: null_count as i64,
+        offset: 0,
+        n_buffers: private.buffer_ptrs.len() as i64,
+        n_children: private.children.len() as i64,
+        buffers: private.buffer_ptrs.as_mut_ptr(),
+        children: private.children.as_mut_ptr(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_array),
+        private_data: Box::into_raw(private).cast(),
+    }
+}
+
+/// Export columns as a struct array, the C data interface form of a RecordBatch
+fn export_struct(columns: Vec<ArrowColumn>, length: usize) -> (ArrowSchema, ArrowArray) {
+    let (schemas, arrays) = columns
+        .into_iter()
+        .map(|column| {
+            let schema = export_schema(
+                column.format.to_owned(),
+                column.name,
+                ARROW_FLAG_NULLABLE,
+                Vec::new(),
+            );
+            let array = export_array(
+                column.length,
+                column.null_count,
+                column.validity,
+                column.buffers,
+                Vec::new(),
+            );
+            (schema, array)
+        })
+        .unzip();
+    (
+        export_schema(c"+s".to_owned(), c"".to_owned(), 0, schemas),
+        export_array(length, 0, None, Vec::new(), arrays),
+    )
+}
+
+unsafe extern "C" fn drop_schema_capsule(capsule: *mut ffi::PyObject) {
+    let schema =
+        ffi::PyCapsule_GetPointer(capsule, SCHEMA_CAPSULE_NAME.as_ptr()).cast::<ArrowSchema>();
+    if schema.is_null() {
+        ffi::PyErr_Clear();
+        return;
+    }
+    if let Some(release) = (*schema).release {
+        release(schema);
+    }
+    drop(Box::from_raw(schema));
+}
+
+unsafe extern "C" fn drop_array_capsule(capsule: *mut ffi::PyObject) {
+    let array =
+        ffi::PyCapsule_GetPointer(capsule, ARRAY_CAPSULE_NAME.as_ptr()).cast::<ArrowArray>();
+    if array.is_null() {
+        ffi::PyErr_Clear();
+        return;
+    }
+    if let Some(release) = (*array).release {
+        release(array);
+    }
+    drop(Box::from_raw(array));
+}
+
+/// Wrap a heap-allocated C struct in a capsule that releases it when collected
+fn new_capsule<'py, T>(
+    py: Python<'py>,
+    value: T,
+    name: &'static CStr,
+    destructor: unsafe extern "C" fn(*mut ffi::PyObject),
+) -> PyResult<Bound<'py, PyAny>> {
+    let ptr = Box::into_raw(Box::new(value));
+    // SAFETY: the capsule takes ownership of `ptr`; `destructor` frees it exactly once
+    unsafe {
+        let capsule = ffi::PyCapsule_New(ptr.cast(), name.as_ptr(), Some(destructor));
+        if capsule.is_null() {
+            drop(Box::from_raw(ptr));
+        }
+        Bound::from_owned_ptr_or_err(py, capsule)
+    }
+}
+
+/// Columns exposed to pyarrow through `__arrow_c_array__`
+#[pyclass]
+struct ArrowBatchExport {
+    columns: Option<Vec<ArrowColumn>>,
+    length: usize,
+}
+
+#[pymethods]
+impl ArrowBatchExport {
+    /// Export as (schema, array) capsules; consumes the columns
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_array__<'py>(
+        &mut self,
+        py: Python<'py>,
+        requested_schema: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+        let _ = requested_schema;
+        let columns = self
+            .columns
+            .take()
+            .ok_or_else(|| PyValueError::new_err("Arrow batch has already been exported"))?;
+        let (schema, array) = export_struct(columns, self.length);
+        Ok((
+            new_capsule(py, schema, SCHEMA_CAPSULE_NAME, drop_schema_capsule)?,
+            new_capsule(py, array, ARRAY_CAPSULE_NAME, drop_array_capsule)?,
+        ))
+    }
+}
+
+/// Build a `pyarrow.RecordBatch` from equal-length columns
+pub(crate) fn record_batch(
+    py: Python<'_>,
+    columns: Vec<ArrowColumn>,
+) -> PyResult<Bound<'_, PyAny>> {
+    let length = columns.first().map_or(0, |column| column.length);
+    let export = Bound::new(
+        py,
+        ArrowBatchExport {
+            columns: Some(columns),
+            length,
+        },
+    )?;
+    py.import("pyarrow")?
+        .call_method1("record_batch", (export,))
+}
+
+/// Build an `*_arrow` batch function's return value
+///
+/// Failed items are nulls in `columns`. With `return_status`, `status` and
+/// `status_message` columns are appended.
+pub(crate) fn record_batch_output<'py, T>(
+    py: Python<'py>,
+    mut columns: Vec<ArrowColumn>,
+    outcomes: &[ItemResult<T>],
+    return_status: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    if return_status {
+        columns.push(ArrowColumn::utf8(
+            "status",
+            outcomes.iter().map(|outcome| {
+                Some(
+                    outcome
+                        .as_ref()
+                        .map_or_else(|err| err.status.as_str(), |_| "ok"),
+                )
+            }),
+        ));
+        columns.push(ArrowColumn::utf8(
+            "status_message",
+            outcomes
+                .iter()
+                .map(|outcome| outcome.as_ref().err().map(|err| err.message.as_str())),
+        ));
+    }
+    record_batch(py, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Export a column's buffers as a standalone array
+    fn export_column(column: ArrowColumn) -> ArrowArray {
+        export_array(
+            column.length,
+            column.null_count,
+            column.validity,
+            column.buffers,
+            Vec::new(),
+        )
+    }
+
+    fn release(mut array: ArrowArray) {
+        // SAFETY: the array was built by `export_array` and is released once
+        unsafe { release_array(&mut array) };
+    }
+
+    #[test]
+    fn binary_values_follow_offsets_and_validity() {
+        let values = [Some(&b"abc"[..]), None, Some(b""), Some(b"xy")];
+        let array = export_column(ArrowColumn::binary("image", values));
+        let large_offsets = check_binary_layout(b"z", &array).unwrap();
+        assert!(!large_offsets);
+        // SAFETY: the buffers are owned by `array`, which outlives the slices
+        assert_eq!(unsafe { binary_values(&array, large_offsets) }, values);
+        release(array);
+    }
+
+    #[test]
+    fn binary_values_respect_the_array_offset() {
+        let values = [Some(&b"abc"[..]), None, Some(b"de"), Some(b"f")];
+        let mut array = export_column(ArrowColumn::binary("image", values));
+        array.offset = 1;
+        array.length = 2;
+        // SAFETY: as above; the slice stays within the exported buffers
+        assert_eq!(
+            unsafe { binary_values(&array, false) },
+            [None, Some(&b"de"[..])]
+        );
+        release(array);
+    }
+
+    #[test]
+    fn binary_values_read_large_offsets() {
+        let offsets = Buffer::I64(vec![0, 2, 2, 5]);
+        let data = Buffer::U8(b"hello".to_vec());
+        let array = export_array(3, 0, None, vec![offsets, data], Vec::new());
+        assert!(check_binary_layout(b"U", &array).unwrap());
+        // SAFETY: as above
+        let values = unsafe { binary_values(&array, true) };
+        assert_eq!(values, [Some(&b"he"[..]), Some(b""), Some(b"llo")]);
+        release(array);
+    }
+
+    #[test]
+    fn unsupported_or_malformed_arrays_are_rejected() {
+        let array = export_column(ArrowColumn::binary("image", [Some(b"a")]));
+        assert!(check_binary_layout(b"i", &array).is_err());
+        release(array);
+
+        let primitive = export_column(ArrowColumn::primitive("width", [Some(1u32)]));
+        assert!(check_binary_layout(b"z", &primitive).is_err());
+        release(primitive);
+
+        let mut released = export_column(ArrowColumn::binary("image", [Some(b"a")]));
+        // SAFETY: released exactly once; only the (now cleared) fields are read after
+        unsafe { release_array(&mut released) };
+        assert!(check_binary_layout(b"z", &released).is_err());
+    }
+
+    #[test]
+    fn validity_bitmap_is_omitted_without_nulls() {
+        assert_eq!(validity_bitmap(&[true, true]), (None, 0));
+        let (bitmap, null_count) =
+            validity_bitmap(&[true, false, true, true, false, true, true, true, false]);
+        assert_eq!(bitmap, Some(vec![0b1110_1101, 0]));
+        assert_eq!(null_count, 3);
+    }
+}
@@ -0,0 +1,150 @@
+//! Non-blocking batch submission
+//!
+//! `submit_*` functions validate their arguments, start the batch on the rayon pool
+//! without holding the GIL and return a `BatchFuture` immediately:
+//! - `BatchFuture.done()`: Whether the batch has finished (never blocks)
+//! - `BatchFuture.result(timeout=None)`: Wait for the batch and return what the
+//!   matching `*_batch` function would have returned
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError};
+use pyo3::prelude::*;
+
+use crate::executor::spawn_in_pool;
+use crate::status::parse_timeout;
+
+/// Converts a finished batch's Rust results into the Python return value
+pub(crate) type Finisher =
+    Box<dyn for<'py> FnOnce(Python<'py>) -> PyResult<Bound<'py, PyAny>> + Send>;
+
+/// Box a conversion closure as a `Finisher` (pins down its higher-ranked signature)
+pub(crate) fn finisher<F>(convert: F) -> Finisher
+where
+    F: for<'py> FnOnce(Python<'py>) -> PyResult<Bound<'py, PyAny>> + Send + 'static,
+{
+    Box::new(convert)
+}
+
+enum Slot {
+    /// Batch still running
+    Running,
+    /// Batch finished; results not yet converted to Python
+    Finished(Finisher),
+    /// A `result()` call is converting the results
+    Converting,
+    /// Python return value, kept so `result()` can be called repeatedly
+    Converted(PyResult<Py<PyAny>>),
+}
+
+/// State shared with the rayon job
+///
+/// The mutex is never held while waiting for the GIL or running Python code; waiting
+/// for the batch happens on the condvar with the GIL released.
+struct Shared {
+    slot: Mutex<Slot>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Slot> {
+        self.slot.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set(&self, slot: Slot) {
+        *self.lock() = slot;
+        self.changed.notify_all();
+    }
+}
+
+/// Handle to a batch running in the background
+#[pyclass]
+pub struct BatchFuture {
+    shared: Arc<Shared>,
+}
+
+impl BatchFuture {
+    /// Run `job` on the rayon pool; its returned finisher builds the Python result
+    ///
+    /// Uses the pool of the `RustExecutor` active on the calling thread, if any.
+    pub(crate) fn spawn<F>(job: F) -> Self
+    where
+        F: FnOnce() -> Finisher + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            slot: Mutex::new(Slot::Running),
+            changed: Condvar::new(),
+        });
+        let job_shared = Arc::clone(&shared);
+        spawn_in_pool(move || {
+            let finisher = panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| {
+                Box::new(|_py: Python<'_>| Err(PyRuntimeError::new_err("batch job panicked")))
+            });
+            job_shared.set(Slot::Finished(finisher));
+        });
+        Self { shared }
+    }
+}
+
+#[pymethods]
+impl BatchFuture {
+    /// Whether the batch has finished
+    fn done(&self) -> bool {
+        !matches!(*self.shared.lock(), Slot::Running)
+    }
+
+    /// Wait for the batch and return its result
+    ///
+    /// Waits without holding the GIL. Raises TimeoutError if `timeout` seconds pass
+    /// first; the batch keeps running and `result()` can be called again.
+    #[pyo3(signature = (timeout=None))]
+    fn result(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        let timeout = parse_timeout(timeout)?;
+
+        // Wait until the results are available; take them if nobody converted them yet
+        let shared = &*self.shared;
+        let finisher = py.detach(|| {
+            let pending = |slot: &mut Slot| matches!(slot, Slot::Running | Slot::Converting);
+            let mut slot = match timeout {
+                Some(timeout) => {
+                    let (slot, wait) = shared
+                        .changed
+                        .wait_timeout_while(shared.lock(), timeout, pending)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if wait.timed_out() {
+                        return Err(());
+                    }
+                    slot
+                }
+                None => shared
+                    .changed
+                    .wait_while(shared.lock(), pending)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+            match std::mem::replace(&mut *slot, Slot::Converting) {
+                Slot::Finished(finisher) => Ok(Some(finisher)),
+                other => {
+                    *slot = other;
+                    Ok(None)
+                }
+            }
+        });
+        let finisher = finisher
+            .map_err(|()| PyTimeoutError::new_err("batch did not finish within timeout"))?;
+
+        if let Some(finisher) = finisher {
+            let value = panic::catch_unwind(AssertUnwindSafe(|| finisher(py)))
+                .unwrap_or_else(|_| {
+                    Err(PyRuntimeError::new_err("batch result conversion panicked"))
+                })
+                .map(Bound::unbind);
+            shared.set(Slot::Converted(value));
+        }
+        match &*shared.lock() {
+            Slot::Converted(Ok(value)) => Ok(value.clone_ref(py)),
+            Slot::Converted(Err(err)) => Err(err.clone_ref(py)),
+            _ => unreachable!("results are converted before the slot is released"),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Color management: ICC-aware conversion to sRGB, CMYK decoding and alpha compositing
+//!
+//! - `color_space_name`: Color space an image was encoded in, from its ICC profile or,
+//!   without one, its pixel format
+//! - `convert_to_srgb`: Apply an embedded RGB ICC profile (Display P3, Adobe RGB, ...),
+//!   converting the pixels to sRGB
+//! - `ycck_to_cmyk` / `cmyk_to_srgb`: Convert the raw samples of CMYK and YCCK JPEGs to
+//!   sRGB, through the embedded profile when there is one
+//! - `flatten_alpha`: Composite a transparent image onto a solid background
+//!
+//! Transforms use `moxcms` with its default (perceptual) rendering intent. Images whose
+//! profile cannot be parsed or applied keep their pixels and are treated as sRGB.
+
+use std::array;
+
+use image::{DynamicImage, ExtendedColorType, ImageBuffer, Rgb, RgbImage};
+use moxcms::{
+    CmsError, ColorProfile, DataColorSpace, Layout, TransformExecutor, TransformOptions, Xyzd,
+};
+
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+/// Background that transparent pixels are composited onto by default (white)
+pub(crate) const DEFAULT_BACKGROUND: [u8; 3] = [255, 255, 255];
+
+/// Largest colorant difference (D50 XYZ) for an ICC profile to match a reference space
+const COLORANT_TOLERANCE: f64 = 0.005;
+
+/// Parse an embedded ICC profile
+fn parse_profile(icc: Option<&[u8]>) -> Option<ColorProfile> {
+    ColorProfile::new_from_slice(icc?).ok()
+}
+
+fn colorants_match(a: &ColorProfile, b: &ColorProfile) -> bool {
+    let close = |a: Xyzd, b: Xyzd| {
+        (a.x - b.x).abs() <= COLORANT_TOLERANCE
+            && (a.y - b.y).abs() <= COLORANT_TOLERANCE
+            && (a.z - b.z).abs() <= COLORANT_TOLERANCE
+    };
+    close(a.red_colorant, b.red_colorant)
+        && close(a.green_colorant, b.green_colorant)
+        && close(a.blue_colorant, b.blue_colorant)
+}
+
+/// Constructor of a reference profile
+type ReferenceProfile = fn() -> ColorProfile;
+
+/// Reference RGB spaces that tagged images are matched against, by primaries
+const RGB_SPACES: [(&str, ReferenceProfile); 5] = [
+    ("srgb", ColorProfile::new_srgb),
+    ("display_p3", ColorProfile::new_display_p3),
+    ("adobe_rgb", ColorProfile::new_adobe_rgb),
+    ("prophoto_rgb", ColorProfile::new_pro_photo_rgb),
+    ("rec2020", ColorProfile::new_bt2020),
+];
+
+/// Name of the RGB space whose primaries an RGB profile uses
+fn rgb_space_name(profile: &ColorProfile) -> &'static str {
+    RGB_SPACES
+        .into_iter()
+        .find(|(_, reference)| colorants_match(profile, &reference()))
+        .map_or("other_rgb", |(name, _)| name)
+}
+
+/// Color space an image was encoded in
+///
+/// With an ICC profile: `"srgb"`, `"display_p3"`, `"adobe_rgb"`, `"prophoto_rgb"`,
+/// `"rec2020"` or `"other_rgb"` (matched by primaries), `"gray"`, `"cmyk"` or `"other"`.
+/// Without one (or with an unreadable one), `"gray"` or `"cmyk"` by pixel format and
+/// `"srgb"` otherwise.
+pub(crate) fn color_space_name(icc: Option<&[u8]>, color: ExtendedColorType) -> &'static str {
+    if let Some(profile) = parse_profile(icc) {
+        return match profile.color_space {
+            DataColorSpace::Rgb => rgb_space_name(&profile),
+            DataColorSpace::Gray => "gray",
+            DataColorSpace::Cmyk => "cmyk",
+            _ => "other",
+        };
+    }
+    use ExtendedColorType::*;
+    match color {
+        L1 | La1 | L2 | La2 | L4 | La4 | L8 | La8 | L16 | La16 => "gray",
+        Cmyk8 | Cmyk16 => "cmyk",
+        Unknown(_) => "other",
+        _ => "srgb",
+    }
+}
+
+/// Run a transform over `samples`, leaving them unchanged if it fails
+fn transform_samples<V: Copy + Default>(
+    transform: Result<std::sync::Arc<dyn TransformExecutor<V> + Send + Sync>, CmsError>,
+    samples: &mut [V],
+) {
+    let Ok(transform) = transform else {
+        return;
+    };
+    let mut converted = vec![V::default(); samples.len()];
+    if transform.transform(samples, &mut converted).is_ok() {
+        samples.copy_from_slice(&converted);
+    }
+}
+
+/// Convert an image with an embedded RGB ICC profile to sRGB
+///
+/// Images without a profile, with an sRGB profile or with a non-RGB profile are
+/// returned unchanged, as are pixel formats other than RGB(A) 8/16/32F. Alpha is kept.
+pub(crate) fn convert_to_srgb(image: DynamicImage, icc: Option<&[u8]>) -> DynamicImage {
+    let Some(profile) = parse_profile(icc) else {
+        return image;
+    };
+    if profile.color_space != DataColorSpace::Rgb || rgb_space_name(&profile) == "srgb" {
+        return image;
+    }
+    let srgb = ColorProfile::new_srgb();
+    let options = TransformOptions::default();
+    let mut image = image;
+    match &mut image {
+        DynamicImage::ImageRgb8(buffer) => transform_samples(
+            profile.create_transform_8bit(Layout::Rgb, &srgb, Layout::Rgb, options),
+            buffer,
+        ),
+        DynamicImage::ImageRgba8(buffer) => transform_samples(
+            profile.create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgba, options),
+            buffer,
+        ),
+        DynamicImage::ImageRgb16(buffer) => transform_samples(
+            profile.create_transform_16bit(Layout::Rgb, &srgb, Layout::Rgb, options),
+            buffer,
+        ),
+        DynamicImage::ImageRgba16(buffer) => transform_samples(
+            profile.create_transform_16bit(Layout::Rgba, &srgb, Layout::Rgba, options),
+            buffer,
+        ),
+        DynamicImage::ImageRgb32F(buffer) => transform_samples(
+            profile.create_transform_f32(Layout::Rgb, &srgb, Layout::Rgb, options),
+            buffer,
+        ),
+        DynamicImage::ImageRgba32F(buffer) => transform_samples(
+            profile.create_transform_f32(Layout::Rgba, &srgb, Layout::Rgba, options),
+            buffer,
+        ),
+        _ => {}
+    }
+    image
+}
+
+/// Convert YCCK samples (as stored in Adobe JPEGs) to CMYK in place
+///
+/// Y/Cb/Cr are converted to RGB with the full-range JFIF equations and inverted;
+/// K is kept. The result uses Adobe's inverted convention, like stored CMYK.
+pub(crate) fn ycck_to_cmyk(samples: &mut [u8]) {
+    for pixel in samples.chunks_exact_mut(4) {
+        let y = f32::from(pixel[0]);
+        let cb = f32::from(pixel[1]) - 128.0;
+        let cr = f32::from(pixel[2]) - 128.0;
+        let rgb = [
+            y + 1.402 * cr,
+            y - 0.344_136 * cb - 0.714_136 * cr,
+            y + 1.772 * cb,
+        ];
+        for (sample, value) in pixel.iter_mut().zip(rgb) {
+            *sample = 255 - value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Convert CMYK samples to an sRGB image
+///
+/// `inverted` marks samples in Adobe's convention (255 = no ink), used by every JPEG
+/// with an Adobe marker. With a CMYK ICC profile the samples go through it; otherwise
+/// the naive `(1 - C)(1 - K)` conversion is used. Fails with `DecodeError` if there
+/// are not exactly `width * height` samples of four channels.
+pub(crate) fn cmyk_to_srgb(
+    width: u32,
+    height: u32,
+    mut samples: Vec<u8>,
+    inverted: bool,
+    icc: Option<&[u8]>,
+) -> ItemResult<RgbImage> {
+    let pixels = u64::from(width) * u64::from(height);
+    if !samples.len().is_multiple_of(4) || (samples.len() / 4) as u64 != pixels {
+        return Err(ItemError::new(
+            ItemStatus::DecodeError,
+            format!(
+                "CMYK decoder returned {} samples for {pixels} pixels",
+                samples.len()
+            ),
+        ));
+    }
+    if inverted {
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample = 255 - *sample);
+    }
+    let mut rgb = vec![0u8; samples.len() / 4 * 3];
+    let transform = parse_profile(icc)
+        .filter(|profile| profile.color_space == DataColorSpace::Cmyk)
+        .and_then(|profile| {
+            profile
+                .create_transform_8bit(
+                    Layout::Rgba,
+                    &ColorProfile::new_srgb(),
+                    Layout::Rgb,
+                    TransformOptions::default(),
+                )
+                .ok()
+        });
+    let converted = transform.is_some_and(|t| t.transform(&samples, &mut rgb).is_ok());
+    if !converted {
+        for (out, ink) in rgb.chunks_exact_mut(3).zip(samples.chunks_exact(4)) {
+            let white = 255 - u32::from(ink[3]);
+            for (sample, &ink) in out.iter_mut().zip(&ink[..3]) {
+                *sample = ((255 - u32::from(ink)) * white / 255) as u8;
+            }
+        }
+    }
+    ImageBuffer::from_raw(width, height, rgb).ok_or_else(|| {
+        ItemError::new(
+            ItemStatus::DecodeError,
+            "CMYK dimensions do not match the samples",
+        )
+    })
+}
+
+/// Composite an image onto a solid `background`, dropping its alpha channel
+///
+/// Images without alpha are converted to RGB8 as they are.
+pub(crate) fn flatten_alpha(image: &DynamicImage, background: [u8; 3]) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+    let rgba = image.to_rgba8();
+    let mut rgb = RgbImage::new(rgba.width(), rgba.height());
+    for (out, pixel) in rgb.pixels_mut().zip(rgba.pixels()) {
+        let [red, green, blue, alpha] = pixel.0;
+        let color = [red, green, blue];
+        let alpha = u32::from(alpha);
+        *out = Rgb(array::from_fn(|c| {
+            ((u32::from(color[c]) * alpha + u32::from(background[c]) * (255 - alpha) + 127) / 255)
+                as u8
+        }));
+    }
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn cmyk_converts_naively_without_profile() {
+        let samples = vec![0, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 255, 0, 128, 255, 0];
+        let rgb = cmyk_to_srgb(2, 2, samples, false, None).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(rgb.get_pixel(1, 0).0, [0, 255, 255]);
+        assert_eq!(rgb.get_pixel(0, 1).0, [0, 0, 0]);
+        assert_eq!(rgb.get_pixel(1, 1).0, [255, 127, 0]);
+    }
+
+    #[test]
+    fn adobe_cmyk_is_inverted() {
+        let rgb = cmyk_to_srgb(1, 1, vec![0, 255, 255, 255], true, None).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [0, 255, 255]);
+    }
+
+    #[test]
+    fn ycck_becomes_inverted_cmyk() {
+        // Y/Cb/Cr of pure cyan (R=0, G=B=255) and K kept as stored
+        let mut samples = vec![179, 171, 1, 200];
+        ycck_to_cmyk(&mut samples);
+        assert!(samples[0] >= 254 && samples[1] <= 1 && samples[2] <= 1);
+        assert_eq!(samples[3], 200);
+    }
+
+    #[test]
+    fn cmyk_with_wrong_sample_count_fails() {
+        let err = cmyk_to_srgb(2, 2, vec![0; 12], false, None).unwrap_err();
+        assert_eq!(err.status, ItemStatus::DecodeError);
+        assert!(cmyk_to_srgb(u32::MAX, u32::MAX, vec![0; 4], false, None).is_err());
+    }
+
+    #[test]
+    fn color_space_falls_back_to_pixel_format() {
+        assert_eq!(color_space_name(None, ExtendedColorType::Rgb8), "srgb");
+        assert_eq!(color_space_name(None, ExtendedColorType::La8), "gray");
+        assert_eq!(color_space_name(None, ExtendedColorType::Cmyk8), "cmyk");
+        assert_eq!(
+            color_space_name(Some(b"junk"), ExtendedColorType::L16),
+            "gray"
+        );
+    }
+
+    #[test]
+    fn rgb_profiles_are_matched_by_primaries() {
+        for (name, profile) in RGB_SPACES {
+            let icc = profile().encode().unwrap();
+            assert_eq!(color_space_name(Some(&icc), ExtendedColorType::Rgb8), name);
+        }
+    }
+
+    #[test]
+    fn srgb_and_untagged_pixels_are_unchanged() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([200, 100, 60])));
+        let srgb = ColorProfile::new_srgb().encode().unwrap();
+        assert_eq!(convert_to_srgb(image.clone(), None), image);
+        assert_eq!(convert_to_srgb(image.clone(), Some(&srgb)), image);
+    }
+
+    #[test]
+    fn wide_gamut_pixels_are_converted() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([200, 100, 60])));
+        let p3 = ColorProfile::new_display_p3().encode().unwrap();
+        let converted = convert_to_srgb(image, Some(&p3)).to_rgb8();
+        let [r, g, b] = converted.get_pixel(0, 0).0;
+        assert!(r > 205 && g < 100 && b < 60, "{:?}", (r, g, b));
+    }
+
+    #[test]
+    fn alpha_is_composited_onto_background() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| {
+            Rgba([0, 0, 0, if x == 0 { 0 } else { 128 }])
+        }));
+        let rgb = flatten_alpha(&image, [255, 0, 0]);
+        assert_eq!(rgb.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(rgb.get_pixel(1, 0).0, [127, 0, 0]);
+    }
+}
@@ -0,0 +1,300 @@
+//! Dedicated rayon thread pools for batch operators
+//!
+//! Batch functions default to rayon's global pool, which spans every core on the node.
+//! A `RustExecutor` owns its own pool so a worker can match its CPU allocation:
+//! - `with executor:`: Batch functions called on this thread run in the executor's pool
+//! - `executor.run(func, *args, **kwargs)`: Call one function with the pool active
+//! - `num_threads` / `cpu_ids`: Pool size and optional core pinning (Linux)
+//!
+//! Items with a wall-clock budget (see `status::run_with_timeout`) run on a separate
+//! budget pool. Each executor has its own, with the same size and pinning, so a
+//! worker's timed items stay within its allocation; outside any executor they use
+//! one shared pool with a thread per core.
+
+use std::cell::RefCell;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+thread_local! {
+    /// Pools of the executors entered on this thread, innermost last
+    static ACTIVE_POOLS: RefCell<Vec<Arc<ThreadPool>>> = const { RefCell::new(Vec::new()) };
+
+    /// Budget pool of the executor this thread is a pool thread of
+    static WORKER_BUDGET_POOL: RefCell<Option<Arc<BudgetPool>>> = const { RefCell::new(None) };
+}
+
+/// Pool of the innermost executor entered on this thread
+fn active_pool() -> Option<Arc<ThreadPool>> {
+    ACTIVE_POOLS.with(|pools| pools.borrow().last().cloned())
+}
+
+/// Run `op` without the GIL, in the active executor's pool or else the global pool
+pub(crate) fn detach_in_pool<T, F>(py: Python<'_>, op: F) -> T
+where
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    let pool = active_pool();
+    py.detach(move || match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    })
+}
+
+/// Spawn `job` in the active executor's pool, or else the global pool
+pub(crate) fn spawn_in_pool<F>(job: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    match active_pool() {
+        Some(pool) => pool.spawn(job),
+        None => rayon::spawn(job),
+    }
+}
+
+/// Pool that runs items with a wall-clock budget, built on first use
+///
+/// Items run outside the batch's own pool so the caller can stop waiting for them.
+/// Overrunning items keep their thread until they finish, so at most one item per
+/// budget thread runs past its budget; later items queue behind them. While a timed
+/// item runs, the batch thread waiting for it is blocked, so an executor with N
+/// threads has at most N timed items running plus N overrunning ones.
+pub(crate) struct BudgetPool {
+    num_threads: usize,
+    cpu_ids: Option<Vec<usize>>,
+    pool: OnceLock<Result<ThreadPool, String>>,
+}
+
+impl BudgetPool {
+    pub(crate) fn new(num_threads: usize, cpu_ids: Option<Vec<usize>>) -> Self {
+        Self {
+            num_threads,
+            cpu_ids,
+            pool: OnceLock::new(),
+        }
+    }
+
+    /// The pool, building it on first use
+    pub(crate) fn get(&self) -> Result<&ThreadPool, String> {
+        self.pool
+            .get_or_init(|| {
+                let mut builder = ThreadPoolBuilder::new()
+                    .num_threads(self.num_threads)
+                    .thread_name(|i| format!("item-worker-{i}"));
+                #[cfg(target_os = "linux")]
+                if let Some(cpu_ids) = self.cpu_ids.clone() {
+                    builder = builder.start_handler(move |i| {
+                        let _ = pin_current_thread(cpu_ids[i % cpu_ids.len()]);
+                    });
+                }
+                builder.build().map_err(|err| err.to_string())
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+}
+
+/// Budget pool for items processed on this thread
+///
+/// Pool threads of an executor, and threads with an executor active, use that
+/// executor's budget pool; everything else shares one pool with a thread per core.
+pub(crate) fn budget_pool() -> Arc<BudgetPool> {
+    static SHARED: OnceLock<Arc<BudgetPool>> = OnceLock::new();
+    worker_budget_pool()
+        .or_else(|| active_pool().and_then(|pool| pool.install(worker_budget_pool)))
+        .unwrap_or_else(|| {
+            Arc::clone(SHARED.get_or_init(|| {
+                let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+                Arc::new(BudgetPool::new(num_threads, None))
+            }))
+        })
+}
+
+/// Budget pool of the executor the calling pool thread belongs to
+fn worker_budget_pool() -> Option<Arc<BudgetPool>> {
+    WORKER_BUDGET_POOL.with(|budget| budget.borrow().clone())
+}
+
+/// Keeps a pool active on this thread until dropped
+struct ActivePoolGuard;
+
+impl ActivePoolGuard {
+    fn enter(pool: &Arc<ThreadPool>) -> Self {
+        ACTIVE_POOLS.with(|pools| pools.borrow_mut().push(Arc::clone(pool)));
+        Self
+    }
+}
+
+impl Drop for ActivePoolGuard {
+    fn drop(&mut self) {
+        ACTIVE_POOLS.with(|pools| pools.borrow_mut().pop());
+    }
+}
+
+/// Pin the calling thread to one CPU
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cpu: usize) -> std::io::Result<()> {
+    // SAFETY: `set` is a zeroed cpu_set_t and `cpu` was checked against CPU_SETSIZE
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Check that `cpu_ids` can be used for pinning
+fn check_cpu_ids(cpu_ids: &[usize]) -> PyResult<()> {
+    if cpu_ids.is_empty() {
+        return Err(PyValueError::new_err("cpu_ids must not be empty"));
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(&cpu) = cpu_ids
+        .iter()
+        .find(|&&cpu| cpu >= libc::CPU_SETSIZE as usize)
+    {
+        return Err(PyValueError::new_err(format!("invalid cpu id: {cpu}")));
+    }
+    #[cfg(not(target_os = "linux"))]
+    return Err(PyValueError::new_err(
+        "cpu_ids (core pinning) is only supported on Linux",
+    ));
+    #[cfg(target_os = "linux")]
+    Ok(())
+}
+
+/// Rayon thread pool that batch functions can be routed through
+///
+/// `num_threads` defaults to `len(cpu_ids)` if given, otherwise to rayon's default
+/// (the available cores). With `cpu_ids`, pool thread i is pinned to
+/// `cpu_ids[i % len(cpu_ids)]`; pinning is best-effort.
+#[pyclass]
+pub struct RustExecutor {
+    pool: Arc<ThreadPool>,
+    cpu_ids: Option<Vec<usize>>,
+}
+
+#[pymethods]
+impl RustExecutor {
+    #[new]
+    #[pyo3(signature = (num_threads=None, cpu_ids=None))]
+    fn new(num_threads: Option<usize>, cpu_ids: Option<Vec<usize>>) -> PyResult<Self> {
+        if num_threads == Some(0) {
+            return Err(PyValueError::new_err("num_threads must be positive"));
+        }
+        if let Some(cpu_ids) = &cpu_ids {
+            check_cpu_ids(cpu_ids)?;
+        }
+
+        let mut builder = ThreadPoolBuilder::new()
+            .num_threads(num_threads.or(cpu_ids.as_ref().map(Vec::len)).unwrap_or(0))
+            .thread_name(|i| format!("rust-executor-{i}"));
+        #[cfg(target_os = "linux")]
+        if let Some(cpu_ids) = cpu_ids.clone() {
+            builder = builder.start_handler(move |i| {
+                let _ = pin_current_thread(cpu_ids[i % cpu_ids.len()]);
+            });
+        }
+        let pool = builder
+            .build()
+            .map_err(|e| PyRuntimeError::new_err(format!("failed to build thread pool: {e}")))?;
+
+        // Give every pool thread a budget pool of the same size and pinning
+        let budget = Arc::new(BudgetPool::new(pool.current_num_threads(), cpu_ids.clone()));
+        pool.broadcast(|_| {
+            WORKER_BUDGET_POOL.with(|slot| *slot.borrow_mut() = Some(Arc::clone(&budget)));
+        });
+
+        Ok(Self {
+            pool: Arc::new(pool),
+            cpu_ids,
+        })
+    }
+
+    /// Number of threads in the pool
+    #[getter]
+    fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// CPUs the pool threads are pinned to, if any
+    #[getter]
+    fn cpu_ids(&self) -> Option<Vec<usize>> {
+        self.cpu_ids.clone()
+    }
+
+    /// Call `func(*args, **kwargs)` with this executor's pool active
+    #[pyo3(signature = (func, *args, **kwargs))]
+    fn run<'py>(
+        &self,
+        func: &Bound<'py, PyAny>,
+        args: &Bound<'py, PyTuple>,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let _active = ActivePoolGuard::enter(&self.pool);
+        func.call(args, kwargs)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        ACTIVE_POOLS.with(|pools| pools.borrow_mut().push(Arc::clone(&slf.pool)));
+        slf
+    }
+
+    /// Leave the executor; only the innermost executor entered on this thread is left
+    #[pyo3(signature = (*_exc_info))]
+    fn __exit__(&self, _exc_info: &Bound<'_, PyTuple>) -> bool {
+        ACTIVE_POOLS.with(|pools| {
+            let mut pools = pools.borrow_mut();
+            if pools
+                .last()
+                .is_some_and(|pool| Arc::ptr_eq(pool, &self.pool))
+            {
+                pools.pop();
+            }
+        });
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.cpu_ids {
+            Some(cpu_ids) => format!(
+                "RustExecutor(num_threads={}, cpu_ids={cpu_ids:?})",
+                self.num_threads()
+            ),
+            None => format!("RustExecutor(num_threads={})", self.num_threads()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget_threads() -> usize {
+        budget_pool().get().unwrap().current_num_threads()
+    }
+
+    #[test]
+    fn executors_size_their_budget_pool_like_their_pool() {
+        let executor = RustExecutor::new(Some(3), None).unwrap();
+        assert_eq!(executor.pool.install(budget_threads), 3);
+        let in_pool = executor.pool.install(budget_pool);
+        assert!(Arc::ptr_eq(&in_pool, &executor.pool.install(budget_pool)));
+
+        // Threads with the executor active use its budget pool too
+        let active = ActivePoolGuard::enter(&executor.pool);
+        assert!(Arc::ptr_eq(&in_pool, &budget_pool()));
+        drop(active);
+
+        let shared = thread::available_parallelism().map_or(1, |n| n.get());
+        assert!(!Arc::ptr_eq(&in_pool, &budget_pool()));
+        assert_eq!(budget_threads(), shared);
+    }
+}
@@ -0,0 +1,709 @@
+//! Resource-limited image decoding
+//!
+//! Every image operator decodes untrusted bytes through `open_image`, which enforces
+//! the caller's `DecodeLimits` before any pixel buffer is allocated. Python builds the
+//! limits once (`DecodeLimits(max_pixels=..., ...)`) and passes them to any image
+//! function as `limits=`:
+//! - `max_input_bytes`: Encoded size (status `"input_too_large"`)
+//! - `allowed_formats`: Detected container format (status `"format_not_allowed"`)
+//! - `max_pixels`: Declared width x height (status `"too_many_pixels"`)
+//! - `max_alloc_bytes`: Decoded buffer and decoder allocations (status `"alloc_limit"`),
+//!   512 MiB by default
+//!
+//! Decoded pixels are color managed (see `color`): embedded RGB ICC profiles are
+//! applied so pixels are sRGB, and CMYK / YCCK JPEGs are decoded from their raw ink
+//! samples instead of the naive conversion `image` applies. `decode_raw_pixels` also
+//! exposes the pixels as stored, for hashes that must match Pillow's `Image.open`.
+//!
+//! `decode_pixels_reduced` decodes images reduced to a target `analysis_max_side` for
+//! metrics that do not need full resolution, and reports the scale it used. JPEGs are
+//! decoded at 1/2, 1/4 or 1/8 scale in the DCT domain (see `jpeg_decode`) and limited
+//! at that size; other formats are decoded at full resolution and resized.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::ops::{Deref, DerefMut};
+
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader, Limits};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use zune_core::bytestream::ZCursor;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+use crate::color::{cmyk_to_srgb, color_space_name, convert_to_srgb, ycck_to_cmyk};
+use crate::image_metadata::jpeg_has_adobe_marker;
+use crate::jpeg_decode::{decode_jpeg_scaled, JpegFrame, SCALE_FACTORS};
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+/// Allocation cap used when `max_alloc_bytes` is not given (the `image` crate default)
+const DEFAULT_MAX_ALLOC_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Resource limits applied to each image before it is decoded
+///
+/// All limits are optional; `allowed_formats` names are matched like file extensions
+/// (e.g. `["jpeg", "png"]`). Image functions given no limits apply only the default
+/// allocation cap.
+#[pyclass(frozen, from_py_object, module = "mega_data_factory.rust_operators")]
+#[derive(Clone, Debug, Default)]
+pub struct DecodeLimits {
+    max_pixels: Option<u64>,
+    max_alloc_bytes: Option<u64>,
+    max_input_bytes: Option<usize>,
+    allowed_formats: Option<Vec<ImageFormat>>,
+}
+
+/// Constructor arguments of a `DecodeLimits`, as pickled
+type DecodeLimitsArgs = (
+    Option<u64>,
+    Option<u64>,
+    Option<usize>,
+    Option<Vec<&'static str>>,
+);
+
+#[pymethods]
+impl DecodeLimits {
+    #[new]
+    #[pyo3(signature = (max_pixels=None, max_alloc_bytes=None, max_input_bytes=None, allowed_formats=None))]
+    pub(crate) fn new(
+        max_pixels: Option<u64>,
+        max_alloc_bytes: Option<u64>,
+        max_input_bytes: Option<usize>,
+        allowed_formats: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        if max_pixels == Some(0) || max_alloc_bytes == Some(0) || max_input_bytes == Some(0) {
+            return Err(PyValueError::new_err("image limits must be positive"));
+        }
+        let allowed_formats = allowed_formats
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| {
+                        ImageFormat::from_extension(name).ok_or_else(|| {
+                            PyValueError::new_err(format!("unknown image format: {name}"))
+                        })
+                    })
+                    .collect::<PyResult<Vec<_>>>()
+            })
+            .transpose()?;
+        Ok(Self {
+            max_pixels,
+            max_alloc_bytes,
+            max_input_bytes,
+            allowed_formats,
+        })
+    }
+
+    #[getter]
+    fn max_pixels(&self) -> Option<u64> {
+        self.max_pixels
+    }
+
+    #[getter]
+    fn max_alloc_bytes(&self) -> Option<u64> {
+        self.max_alloc_bytes
+    }
+
+    #[getter]
+    fn max_input_bytes(&self) -> Option<usize> {
+        self.max_input_bytes
+    }
+
+    /// Allowed formats by their canonical extension, if restricted
+    #[getter]
+    fn allowed_formats(&self) -> Option<Vec<&'static str>> {
+        self.allowed_formats.as_ref().map(|formats| {
+            formats
+                .iter()
+                .map(|format| format.extensions_str()[0])
+                .collect()
+        })
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> (Bound<'py, PyType>, DecodeLimitsArgs) {
+        let limits = slf.get();
+        (
+            slf.get_type(),
+            (
+                limits.max_pixels,
+                limits.max_alloc_bytes,
+                limits.max_input_bytes,
+                limits.allowed_formats(),
+            ),
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        let numbers = [
+            ("max_pixels", self.max_pixels),
+            ("max_alloc_bytes", self.max_alloc_bytes),
+            ("max_input_bytes", self.max_input_bytes.map(|n| n as u64)),
+        ];
+        let mut fields: Vec<String> = numbers
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| format!("{name}={value}")))
+            .collect();
+        if let Some(formats) = self.allowed_formats() {
+            fields.push(format!("allowed_formats={formats:?}"));
+        }
+        format!("DecodeLimits({})", fields.join(", "))
+    }
+}
+
+impl DecodeLimits {
+    fn max_alloc(&self) -> u64 {
+        self.max_alloc_bytes.unwrap_or(DEFAULT_MAX_ALLOC_BYTES)
+    }
+
+    /// Check the encoded size before the image is parsed at all
+    fn check_input(&self, image_bytes: &[u8]) -> ItemResult<()> {
+        match self.max_input_bytes {
+            Some(max) if image_bytes.len() > max => Err(ItemError::new(
+                ItemStatus::InputTooLarge,
+                format!(
+                    "input is {} bytes (max_input_bytes={max})",
+                    image_bytes.len()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_format(&self, format: Option<ImageFormat>) -> ItemResult<()> {
+        match (&self.allowed_formats, format) {
+            (Some(allowed), Some(format)) if !allowed.contains(&format) => Err(ItemError::new(
+                ItemStatus::FormatNotAllowed,
+                format!("format {format:?} is not in allowed_formats"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the header-declared size against `max_pixels`
+    fn check_pixels(&self, decoder: &impl ImageDecoder) -> ItemResult<()> {
+        let (width, height) = decoder.dimensions();
+        self.check_dimensions(width, height)
+    }
+
+    /// Check the size pixels are decoded at against `max_pixels`
+    fn check_dimensions(&self, width: u32, height: u32) -> ItemResult<()> {
+        let pixels = u64::from(width) * u64::from(height);
+        match self.max_pixels {
+            Some(max) if pixels > max => Err(ItemError::new(
+                ItemStatus::TooManyPixels,
+                format!("{width}x{height} image has {pixels} pixels (max_pixels={max})"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the decoded buffer size against `max_alloc_bytes` (or the default cap)
+    fn check_alloc(&self, decoder: &impl ImageDecoder) -> ItemResult<()> {
+        self.check_alloc_bytes(decoder.total_bytes())
+    }
+
+    fn check_alloc_bytes(&self, total_bytes: u64) -> ItemResult<()> {
+        if total_bytes > self.max_alloc() {
+            return Err(ItemError::new(
+                ItemStatus::AllocLimit,
+                format!(
+                    "decoding needs {total_bytes} bytes (max_alloc_bytes={})",
+                    self.max_alloc()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An image opened with `open_image`: its header decoder plus the encoded bytes
+///
+/// Dereferences to the decoder, so header accessors can be called on it directly.
+pub(crate) struct OpenedImage<'a, D> {
+    decoder: D,
+    bytes: &'a [u8],
+    format: Option<ImageFormat>,
+    /// Long side `decode_pixels_reduced` reduces to (`open_image_reduced` only)
+    max_side: Option<u32>,
+    /// Scaled-IDCT decoding planned for a JPEG by `open_image_reduced`
+    reduction: Option<JpegReduction>,
+}
+
+/// A JPEG to decode at 1/`factor` of its size
+struct JpegReduction {
+    frame: JpegFrame,
+    factor: usize,
+}
+
+impl<D> Deref for OpenedImage<'_, D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.decoder
+    }
+}
+
+impl<D> DerefMut for OpenedImage<'_, D> {
+    fn deref_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+}
+
+impl<D: ImageDecoder> OpenedImage<'_, D> {
+    /// Color space the image was encoded in (see `color::color_space_name`)
+    pub(crate) fn color_space(&mut self) -> &'static str {
+        let icc = self.decoder.icc_profile().ok().flatten();
+        let color = if self.cmyk_jpeg_colorspace().is_some() {
+            ExtendedColorType::Cmyk8
+        } else {
+            self.decoder.original_color_type()
+        };
+        color_space_name(icc.as_deref(), color)
+    }
+
+    /// Ink model of a four-component JPEG (CMYK or YCCK); `image` converts these to
+    /// RGB itself, so its decoder reports them as RGB
+    fn cmyk_jpeg_colorspace(&self) -> Option<ColorSpace> {
+        if self.format != Some(ImageFormat::Jpeg) {
+            return None;
+        }
+        let mut decoder = JpegDecoder::new_with_options(ZCursor::new(self.bytes), jpeg_options());
+        decoder.decode_headers().ok()?;
+        decoder
+            .input_colorspace()
+            .filter(|colorspace| matches!(colorspace, ColorSpace::CMYK | ColorSpace::YCCK))
+    }
+}
+
+/// Open an image and read its header, enforcing `limits`
+///
+/// Returns the detected format and a decoder that has not decoded any pixels yet.
+/// An explicit `max_alloc_bytes` is checked here; the default cap only applies once
+/// pixels are decoded, so header-only operators still report oversized images.
+pub(crate) fn open_image<'a>(
+    image_bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> ItemResult<(Option<ImageFormat>, OpenedImage<'a, impl ImageDecoder + 'a>)> {
+    open_image_reduced(image_bytes, limits, None)
+}
+
+/// Open an image for `decode_pixels_reduced` to reduce to a long side of `max_side`
+///
+/// Like `open_image`, except that a JPEG the scaled decoder will read at 1/2, 1/4 or
+/// 1/8 of its size has `max_pixels` and `max_alloc_bytes` checked at that size.
+pub(crate) fn open_image_reduced<'a>(
+    image_bytes: &'a [u8],
+    limits: &DecodeLimits,
+    max_side: Option<u32>,
+) -> ItemResult<(Option<ImageFormat>, OpenedImage<'a, impl ImageDecoder + 'a>)> {
+    limits.check_input(image_bytes)?;
+    let mut reader = ImageReader::new(Cursor::new(image_bytes))
+        .with_guessed_format()
+        .map_err(|e| ItemError::new(ItemStatus::DecodeError, e.to_string()))?;
+    let format = reader.format();
+    limits.check_format(format)?;
+
+    let mut decoder_limits = Limits::default();
+    decoder_limits.max_alloc = Some(limits.max_alloc());
+    reader.limits(decoder_limits);
+    let decoder = reader.into_decoder()?;
+    let reduction = max_side.and_then(|max_side| {
+        plan_jpeg_reduction(format, image_bytes, decoder.dimensions(), max_side)
+    });
+    match &reduction {
+        Some(JpegReduction { frame, factor }) => {
+            let (width, height) = frame.scaled_size(*factor);
+            limits.check_dimensions(width, height)?;
+            if limits.max_alloc_bytes.is_some() {
+                limits.check_alloc_bytes(frame.decode_bytes(*factor))?;
+            }
+        }
+        None => {
+            limits.check_pixels(&decoder)?;
+            if limits.max_alloc_bytes.is_some() {
+                limits.check_alloc(&decoder)?;
+            }
+        }
+    }
+    Ok((
+        format,
+        OpenedImage {
+            decoder,
+            bytes: image_bytes,
+            format,
+            max_side,
+            reduction,
+        },
+    ))
+}
+
+/// Scaled-IDCT decoding of a JPEG whose long side exceeds `max_side`, if the scaled
+/// decoder supports it
+fn plan_jpeg_reduction(
+    format: Option<ImageFormat>,
+    bytes: &[u8],
+    (width, height): (u32, u32),
+    max_side: u32,
+) -> Option<JpegReduction> {
+    if format != Some(ImageFormat::Jpeg) || width.max(height) <= max_side {
+        return None;
+    }
+    let factor = jpeg_scale_factor(width.max(height), max_side);
+    if factor == 1 {
+        return None;
+    }
+    let frame = JpegFrame::read(bytes)?;
+    Some(JpegReduction {
+        frame,
+        factor: factor as usize,
+    })
+}
+
+/// Options matching those `image` uses for its own JPEG decoding
+fn jpeg_options() -> DecoderOptions {
+    DecoderOptions::default()
+        .set_strict_mode(false)
+        .set_max_width(usize::MAX)
+        .set_max_height(usize::MAX)
+}
+
+/// Raw ink samples of a CMYK or YCCK JPEG, converted to CMYK
+struct CmykSamples {
+    width: u32,
+    height: u32,
+    samples: Vec<u8>,
+    /// Adobe JPEGs store inverted ink values
+    inverted: bool,
+}
+
+/// Decode a CMYK or YCCK JPEG to its raw CMYK samples
+fn decode_cmyk_jpeg(
+    bytes: &[u8],
+    colorspace: ColorSpace,
+    dimensions: (u32, u32),
+    limits: &DecodeLimits,
+) -> ItemResult<CmykSamples> {
+    let (width, height) = dimensions;
+    limits.check_alloc_bytes(u64::from(width) * u64::from(height) * 4)?;
+    let options = jpeg_options().jpeg_set_out_colorspace(colorspace);
+    let mut samples = JpegDecoder::new_with_options(ZCursor::new(bytes), options)
+        .decode()
+        .map_err(|e| ItemError::new(ItemStatus::DecodeError, e.to_string()))?;
+    if colorspace == ColorSpace::YCCK {
+        ycck_to_cmyk(&mut samples);
+    }
+    Ok(CmykSamples {
+        width,
+        height,
+        samples,
+        inverted: jpeg_has_adobe_marker(bytes),
+    })
+}
+
+/// Decoded pixels before color management
+enum StoredSamples {
+    Pixels(DynamicImage),
+    Cmyk(CmykSamples),
+}
+
+/// Pixels decoded by `decode_raw_pixels`, with the profile needed to color manage them
+pub(crate) struct RawPixels {
+    samples: StoredSamples,
+    icc: Option<Vec<u8>>,
+}
+
+impl RawPixels {
+    /// Pixels converted to sRGB (what `decode_pixels` returns)
+    pub(crate) fn into_srgb(self) -> ItemResult<DynamicImage> {
+        let icc = self.icc.as_deref();
+        match self.samples {
+            StoredSamples::Pixels(pixels) => Ok(convert_to_srgb(pixels, icc)),
+            StoredSamples::Cmyk(cmyk) => {
+                cmyk_to_srgb(cmyk.width, cmyk.height, cmyk.samples, cmyk.inverted, icc)
+                    .map(DynamicImage::ImageRgb8)
+            }
+        }
+    }
+
+    /// Pixels as stored in the file, ignoring any embedded profile
+    ///
+    /// This is what Pillow's `Image.open` returns: RGB and gray pixels are left as
+    /// encoded, and CMYK is converted with the plain ink formula Pillow uses, so hashes
+    /// of these pixels match hashes computed on Pillow images.
+    pub(crate) fn stored(&self) -> ItemResult<Cow<'_, DynamicImage>> {
+        match &self.samples {
+            StoredSamples::Pixels(pixels) => Ok(Cow::Borrowed(pixels)),
+            StoredSamples::Cmyk(cmyk) => {
+                let samples = cmyk.samples.clone();
+                cmyk_to_srgb(cmyk.width, cmyk.height, samples, cmyk.inverted, None)
+                    .map(|rgb| Cow::Owned(DynamicImage::ImageRgb8(rgb)))
+            }
+        }
+    }
+}
+
+/// Decode the pixels of an image opened with `open_image`, without color management
+pub(crate) fn decode_raw_pixels(
+    image: OpenedImage<'_, impl ImageDecoder>,
+    limits: &DecodeLimits,
+) -> ItemResult<RawPixels> {
+    let cmyk_colorspace = image.cmyk_jpeg_colorspace();
+    let OpenedImage {
+        mut decoder, bytes, ..
+    } = image;
+    let icc = decoder.icc_profile().ok().flatten();
+    let samples = if let Some(colorspace) = cmyk_colorspace {
+        let dimensions = decoder.dimensions();
+        StoredSamples::Cmyk(decode_cmyk_jpeg(bytes, colorspace, dimensions, limits)?)
+    } else {
+        limits.check_alloc(&decoder)?;
+        StoredSamples::Pixels(DynamicImage::from_decoder(decoder)?)
+    };
+    Ok(RawPixels { samples, icc })
+}
+
+/// Decode the pixels of an image opened with `open_image`, converted to sRGB
+pub(crate) fn decode_pixels(
+    image: OpenedImage<'_, impl ImageDecoder>,
+    limits: &DecodeLimits,
+) -> ItemResult<DynamicImage> {
+    decode_raw_pixels(image, limits)?.into_srgb()
+}
+
+/// Decoded image, reduced for analysis
+pub(crate) struct ScaledImage {
+    pub image: DynamicImage,
+    /// Output size / source size (1.0 when not reduced)
+    pub scale: f64,
+    /// Dimensions declared by the source image
+    pub source_dimensions: (u32, u32),
+}
+
+/// Power-of-two reduction for JPEG (1/2, 1/4 or 1/8)
+///
+/// Picks the largest factor that keeps the long side at least `max_side`.
+fn jpeg_scale_factor(long_side: u32, max_side: u32) -> u32 {
+    SCALE_FACTORS
+        .into_iter()
+        .rev()
+        .map(|factor| factor as u32)
+        .find(|&factor| long_side.div_ceil(factor) >= max_side)
+        .unwrap_or(1)
+}
+
+/// Output long side / source long side
+fn long_side_scale(output: (u32, u32), source: (u32, u32)) -> f64 {
+    f64::from(output.0.max(output.1)) / f64::from(source.0.max(source.1))
+}
+
+/// Decode an image opened with `open_image_reduced`, reduced so its long side is
+/// about its `max_side`
+///
+/// JPEGs are decoded at 1/2, 1/4 or 1/8 scale with the scaled IDCT of `jpeg_decode`,
+/// picking the largest reduction that keeps the long side at least `max_side`, so
+/// their output can be up to twice `max_side`. JPEGs it does not support (CMYK,
+/// arithmetic-coded, 12-bit) or rejects as corrupt, and all other formats, are
+/// decoded at full resolution (and limited at that size) and then reduced by
+/// `reduce_pixels`. Images already within `max_side` (or with no `max_side`) are
+/// returned as decoded.
+pub(crate) fn decode_pixels_reduced(
+    mut image: OpenedImage<'_, impl ImageDecoder>,
+    limits: &DecodeLimits,
+) -> ItemResult<ScaledImage> {
+    let source_dimensions = image.decoder.dimensions();
+    if let Some(JpegReduction { frame, factor }) = image.reduction.take() {
+        limits.check_alloc_bytes(frame.decode_bytes(factor))?;
+        if let Ok(pixels) = decode_jpeg_scaled(image.bytes, &frame, factor) {
+            let icc = image.decoder.icc_profile().ok().flatten();
+            return Ok(ScaledImage {
+                scale: long_side_scale((pixels.width(), pixels.height()), source_dimensions),
+                image: convert_to_srgb(pixels, icc.as_deref()),
+                source_dimensions,
+            });
+        }
+        // Checked at the reduced size when opened
+        limits.check_pixels(&image.decoder)?;
+    }
+    let (format, max_side) = (image.format, image.max_side);
+    let pixels = decode_pixels(image, limits)?;
+    Ok(reduce_pixels(format, pixels, source_dimensions, max_side))
+}
+
+/// Reduce full-resolution pixels so the long side is about `max_side`
+///
+/// JPEGs are box-filtered by the factor `decode_pixels_reduced` would have decoded
+/// them at; other formats are resized to fit `max_side`.
+pub(crate) fn reduce_pixels(
+    format: Option<ImageFormat>,
+    image: DynamicImage,
+    source_dimensions: (u32, u32),
+    max_side: Option<u32>,
+) -> ScaledImage {
+    let (width, height) = source_dimensions;
+    let long_side = width.max(height);
+    let Some(max_side) = max_side.filter(|&max_side| long_side > max_side) else {
+        return ScaledImage {
+            image,
+            scale: 1.0,
+            source_dimensions,
+        };
+    };
+
+    let image = if format == Some(ImageFormat::Jpeg) {
+        match jpeg_scale_factor(long_side, max_side) {
+            1 => image,
+            factor => image.thumbnail_exact(width.div_ceil(factor), height.div_ceil(factor)),
+        }
+    } else {
+        image.thumbnail(max_side, max_side)
+    };
+    ScaledImage {
+        scale: long_side_scale((image.width(), image.height()), source_dimensions),
+        image,
+        source_dimensions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::png::PngEncoder;
+    use image::{GenericImageView, ImageBuffer, ImageEncoder, Rgb};
+    use moxcms::ColorProfile;
+
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 128]));
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    fn reduce(bytes: &[u8], max_side: Option<u32>) -> ScaledImage {
+        let limits = DecodeLimits::default();
+        let (_, decoder) = open_image_reduced(bytes, &limits, max_side).unwrap();
+        decode_pixels_reduced(decoder, &limits).unwrap()
+    }
+
+    #[test]
+    fn jpeg_scale_factor_keeps_long_side_at_least_max_side() {
+        assert_eq!(jpeg_scale_factor(4000, 512), 4);
+        assert_eq!(jpeg_scale_factor(4096, 512), 8);
+        assert_eq!(jpeg_scale_factor(1000, 512), 1);
+        assert_eq!(jpeg_scale_factor(1024, 512), 2);
+        assert_eq!(jpeg_scale_factor(100, 512), 1);
+    }
+
+    #[test]
+    fn jpegs_are_reduced_by_power_of_two() {
+        let scaled = reduce(&encode(200, 100, ImageFormat::Jpeg), Some(50));
+        assert_eq!(scaled.image.dimensions(), (50, 25));
+        assert_eq!(scaled.scale, 0.25);
+        assert_eq!(scaled.source_dimensions, (200, 100));
+
+        // Rounded up as libjpeg does, and the scale is the true output / source ratio
+        let scaled = reduce(&encode(201, 99, ImageFormat::Jpeg), Some(50));
+        assert_eq!(scaled.image.dimensions(), (51, 25));
+        assert_eq!(scaled.scale, 51.0 / 201.0);
+    }
+
+    #[test]
+    fn reduced_jpegs_match_reduced_full_decode() {
+        let bytes = encode(200, 100, ImageFormat::Jpeg);
+        let limits = DecodeLimits::default();
+        let (format, decoder) = open_image(&bytes, &limits).unwrap();
+        let full = decode_pixels(decoder, &limits).unwrap();
+        let expected = reduce_pixels(format, full, (200, 100), Some(50))
+            .image
+            .to_rgb8();
+        let actual = reduce(&bytes, Some(50)).image.to_rgb8();
+        let max_difference = expected
+            .as_raw()
+            .iter()
+            .zip(actual.as_raw())
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max();
+        assert!(max_difference < Some(12), "{max_difference:?}");
+    }
+
+    #[test]
+    fn reduced_jpegs_are_limited_at_decoded_size() {
+        let bytes = encode(200, 100, ImageFormat::Jpeg);
+        let limits = DecodeLimits::new(Some(4000), None, None, None).unwrap();
+        let err = open_image(&bytes, &limits).err().unwrap();
+        assert_eq!(err.status, ItemStatus::TooManyPixels);
+        let (_, decoder) = open_image_reduced(&bytes, &limits, Some(50)).unwrap();
+        let scaled = decode_pixels_reduced(decoder, &limits).unwrap();
+        assert_eq!(scaled.image.dimensions(), (50, 25));
+        let err = open_image_reduced(&bytes, &limits, Some(100))
+            .err()
+            .unwrap();
+        assert_eq!(err.status, ItemStatus::TooManyPixels);
+
+        let needed = JpegFrame::read(&bytes).unwrap().decode_bytes(4);
+        let limits = DecodeLimits::new(None, Some(needed), None, None).unwrap();
+        assert!(open_image_reduced(&bytes, &limits, Some(50)).is_ok());
+        assert!(open_image(&bytes, &limits).is_err());
+        let limits = DecodeLimits::new(None, Some(needed - 1), None, None).unwrap();
+        let err = open_image_reduced(&bytes, &limits, Some(50)).err().unwrap();
+        assert_eq!(err.status, ItemStatus::AllocLimit);
+    }
+
+    #[test]
+    fn other_formats_are_resized_to_fit() {
+        let scaled = reduce(&encode(200, 100, ImageFormat::Png), Some(64));
+        assert_eq!(scaled.image.dimensions(), (64, 32));
+        assert!((scaled.scale - 0.32).abs() < 1e-9);
+    }
+
+    #[test]
+    fn small_images_are_not_reduced() {
+        let bytes = encode(40, 30, ImageFormat::Png);
+        for max_side in [None, Some(40), Some(100)] {
+            let scaled = reduce(&bytes, max_side);
+            assert_eq!(scaled.image.dimensions(), (40, 30));
+            assert_eq!(scaled.scale, 1.0);
+        }
+    }
+
+    #[test]
+    fn stored_pixels_ignore_embedded_profile() {
+        let image = ImageBuffer::from_pixel(4, 4, Rgb([128u8, 160, 96]));
+        let mut bytes = Vec::new();
+        let mut encoder = PngEncoder::new(&mut bytes);
+        let p3 = ColorProfile::new_display_p3().encode().unwrap();
+        encoder.set_icc_profile(p3).unwrap();
+        image.write_with_encoder(encoder).unwrap();
+
+        let limits = DecodeLimits::default();
+        let (_, decoder) = open_image(&bytes, &limits).unwrap();
+        let pixels = decode_raw_pixels(decoder, &limits).unwrap();
+        assert_eq!(pixels.stored().unwrap().to_rgb8(), image);
+        assert_ne!(pixels.into_srgb().unwrap().to_rgb8(), image);
+    }
+
+    #[test]
+    fn limits_reject_before_decoding() {
+        let bytes = encode(40, 30, ImageFormat::Png);
+        let limits = DecodeLimits::new(Some(100), None, None, None).unwrap();
+        let err = open_image(&bytes, &limits).err().unwrap();
+        assert_eq!(err.status, ItemStatus::TooManyPixels);
+
+        let limits = DecodeLimits::new(None, None, None, Some(vec!["jpeg".into()])).unwrap();
+        let err = open_image(&bytes, &limits).err().unwrap();
+        assert_eq!(err.status, ItemStatus::FormatNotAllowed);
+    }
+
+    #[test]
+    fn repr_shows_only_set_limits() {
+        assert_eq!(DecodeLimits::default().__repr__(), "DecodeLimits()");
+        let formats = Some(vec!["JPG".into(), "png".into()]);
+        let limits = DecodeLimits::new(Some(1000), None, Some(50), formats).unwrap();
+        assert_eq!(limits.allowed_formats(), Some(vec!["jpg", "png"]));
+        assert_eq!(
+            limits.__repr__(),
+            r#"DecodeLimits(max_pixels=1000, max_input_bytes=50, allowed_formats=["jpg", "png"])"#
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Image encoding for operators that return re-encoded image bytes
+//!
+//! - `OutputFormat`: Target format, chosen by name or derived from the source format
+//! - `encode_image`: Encode decoded pixels, converting the pixel layout if the format needs it
+//! - `encode_image_with`: Same, with JPEG chroma subsampling and embedded metadata
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageEncoder, ImageFormat};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::color::{flatten_alpha, DEFAULT_BACKGROUND};
+use crate::jpeg_encode::{encode_jpeg, ChromaSubsampling};
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+/// Default JPEG quality of re-encoded images
+pub(crate) const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Format of re-encoded images
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Jpeg,
+    Png,
+    /// Lossless WebP (the `image` crate has no lossy WebP encoder)
+    WebP,
+}
+
+impl OutputFormat {
+    /// Parse a format name ("jpeg"/"jpg", "png" or "webp")
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
+            "webp" => Ok(Self::WebP),
+            other => Err(PyValueError::new_err(format!(
+                "unsupported output format: {other} (expected 'jpeg', 'png' or 'webp')"
+            ))),
+        }
+    }
+
+    /// Parse an optional format name; `None` keeps the source format
+    pub(crate) fn parse_optional(name: Option<&str>) -> PyResult<Option<Self>> {
+        name.map(Self::parse).transpose()
+    }
+
+    /// Lowercase format name, as accepted by `parse`
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+            Self::WebP => "webp",
+        }
+    }
+
+    /// Format to re-encode an image from `source` in: JPEG is kept, WebP becomes JPEG
+    /// (PNG if `has_alpha`) since WebP output is lossless and would usually be several
+    /// times larger than a lossy source, and anything else becomes PNG
+    pub(crate) fn for_source(source: Option<ImageFormat>, has_alpha: bool) -> Self {
+        match source {
+            Some(ImageFormat::Jpeg) => Self::Jpeg,
+            Some(ImageFormat::WebP) if !has_alpha => Self::Jpeg,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Check a JPEG quality passed from Python
+pub(crate) fn check_quality(quality: u8) -> PyResult<()> {
+    if !(1..=100).contains(&quality) {
+        return Err(PyValueError::new_err("quality must be between 1 and 100"));
+    }
+    Ok(())
+}
+
+/// Parse a chroma subsampling name ("4:4:4", "4:2:2" or "4:2:0"; the colons are optional)
+pub(crate) fn parse_chroma_subsampling(name: &str) -> PyResult<ChromaSubsampling> {
+    match name {
+        "4:4:4" | "444" => Ok(ChromaSubsampling::Yuv444),
+        "4:2:2" | "422" => Ok(ChromaSubsampling::Yuv422),
+        "4:2:0" | "420" => Ok(ChromaSubsampling::Yuv420),
+        other => Err(PyValueError::new_err(format!(
+            "unsupported chroma subsampling: {other} (expected '4:4:4', '4:2:2' or '4:2:0')"
+        ))),
+    }
+}
+
+/// Metadata embedded in a re-encoded image
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EncodeMetadata {
+    pub icc_profile: Option<Vec<u8>>,
+    /// Raw EXIF (TIFF) data, without the JPEG "Exif\0\0" prefix
+    pub exif: Option<Vec<u8>>,
+}
+
+impl EncodeMetadata {
+    /// Hand the metadata to an `image` crate encoder
+    fn apply(&self, encoder: &mut impl ImageEncoder) -> ItemResult<()> {
+        let unsupported = |e: image::error::UnsupportedError| {
+            ItemError::new(ItemStatus::EncodeError, e.to_string())
+        };
+        if let Some(icc_profile) = &self.icc_profile {
+            encoder
+                .set_icc_profile(icc_profile.clone())
+                .map_err(unsupported)?;
+        }
+        if let Some(exif) = &self.exif {
+            encoder
+                .set_exif_metadata(exif.clone())
+                .map_err(unsupported)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encode an image without metadata; `quality` only applies to JPEG
+pub(crate) fn encode_image(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+) -> ItemResult<Vec<u8>> {
+    encode_image_with(
+        image,
+        format,
+        quality,
+        ChromaSubsampling::Yuv444,
+        &EncodeMetadata::default(),
+    )
+}
+
+/// Encode an image; `quality` and `subsampling` only apply to JPEG
+///
+/// Pixels are converted to a layout the encoder accepts: JPEG composites transparent
+/// images onto white, WebP is 8-bit, and PNG keeps 16-bit depth but not floating point. Grayscale JPEGs have no
+/// chroma, so `subsampling` does not affect them.
+pub(crate) fn encode_image_with(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    metadata: &EncodeMetadata,
+) -> ItemResult<Vec<u8>> {
+    let has_alpha = image.color().has_alpha();
+    let grayscale = !image.color().has_color();
+    let mut bytes = Vec::new();
+    let flattened;
+    let image = if format == OutputFormat::Jpeg && has_alpha {
+        flattened = DynamicImage::ImageRgb8(flatten_alpha(image, DEFAULT_BACKGROUND));
+        &flattened
+    } else {
+        image
+    };
+    match format {
+        OutputFormat::Jpeg if !grayscale && subsampling != ChromaSubsampling::Yuv444 => {
+            bytes = encode_jpeg(
+                &image.to_rgb8(),
+                quality,
+                subsampling,
+                metadata.icc_profile.as_deref(),
+                metadata.exif.as_deref(),
+            )?;
+        }
+        OutputFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            metadata.apply(&mut encoder)?;
+            if grayscale {
+                encoder.encode_image(&image.to_luma8())?;
+            } else {
+                encoder.encode_image(&image.to_rgb8())?;
+            }
+        }
+        OutputFormat::Png => {
+            let converted;
+            let image = match image {
+                DynamicImage::ImageRgb32F(_) => {
+                    converted = DynamicImage::ImageRgb16(image.to_rgb16());
+                    &converted
+                }
+                DynamicImage::ImageRgba32F(_) => {
+                    converted = DynamicImage::ImageRgba16(image.to_rgba16());
+                    &converted
+                }
+                _ => image,
+            };
+            let mut encoder = PngEncoder::new(&mut bytes);
+            metadata.apply(&mut encoder)?;
+            image.write_with_encoder(encoder)?;
+        }
+        OutputFormat::WebP => {
+            let converted = match (grayscale, has_alpha) {
+                (true, false) => DynamicImage::ImageLuma8(image.to_luma8()),
+                (true, true) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+                (false, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+                (false, true) => DynamicImage::ImageRgba8(image.to_rgba8()),
+            };
+            let mut encoder = WebPEncoder::new_lossless(&mut bytes);
+            metadata.apply(&mut encoder)?;
+            converted.write_with_encoder(encoder)?;
+        }
+    }
+    Ok(bytes)
+}
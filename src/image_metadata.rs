@@ -0,0 +1,1268 @@
+//! Embedded image metadata: EXIF, XMP, ICC profiles and C2PA manifests
+//!
+//! - `ExifSummary`: Camera make/model, software, capture time, orientation and GPS
+//!   presence from a TIFF-structured EXIF block
+//! - `XmpSummary`: Creator tool, IPTC digital source type and GPS presence from an XMP packet
+//! - `icc_profile_name`: Description of an ICC profile (v2 `desc` or v4 `mluc`)
+//! - `find_c2pa_manifest` / `c2pa_source_types`: C2PA manifest store embedded in a JPEG,
+//!   PNG or WebP, and the digital source types it declares
+//! - `jpeg_has_adobe_marker`: Adobe APP14 marker, which implies inverted CMYK samples
+//! - `strip_metadata`: Remove GPS data or all metadata by rewriting the container,
+//!   without re-encoding the image
+//!
+//! Parsers are lenient: malformed or truncated blocks yield whatever could be read.
+
+use std::ops::Range;
+
+use image::ImageFormat;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+// ============================================================================
+// EXIF
+// ============================================================================
+
+/// Prefix of EXIF blocks in JPEG APP1 segments (some writers keep it elsewhere too)
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+// IFD0 tags
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_DATETIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+
+// Exif IFD tags
+const TAG_DATETIME_ORIGINAL: u16 = 0x9003;
+
+// Field types
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_IFD: u16 = 13;
+
+/// Size in bytes of one value of a TIFF field type
+fn type_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// One 12-byte IFD entry
+struct IfdEntry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// Offset of the 4-byte value/offset field
+    value_at: usize,
+}
+
+/// Byte-order-aware reader over a TIFF structure
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// Reader over an EXIF block, with or without the `Exif\0\0` prefix
+    fn new(exif: &'a [u8]) -> Option<Self> {
+        let data = exif.strip_prefix(EXIF_PREFIX).unwrap_or(exif);
+        let big_endian = match data.get(..4)? {
+            b"II*\0" => false,
+            b"MM\0*" => true,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self
+            .data
+            .get(offset..offset.checked_add(2)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self
+            .data
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Offset of IFD0
+    fn first_ifd(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    /// Number of entries of the IFD at `offset`
+    fn entry_count(&self, offset: usize) -> usize {
+        self.u16(offset).map_or(0, usize::from)
+    }
+
+    /// Entries of the IFD at `offset` (stops at the end of the data)
+    fn entries(&self, offset: usize) -> impl Iterator<Item = IfdEntry> + '_ {
+        (0..self.entry_count(offset)).map_while(move |i| {
+            let at = offset + 2 + 12 * i;
+            Some(IfdEntry {
+                tag: self.u16(at)?,
+                kind: self.u16(at + 2)?,
+                count: self.u32(at + 4)?,
+                value_at: at + 8,
+            })
+        })
+    }
+
+    /// Byte range of an entry's value (values of up to 4 bytes live in the entry itself)
+    fn value_range(&self, entry: &IfdEntry) -> Option<Range<usize>> {
+        let len = type_size(entry.kind)?.checked_mul(entry.count as usize)?;
+        let start = if len <= 4 {
+            entry.value_at
+        } else {
+            self.u32(entry.value_at)? as usize
+        };
+        let end = start.checked_add(len)?;
+        (end <= self.data.len()).then_some(start..end)
+    }
+
+    /// ASCII value up to its first NUL, trimmed; `None` if empty
+    fn ascii(&self, entry: &IfdEntry) -> Option<String> {
+        if entry.kind != TYPE_ASCII {
+            return None;
+        }
+        let bytes = &self.data[self.value_range(entry)?];
+        let bytes = bytes.split(|&b| b == 0).next().unwrap_or_default();
+        let text = String::from_utf8_lossy(bytes).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// SHORT value
+    fn short(&self, entry: &IfdEntry) -> Option<u16> {
+        (entry.kind == TYPE_SHORT).then(|| self.u16(entry.value_at))?
+    }
+
+    /// Offset stored in a sub-IFD pointer entry
+    fn ifd_pointer(&self, entry: &IfdEntry) -> Option<usize> {
+        matches!(entry.kind, TYPE_LONG | TYPE_IFD)
+            .then(|| self.u32(entry.value_at))?
+            .map(|offset| offset as usize)
+    }
+}
+
+/// Fields read from an EXIF block
+#[derive(Default)]
+pub(crate) struct ExifSummary {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub software: Option<String>,
+    /// DateTimeOriginal, falling back to DateTime ("YYYY:MM:DD HH:MM:SS")
+    pub capture_time: Option<String>,
+    pub orientation: Option<u16>,
+    /// A non-empty GPS IFD is present
+    pub has_gps: bool,
+}
+
+impl ExifSummary {
+    pub(crate) fn parse(exif: &[u8]) -> Self {
+        let mut summary = Self::default();
+        let Some(tiff) = Tiff::new(exif) else {
+            return summary;
+        };
+        let Some(ifd0) = tiff.first_ifd() else {
+            return summary;
+        };
+
+        let mut exif_ifd = None;
+        for entry in tiff.entries(ifd0) {
+            match entry.tag {
+                TAG_MAKE => summary.make = tiff.ascii(&entry),
+                TAG_MODEL => summary.model = tiff.ascii(&entry),
+                TAG_SOFTWARE => summary.software = tiff.ascii(&entry),
+                TAG_DATETIME => summary.capture_time = tiff.ascii(&entry),
+                TAG_ORIENTATION => summary.orientation = tiff.short(&entry),
+                TAG_EXIF_IFD => exif_ifd = tiff.ifd_pointer(&entry),
+                TAG_GPS_IFD => {
+                    summary.has_gps = tiff
+                        .ifd_pointer(&entry)
+                        .is_some_and(|gps| tiff.entry_count(gps) > 0)
+                }
+                _ => {}
+            }
+        }
+        let original = exif_ifd
+            .and_then(|ifd| tiff.entries(ifd).find(|e| e.tag == TAG_DATETIME_ORIGINAL))
+            .and_then(|entry| tiff.ascii(&entry));
+        if original.is_some() {
+            summary.capture_time = original;
+        }
+        summary
+    }
+}
+
+/// Write a u16 in the block's byte order
+fn put_u16(data: &mut [u8], offset: usize, value: u16, big_endian: bool) {
+    let bytes = if big_endian {
+        value.to_be_bytes()
+    } else {
+        value.to_le_bytes()
+    };
+    data[offset..offset + 2].copy_from_slice(&bytes);
+}
+
+/// Remove the GPS IFD from an EXIF block in place, keeping the block's size
+///
+/// The GPS entries and their out-of-line values are zeroed, and the GPS pointer is
+/// removed from IFD0 (later entries move up and the freed slot is zeroed), so no other
+/// offset changes. Returns whether the block is free of GPS data: `false` if IFD0 or
+/// the GPS pointer cannot be read, in which case the block is left as-is and callers
+/// must drop it.
+pub(crate) fn remove_exif_gps(exif: &mut [u8]) -> bool {
+    let prefix = if exif.starts_with(EXIF_PREFIX) {
+        EXIF_PREFIX.len()
+    } else {
+        0
+    };
+    let data = &mut exif[prefix..];
+
+    // Locate everything to rewrite before mutating
+    let Some(located) = (|| {
+        let tiff = Tiff::new(data)?;
+        let ifd0 = tiff.first_ifd()?;
+        let count = tiff.entry_count(ifd0);
+        // IFD0 including its next-IFD offset must be intact to shift entries
+        if ifd0.checked_add(2 + 12 * count + 4)? > data.len() {
+            return None;
+        }
+        let Some((index, pointer)) = tiff
+            .entries(ifd0)
+            .enumerate()
+            .find(|(_, entry)| entry.tag == TAG_GPS_IFD)
+        else {
+            return Some(None);
+        };
+
+        let gps = tiff.ifd_pointer(&pointer)?;
+        let mut wipe = Vec::new();
+        for entry in tiff.entries(gps) {
+            if let Some(range) = tiff.value_range(&entry) {
+                if range.start != entry.value_at {
+                    wipe.push(range);
+                }
+            }
+        }
+        let table_end = (gps + 2 + 12 * tiff.entry_count(gps) + 4).min(data.len());
+        if gps < table_end {
+            wipe.push(gps..table_end);
+        }
+        Some(Some((tiff.big_endian, ifd0, count, index, wipe)))
+    })() else {
+        return false;
+    };
+    let Some((big_endian, ifd0, count, index, wipe)) = located else {
+        return true;
+    };
+
+    for range in wipe {
+        data[range].fill(0);
+    }
+    let entry_at = |i: usize| ifd0 + 2 + 12 * i;
+    let table_end = entry_at(count) + 4;
+    data.copy_within(entry_at(index + 1)..table_end, entry_at(index));
+    data[table_end - 12..table_end].fill(0);
+    put_u16(data, ifd0, (count - 1) as u16, big_endian);
+    true
+}
+
+// ============================================================================
+// XMP
+// ============================================================================
+
+/// Fields read from an XMP packet
+#[derive(Default)]
+pub(crate) struct XmpSummary {
+    /// `xmp:CreatorTool`
+    pub creator_tool: Option<String>,
+    /// Code of `Iptc4xmpExt:DigitalSourceType` (e.g. "trainedAlgorithmicMedia")
+    pub digital_source_type: Option<String>,
+    /// GPS properties of the EXIF schema are present
+    pub has_gps: bool,
+}
+
+impl XmpSummary {
+    pub(crate) fn parse(xmp: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(xmp);
+        Self {
+            creator_tool: xmp_property(&text, "xmp:CreatorTool"),
+            digital_source_type: xmp_property(&text, "Iptc4xmpExt:DigitalSourceType")
+                .map(|uri| source_type_code(&uri).to_string()),
+            has_gps: xmp_has_gps(xmp),
+        }
+    }
+}
+
+/// Whether an XMP packet carries GPS properties (`exif:GPSLatitude` and friends)
+fn xmp_has_gps(xmp: &[u8]) -> bool {
+    xmp.windows(8).any(|window| window == b"exif:GPS")
+}
+
+/// Value of a simple XMP property, in attribute (`name="value"`) or element
+/// (`<name>value</name>`) form; nested markup such as `rdf:Alt` is flattened
+fn xmp_property(text: &str, name: &str) -> Option<String> {
+    for (at, _) in text.match_indices(name) {
+        let preceded_ok = text[..at]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_whitespace() || c == '<');
+        if !preceded_ok {
+            continue;
+        }
+        let rest = &text[at + name.len()..];
+        let value = if let Some(value) = rest.trim_start().strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|&q| q == '"' || q == '\'')?;
+            let value = &value[1..];
+            value[..value.find(quote)?].to_string()
+        } else if rest.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            let open_end = rest.find('>')?;
+            if rest[..open_end].ends_with('/') {
+                continue;
+            }
+            let content = &rest[open_end + 1..];
+            let close = content.find(&format!("</{name}"))?;
+            strip_tags(&content[..close])
+        } else {
+            continue;
+        };
+        let value = decode_entities(value.trim());
+        if !value.is_empty() {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Text content of an XML fragment with its tags removed
+fn strip_tags(fragment: &str) -> String {
+    let mut text = String::with_capacity(fragment.len());
+    let mut in_tag = false;
+    for c in fragment.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Decode the predefined XML entities
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Last path segment of a digital source type URI
+/// (`http://cv.iptc.org/newscodes/digitalsourcetype/<code>`)
+fn source_type_code(uri: &str) -> &str {
+    uri.trim().rsplit('/').next().unwrap_or_default()
+}
+
+// ============================================================================
+// IPTC Digital Source Types / C2PA
+// ============================================================================
+
+/// Codes of the IPTC digital source type vocabulary
+const DIGITAL_SOURCE_TYPES: [&str; 18] = [
+    "digitalCapture",
+    "computationalCapture",
+    "negativeFilm",
+    "positiveFilm",
+    "print",
+    "minorHumanEdits",
+    "humanEdits",
+    "compositeCapture",
+    "algorithmicallyEnhanced",
+    "dataDrivenMedia",
+    "digitalArt",
+    "virtualRecording",
+    "compositeSynthetic",
+    "trainedAlgorithmicMedia",
+    "compositeWithTrainedAlgorithmicMedia",
+    "algorithmicMedia",
+    "screenCapture",
+    "digitalCreation",
+];
+
+/// Whether a digital source type declares generative-AI content
+pub(crate) fn is_ai_generated_source(code: &str) -> bool {
+    matches!(
+        code,
+        "trainedAlgorithmicMedia" | "compositeWithTrainedAlgorithmicMedia"
+    )
+}
+
+/// Digital source types referenced by a C2PA manifest store, in order of appearance
+///
+/// Manifests are CBOR inside JUMBF boxes, so assertions are not decoded; the store is
+/// scanned for IPTC digital source type URIs instead.
+pub(crate) fn c2pa_source_types(manifest: &[u8]) -> Vec<&'static str> {
+    const NEEDLE: &[u8] = b"digitalsourcetype/";
+    let mut types = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = manifest[pos..]
+        .windows(NEEDLE.len())
+        .position(|window| window == NEEDLE)
+    {
+        pos += found + NEEDLE.len();
+        let tail = &manifest[pos..];
+        // Longest match, as some codes are prefixes of others
+        let code = DIGITAL_SOURCE_TYPES
+            .iter()
+            .filter(|code| tail.starts_with(code.as_bytes()))
+            .max_by_key(|code| code.len());
+        if let Some(&code) = code {
+            if !types.contains(&code) {
+                types.push(code);
+            }
+        }
+    }
+    types
+}
+
+/// C2PA manifest store (JUMBF) embedded in a JPEG (APP11), PNG (`caBX`) or WebP (`C2PA`)
+pub(crate) fn find_c2pa_manifest(data: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => {
+            let (segments, _) = jpeg_segments(data)?;
+            // JPEG XT boxes: "JP", instance (2), sequence number (4), then box data;
+            // every continuation segment repeats the box header (8 bytes)
+            let mut manifest = Vec::new();
+            for segment in segments.iter().filter(|s| s.marker == APP11) {
+                let payload = &data[segment.payload.clone()];
+                if payload.starts_with(b"JP") && payload.len() > 8 {
+                    let skip = if manifest.is_empty() { 8 } else { 16 };
+                    manifest.extend_from_slice(payload.get(skip..).unwrap_or_default());
+                }
+            }
+            let is_c2pa = manifest.windows(4).any(|window| window == b"c2pa");
+            is_c2pa.then_some(manifest)
+        }
+        ImageFormat::Png => png_chunks(data)?
+            .into_iter()
+            .find(|chunk| &chunk.kind == b"caBX")
+            .map(|chunk| data[chunk.data].to_vec()),
+        ImageFormat::WebP => webp_chunks(data)?
+            .into_iter()
+            .find(|chunk| &chunk.kind == b"C2PA")
+            .map(|chunk| data[chunk.data].to_vec()),
+        _ => None,
+    }
+}
+
+// ============================================================================
+// ICC Profiles
+// ============================================================================
+
+/// Profile description of an ICC profile (the `desc` tag)
+pub(crate) fn icc_profile_name(icc: &[u8]) -> Option<String> {
+    let be32 = |data: &[u8], at: usize| -> Option<usize> {
+        let bytes: [u8; 4] = data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+        Some(u32::from_be_bytes(bytes) as usize)
+    };
+
+    let tag_count = be32(icc, 128)?;
+    let entry = (0..tag_count)
+        .map(|i| 132 + 12 * i)
+        .take_while(|&at| at + 12 <= icc.len())
+        .find(|&at| &icc[at..at + 4] == b"desc")?;
+    let (offset, size) = (be32(icc, entry + 4)?, be32(icc, entry + 8)?);
+    let tag = icc.get(offset..offset.checked_add(size)?)?;
+
+    let name = match tag.get(..4)? {
+        // v2 textDescriptionType: ASCII count and string
+        b"desc" => {
+            let len = be32(tag, 8)?;
+            let ascii = tag.get(12..12usize.checked_add(len)?)?;
+            let ascii = ascii.split(|&b| b == 0).next().unwrap_or_default();
+            String::from_utf8_lossy(ascii).into_owned()
+        }
+        // v4 multiLocalizedUnicodeType: first record, UTF-16BE
+        b"mluc" => {
+            if be32(tag, 8)? == 0 {
+                return None;
+            }
+            let (len, start) = (be32(tag, 20)?, be32(tag, 24)?);
+            let units: Vec<u16> = tag
+                .get(start..start.checked_add(len)?)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let name = name.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// ============================================================================
+// Containers
+// ============================================================================
+
+// JPEG markers
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const APP11: u8 = 0xEB;
+const APP13: u8 = 0xED;
+const APP14: u8 = 0xEE;
+const COM: u8 = 0xFE;
+const SOS: u8 = 0xDA;
+const EOI: u8 = 0xD9;
+
+// Identifiers at the start of JPEG APPn payloads
+const XMP_ID: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_EXTENSION_ID: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+const PHOTOSHOP_ID: &[u8] = b"Photoshop 3.0\0";
+const MPF_ID: &[u8] = b"MPF\0";
+const ADOBE_ID: &[u8] = b"Adobe";
+
+/// PNG signature
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// PNG text keyword of XMP packets
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+// WebP VP8X flags
+const VP8X_XMP: u8 = 0x04;
+const VP8X_EXIF: u8 = 0x08;
+
+/// A JPEG marker segment before the scan data
+struct JpegSegment {
+    marker: u8,
+    /// Offset of the segment's 0xFF
+    start: usize,
+    /// Payload after the length field (empty for standalone markers)
+    payload: Range<usize>,
+}
+
+/// Marker segments of a JPEG up to the first SOS (or EOI), and the offset where
+/// that marker starts; everything from there on is copied verbatim when rewriting
+fn jpeg_segments(data: &[u8]) -> Option<(Vec<JpegSegment>, usize)> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        // Fill bytes before the marker
+        let mut marker_at = pos;
+        while data.get(marker_at + 1) == Some(&0xFF) {
+            marker_at += 1;
+        }
+        let marker = *data.get(marker_at + 1)?;
+        match marker {
+            SOS | EOI => return Some((segments, pos)),
+            0x01 | 0xD0..=0xD7 => {
+                segments.push(JpegSegment {
+                    marker,
+                    start: pos,
+                    payload: marker_at + 2..marker_at + 2,
+                });
+                pos = marker_at + 2;
+            }
+            _ => {
+                let length =
+                    u16::from_be_bytes([*data.get(marker_at + 2)?, *data.get(marker_at + 3)?]);
+                let end = marker_at + 2 + usize::from(length);
+                if length < 2 || end > data.len() {
+                    return None;
+                }
+                segments.push(JpegSegment {
+                    marker,
+                    start: pos,
+                    payload: marker_at + 4..end,
+                });
+                pos = end;
+            }
+        }
+    }
+}
+
+/// Whether a JPEG has an Adobe APP14 marker, whose writers store CMYK inverted
+pub(crate) fn jpeg_has_adobe_marker(data: &[u8]) -> bool {
+    jpeg_segments(data).is_some_and(|(segments, _)| {
+        segments.iter().any(|segment| {
+            segment.marker == APP14 && data[segment.payload.clone()].starts_with(ADOBE_ID)
+        })
+    })
+}
+
+/// A PNG chunk
+struct PngChunk {
+    kind: [u8; 4],
+    start: usize,
+    data: Range<usize>,
+    /// Offset after the CRC
+    end: usize,
+}
+
+/// Chunks of a PNG up to and including IEND
+fn png_chunks(data: &[u8]) -> Option<Vec<PngChunk>> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        let kind: [u8; 4] = data[pos + 4..pos + 8].try_into().ok()?;
+        let data_end = (pos + 8).checked_add(length)?;
+        let end = data_end.checked_add(4)?;
+        if end > data.len() {
+            return None;
+        }
+        chunks.push(PngChunk {
+            kind,
+            start: pos,
+            data: pos + 8..data_end,
+            end,
+        });
+        pos = end;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Some(chunks)
+}
+
+/// A RIFF chunk of a WebP file
+struct WebpChunk {
+    kind: [u8; 4],
+    start: usize,
+    data: Range<usize>,
+    /// Offset after the padding byte of odd-sized chunks
+    end: usize,
+}
+
+/// Chunks of a WebP file
+fn webp_chunks(data: &[u8]) -> Option<Vec<WebpChunk>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let kind: [u8; 4] = data[pos..pos + 4].try_into().ok()?;
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let data_end = (pos + 8).checked_add(size)?;
+        if data_end > data.len() {
+            return None;
+        }
+        let end = (data_end + (size & 1)).min(data.len());
+        chunks.push(WebpChunk {
+            kind,
+            start: pos,
+            data: pos + 8..data_end,
+            end,
+        });
+        pos = end;
+    }
+    Some(chunks)
+}
+
+// ============================================================================
+// Stripping
+// ============================================================================
+
+/// What `strip_metadata` removes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StripMode {
+    /// GPS data only: the EXIF GPS IFD and XMP packets with GPS properties (EXIF
+    /// blocks whose GPS IFD cannot be located are dropped whole)
+    Gps,
+    /// EXIF, XMP, IPTC, C2PA, comments, text chunks and timestamps (ICC profiles are kept)
+    All,
+}
+
+impl StripMode {
+    /// Parse a mode name ("gps" or "all")
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gps" => Ok(Self::Gps),
+            "all" => Ok(Self::All),
+            other => Err(PyValueError::new_err(format!(
+                "unsupported strip mode: {other} (expected 'gps' or 'all')"
+            ))),
+        }
+    }
+
+    /// Parse an optional mode name; `None` strips nothing
+    pub(crate) fn parse_optional(name: Option<&str>) -> PyResult<Option<Self>> {
+        name.map(Self::parse).transpose()
+    }
+}
+
+/// Remove metadata from a JPEG, PNG or WebP without re-encoding the image
+///
+/// Only metadata blocks are dropped or rewritten; all other bytes are copied as-is,
+/// except that data appended after a JPEG's end of image is dropped.
+pub(crate) fn strip_metadata(
+    data: &[u8],
+    format: ImageFormat,
+    mode: StripMode,
+) -> ItemResult<Vec<u8>> {
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(data, mode),
+        ImageFormat::Png => strip_png(data, mode),
+        ImageFormat::WebP => strip_webp(data, mode),
+        _ => {
+            return Err(ItemError::new(
+                ItemStatus::UnsupportedFormat,
+                "metadata stripping supports JPEG, PNG and WebP",
+            ))
+        }
+    };
+    stripped.ok_or_else(|| {
+        ItemError::new(
+            ItemStatus::DecodeError,
+            format!("malformed {format:?} container"),
+        )
+    })
+}
+
+fn strip_jpeg(data: &[u8], mode: StripMode) -> Option<Vec<u8>> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    for segment in &segments {
+        let whole = &data[segment.start..segment.payload.end];
+        let payload = &data[segment.payload.clone()];
+        let is_exif = segment.marker == APP1 && payload.starts_with(EXIF_PREFIX);
+        let is_xmp = segment.marker == APP1
+            && (payload.starts_with(XMP_ID) || payload.starts_with(XMP_EXTENSION_ID));
+        let is_mpf = segment.marker == APP2 && payload.starts_with(MPF_ID);
+        match mode {
+            // The multi-picture index points at the images dropped below
+            _ if is_mpf => {}
+            StripMode::Gps if is_exif => {
+                let mut rewritten = whole.to_vec();
+                if remove_exif_gps(&mut rewritten[segment.payload.start - segment.start..]) {
+                    out.extend_from_slice(&rewritten);
+                }
+            }
+            StripMode::Gps if is_xmp && xmp_has_gps(payload) => {}
+            StripMode::All
+                if is_exif
+                    || is_xmp
+                    || (segment.marker == APP13 && payload.starts_with(PHOTOSHOP_ID))
+                    || (segment.marker == APP11 && payload.starts_with(b"JP"))
+                    || segment.marker == COM => {}
+            _ => out.extend_from_slice(whole),
+        }
+    }
+    // Data after the image (secondary images, motion-photo trailers) has its own metadata
+    let end = jpeg_image_end(data, scan).unwrap_or(data.len());
+    out.extend_from_slice(&data[scan..end]);
+    Some(out)
+}
+
+/// Offset after the EOI marker that ends the image whose first SOS (or EOI) is at `scan`
+fn jpeg_image_end(data: &[u8], scan: usize) -> Option<usize> {
+    let mut pos = scan;
+    loop {
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            EOI => return Some(pos + 2),
+            // Fill byte
+            0xFF => pos += 1,
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            _ => {
+                let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]);
+                pos += 2 + usize::from(length);
+                if marker == SOS {
+                    // Entropy-coded data runs to the next marker that is not a
+                    // stuffed zero or a restart marker
+                    loop {
+                        let at = pos + data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+                        match *data.get(at + 1)? {
+                            0x00 | 0xD0..=0xD7 => pos = at + 2,
+                            _ => {
+                                pos = at;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+    }
+}
+
+/// Append a PNG chunk with its CRC
+fn push_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+fn strip_png(data: &[u8], mode: StripMode) -> Option<Vec<u8>> {
+    let chunks = png_chunks(data)?;
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+    for chunk in &chunks {
+        let body = &data[chunk.data.clone()];
+        let is_text = matches!(&chunk.kind, b"tEXt" | b"zTXt" | b"iTXt");
+        let is_xmp = is_text && body.starts_with(PNG_XMP_KEYWORD);
+        match (mode, &chunk.kind) {
+            (StripMode::Gps, b"eXIf") => {
+                let mut exif = body.to_vec();
+                if remove_exif_gps(&mut exif) {
+                    push_png_chunk(&mut out, &chunk.kind, &exif);
+                }
+            }
+            // Compressed packets cannot be inspected without inflating them
+            (StripMode::Gps, kind) if is_xmp => {
+                let compressed = match kind {
+                    b"zTXt" => true,
+                    b"iTXt" => body.get(PNG_XMP_KEYWORD.len() + 1) != Some(&0),
+                    _ => false,
+                };
+                if !compressed && !xmp_has_gps(body) {
+                    out.extend_from_slice(&data[chunk.start..chunk.end]);
+                }
+            }
+            (StripMode::All, b"eXIf" | b"tIME" | b"caBX") => {}
+            (StripMode::All, _) if is_text => {}
+            _ => out.extend_from_slice(&data[chunk.start..chunk.end]),
+        }
+    }
+    Some(out)
+}
+
+fn strip_webp(data: &[u8], mode: StripMode) -> Option<Vec<u8>> {
+    let chunks = webp_chunks(data)?;
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(b"RIFF\0\0\0\0WEBP");
+    let mut cleared_flags = 0;
+    let mut vp8x_at = None;
+    for chunk in &chunks {
+        let whole = &data[chunk.start..chunk.end];
+        match (mode, &chunk.kind) {
+            (StripMode::Gps, b"EXIF") => {
+                let mut rewritten = whole.to_vec();
+                if remove_exif_gps(&mut rewritten[8..8 + chunk.data.len()]) {
+                    out.extend_from_slice(&rewritten);
+                } else {
+                    cleared_flags |= VP8X_EXIF;
+                }
+            }
+            (StripMode::Gps, b"XMP ") if xmp_has_gps(&data[chunk.data.clone()]) => {
+                cleared_flags |= VP8X_XMP;
+            }
+            (StripMode::All, b"EXIF") => cleared_flags |= VP8X_EXIF,
+            (StripMode::All, b"XMP ") => cleared_flags |= VP8X_XMP,
+            (StripMode::All, b"C2PA") => {}
+            (_, b"VP8X") => {
+                vp8x_at = Some(out.len());
+                out.extend_from_slice(whole);
+            }
+            _ => out.extend_from_slice(whole),
+        }
+    }
+    if let Some(flags) = vp8x_at.and_then(|at| out.get_mut(at + 8)) {
+        *flags &= !cleared_flags;
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+    use std::io::Cursor;
+
+    const TAG_DNG_VERSION: u16 = 0xC612;
+    const TYPE_BYTE: u16 = 1;
+    const TYPE_RATIONAL: u16 = 5;
+
+    const XMP_WITH_GPS: &[u8] = b"<x:xmpmeta><rdf:Description xmp:CreatorTool=\"Editor 1.0\" \
+        exif:GPSLatitude=\"51,30.0N\"/></x:xmpmeta>";
+    const XMP_WITHOUT_GPS: &[u8] =
+        b"<x:xmpmeta><rdf:Description xmp:CreatorTool=\"Editor 1.0\"/></x:xmpmeta>";
+
+    /// Offsets of the GPS IFD and its latitude values in `exif_with_gps` (after the prefix)
+    const GPS_IFD_AT: usize = 86;
+    const GPS_VALUES: Range<usize> = 116..140;
+
+    /// An EXIF block whose IFD0 holds Make, Orientation (6), Software, a GPS IFD pointer
+    /// and DNGVersion, and whose GPS IFD holds a latitude reference and an out-of-line
+    /// latitude
+    fn exif_with_gps(big_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let entry = |tag: u16, kind: u16, count: u32, value: [u8; 4]| {
+            [
+                &u16_bytes(tag)[..],
+                &u16_bytes(kind),
+                &u32_bytes(count),
+                &value,
+            ]
+            .concat()
+        };
+        let short = |v: u16| {
+            let bytes = u16_bytes(v);
+            [bytes[0], bytes[1], 0, 0]
+        };
+
+        let mut tiff = if big_endian {
+            b"MM\0*".to_vec()
+        } else {
+            b"II*\0".to_vec()
+        };
+        tiff.extend_from_slice(&u32_bytes(8));
+        // IFD0 at 8: 5 entries, ends at 74
+        tiff.extend_from_slice(&u16_bytes(5));
+        tiff.extend(entry(TAG_MAKE, TYPE_ASCII, 4, *b"Cam\0"));
+        tiff.extend(entry(TAG_ORIENTATION, TYPE_SHORT, 1, short(6)));
+        tiff.extend(entry(TAG_SOFTWARE, TYPE_ASCII, 11, u32_bytes(74)));
+        tiff.extend(entry(
+            TAG_GPS_IFD,
+            TYPE_LONG,
+            1,
+            u32_bytes(GPS_IFD_AT as u32),
+        ));
+        tiff.extend(entry(TAG_DNG_VERSION, TYPE_BYTE, 4, [1, 4, 0, 0]));
+        tiff.extend_from_slice(&u32_bytes(0));
+        // Software value at 74, padded to 86
+        tiff.extend_from_slice(b"Editor 1.0\0\0");
+        // GPS IFD at 86: 2 entries, ends at 116
+        tiff.extend_from_slice(&u16_bytes(2));
+        tiff.extend(entry(0x0001, TYPE_ASCII, 2, *b"N\0\0\0"));
+        tiff.extend(entry(
+            0x0002,
+            TYPE_RATIONAL,
+            3,
+            u32_bytes(GPS_VALUES.start as u32),
+        ));
+        tiff.extend_from_slice(&u32_bytes(0));
+        // Latitude 51/1 30/1 2617/100 at 116..140
+        for (num, den) in [(51, 1), (30, 1), (2617, 100)] {
+            tiff.extend_from_slice(&u32_bytes(num));
+            tiff.extend_from_slice(&u32_bytes(den));
+        }
+        assert_eq!(tiff.len(), GPS_VALUES.end);
+        [EXIF_PREFIX, &tiff].concat()
+    }
+
+    fn ifd0_tags(exif: &[u8]) -> Vec<u16> {
+        let tiff = Tiff::new(exif).unwrap();
+        tiff.entries(tiff.first_ifd().unwrap())
+            .map(|entry| entry.tag)
+            .collect()
+    }
+
+    fn check_gps_removed(big_endian: bool) {
+        let mut exif = exif_with_gps(big_endian);
+        assert!(ExifSummary::parse(&exif).has_gps);
+        assert!(remove_exif_gps(&mut exif));
+        assert_eq!(exif.len(), EXIF_PREFIX.len() + GPS_VALUES.end);
+
+        let summary = ExifSummary::parse(&exif);
+        assert!(!summary.has_gps);
+        assert_eq!(summary.make.as_deref(), Some("Cam"));
+        assert_eq!(summary.software.as_deref(), Some("Editor 1.0"));
+        assert_eq!(summary.orientation, Some(6));
+        assert_eq!(
+            ifd0_tags(&exif),
+            [TAG_MAKE, TAG_ORIENTATION, TAG_SOFTWARE, TAG_DNG_VERSION]
+        );
+        let tiff = Tiff::new(&exif).unwrap();
+        let dng = tiff.entries(8).find(|e| e.tag == TAG_DNG_VERSION).unwrap();
+        assert_eq!(&tiff.data[dng.value_at..dng.value_at + 4], [1, 4, 0, 0]);
+        // The GPS IFD and its values are zeroed
+        let data = &exif[EXIF_PREFIX.len()..];
+        assert!(data[GPS_IFD_AT..GPS_VALUES.end].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn remove_exif_gps_little_endian() {
+        check_gps_removed(false);
+    }
+
+    #[test]
+    fn remove_exif_gps_big_endian() {
+        check_gps_removed(true);
+    }
+
+    #[test]
+    fn remove_exif_gps_without_gps_is_a_no_op() {
+        let mut exif = exif_with_gps(false);
+        assert!(remove_exif_gps(&mut exif));
+        let stripped = exif.clone();
+        assert!(remove_exif_gps(&mut exif));
+        assert_eq!(exif, stripped);
+    }
+
+    #[test]
+    fn remove_exif_gps_fails_closed_on_truncated_ifd0() {
+        let full = exif_with_gps(false);
+        // Cut inside IFD0's entry table (it ends at 74), and inside the header
+        for len in [EXIF_PREFIX.len() + 50, EXIF_PREFIX.len() + 6] {
+            let mut exif = full[..len].to_vec();
+            assert!(!remove_exif_gps(&mut exif));
+            assert_eq!(exif, &full[..len]);
+            // Parsing the truncated block does not panic either
+            ExifSummary::parse(&exif);
+        }
+    }
+
+    #[test]
+    fn remove_exif_gps_fails_closed_on_unreadable_pointer() {
+        let mut exif = exif_with_gps(false);
+        // GPS pointer entry is the fourth in IFD0; make its type ASCII
+        let kind_at = EXIF_PREFIX.len() + 8 + 2 + 12 * 3 + 2;
+        exif[kind_at..kind_at + 2].copy_from_slice(&TYPE_ASCII.to_le_bytes());
+        assert!(!remove_exif_gps(&mut exif));
+    }
+
+    fn pixels() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(16, 12, |x, y| {
+            image::Rgb([(x * 15) as u8, (y * 20) as u8, 128])
+        }))
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        pixels().write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    fn jpeg_with(exif: &[u8], xmp: &[u8]) -> Vec<u8> {
+        let segment = |marker: u8, payload: &[u8]| {
+            let length = (payload.len() + 2) as u16;
+            [&[0xFF, marker][..], &length.to_be_bytes(), payload].concat()
+        };
+        let jpeg = encode(ImageFormat::Jpeg);
+        [
+            &jpeg[..2],
+            &segment(APP1, exif),
+            &segment(APP1, &[XMP_ID, xmp].concat()),
+            &jpeg[2..],
+        ]
+        .concat()
+    }
+
+    fn png_with(exif: &[u8], xmp: &[u8]) -> Vec<u8> {
+        let png = encode(ImageFormat::Png);
+        let chunks = png_chunks(&png).unwrap();
+        // Metadata goes after IHDR
+        let ihdr_end = chunks[0].end;
+        let mut out = png[..ihdr_end].to_vec();
+        push_png_chunk(&mut out, b"eXIf", &exif[EXIF_PREFIX.len()..]);
+        // Keyword, no compression, empty language and translated keyword
+        let itxt = [PNG_XMP_KEYWORD, b"\0\0\0\0\0", xmp].concat();
+        push_png_chunk(&mut out, b"iTXt", &itxt);
+        out.extend_from_slice(&png[ihdr_end..]);
+        out
+    }
+
+    fn webp_with(exif: &[u8], xmp: &[u8]) -> Vec<u8> {
+        let webp = encode(ImageFormat::WebP);
+        let chunk = |kind: &[u8; 4], data: &[u8]| {
+            let mut chunk = [&kind[..], &(data.len() as u32).to_le_bytes(), data].concat();
+            if data.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+        let mut vp8x = vec![VP8X_EXIF | VP8X_XMP, 0, 0, 0];
+        vp8x.extend_from_slice(&15u32.to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&11u32.to_le_bytes()[..3]);
+        let body = [
+            &b"WEBP"[..],
+            &chunk(b"VP8X", &vp8x),
+            &webp[12..],
+            &chunk(b"EXIF", &exif[EXIF_PREFIX.len()..]),
+            &chunk(b"XMP ", xmp),
+        ]
+        .concat();
+        [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat()
+    }
+
+    /// EXIF blocks and XMP packets left in a container
+    fn metadata_blocks(data: &[u8], format: ImageFormat) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let (mut exif, mut xmp) = (Vec::new(), Vec::new());
+        match format {
+            ImageFormat::Jpeg => {
+                for segment in jpeg_segments(data).unwrap().0 {
+                    let payload = &data[segment.payload];
+                    if segment.marker != APP1 {
+                        continue;
+                    }
+                    if payload.starts_with(EXIF_PREFIX) {
+                        exif.push(payload.to_vec());
+                    } else if payload.starts_with(XMP_ID) {
+                        xmp.push(payload.to_vec());
+                    }
+                }
+            }
+            ImageFormat::Png => {
+                for chunk in png_chunks(data).unwrap() {
+                    match &chunk.kind {
+                        b"eXIf" => exif.push(data[chunk.data].to_vec()),
+                        b"iTXt" => xmp.push(data[chunk.data].to_vec()),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {
+                for chunk in webp_chunks(data).unwrap() {
+                    match &chunk.kind {
+                        b"EXIF" => exif.push(data[chunk.data].to_vec()),
+                        b"XMP " => xmp.push(data[chunk.data].to_vec()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        (exif, xmp)
+    }
+
+    /// Flags byte of a WebP's VP8X chunk
+    fn vp8x_flags(data: &[u8]) -> u8 {
+        let chunks = webp_chunks(data).unwrap();
+        let vp8x = chunks.iter().find(|c| &c.kind == b"VP8X").unwrap();
+        data[vp8x.data.start]
+    }
+
+    /// Builds a container from an EXIF block and an XMP packet
+    type Build = fn(&[u8], &[u8]) -> Vec<u8>;
+
+    fn check_round_trip(format: ImageFormat, build: Build) {
+        let exif = exif_with_gps(format == ImageFormat::Png);
+        let original = build(&exif, XMP_WITH_GPS);
+        let decoded = image::load_from_memory(&original).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 12));
+        let (exif_blocks, xmp_packets) = metadata_blocks(&original, format);
+        assert_eq!((exif_blocks.len(), xmp_packets.len()), (1, 1));
+
+        // GPS: the GPS IFD and the XMP packet carrying GPS properties go
+        let gps = strip_metadata(&original, format, StripMode::Gps).unwrap();
+        let image = image::load_from_memory(&gps).unwrap();
+        assert_eq!(image.to_rgb8(), decoded.to_rgb8());
+        let (exif_blocks, xmp_packets) = metadata_blocks(&gps, format);
+        assert_eq!(exif_blocks.len(), 1);
+        let summary = ExifSummary::parse(&exif_blocks[0]);
+        assert!(!summary.has_gps);
+        assert_eq!(summary.orientation, Some(6));
+        assert!(xmp_packets.is_empty());
+
+        // XMP without GPS properties is kept
+        let original = build(&exif, XMP_WITHOUT_GPS);
+        let gps = strip_metadata(&original, format, StripMode::Gps).unwrap();
+        image::load_from_memory(&gps).unwrap();
+        let (_, xmp_packets) = metadata_blocks(&gps, format);
+        assert_eq!(xmp_packets.len(), 1);
+        assert!(!xmp_has_gps(&xmp_packets[0]));
+
+        // All: no EXIF or XMP remains
+        let all = strip_metadata(&original, format, StripMode::All).unwrap();
+        let image = image::load_from_memory(&all).unwrap();
+        assert_eq!(image.to_rgb8(), decoded.to_rgb8());
+        let (exif_blocks, xmp_packets) = metadata_blocks(&all, format);
+        assert!(exif_blocks.is_empty() && xmp_packets.is_empty());
+    }
+
+    #[test]
+    fn strip_jpeg_round_trip() {
+        check_round_trip(ImageFormat::Jpeg, jpeg_with);
+    }
+
+    #[test]
+    fn strip_png_round_trip() {
+        check_round_trip(ImageFormat::Png, png_with);
+    }
+
+    #[test]
+    fn strip_webp_round_trip() {
+        check_round_trip(ImageFormat::WebP, webp_with);
+
+        let original = webp_with(&exif_with_gps(false), XMP_WITH_GPS);
+        let gps = strip_metadata(&original, ImageFormat::WebP, StripMode::Gps).unwrap();
+        assert_eq!(vp8x_flags(&gps), VP8X_EXIF);
+        let all = strip_metadata(&original, ImageFormat::WebP, StripMode::All).unwrap();
+        assert_eq!(vp8x_flags(&all), 0);
+        assert_eq!(
+            u32::from_le_bytes(all[4..8].try_into().unwrap()) as usize,
+            all.len() - 8
+        );
+    }
+
+    #[test]
+    fn strip_gps_drops_unreadable_exif() {
+        let truncated = &exif_with_gps(false)[..EXIF_PREFIX.len() + 50];
+        let builders: [(ImageFormat, Build); 3] = [
+            (ImageFormat::Jpeg, jpeg_with),
+            (ImageFormat::Png, png_with),
+            (ImageFormat::WebP, webp_with),
+        ];
+        for (format, build) in builders {
+            let original = build(truncated, XMP_WITHOUT_GPS);
+            let gps = strip_metadata(&original, format, StripMode::Gps).unwrap();
+            image::load_from_memory(&gps).unwrap();
+            let (exif_blocks, xmp_packets) = metadata_blocks(&gps, format);
+            assert!(exif_blocks.is_empty(), "{format:?}");
+            assert_eq!(xmp_packets.len(), 1, "{format:?}");
+            if format == ImageFormat::WebP {
+                assert_eq!(vp8x_flags(&gps), VP8X_XMP);
+            }
+        }
+    }
+}
@@ -0,0 +1,585 @@
+//! Single-decode multi-metric analysis
+
+use image::imageops;
+use image::{ImageDecoder, ImageFormat};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+
+use crate::batch_future::{finisher, BatchFuture};
+use crate::color::{flatten_alpha, DEFAULT_BACKGROUND};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{
+    decode_pixels_reduced, decode_raw_pixels, open_image_reduced, reduce_pixels, DecodeLimits,
+};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::borders::{detect_borders, BorderParams, DEFAULT_BORDER_TOLERANCE, DEFAULT_MIN_BORDER};
+use super::phash::{phash_from_image, prepare_phash, HashBits, HashEncoding, PhashAlgorithm};
+use super::placeholder::{
+    classify_placeholder_pixels, placeholder_tiny, PlaceholderClass, DEFAULT_PLACEHOLDER_MIN_SIDE,
+};
+use super::quality::{
+    calculate_entropy_from_rgb, check_analysis_max_side, detect_compression_artifacts_from_rgb,
+    exposure_stats_from_rgb, noise_sigma_from_luma, sharpness_from_luma, ExposureStats,
+    SharpnessMetrics, DEFAULT_SHARP_TILE_THRESHOLD, EXPOSURE_METRICS,
+};
+
+/// Metric names accepted by `image_analyze_batch`
+const ANALYSIS_METRICS: [&str; 21] = [
+    "width",
+    "height",
+    "format",
+    "file_size",
+    "compression_artifacts",
+    "entropy",
+    "laplacian_variance",
+    "tenengrad",
+    "sharp_tile_fraction",
+    "noise_sigma",
+    "luma_mean",
+    "luma_std",
+    "highlight_clip_fraction",
+    "shadow_clip_fraction",
+    "rms_contrast",
+    "colorfulness",
+    "saturation_mean",
+    "placeholder_category",
+    "placeholder_confidence",
+    "border_fraction",
+    "phash",
+];
+
+/// Set of metrics requested from `image_analyze_batch`, and the resolution to use
+#[derive(Clone, Copy, Default)]
+struct AnalysisRequest {
+    width: bool,
+    height: bool,
+    format: bool,
+    file_size: bool,
+    compression_artifacts: bool,
+    entropy: bool,
+    laplacian_variance: bool,
+    tenengrad: bool,
+    sharp_tile_fraction: bool,
+    noise_sigma: bool,
+    /// Requested exposure statistics, in `EXPOSURE_METRICS` order
+    exposure: [bool; 7],
+    placeholder_category: bool,
+    placeholder_confidence: bool,
+    border_fraction: bool,
+    phash: bool,
+    /// Compute pixel metrics at reduced resolution (see `decode_pixels_reduced`)
+    analysis_max_side: Option<u32>,
+    /// Color transparent images are composited onto for RGB metrics
+    background: [u8; 3],
+}
+
+impl AnalysisRequest {
+    /// Parse metric names; `None` requests every metric
+    fn parse(metrics: Option<Vec<String>>) -> PyResult<Self> {
+        let names = metrics.unwrap_or_else(|| ANALYSIS_METRICS.map(String::from).to_vec());
+        let mut request = Self::default();
+        for name in &names {
+            match name.as_str() {
+                "width" => request.width = true,
+                "height" => request.height = true,
+                "format" => request.format = true,
+                "file_size" => request.file_size = true,
+                "compression_artifacts" => request.compression_artifacts = true,
+                "entropy" => request.entropy = true,
+                "laplacian_variance" => request.laplacian_variance = true,
+                "tenengrad" => request.tenengrad = true,
+                "sharp_tile_fraction" => request.sharp_tile_fraction = true,
+                "noise_sigma" => request.noise_sigma = true,
+                "placeholder_category" => request.placeholder_category = true,
+                "placeholder_confidence" => request.placeholder_confidence = true,
+                "border_fraction" => request.border_fraction = true,
+                "phash" => request.phash = true,
+                other => match EXPOSURE_METRICS.iter().position(|&metric| metric == other) {
+                    Some(index) => request.exposure[index] = true,
+                    None => {
+                        return Err(PyValueError::new_err(format!(
+                            "unknown metric: {other} (expected one of {})",
+                            ANALYSIS_METRICS.join(", ")
+                        )))
+                    }
+                },
+            }
+        }
+        Ok(request)
+    }
+
+    /// Whether any requested metric needs decoded pixels (rather than just the header)
+    fn needs_pixels(&self) -> bool {
+        self.compression_artifacts
+            || self.entropy
+            || self.needs_sharpness()
+            || self.noise_sigma
+            || self.needs_exposure()
+            || self.needs_placeholder()
+            || self.border_fraction
+            || self.phash
+    }
+
+    fn needs_sharpness(&self) -> bool {
+        self.laplacian_variance || self.tenengrad || self.sharp_tile_fraction
+    }
+
+    fn needs_exposure(&self) -> bool {
+        self.exposure.contains(&true)
+    }
+
+    fn needs_placeholder(&self) -> bool {
+        self.placeholder_category || self.placeholder_confidence
+    }
+}
+
+/// Format name as reported by Pillow's `Image.format`
+pub(super) fn pil_format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::WebP => "WEBP",
+        ImageFormat::Pnm => "PPM",
+        ImageFormat::Tiff => "TIFF",
+        ImageFormat::Tga => "TGA",
+        ImageFormat::Dds => "DDS",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Ico => "ICO",
+        ImageFormat::Hdr => "HDR",
+        ImageFormat::OpenExr => "OPENEXR",
+        ImageFormat::Farbfeld => "FARBFELD",
+        ImageFormat::Avif => "AVIF",
+        ImageFormat::Qoi => "QOI",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Metrics of one image; pixel-based metrics are `None` unless requested
+struct ImageAnalysis {
+    width: u32,
+    height: u32,
+    format: &'static str,
+    file_size: usize,
+    compression_artifacts: Option<f64>,
+    entropy: Option<f64>,
+    sharpness: Option<SharpnessMetrics>,
+    noise_sigma: Option<f64>,
+    exposure: Option<ExposureStats>,
+    placeholder: Option<PlaceholderClass>,
+    border_fraction: Option<f64>,
+    phash: Option<HashBits>,
+    /// Scale pixel metrics were computed at; only reported with `analysis_max_side`
+    analysis_scale: Option<f64>,
+}
+
+/// Analyze a single image, decoding it at most once
+///
+/// Only the header is read when no pixel-based metric is requested.
+fn image_analyze_core(
+    image_bytes: &[u8],
+    request: AnalysisRequest,
+    hash_size: u32,
+    hash_algorithm: PhashAlgorithm,
+    preproc_dct: bool,
+    limits: &DecodeLimits,
+) -> ItemResult<ImageAnalysis> {
+    // The hash needs full-resolution pixels, so only reduce the decode without it
+    let reduce_to = request.analysis_max_side.filter(|_| !request.phash);
+    let (format, decoder) = open_image_reduced(image_bytes, limits, reduce_to)?;
+
+    let mut analysis = ImageAnalysis {
+        width: 0,
+        height: 0,
+        format: format.map_or("UNKNOWN", pil_format_name),
+        file_size: image_bytes.len(),
+        compression_artifacts: None,
+        entropy: None,
+        sharpness: None,
+        noise_sigma: None,
+        exposure: None,
+        placeholder: None,
+        border_fraction: None,
+        phash: None,
+        analysis_scale: None,
+    };
+
+    (analysis.width, analysis.height) = decoder.dimensions();
+    if !request.needs_pixels() {
+        return Ok(analysis);
+    }
+    if request.needs_placeholder() {
+        analysis.placeholder = placeholder_tiny(
+            (analysis.width, analysis.height),
+            DEFAULT_PLACEHOLDER_MIN_SIDE,
+        );
+    }
+
+    // The hash is taken from the full-resolution pixels as stored, so it matches
+    // `image_compute_phash_batch` and the Python fallback's `imagehash.phash`
+    let scaled = if request.phash {
+        let source_dimensions = decoder.dimensions();
+        let pixels = decode_raw_pixels(decoder, limits)?;
+        analysis.phash = Some(phash_from_image(
+            &*pixels.stored()?,
+            hash_size,
+            hash_algorithm,
+            preproc_dct,
+        ));
+        reduce_pixels(
+            format,
+            pixels.into_srgb()?,
+            source_dimensions,
+            request.analysis_max_side,
+        )
+    } else {
+        decode_pixels_reduced(decoder, limits)?
+    };
+    let img = scaled.image;
+    if request.analysis_max_side.is_some() {
+        analysis.analysis_scale = Some(scaled.scale);
+    }
+    let needs_luma = request.needs_sharpness() || request.noise_sigma;
+    if request.compression_artifacts || request.entropy || request.needs_exposure() || needs_luma {
+        // Every metric but the placeholder and border ones sees the composited image
+        let rgb_img = flatten_alpha(&img, request.background);
+        if request.compression_artifacts {
+            analysis.compression_artifacts = Some(detect_compression_artifacts_from_rgb(
+                &rgb_img,
+                image_bytes.len(),
+                scaled.source_dimensions,
+            ));
+        }
+        if request.entropy {
+            analysis.entropy = Some(calculate_entropy_from_rgb(&rgb_img));
+        }
+        if request.needs_exposure() {
+            analysis.exposure = Some(exposure_stats_from_rgb(&rgb_img));
+        }
+        if needs_luma {
+            let gray = imageops::grayscale(&rgb_img);
+            if request.needs_sharpness() {
+                analysis.sharpness = Some(sharpness_from_luma(&gray, DEFAULT_SHARP_TILE_THRESHOLD));
+            }
+            if request.noise_sigma {
+                analysis.noise_sigma = Some(noise_sigma_from_luma(&gray));
+            }
+        }
+    }
+    if request.needs_placeholder() && analysis.placeholder.is_none() {
+        analysis.placeholder = Some(classify_placeholder_pixels(&img));
+    }
+    if request.border_fraction {
+        let params = BorderParams {
+            tolerance: DEFAULT_BORDER_TOLERANCE,
+            min_border: DEFAULT_MIN_BORDER,
+            any_color: false,
+        };
+        analysis.border_fraction = Some(detect_borders(&img, params).border_fraction);
+    }
+    Ok(analysis)
+}
+
+/// Convert an analysis to a dict holding only the requested metrics
+fn analysis_to_dict<'py>(
+    py: Python<'py>,
+    analysis: ImageAnalysis,
+    request: AnalysisRequest,
+    hash_encoding: HashEncoding,
+) -> PyResult<Bound<'py, PyAny>> {
+    let dict = PyDict::new(py);
+    if request.width {
+        dict.set_item("width", analysis.width)?;
+    }
+    if request.height {
+        dict.set_item("height", analysis.height)?;
+    }
+    if request.format {
+        dict.set_item("format", analysis.format)?;
+    }
+    if request.file_size {
+        dict.set_item("file_size", analysis.file_size)?;
+    }
+    if let Some(value) = analysis.compression_artifacts {
+        dict.set_item("compression_artifacts", value)?;
+    }
+    if let Some(value) = analysis.entropy {
+        dict.set_item("entropy", value)?;
+    }
+    if let Some(sharpness) = analysis.sharpness {
+        if request.laplacian_variance {
+            dict.set_item("laplacian_variance", sharpness.laplacian_variance)?;
+        }
+        if request.tenengrad {
+            dict.set_item("tenengrad", sharpness.tenengrad)?;
+        }
+        if request.sharp_tile_fraction {
+            dict.set_item("sharp_tile_fraction", sharpness.sharp_tile_fraction)?;
+        }
+    }
+    if let Some(value) = analysis.noise_sigma {
+        dict.set_item("noise_sigma", value)?;
+    }
+    if let Some(stats) = analysis.exposure {
+        for ((name, value), requested) in EXPOSURE_METRICS
+            .into_iter()
+            .zip(stats.values())
+            .zip(request.exposure)
+        {
+            if requested {
+                dict.set_item(name, value)?;
+            }
+        }
+    }
+    if let Some(class) = analysis.placeholder {
+        if request.placeholder_category {
+            dict.set_item("placeholder_category", class.category.as_str())?;
+        }
+        if request.placeholder_confidence {
+            dict.set_item("placeholder_confidence", class.confidence)?;
+        }
+    }
+    if let Some(value) = analysis.border_fraction {
+        dict.set_item("border_fraction", value)?;
+    }
+    if let Some(hash) = analysis.phash {
+        dict.set_item("phash", hash_encoding.encode(py, &hash)?)?;
+    }
+    if let Some(scale) = analysis.analysis_scale {
+        dict.set_item("analysis_scale", scale)?;
+    }
+    Ok(dict.into_any())
+}
+
+/// Analyze every image in parallel
+fn image_analyze_all(
+    image_bytes_list: &[Vec<u8>],
+    request: AnalysisRequest,
+    hash_size: u32,
+    hash_algorithm: PhashAlgorithm,
+    preproc_dct: bool,
+    limits: &DecodeLimits,
+) -> Vec<ItemResult<ImageAnalysis>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_analyze_core(
+                    image_bytes,
+                    request,
+                    hash_size,
+                    hash_algorithm,
+                    preproc_dct,
+                    limits,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Convert analyses to dicts and build an analyze batch function's return value
+fn analyze_batch_output(
+    py: Python<'_>,
+    analyses: Vec<ItemResult<ImageAnalysis>>,
+    request: AnalysisRequest,
+    hash_encoding: HashEncoding,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = analyses
+        .into_iter()
+        .map(|analysis| match analysis {
+            Ok(analysis) => analysis_to_dict(py, analysis, request, hash_encoding).map(Ok),
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Validate the metric names and hash options passed from Python
+fn prepare_analyze(
+    metrics: Option<Vec<String>>,
+    hash_size: u32,
+    hash_algorithm: &str,
+    hash_encoding: &str,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<(AnalysisRequest, PhashAlgorithm, HashEncoding)> {
+    let (hash_algorithm, hash_encoding) = prepare_phash(hash_size, hash_algorithm, hash_encoding)?;
+    check_analysis_max_side(analysis_max_side)?;
+    let mut request = AnalysisRequest::parse(metrics)?;
+    request.analysis_max_side = analysis_max_side;
+    request.background = background;
+    Ok((request, hash_algorithm, hash_encoding))
+}
+
+/// Batch analyze images in parallel, decoding each image once (GIL released)
+///
+/// `metrics` selects from width, height, format (Pillow names such as "JPEG"),
+/// file_size, compression_artifacts, entropy, laplacian_variance, tenengrad,
+/// sharp_tile_fraction (as in `image_assess_sharpness_batch`, default threshold),
+/// noise_sigma (as in `image_estimate_noise_batch`), the statistics of
+/// `image_exposure_stats_batch` (luma_mean, luma_std, highlight_clip_fraction,
+/// shadow_clip_fraction, rms_contrast, colorfulness, saturation_mean),
+/// placeholder_category and placeholder_confidence (as in
+/// `image_detect_placeholder_batch`, default `min_side`), border_fraction (as in
+/// `image_detect_borders_batch`, default options) and phash; `None` computes all of them.
+/// If only header metrics are requested, images are not decoded at all. The phash is
+/// configured like `image_compute_phash_batch` and, like it, hashes the full-resolution
+/// pixels before color management. Returns one dict per image, or None
+/// for images that fail to decode. With `return_status=True`, returns
+/// `(results, statuses)` with one (status, message) tuple per image.
+///
+/// With `analysis_max_side`, pixel metrics are computed at reduced resolution as in
+/// `image_assess_quality_batch` and each dict gains `analysis_scale`; width and height
+/// are always the source dimensions. `background` is used by compression_artifacts,
+/// entropy and the exposure statistics, as in `image_assess_quality_batch`.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, metrics=None, hash_size=16, hash_algorithm="double_gradient", hash_encoding="base64", preproc_dct=false, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_analyze_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    metrics: Option<Vec<String>>,
+    hash_size: u32,
+    hash_algorithm: &str,
+    hash_encoding: &str,
+    preproc_dct: bool,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (request, hash_algorithm, hash_encoding) = prepare_analyze(
+        metrics,
+        hash_size,
+        hash_algorithm,
+        hash_encoding,
+        analysis_max_side,
+        background,
+    )?;
+
+    let analyses = detach_in_pool(py, || {
+        image_analyze_all(
+            &image_bytes_list,
+            request,
+            hash_size,
+            hash_algorithm,
+            preproc_dct,
+            &limits,
+        )
+    });
+    analyze_batch_output(py, analyses, request, hash_encoding, return_status)
+}
+
+/// Non-blocking `image_analyze_batch`; returns a `BatchFuture`
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, metrics=None, hash_size=16, hash_algorithm="double_gradient", hash_encoding="base64", preproc_dct=false, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_analyze_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    metrics: Option<Vec<String>>,
+    hash_size: u32,
+    hash_algorithm: &str,
+    hash_encoding: &str,
+    preproc_dct: bool,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let (request, hash_algorithm, hash_encoding) = prepare_analyze(
+        metrics,
+        hash_size,
+        hash_algorithm,
+        hash_encoding,
+        analysis_max_side,
+        background,
+    )?;
+
+    Ok(BatchFuture::spawn(move || {
+        let analyses = image_analyze_all(
+            &image_bytes_list,
+            request,
+            hash_size,
+            hash_algorithm,
+            preproc_dct,
+            &limits,
+        );
+        finisher(move |py| {
+            analyze_batch_output(py, analyses, request, hash_encoding, return_status)
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::quality::{image_assess_sharpness_core, image_estimate_noise_core};
+    use crate::image_ops::test_images::encode;
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+
+    #[test]
+    fn color_under_transparent_pixels_is_ignored() {
+        // Noise everywhere, hidden under zero alpha in the center or painted over with
+        // the background there
+        let noise = |x: u32, y: u32, c: u32| ((x * 73) ^ (y * 151) ^ (c * 89)) as u8;
+        let image = |hidden: image::Rgba<u8>| {
+            let image = RgbaImage::from_fn(48, 48, |x, y| {
+                if (16..32).contains(&x) && (16..32).contains(&y) {
+                    return hidden;
+                }
+                image::Rgba([noise(x, y, 0), noise(x, y, 1), noise(x, y, 2), 255])
+            });
+            encode(DynamicImage::ImageRgba8(image), ImageFormat::Png)
+        };
+        let hidden = image(image::Rgba([200, 30, 90, 0]));
+        let composited = image(image::Rgba([255, 255, 255, 255]));
+
+        let limits = DecodeLimits::default();
+        let sharpness = |bytes: &[u8]| {
+            let (metrics, _) = image_assess_sharpness_core(
+                bytes,
+                &limits,
+                None,
+                DEFAULT_SHARP_TILE_THRESHOLD,
+                DEFAULT_BACKGROUND,
+            )
+            .unwrap();
+            (metrics.laplacian_variance, metrics.tenengrad)
+        };
+        let noise_sigma = |bytes: &[u8]| {
+            image_estimate_noise_core(bytes, &limits, None, DEFAULT_BACKGROUND)
+                .unwrap()
+                .0
+        };
+        assert_eq!(sharpness(&hidden), sharpness(&composited));
+        assert_eq!(noise_sigma(&hidden), noise_sigma(&composited));
+
+        let analyze = |bytes: &[u8]| {
+            let mut request = AnalysisRequest::parse(None).unwrap();
+            request.background = DEFAULT_BACKGROUND;
+            let analysis = image_analyze_core(
+                bytes,
+                request,
+                8,
+                PhashAlgorithm::ImagehashDct,
+                false,
+                &limits,
+            )
+            .unwrap();
+            let metrics = analysis.sharpness.unwrap();
+            (metrics.laplacian_variance, analysis.noise_sigma.unwrap())
+        };
+        assert_eq!(analyze(&hidden), analyze(&composited));
+        assert_eq!(
+            analyze(&hidden),
+            (sharpness(&hidden).0, noise_sigma(&hidden))
+        );
+    }
+}
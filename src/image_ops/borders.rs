@@ -0,0 +1,487 @@
+//! Uniform border / letterbox detection with optional auto-crop
+
+use image::{DynamicImage, Rgba};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels, open_image, DecodeLimits};
+use crate::image_encode::{check_quality, encode_image, OutputFormat, DEFAULT_JPEG_QUALITY};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::placeholder::VISIBLE_ALPHA;
+
+/// Default per-channel deviation from the border color still counted as border
+pub(super) const DEFAULT_BORDER_TOLERANCE: u8 = 16;
+
+/// Default minimum border width; thinner borders (often edge artifacts) are ignored
+pub(super) const DEFAULT_MIN_BORDER: u32 = 2;
+
+/// Fraction of a row or column that must match the border color
+const BORDER_LINE_MIN_MATCH: f64 = 0.98;
+
+/// Channel value at or below which a border color counts as black
+const BORDER_BLACK_MAX: u8 = 40;
+
+/// Channel value at or above which a border color counts as white
+const BORDER_WHITE_MIN: u8 = 215;
+
+/// How a border is searched for
+#[derive(Clone, Copy)]
+pub(super) struct BorderParams {
+    pub(super) tolerance: u8,
+    pub(super) min_border: u32,
+    /// Accept borders of any uniform color, not just black, white or transparent
+    pub(super) any_color: bool,
+}
+
+/// Uniform borders found around an image
+#[derive(Clone, Copy)]
+pub(super) struct BorderDetection {
+    /// (left, top, right, bottom) of the content, right/bottom exclusive; `None` when
+    /// the whole image is border
+    content_box: Option<(u32, u32, u32, u32)>,
+    /// Fraction of the image area outside the content box
+    pub(super) border_fraction: f64,
+}
+
+/// Pixel with fully transparent pixels normalized, so any transparent color matches
+fn border_pixel(pixel: &Rgba<u8>) -> [u8; 4] {
+    if pixel[3] < VISIBLE_ALPHA {
+        [0; 4]
+    } else {
+        pixel.0
+    }
+}
+
+/// Whether `color` may start a border under `params`
+fn is_border_color(color: [u8; 4], params: BorderParams) -> bool {
+    let [r, g, b, a] = color;
+    params.any_color
+        || a == 0
+        || r.max(g).max(b) <= BORDER_BLACK_MAX
+        || r.min(g).min(b) >= BORDER_WHITE_MIN
+}
+
+/// Mean color of a line of pixels
+fn mean_color(pixels: &[[u8; 4]]) -> [u8; 4] {
+    let mut sums = [0u64; 4];
+    for pixel in pixels {
+        for (sum, &channel) in sums.iter_mut().zip(pixel) {
+            *sum += u64::from(channel);
+        }
+    }
+    sums.map(|sum| (sum / pixels.len().max(1) as u64) as u8)
+}
+
+/// Whether nearly every pixel of a line is within `tolerance` of `color`
+fn line_matches(pixels: &[[u8; 4]], color: [u8; 4], tolerance: u8) -> bool {
+    let matching = pixels
+        .iter()
+        .filter(|pixel| {
+            pixel
+                .iter()
+                .zip(color)
+                .all(|(&channel, reference)| channel.abs_diff(reference) <= tolerance)
+        })
+        .count();
+    matching as f64 >= BORDER_LINE_MIN_MATCH * pixels.len() as f64
+}
+
+/// Count consecutive border lines from one side
+///
+/// `line(i)` returns the i-th line inward; the outermost line sets the border color.
+fn border_width(count: u32, line: impl Fn(u32) -> Vec<[u8; 4]>, params: BorderParams) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+    let outer = line(0);
+    let color = mean_color(&outer);
+    if !is_border_color(color, params) || !line_matches(&outer, color, params.tolerance) {
+        return 0;
+    }
+    (1..count)
+        .find(|&i| !line_matches(&line(i), color, params.tolerance))
+        .unwrap_or(count)
+}
+
+/// Find uniform borders on each side of an image
+pub(super) fn detect_borders(image: &DynamicImage, params: BorderParams) -> BorderDetection {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let row = |y: u32, x0: u32, x1: u32| -> Vec<[u8; 4]> {
+        (x0..x1)
+            .map(|x| border_pixel(rgba.get_pixel(x, y)))
+            .collect()
+    };
+    let column = |x: u32, y0: u32, y1: u32| -> Vec<[u8; 4]> {
+        (y0..y1)
+            .map(|y| border_pixel(rgba.get_pixel(x, y)))
+            .collect()
+    };
+
+    // The whole image is border: there is no content box to crop to
+    let all_border = BorderDetection {
+        content_box: None,
+        border_fraction: 1.0,
+    };
+    let top = border_width(height, |i| row(i, 0, width), params);
+    if top == height {
+        return all_border;
+    }
+    let bottom = border_width(height - top, |i| row(height - 1 - i, 0, width), params);
+    let (y0, y1) = (top, height - bottom);
+    let left = border_width(width, |i| column(i, y0, y1), params);
+    if left == width {
+        return all_border;
+    }
+    let right = border_width(width - left, |i| column(width - 1 - i, y0, y1), params);
+
+    let keep = |border: u32| {
+        if border >= params.min_border {
+            border
+        } else {
+            0
+        }
+    };
+    let (left, top, right, bottom) = (keep(left), keep(top), keep(right), keep(bottom));
+    let content_box = (left, top, width - right, height - bottom);
+    let content_area = u64::from(content_box.2 - left) * u64::from(content_box.3 - top);
+    let total_area = u64::from(width) * u64::from(height);
+    BorderDetection {
+        content_box: Some(content_box),
+        border_fraction: 1.0 - content_area as f64 / total_area as f64,
+    }
+}
+
+/// Border detection result of one image, with the cropped image if requested
+struct BorderResult {
+    detection: BorderDetection,
+    /// Re-encoded content; `None` unless cropping was requested and a border was found
+    cropped: Option<Vec<u8>>,
+}
+
+/// Cropping options; `None` format keeps the source format (see `OutputFormat::for_source`)
+#[derive(Clone, Copy)]
+struct CropParams {
+    format: Option<OutputFormat>,
+    quality: u8,
+}
+
+/// Decode one image, detect its borders and optionally crop them off
+fn image_detect_borders_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    params: BorderParams,
+    crop: Option<CropParams>,
+) -> ItemResult<BorderResult> {
+    let (format, decoder) = open_image(image_bytes, limits)?;
+    let img = decode_pixels(decoder, limits)?;
+    let detection = detect_borders(&img, params);
+
+    let cropped = match (crop, detection.content_box) {
+        (Some(crop), Some((left, top, right, bottom))) if detection.border_fraction > 0.0 => {
+            let content = img.crop_imm(left, top, right - left, bottom - top);
+            let output_format = crop.format.unwrap_or(OutputFormat::for_source(
+                format,
+                content.color().has_alpha(),
+            ));
+            Some(encode_image(&content, output_format, crop.quality)?)
+        }
+        _ => None,
+    };
+    Ok(BorderResult { detection, cropped })
+}
+
+/// Detect the borders of every image in parallel
+fn image_detect_borders_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    params: BorderParams,
+    crop: Option<CropParams>,
+) -> Vec<ItemResult<BorderResult>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| image_detect_borders_core(image_bytes, limits, params, crop))
+        })
+        .collect()
+}
+
+/// Convert border results to dicts and build a border batch function's return value
+///
+/// Dicts hold `content_box` and `border_fraction`, plus `cropped` when cropping;
+/// failed images become None.
+fn borders_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<BorderResult>>,
+    crop: bool,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = results
+        .into_iter()
+        .map(|result| match result {
+            Ok(result) => {
+                let dict = PyDict::new(py);
+                dict.set_item("content_box", result.detection.content_box)?;
+                dict.set_item("border_fraction", result.detection.border_fraction)?;
+                if crop {
+                    let cropped = result.cropped.map(|bytes| PyBytes::new(py, &bytes));
+                    dict.set_item("cropped", cropped)?;
+                }
+                Ok(Ok(dict.into_any()))
+            }
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Validate the border and crop options passed from Python
+fn prepare_borders(
+    tolerance: u8,
+    min_border: u32,
+    any_color: bool,
+    crop: bool,
+    crop_format: Option<&str>,
+    crop_quality: u8,
+) -> PyResult<(BorderParams, Option<CropParams>)> {
+    if min_border == 0 {
+        return Err(PyValueError::new_err("min_border must be positive"));
+    }
+    check_quality(crop_quality)?;
+    let format = OutputFormat::parse_optional(crop_format)?;
+    let params = BorderParams {
+        tolerance,
+        min_border,
+        any_color,
+    };
+    let crop = crop.then_some(CropParams {
+        format,
+        quality: crop_quality,
+    });
+    Ok((params, crop))
+}
+
+/// Batch detect uniform borders and letterboxing in parallel (GIL released)
+///
+/// Each side is scanned inward while rows (or columns) match the color of the
+/// outermost one: at least 98% of a line's pixels must be within `tolerance` of it
+/// in every channel. By default only black, white or transparent borders count;
+/// `any_color=True` accepts any uniform color (which may also catch flat skies or
+/// studio backdrops). Borders thinner than `min_border` pixels are ignored.
+///
+/// Returns one dict per image (None if it fails to decode):
+/// - `content_box`: (left, top, right, bottom) of the content, right/bottom exclusive
+///   (a PIL crop box), or None if the whole image is uniform
+/// - `border_fraction`: Fraction of the image area outside the content box
+/// - `cropped` (with `crop=True`): The content re-encoded as `crop_format` ("jpeg",
+///   "png" or "webp"; default: JPEG for JPEG and opaque WebP sources, otherwise PNG), or None
+///   when there is no border to crop
+///
+/// Images are decoded at full resolution.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, tolerance=DEFAULT_BORDER_TOLERANCE, min_border=DEFAULT_MIN_BORDER, any_color=false, crop=false, crop_format=None, crop_quality=DEFAULT_JPEG_QUALITY))]
+pub fn image_detect_borders_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    tolerance: u8,
+    min_border: u32,
+    any_color: bool,
+    crop: bool,
+    crop_format: Option<&str>,
+    crop_quality: u8,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (params, crop_params) = prepare_borders(
+        tolerance,
+        min_border,
+        any_color,
+        crop,
+        crop_format,
+        crop_quality,
+    )?;
+    let results = detach_in_pool(py, || {
+        image_detect_borders_all(&image_bytes_list, &limits, params, crop_params)
+    });
+    borders_batch_output(py, results, crop, return_status)
+}
+
+/// Non-blocking `image_detect_borders_batch`; returns a `BatchFuture`
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, tolerance=DEFAULT_BORDER_TOLERANCE, min_border=DEFAULT_MIN_BORDER, any_color=false, crop=false, crop_format=None, crop_quality=DEFAULT_JPEG_QUALITY))]
+pub fn submit_image_detect_borders_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    tolerance: u8,
+    min_border: u32,
+    any_color: bool,
+    crop: bool,
+    crop_format: Option<&str>,
+    crop_quality: u8,
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let (params, crop_params) = prepare_borders(
+        tolerance,
+        min_border,
+        any_color,
+        crop,
+        crop_format,
+        crop_quality,
+    )?;
+    Ok(BatchFuture::spawn(move || {
+        let results = image_detect_borders_all(&image_bytes_list, &limits, params, crop_params);
+        finisher(move |py| borders_batch_output(py, results, crop, return_status))
+    }))
+}
+
+/// Arrow variant of `image_detect_borders_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `content_left`, `content_top`, `content_right`,
+/// `content_bottom` (null when the image is uniform) and `border_fraction` columns,
+/// plus a binary `cropped` column with `crop=True`. Failed or null images are null
+/// throughout. With `return_status=True`, `status` and `status_message` columns are
+/// added.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, tolerance=DEFAULT_BORDER_TOLERANCE, min_border=DEFAULT_MIN_BORDER, any_color=false, crop=false, crop_format=None, crop_quality=DEFAULT_JPEG_QUALITY))]
+pub fn image_detect_borders_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    tolerance: u8,
+    min_border: u32,
+    any_color: bool,
+    crop: bool,
+    crop_format: Option<&str>,
+    crop_quality: u8,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (params, crop_params) = prepare_borders(
+        tolerance,
+        min_border,
+        any_color,
+        crop,
+        crop_format,
+        crop_quality,
+    )?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<BorderResult>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_detect_borders_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        params,
+                        crop_params,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let boxes = || {
+        results
+            .iter()
+            .map(|r| r.as_ref().ok().and_then(|r| r.detection.content_box))
+    };
+    let mut columns = vec![
+        ArrowColumn::primitive("content_left", boxes().map(|b| b.map(|b| b.0))),
+        ArrowColumn::primitive("content_top", boxes().map(|b| b.map(|b| b.1))),
+        ArrowColumn::primitive("content_right", boxes().map(|b| b.map(|b| b.2))),
+        ArrowColumn::primitive("content_bottom", boxes().map(|b| b.map(|b| b.3))),
+        ArrowColumn::primitive(
+            "border_fraction",
+            results
+                .iter()
+                .map(|r| r.as_ref().ok().map(|r| r.detection.border_fraction)),
+        ),
+    ];
+    if crop {
+        columns.push(ArrowColumn::binary(
+            "cropped",
+            results
+                .iter()
+                .map(|r| r.as_ref().ok().and_then(|r| r.cropped.as_deref())),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::test_images::framed;
+    use image::{Rgb, RgbImage};
+
+    const BORDERS: BorderParams = BorderParams {
+        tolerance: DEFAULT_BORDER_TOLERANCE,
+        min_border: DEFAULT_MIN_BORDER,
+        any_color: false,
+    };
+
+    #[test]
+    fn borders_are_found_on_every_side() {
+        let detection = detect_borders(&framed(100, 80, (10, 5, 20, 15)), BORDERS);
+        assert_eq!(detection.content_box, Some((10, 5, 80, 65)));
+        assert!((detection.border_fraction - (1.0 - 70.0 * 60.0 / 8000.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thin_borders_are_ignored() {
+        let detection = detect_borders(&framed(50, 50, (1, 0, 0, 0)), BORDERS);
+        assert_eq!(detection.content_box, Some((0, 0, 50, 50)));
+        assert_eq!(detection.border_fraction, 0.0);
+    }
+
+    #[test]
+    fn colored_borders_need_any_color() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(40, 40, |x, y| {
+            if y < 8 {
+                Rgb([200, 30, 30])
+            } else {
+                Rgb([(x * 37 % 200) as u8, (y * 53 % 200) as u8, 90])
+            }
+        }));
+        assert_eq!(detect_borders(&image, BORDERS).border_fraction, 0.0);
+        let any_color = BorderParams {
+            any_color: true,
+            ..BORDERS
+        };
+        assert_eq!(
+            detect_borders(&image, any_color).content_box,
+            Some((0, 8, 40, 40))
+        );
+    }
+
+    #[test]
+    fn images_that_are_all_border_have_no_content_box() {
+        let solid = DynamicImage::ImageRgb8(RgbImage::new(30, 20));
+        let detection = detect_borders(&solid, BORDERS);
+        assert!(detection.content_box.is_none());
+        assert_eq!(detection.border_fraction, 1.0);
+
+        // Black top half and white bottom half leave no rows for the columns
+        let split = DynamicImage::ImageRgb8(RgbImage::from_fn(30, 20, |_, y| {
+            Rgb(if y < 10 { [0; 3] } else { [255; 3] })
+        }));
+        let detection = detect_borders(&split, BORDERS);
+        assert!(detection.content_box.is_none());
+        assert_eq!(detection.border_fraction, 1.0);
+    }
+}
@@ -0,0 +1,553 @@
+//! Aspect-ratio bucket assignment and crop planning
+
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::ImageDecoder;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels, open_image, DecodeLimits};
+use crate::image_encode::{check_quality, encode_image, OutputFormat, DEFAULT_JPEG_QUALITY};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::preprocess::{cover_crop, parse_resize_filter, CoverCrop};
+
+/// Default bucket pixel budget (1024 x 1024)
+const DEFAULT_BUCKET_AREA: u64 = 1024 * 1024;
+
+/// Default bucket side granularity
+const DEFAULT_BUCKET_STEP: u32 = 64;
+
+/// Default widest (and, inverted, tallest) generated bucket
+const DEFAULT_BUCKET_MAX_ASPECT_RATIO: f64 = 4.0;
+
+/// Most candidate widths tried when generating buckets
+const MAX_BUCKET_WIDTHS: u64 = 1 << 16;
+
+/// Set of (width, height) buckets images are assigned to
+struct BucketSpec {
+    buckets: Vec<(u32, u32)>,
+}
+
+impl BucketSpec {
+    /// Build buckets of about `target_area` pixels with sides that are multiples of `step`
+    ///
+    /// With `ratios` (width / height), there is one bucket per ratio, its sides rounded
+    /// to `step`. Otherwise every width that is a multiple of `step` gets the tallest
+    /// height that keeps the bucket within `target_area`, up to `max_aspect_ratio`.
+    /// Widths past `sqrt(target_area * max_aspect_ratio)` are never tried, and more than
+    /// `MAX_BUCKET_WIDTHS` candidates are an error.
+    fn new(
+        target_area: u64,
+        step: u32,
+        ratios: Option<Vec<f64>>,
+        max_aspect_ratio: f64,
+    ) -> PyResult<Self> {
+        if step == 0 || u64::from(step) * u64::from(step) > target_area {
+            return Err(PyValueError::new_err(
+                "step must be positive and step * step must not exceed target_area",
+            ));
+        }
+        if max_aspect_ratio.is_nan() || max_aspect_ratio < 1.0 {
+            return Err(PyValueError::new_err("max_aspect_ratio must be at least 1"));
+        }
+
+        let step_u64 = u64::from(step);
+        // A bucket of width w is at least w^2 / target_area wide
+        let widest = ((target_area as f64 * max_aspect_ratio).sqrt() / f64::from(step)) as u64;
+        let max_widths = (target_area / (step_u64 * step_u64)).min(widest.saturating_add(1));
+        if ratios.is_none() && max_widths > MAX_BUCKET_WIDTHS {
+            return Err(PyValueError::new_err(format!(
+                "target_area, step and max_aspect_ratio allow {max_widths} bucket widths \
+                 (at most {MAX_BUCKET_WIDTHS}); increase step or lower max_aspect_ratio"
+            )));
+        }
+        let to_step = |side: f64| ((side / f64::from(step)).round() as u64).max(1) * step_u64;
+        let mut buckets: Vec<(u32, u32)> = match ratios {
+            Some(ratios) => {
+                if ratios.is_empty() || ratios.iter().any(|&r| !(r.is_finite() && r > 0.0)) {
+                    return Err(PyValueError::new_err(
+                        "ratios must be a non-empty list of positive numbers",
+                    ));
+                }
+                ratios
+                    .iter()
+                    .map(|&ratio| {
+                        let area = target_area as f64;
+                        (
+                            to_step((area * ratio).sqrt()),
+                            to_step((area / ratio).sqrt()),
+                        )
+                    })
+                    .map(|(width, height)| (clamp_u32(width), clamp_u32(height)))
+                    .collect()
+            }
+            None => (1..=max_widths)
+                .map(|k| k * step_u64)
+                .map(|width| (width, target_area / width / step_u64 * step_u64))
+                .filter(|&(width, height)| {
+                    let ratio = width as f64 / height as f64;
+                    ratio <= max_aspect_ratio && 1.0 / ratio <= max_aspect_ratio
+                })
+                .map(|(width, height)| (clamp_u32(width), clamp_u32(height)))
+                .collect(),
+        };
+        buckets.sort_unstable();
+        buckets.dedup();
+        if buckets.is_empty() {
+            return Err(PyValueError::new_err(
+                "no bucket fits target_area, step and max_aspect_ratio",
+            ));
+        }
+        Ok(Self { buckets })
+    }
+
+    /// Assign an image to the bucket closest in (log) aspect ratio and plan its crop
+    fn plan(&self, dimensions: (u32, u32)) -> BucketPlan {
+        let (width, height) = dimensions;
+        let log_ratio = (f64::from(width) / f64::from(height)).ln();
+        let distance = |&(bucket_width, bucket_height): &(u32, u32)| {
+            ((f64::from(bucket_width) / f64::from(bucket_height)).ln() - log_ratio).abs()
+        };
+        let bucket = self
+            .buckets
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(dimensions);
+        BucketPlan {
+            bucket,
+            crop: cover_crop(dimensions, bucket),
+        }
+    }
+}
+
+fn clamp_u32(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+/// Bucket assignment of one image
+#[derive(Clone, Copy)]
+struct BucketPlan {
+    /// (width, height) of the assigned bucket
+    bucket: (u32, u32),
+    /// Resize covering the bucket and the centered bucket-sized crop
+    crop: CoverCrop,
+}
+
+/// Convert a bucket plan to a dict
+fn bucket_plan_to_dict<'py>(py: Python<'py>, plan: &BucketPlan) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("bucket", plan.bucket)?;
+    dict.set_item("resize", plan.crop.resized)?;
+    dict.set_item("crop_box", plan.crop.crop_box)?;
+    dict.set_item("scale", plan.crop.scale)?;
+    Ok(dict)
+}
+
+/// Re-encoding options for bucketed images; `None` format keeps the source format
+#[derive(Clone, Copy)]
+struct BucketEncode {
+    format: Option<OutputFormat>,
+    quality: u8,
+    filter: FilterType,
+}
+
+/// Bucket assignment of one image, with the resized and cropped image if requested
+struct BucketResult {
+    plan: BucketPlan,
+    image: Option<Vec<u8>>,
+}
+
+/// Dimensions of an image after applying its EXIF orientation
+fn oriented_dimensions(dimensions: (u32, u32), orientation: Orientation) -> (u32, u32) {
+    if orientation.to_exif() >= 5 {
+        (dimensions.1, dimensions.0)
+    } else {
+        dimensions
+    }
+}
+
+/// Assign one image to a bucket, decoding it only when it is re-encoded
+fn image_bucket_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    spec: &BucketSpec,
+    encode: Option<BucketEncode>,
+) -> ItemResult<BucketResult> {
+    let (format, mut decoder) = open_image(image_bytes, limits)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let Some(encode) = encode else {
+        let plan = spec.plan(oriented_dimensions(decoder.dimensions(), orientation));
+        return Ok(BucketResult { plan, image: None });
+    };
+
+    let mut img = decode_pixels(decoder, limits)?;
+    img.apply_orientation(orientation);
+    let plan = spec.plan((img.width(), img.height()));
+    let (resized_width, resized_height) = plan.crop.resized;
+    let (left, top, right, bottom) = plan.crop.crop_box;
+    let bucketed = img
+        .resize_exact(resized_width, resized_height, encode.filter)
+        .crop_imm(left, top, right - left, bottom - top);
+    let output_format = encode.format.unwrap_or(OutputFormat::for_source(
+        format,
+        bucketed.color().has_alpha(),
+    ));
+    let image = encode_image(&bucketed, output_format, encode.quality)?;
+    Ok(BucketResult {
+        plan,
+        image: Some(image),
+    })
+}
+
+/// Assign every image to a bucket in parallel
+fn image_bucket_all(
+    inputs: &[Option<&[u8]>],
+    limits: &DecodeLimits,
+    spec: &BucketSpec,
+    encode: Option<BucketEncode>,
+) -> Vec<ItemResult<BucketResult>> {
+    inputs
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_bucket_core(image_bytes.ok_or_else(null_input)?, limits, spec, encode)
+            })
+        })
+        .collect()
+}
+
+/// Convert bucket results to dicts and build a bucket batch function's return value
+fn bucket_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<BucketResult>>,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = results
+        .into_iter()
+        .map(|result| match result {
+            Ok(result) => {
+                let dict = bucket_plan_to_dict(py, &result.plan)?;
+                if let Some(image) = result.image {
+                    dict.set_item("image", PyBytes::new(py, &image))?;
+                }
+                Ok(Ok(dict.into_any()))
+            }
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Validate re-encoding options passed from Python
+fn parse_bucket_encode(
+    encode: bool,
+    output_format: Option<&str>,
+    quality: u8,
+    resize_filter: &str,
+) -> PyResult<Option<BucketEncode>> {
+    check_quality(quality)?;
+    let format = OutputFormat::parse_optional(output_format)?;
+    let filter = parse_resize_filter(resize_filter)?;
+    Ok(encode.then_some(BucketEncode {
+        format,
+        quality,
+        filter,
+    }))
+}
+
+/// Validate the bucket spec and re-encoding options passed from Python
+#[allow(clippy::too_many_arguments)]
+fn prepare_bucket(
+    target_area: u64,
+    step: u32,
+    ratios: Option<Vec<f64>>,
+    max_aspect_ratio: f64,
+    encode: bool,
+    output_format: Option<&str>,
+    quality: u8,
+    resize_filter: &str,
+) -> PyResult<(BucketSpec, Option<BucketEncode>)> {
+    let spec = BucketSpec::new(target_area, step, ratios, max_aspect_ratio)?;
+    let encode = parse_bucket_encode(encode, output_format, quality, resize_filter)?;
+    Ok((spec, encode))
+}
+
+/// Assign image sizes to aspect-ratio buckets
+///
+/// Buckets have about `target_area` pixels and sides that are multiples of `step`.
+/// With `ratios` (width / height, e.g. [1.0, 4/3, 3/4]) there is one bucket per ratio;
+/// otherwise every multiple of `step` is a bucket width, paired with the tallest height
+/// that stays within `target_area`, up to `max_aspect_ratio` (either orientation).
+///
+/// Each (width, height) is assigned the bucket closest in aspect ratio. Returns one dict
+/// per size (None if a side is 0):
+/// - `bucket`: (width, height) of the bucket
+/// - `resize`: (width, height) to resize the image to, preserving its aspect ratio, so
+///   it covers the bucket
+/// - `crop_box`: (left, top, right, bottom) of the centered bucket-sized crop within the
+///   resized image (a PIL crop box)
+/// - `scale`: Resize factor (> 1 means the image is upscaled)
+///
+/// Sizes are used as given; apply the EXIF orientation first (swap width and height
+/// for orientations 5-8), as `image_bucket_batch` does.
+#[pyfunction]
+#[pyo3(signature = (sizes, target_area=DEFAULT_BUCKET_AREA, step=DEFAULT_BUCKET_STEP, ratios=None, max_aspect_ratio=DEFAULT_BUCKET_MAX_ASPECT_RATIO))]
+pub fn image_plan_buckets<'py>(
+    py: Python<'py>,
+    sizes: Vec<(u32, u32)>,
+    target_area: u64,
+    step: u32,
+    ratios: Option<Vec<f64>>,
+    max_aspect_ratio: f64,
+) -> PyResult<Bound<'py, PyList>> {
+    let spec = BucketSpec::new(target_area, step, ratios, max_aspect_ratio)?;
+    let plans = sizes
+        .into_iter()
+        .map(|(width, height)| {
+            if width == 0 || height == 0 {
+                return Ok(py.None().into_bound(py));
+            }
+            Ok(bucket_plan_to_dict(py, &spec.plan((width, height)))?.into_any())
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    PyList::new(py, plans)
+}
+
+/// Batch assign images to aspect-ratio buckets in parallel (GIL released)
+///
+/// Buckets and the returned dicts are as in `image_plan_buckets`; image sizes are read
+/// from the header and rotated by the EXIF orientation. Failed images become None.
+///
+/// With `encode=True`, each image is also decoded, rotated upright, resized with
+/// `resize_filter` ("bicubic", "bilinear", "lanczos" or "nearest"), cropped to its
+/// bucket and re-encoded; the dict's `image` holds the bytes, in `output_format`
+/// ("jpeg", "png" or "webp"; default: JPEG for JPEG and opaque WebP sources, otherwise
+/// PNG) with JPEG `quality`.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, target_area=DEFAULT_BUCKET_AREA, step=DEFAULT_BUCKET_STEP, ratios=None, max_aspect_ratio=DEFAULT_BUCKET_MAX_ASPECT_RATIO, encode=false, output_format=None, quality=DEFAULT_JPEG_QUALITY, resize_filter="bicubic"))]
+pub fn image_bucket_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    target_area: u64,
+    step: u32,
+    ratios: Option<Vec<f64>>,
+    max_aspect_ratio: f64,
+    encode: bool,
+    output_format: Option<&str>,
+    quality: u8,
+    resize_filter: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (spec, encode) = prepare_bucket(
+        target_area,
+        step,
+        ratios,
+        max_aspect_ratio,
+        encode,
+        output_format,
+        quality,
+        resize_filter,
+    )?;
+    let results = detach_in_pool(py, || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        image_bucket_all(&inputs, &limits, &spec, encode)
+    });
+    bucket_batch_output(py, results, return_status)
+}
+
+/// Non-blocking `image_bucket_batch`; returns a `BatchFuture`
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, target_area=DEFAULT_BUCKET_AREA, step=DEFAULT_BUCKET_STEP, ratios=None, max_aspect_ratio=DEFAULT_BUCKET_MAX_ASPECT_RATIO, encode=false, output_format=None, quality=DEFAULT_JPEG_QUALITY, resize_filter="bicubic"))]
+pub fn submit_image_bucket_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    target_area: u64,
+    step: u32,
+    ratios: Option<Vec<f64>>,
+    max_aspect_ratio: f64,
+    encode: bool,
+    output_format: Option<&str>,
+    quality: u8,
+    resize_filter: &str,
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let (spec, encode) = prepare_bucket(
+        target_area,
+        step,
+        ratios,
+        max_aspect_ratio,
+        encode,
+        output_format,
+        quality,
+        resize_filter,
+    )?;
+    Ok(BatchFuture::spawn(move || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        let results = image_bucket_all(&inputs, &limits, &spec, encode);
+        finisher(move |py| bucket_batch_output(py, results, return_status))
+    }))
+}
+
+/// Arrow variant of `image_bucket_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `bucket_width`, `bucket_height`, `resize_width`,
+/// `resize_height`, `crop_left`, `crop_top`, `crop_right`, `crop_bottom` and `scale`
+/// columns, plus a binary `image` column with `encode=True`. Failed or null images are
+/// null throughout. With `return_status=True`, `status` and `status_message` columns
+/// are added.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, target_area=DEFAULT_BUCKET_AREA, step=DEFAULT_BUCKET_STEP, ratios=None, max_aspect_ratio=DEFAULT_BUCKET_MAX_ASPECT_RATIO, encode=false, output_format=None, quality=DEFAULT_JPEG_QUALITY, resize_filter="bicubic"))]
+pub fn image_bucket_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    target_area: u64,
+    step: u32,
+    ratios: Option<Vec<f64>>,
+    max_aspect_ratio: f64,
+    encode: bool,
+    output_format: Option<&str>,
+    quality: u8,
+    resize_filter: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (spec, encode_options) = prepare_bucket(
+        target_area,
+        step,
+        ratios,
+        max_aspect_ratio,
+        encode,
+        output_format,
+        quality,
+        resize_filter,
+    )?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results = detach_in_pool(py, || {
+        image_bucket_all(&values, &limits, &spec, encode_options)
+    });
+
+    let plans = || results.iter().map(|r| r.as_ref().ok().map(|r| r.plan));
+    let mut columns = vec![
+        ArrowColumn::primitive("bucket_width", plans().map(|p| p.map(|p| p.bucket.0))),
+        ArrowColumn::primitive("bucket_height", plans().map(|p| p.map(|p| p.bucket.1))),
+        ArrowColumn::primitive("resize_width", plans().map(|p| p.map(|p| p.crop.resized.0))),
+        ArrowColumn::primitive(
+            "resize_height",
+            plans().map(|p| p.map(|p| p.crop.resized.1)),
+        ),
+        ArrowColumn::primitive("crop_left", plans().map(|p| p.map(|p| p.crop.crop_box.0))),
+        ArrowColumn::primitive("crop_top", plans().map(|p| p.map(|p| p.crop.crop_box.1))),
+        ArrowColumn::primitive("crop_right", plans().map(|p| p.map(|p| p.crop.crop_box.2))),
+        ArrowColumn::primitive("crop_bottom", plans().map(|p| p.map(|p| p.crop.crop_box.3))),
+        ArrowColumn::primitive("scale", plans().map(|p| p.map(|p| p.crop.scale))),
+    ];
+    if encode {
+        columns.push(ArrowColumn::binary(
+            "image",
+            results
+                .iter()
+                .map(|r| r.as_ref().ok().and_then(|r| r.image.as_deref())),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Buckets from every width up to `target_area / step`, as before the width cap
+    fn all_width_buckets(target_area: u64, step: u64, max_aspect_ratio: f64) -> Vec<(u32, u32)> {
+        (1..=target_area / (step * step))
+            .map(|k| (k * step, target_area / (k * step) / step * step))
+            .filter(|&(width, height)| {
+                let ratio = width as f64 / height as f64;
+                ratio <= max_aspect_ratio && 1.0 / ratio <= max_aspect_ratio
+            })
+            .map(|(width, height)| (width as u32, height as u32))
+            .collect()
+    }
+
+    #[test]
+    fn capped_bucket_widths_give_the_same_buckets() {
+        for (area, step, max_ratio) in [
+            (1024 * 1024, 64, 4.0),
+            (512 * 512, 32, 2.0),
+            (1000 * 1000, 7, 1.05),
+            (4096, 64, f64::INFINITY),
+        ] {
+            let spec = BucketSpec::new(area, step, None, max_ratio).unwrap();
+            assert_eq!(
+                spec.buckets,
+                all_width_buckets(area, step.into(), max_ratio)
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_bucket_widths_are_rejected() {
+        assert!(BucketSpec::new(u64::MAX / 2, 1, None, 4.0).is_err());
+        assert!(BucketSpec::new(1 << 40, 1, None, f64::INFINITY).is_err());
+        // Ratios do not enumerate widths
+        let spec = BucketSpec::new(1 << 40, 1, Some(vec![1.0, 2.0]), 4.0).unwrap();
+        assert_eq!(spec.buckets.len(), 2);
+    }
+
+    #[test]
+    fn images_go_to_the_closest_bucket_in_log_ratio() {
+        let spec = BucketSpec::new(1 << 20, 64, Some(vec![1.0, 2.0]), 4.0).unwrap();
+        // 1.45 is closer to 1 than to 2, but closer to 2 in log ratio
+        assert_eq!(spec.plan((290, 200)).bucket, (1472, 704));
+        assert_eq!(spec.plan((280, 200)).bucket, (1024, 1024));
+        assert_eq!(spec.plan((200, 800)).bucket, (1024, 1024));
+    }
+
+    #[test]
+    fn crop_boxes_lie_inside_the_resized_image() {
+        let spec = BucketSpec::new(1 << 20, 64, None, 4.0).unwrap();
+        for dimensions in [
+            (1, 1),
+            (1, 4000),
+            (4000, 1),
+            (333, 777),
+            (1920, 1080),
+            (640, 641),
+        ] {
+            let plan = spec.plan(dimensions);
+            let (resized_width, resized_height) = plan.crop.resized;
+            let (left, top, right, bottom) = plan.crop.crop_box;
+            assert!(
+                right <= resized_width && bottom <= resized_height,
+                "{dimensions:?}"
+            );
+            assert_eq!((right - left, bottom - top), plan.bucket, "{dimensions:?}");
+            assert!(resized_width - plan.bucket.0 <= 1 || resized_height - plan.bucket.1 <= 1);
+        }
+    }
+
+    #[test]
+    fn orientations_5_to_8_swap_dimensions() {
+        for exif in 1..=8 {
+            let orientation = Orientation::from_exif(exif).unwrap();
+            let expected = if exif >= 5 { (200, 300) } else { (300, 200) };
+            assert_eq!(oriented_dimensions((300, 200), orientation), expected);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! EXIF/XMP/ICC/C2PA metadata extraction and stripping
+
+use image::{ImageDecoder, ImageFormat};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{open_image, DecodeLimits};
+use crate::image_metadata::{
+    c2pa_source_types, find_c2pa_manifest, icc_profile_name, is_ai_generated_source,
+    strip_metadata, ExifSummary, StripMode, XmpSummary,
+};
+use crate::status::{batch_output, catch_panic, ItemError, ItemResult, ItemStatus};
+
+/// EXIF, XMP, ICC and C2PA metadata of one image
+struct EmbeddedMetadata {
+    exif: Option<ExifSummary>,
+    xmp: Option<XmpSummary>,
+    has_gps: bool,
+    /// From XMP, falling back to the first one declared in the C2PA manifest
+    digital_source_type: Option<String>,
+    has_c2pa: bool,
+    /// XMP or C2PA declare the image as (partly) produced by generative AI
+    declared_ai_generated: bool,
+    has_icc_profile: bool,
+    icc_profile_name: Option<String>,
+    /// Image bytes with metadata removed, if stripping was requested
+    stripped: Option<Vec<u8>>,
+}
+
+/// Read the metadata embedded in one image and optionally strip it (no pixel decoding)
+fn image_embedded_metadata_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    strip: Option<StripMode>,
+) -> ItemResult<EmbeddedMetadata> {
+    let (format, mut decoder) = open_image(image_bytes, limits)?;
+    // Unreadable metadata blocks count as absent
+    let exif = decoder.exif_metadata().ok().flatten();
+    let xmp = decoder.xmp_metadata().ok().flatten();
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let exif = exif.map(|exif| ExifSummary::parse(&exif));
+    let xmp = xmp.map(|xmp| XmpSummary::parse(&xmp));
+    let c2pa = format.and_then(|format| find_c2pa_manifest(image_bytes, format));
+    let c2pa_sources = c2pa.as_deref().map(c2pa_source_types).unwrap_or_default();
+
+    let xmp_source = xmp.as_ref().and_then(|xmp| xmp.digital_source_type.clone());
+    let declared_ai_generated = xmp_source.as_deref().is_some_and(is_ai_generated_source)
+        || c2pa_sources.iter().any(|code| is_ai_generated_source(code));
+    let has_gps = exif.as_ref().is_some_and(|exif| exif.has_gps)
+        || xmp.as_ref().is_some_and(|xmp| xmp.has_gps);
+
+    let stripped = match (strip, format) {
+        (None, _) => None,
+        (Some(mode), Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP))) => {
+            Some(strip_metadata(image_bytes, format, mode)?)
+        }
+        // Other containers are only passed through when there is nothing to remove
+        (Some(mode), _) => {
+            let has_target = match mode {
+                StripMode::Gps => has_gps,
+                StripMode::All => exif.is_some() || xmp.is_some() || c2pa.is_some(),
+            };
+            if has_target {
+                return Err(ItemError::new(
+                    ItemStatus::UnsupportedFormat,
+                    "metadata stripping supports JPEG, PNG and WebP",
+                ));
+            }
+            Some(image_bytes.to_vec())
+        }
+    };
+
+    Ok(EmbeddedMetadata {
+        has_gps,
+        digital_source_type: xmp_source.or_else(|| c2pa_sources.first().map(|c| c.to_string())),
+        has_c2pa: c2pa.is_some(),
+        declared_ai_generated,
+        has_icc_profile: icc_profile.is_some(),
+        icc_profile_name: icc_profile.as_deref().and_then(icc_profile_name),
+        exif,
+        xmp,
+        stripped,
+    })
+}
+
+/// Convert embedded metadata to a dict
+fn embedded_metadata_to_dict(
+    py: Python<'_>,
+    metadata: EmbeddedMetadata,
+) -> PyResult<Bound<'_, PyAny>> {
+    let exif = metadata.exif.as_ref();
+    let xmp = metadata.xmp.as_ref();
+    let dict = PyDict::new(py);
+    dict.set_item("has_exif", exif.is_some())?;
+    dict.set_item("camera_make", exif.and_then(|e| e.make.as_deref()))?;
+    dict.set_item("camera_model", exif.and_then(|e| e.model.as_deref()))?;
+    dict.set_item("software", exif.and_then(|e| e.software.as_deref()))?;
+    dict.set_item("capture_time", exif.and_then(|e| e.capture_time.as_deref()))?;
+    dict.set_item("orientation", exif.and_then(|e| e.orientation))?;
+    dict.set_item("has_gps", metadata.has_gps)?;
+    dict.set_item("has_xmp", xmp.is_some())?;
+    dict.set_item("creator_tool", xmp.and_then(|x| x.creator_tool.as_deref()))?;
+    dict.set_item(
+        "digital_source_type",
+        metadata.digital_source_type.as_deref(),
+    )?;
+    dict.set_item("has_c2pa", metadata.has_c2pa)?;
+    dict.set_item("declared_ai_generated", metadata.declared_ai_generated)?;
+    dict.set_item("has_icc_profile", metadata.has_icc_profile)?;
+    dict.set_item("icc_profile_name", metadata.icc_profile_name.as_deref())?;
+    if let Some(stripped) = &metadata.stripped {
+        dict.set_item("image", PyBytes::new(py, stripped))?;
+    }
+    Ok(dict.into_any())
+}
+
+/// Read embedded metadata of every image in parallel
+fn image_embedded_metadata_all(
+    inputs: &[Option<&[u8]>],
+    limits: &DecodeLimits,
+    strip: Option<StripMode>,
+) -> Vec<ItemResult<EmbeddedMetadata>> {
+    inputs
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_embedded_metadata_core(image_bytes.ok_or_else(null_input)?, limits, strip)
+            })
+        })
+        .collect()
+}
+
+/// Convert metadata to dicts and build an embedded metadata batch function's return value
+fn embedded_metadata_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<EmbeddedMetadata>>,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = results
+        .into_iter()
+        .map(|result| match result {
+            Ok(metadata) => embedded_metadata_to_dict(py, metadata).map(Ok),
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Validate the strip mode passed from Python
+fn prepare_embedded_metadata(strip: Option<&str>) -> PyResult<Option<StripMode>> {
+    let strip = StripMode::parse_optional(strip)?;
+    Ok(strip)
+}
+
+/// Batch read EXIF, XMP, ICC and C2PA metadata in parallel, optionally stripping it
+/// (no pixel decoding, GIL released)
+///
+/// Returns one dict per image with:
+/// - `has_exif`, `camera_make`, `camera_model`, `software`, `capture_time`
+///   (DateTimeOriginal, else DateTime, as "YYYY:MM:DD HH:MM:SS") and `orientation`
+///   from EXIF
+/// - `has_gps`: EXIF or XMP carry GPS data
+/// - `has_xmp`, `creator_tool` (`xmp:CreatorTool`) and `digital_source_type`: IPTC
+///   code such as "digitalCapture" or "trainedAlgorithmicMedia", from XMP or else the
+///   C2PA manifest
+/// - `has_c2pa`: A C2PA manifest store is embedded
+/// - `declared_ai_generated`: XMP or C2PA declare "trainedAlgorithmicMedia" or
+///   "compositeWithTrainedAlgorithmicMedia"
+/// - `has_icc_profile` and `icc_profile_name`
+///
+/// With `strip="gps"` or `strip="all"`, the dict also has `image`: the bytes with the GPS
+/// data, or all EXIF/XMP/IPTC/C2PA metadata and comments, removed without re-encoding
+/// (ICC profiles are kept; EXIF blocks too malformed to locate their GPS data in are
+/// dropped whole). Stripping supports JPEG, PNG and WebP; other formats fail
+/// with "unsupported_format" unless they carry nothing to remove. Fields describe the
+/// original image.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, strip=None))]
+pub fn image_embedded_metadata_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    strip: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let strip = prepare_embedded_metadata(strip)?;
+    let results = detach_in_pool(py, || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        image_embedded_metadata_all(&inputs, &limits, strip)
+    });
+    embedded_metadata_batch_output(py, results, return_status)
+}
+
+/// Non-blocking `image_embedded_metadata_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, strip=None))]
+pub fn submit_image_embedded_metadata_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    strip: Option<&str>,
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let strip = prepare_embedded_metadata(strip)?;
+    Ok(BatchFuture::spawn(move || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        let results = image_embedded_metadata_all(&inputs, &limits, strip);
+        finisher(move |py| embedded_metadata_batch_output(py, results, return_status))
+    }))
+}
+
+/// Arrow variant of `image_embedded_metadata_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with one column per dict key (`image` only when `strip` is
+/// set). Failed or null images are null throughout. With `return_status=True`,
+/// `status` and `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, strip=None))]
+pub fn image_embedded_metadata_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    strip: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let strip = prepare_embedded_metadata(strip)?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results = detach_in_pool(py, || image_embedded_metadata_all(&values, &limits, strip));
+
+    let found = || results.iter().map(|r| r.as_ref().ok());
+    let exif_text = |field: fn(&ExifSummary) -> Option<&String>| {
+        found().map(move |r| r.and_then(|m| m.exif.as_ref().and_then(field)))
+    };
+    let mut columns = vec![
+        ArrowColumn::boolean("has_exif", found().map(|r| r.map(|m| m.exif.is_some()))),
+        ArrowColumn::utf8("camera_make", exif_text(|e| e.make.as_ref())),
+        ArrowColumn::utf8("camera_model", exif_text(|e| e.model.as_ref())),
+        ArrowColumn::utf8("software", exif_text(|e| e.software.as_ref())),
+        ArrowColumn::utf8("capture_time", exif_text(|e| e.capture_time.as_ref())),
+        ArrowColumn::primitive(
+            "orientation",
+            found().map(|r| r.and_then(|m| m.exif.as_ref()?.orientation).map(u32::from)),
+        ),
+        ArrowColumn::boolean("has_gps", found().map(|r| r.map(|m| m.has_gps))),
+        ArrowColumn::boolean("has_xmp", found().map(|r| r.map(|m| m.xmp.is_some()))),
+        ArrowColumn::utf8(
+            "creator_tool",
+            found().map(|r| r.and_then(|m| m.xmp.as_ref()?.creator_tool.as_ref())),
+        ),
+        ArrowColumn::utf8(
+            "digital_source_type",
+            found().map(|r| r.and_then(|m| m.digital_source_type.as_ref())),
+        ),
+        ArrowColumn::boolean("has_c2pa", found().map(|r| r.map(|m| m.has_c2pa))),
+        ArrowColumn::boolean(
+            "declared_ai_generated",
+            found().map(|r| r.map(|m| m.declared_ai_generated)),
+        ),
+        ArrowColumn::boolean(
+            "has_icc_profile",
+            found().map(|r| r.map(|m| m.has_icc_profile)),
+        ),
+        ArrowColumn::utf8(
+            "icc_profile_name",
+            found().map(|r| r.and_then(|m| m.icc_profile_name.as_ref())),
+        ),
+    ];
+    if strip.is_some() {
+        columns.push(ArrowColumn::binary(
+            "image",
+            found().map(|r| r.and_then(|m| m.stripped.as_ref())),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
@@ -0,0 +1,935 @@
+//! Perceptual hashing and Hamming-distance near-duplicate search
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use image::{DynamicImage, GrayImage, Luma};
+use image_hasher::{HashAlg, HasherConfig};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyString};
+use pyo3::IntoPyObjectExt;
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_raw_pixels, open_image, DecodeLimits};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+/// Perceptual hash algorithm selected by name
+#[derive(Clone, Copy)]
+pub(super) enum PhashAlgorithm {
+    /// One of the `image_hasher` algorithms
+    Hasher(HashAlg),
+    /// DCT pHash, bit-compatible with Python `imagehash.phash`
+    ImagehashDct,
+}
+
+impl PhashAlgorithm {
+    fn parse(name: &str) -> PyResult<Self> {
+        Ok(match name {
+            "mean" => Self::Hasher(HashAlg::Mean),
+            "median" => Self::Hasher(HashAlg::Median),
+            "gradient" => Self::Hasher(HashAlg::Gradient),
+            "vert_gradient" => Self::Hasher(HashAlg::VertGradient),
+            "double_gradient" => Self::Hasher(HashAlg::DoubleGradient),
+            "blockhash" => Self::Hasher(HashAlg::Blockhash),
+            "phash" => Self::ImagehashDct,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown hash algorithm: {other} (expected mean, median, gradient, \
+                     vert_gradient, double_gradient, blockhash or phash)"
+                )))
+            }
+        })
+    }
+}
+
+/// Output representation of a hash
+#[derive(Clone, Copy)]
+pub(super) enum HashEncoding {
+    Hex,
+    Base64,
+    U64,
+}
+
+impl HashEncoding {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            "u64" => Ok(Self::U64),
+            other => Err(PyValueError::new_err(format!(
+                "unknown hash encoding: {other} (expected 'hex', 'base64' or 'u64')"
+            ))),
+        }
+    }
+
+    /// Convert a hash to its Python representation
+    pub(super) fn encode<'py>(
+        self,
+        py: Python<'py>,
+        hash: &HashBits,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        match self {
+            Self::Hex => hash.to_hex().into_bound_py_any(py),
+            Self::Base64 => STANDARD_NO_PAD.encode(&hash.bytes).into_bound_py_any(py),
+            Self::U64 => hash.to_u64_words().into_bound_py_any(py),
+        }
+    }
+
+    /// String form of a hash; `None` for the non-string u64 encoding
+    fn encode_str(self, hash: &HashBits) -> Option<String> {
+        match self {
+            Self::Hex => Some(hash.to_hex()),
+            Self::Base64 => Some(STANDARD_NO_PAD.encode(&hash.bytes)),
+            Self::U64 => None,
+        }
+    }
+
+    /// Python placeholder for a failed hash: "" or an empty list
+    fn empty<'py>(self, py: Python<'py>) -> Bound<'py, PyAny> {
+        match self {
+            Self::Hex | Self::Base64 => PyString::new(py, "").into_any(),
+            Self::U64 => PyList::empty(py).into_any(),
+        }
+    }
+}
+
+/// Hash bits packed MSB-first into bytes; `bits` may be less than `bytes.len() * 8`
+pub(super) struct HashBits {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl HashBits {
+    fn from_bools(values: &[bool]) -> Self {
+        let mut bytes = vec![0u8; values.len().div_ceil(8)];
+        for (i, _) in values.iter().enumerate().filter(|(_, &v)| v) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+        Self {
+            bytes,
+            bits: values.len(),
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.bytes[i / 8] & (0x80 >> (i % 8)) != 0
+    }
+
+    /// Hex digits of the bits read as one big-endian integer, like `str(imagehash.ImageHash)`
+    fn to_hex(&self) -> String {
+        let pad = (4 - self.bits % 4) % 4;
+        (0..(self.bits + pad) / 4)
+            .map(|nibble| {
+                let value = (0..4).fold(0u32, |acc, k| {
+                    let i = (nibble * 4 + k) as isize - pad as isize;
+                    (acc << 1) | (i >= 0 && self.bit(i as usize)) as u32
+                });
+                char::from_digit(value, 16).unwrap_or('0')
+            })
+            .collect()
+    }
+
+    /// Bytes as big-endian u64 words, the last word zero-padded on the right
+    fn to_u64_words(&self) -> Vec<u64> {
+        self.bytes
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0u8; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u64::from_be_bytes(word)
+            })
+            .collect()
+    }
+}
+
+/// Lanczos-3 kernel as defined by Pillow
+fn pil_lanczos_filter(x: f64) -> f64 {
+    let sinc = |x: f64| {
+        if x == 0.0 {
+            1.0
+        } else {
+            let x = x * std::f64::consts::PI;
+            x.sin() / x
+        }
+    };
+    if (-3.0..3.0).contains(&x) {
+        sinc(x) * sinc(x / 3.0)
+    } else {
+        0.0
+    }
+}
+
+/// Pillow's 8-bit fixed-point resampling coefficients for one axis
+///
+/// Returns, per output pixel, the first input index and its integer weights.
+fn pil_resample_coeffs(in_size: usize, out_size: usize) -> Vec<(usize, Vec<i32>)> {
+    const PRECISION_BITS: u32 = 32 - 8 - 2;
+    let scale = in_size as f64 / out_size as f64;
+    let filterscale = scale.max(1.0);
+    let support = 3.0 * filterscale;
+
+    (0..out_size)
+        .map(|xx| {
+            let center = (xx as f64 + 0.5) * scale;
+            let xmin = ((center - support + 0.5) as i64).max(0) as usize;
+            let xmax = ((center + support + 0.5) as i64).min(in_size as i64) as usize;
+            let weights: Vec<f64> = (xmin..xmax)
+                .map(|x| pil_lanczos_filter((x as f64 - center + 0.5) * (1.0 / filterscale)))
+                .collect();
+            let total: f64 = weights.iter().sum();
+            let fixed = weights
+                .iter()
+                .map(|&w| {
+                    let w = if total != 0.0 { w / total } else { w };
+                    let scaled = w * (1u32 << PRECISION_BITS) as f64;
+                    if scaled < 0.0 {
+                        (scaled - 0.5).trunc() as i32
+                    } else {
+                        (scaled + 0.5).trunc() as i32
+                    }
+                })
+                .collect();
+            (xmin, fixed)
+        })
+        .collect()
+}
+
+/// Apply fixed-point coefficients along one axis, rounding and clipping to 8 bits
+fn pil_resample_pass(
+    src: &[u8],
+    lines: usize,
+    stride: usize,
+    step: usize,
+    coeffs: &[(usize, Vec<i32>)],
+) -> Vec<u8> {
+    const PRECISION_BITS: u32 = 32 - 8 - 2;
+    let mut out = Vec::with_capacity(lines * coeffs.len());
+    for line in 0..lines {
+        for (start, weights) in coeffs {
+            let mut acc: i64 = 1 << (PRECISION_BITS - 1);
+            for (k, &w) in weights.iter().enumerate() {
+                acc += src[line * stride + (start + k) * step] as i64 * w as i64;
+            }
+            out.push((acc >> PRECISION_BITS).clamp(0, 255) as u8);
+        }
+    }
+    out
+}
+
+/// Resize an 8-bit grayscale image exactly like Pillow's `Image.resize(..., LANCZOS)`
+fn pil_resize_lanczos(gray: &GrayImage, out_w: u32, out_h: u32) -> Vec<u8> {
+    let (w, h) = (gray.width() as usize, gray.height() as usize);
+    let (out_w, out_h) = (out_w as usize, out_h as usize);
+    let mut pixels = gray.as_raw().clone();
+
+    // Horizontal pass first, then vertical, each rounding to 8 bits like Pillow
+    let mut cur_w = w;
+    if out_w != w {
+        let coeffs = pil_resample_coeffs(w, out_w);
+        pixels = pil_resample_pass(&pixels, h, w, 1, &coeffs);
+        cur_w = out_w;
+    }
+    if out_h != h {
+        let coeffs = pil_resample_coeffs(h, out_h);
+        let mut out = vec![0u8; out_w * out_h];
+        for x in 0..cur_w {
+            let column = pil_resample_pass(&pixels[x..], 1, 0, cur_w, &coeffs);
+            for (y, v) in column.into_iter().enumerate() {
+                out[y * cur_w + x] = v;
+            }
+        }
+        pixels = out;
+    }
+    pixels
+}
+
+/// Convert to 8-bit grayscale with Pillow's `convert("L")` integer weights
+fn pil_convert_luma(img: &DynamicImage) -> GrayImage {
+    let rgb = img.to_rgb8();
+    GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+        let p = rgb.get_pixel(x, y);
+        let l = (p[0] as u32 * 19595 + p[1] as u32 * 38470 + p[2] as u32 * 7471 + 0x8000) >> 16;
+        Luma([l as u8])
+    })
+}
+
+/// DCT pHash matching `imagehash.phash(img, hash_size, highfreq_factor=4)`
+fn imagehash_phash(img: &DynamicImage, hash_size: u32) -> HashBits {
+    let n = (hash_size * 4) as usize;
+    let hs = hash_size as usize;
+    let pixels = pil_resize_lanczos(&pil_convert_luma(img), n as u32, n as u32);
+
+    // Unnormalized DCT-II basis; the constant factor of scipy's dct does not affect the median test
+    let basis: Vec<Vec<f64>> = (0..hs)
+        .map(|k| {
+            (0..n)
+                .map(|i| {
+                    (std::f64::consts::PI * k as f64 * (2 * i + 1) as f64 / (2 * n) as f64).cos()
+                })
+                .collect()
+        })
+        .collect();
+
+    // DCT along columns (axis 0), keeping only the low-frequency rows
+    let rows: Vec<Vec<f64>> = basis
+        .iter()
+        .map(|b| {
+            (0..n)
+                .map(|x| (0..n).map(|y| b[y] * pixels[y * n + x] as f64).sum())
+                .collect()
+        })
+        .collect();
+
+    // DCT along rows (axis 1), keeping only the low-frequency columns
+    let mut low: Vec<f64> = rows
+        .iter()
+        .flat_map(|row| {
+            basis
+                .iter()
+                .map(move |b| b.iter().zip(row).map(|(c, v)| c * v).sum::<f64>())
+        })
+        .collect();
+
+    // Rounding leaves the AC terms of flat images near, not at, zero; scipy's FFT-based
+    // dct gives exact zeros there, so snap them before the median test
+    let scale = low.iter().fold(0.0f64, |acc, v| acc.max(v.abs()));
+    for v in low.iter_mut().filter(|v| v.abs() <= scale * 1e-9) {
+        *v = 0.0;
+    }
+
+    let mut sorted = low.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    };
+
+    let bits: Vec<bool> = low.iter().map(|&v| v > median).collect();
+    HashBits::from_bools(&bits)
+}
+
+/// Compute perceptual hash of a decoded image
+pub(super) fn phash_from_image(
+    img: &DynamicImage,
+    hash_size: u32,
+    algorithm: PhashAlgorithm,
+    preproc_dct: bool,
+) -> HashBits {
+    match algorithm {
+        PhashAlgorithm::ImagehashDct => imagehash_phash(img, hash_size),
+        PhashAlgorithm::Hasher(alg) => {
+            let mut config = HasherConfig::new()
+                .hash_size(hash_size, hash_size)
+                .hash_alg(alg);
+            if preproc_dct {
+                config = config.preproc_dct();
+            }
+
+            let hash = config.to_hasher().hash_image(img);
+            let bytes = hash.as_bytes().to_vec();
+            let bits = bytes.len() * 8;
+            HashBits { bytes, bits }
+        }
+    }
+}
+
+/// Compute perceptual hash for a single image
+fn image_compute_phash_core(
+    image_bytes: &[u8],
+    hash_size: u32,
+    algorithm: PhashAlgorithm,
+    preproc_dct: bool,
+    limits: &DecodeLimits,
+) -> ItemResult<HashBits> {
+    let (_, decoder) = open_image(image_bytes, limits)?;
+    let pixels = decode_raw_pixels(decoder, limits)?;
+    Ok(phash_from_image(
+        &*pixels.stored()?,
+        hash_size,
+        algorithm,
+        preproc_dct,
+    ))
+}
+
+/// Hash every image in parallel
+fn image_compute_phash_all(
+    image_bytes_list: &[Vec<u8>],
+    hash_size: u32,
+    algorithm: PhashAlgorithm,
+    preproc_dct: bool,
+    limits: &DecodeLimits,
+) -> Vec<ItemResult<HashBits>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_compute_phash_core(image_bytes, hash_size, algorithm, preproc_dct, limits)
+            })
+        })
+        .collect()
+}
+
+/// Encode hashes and build a phash batch function's return value
+fn phash_batch_output(
+    py: Python<'_>,
+    hashes: Vec<ItemResult<HashBits>>,
+    encoding: HashEncoding,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let encoded = hashes
+        .into_iter()
+        .map(|hash| match hash {
+            Ok(bits) => encoding.encode(py, &bits).map(Ok),
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, encoded, || encoding.empty(py), return_status)
+}
+
+/// Validate the hash options passed from Python
+pub(super) fn prepare_phash(
+    hash_size: u32,
+    algorithm: &str,
+    encoding: &str,
+) -> PyResult<(PhashAlgorithm, HashEncoding)> {
+    if hash_size == 0 {
+        return Err(PyValueError::new_err("hash_size must be positive"));
+    }
+    let algorithm = PhashAlgorithm::parse(algorithm)?;
+    let encoding = HashEncoding::parse(encoding)?;
+    Ok((algorithm, encoding))
+}
+
+/// Batch compute perceptual hashes in parallel (GIL released)
+///
+/// `algorithm` is one of mean, median, gradient, vert_gradient, double_gradient,
+/// blockhash (`image_hasher`, optionally with `preproc_dct`) or phash (bit-compatible
+/// with Python `imagehash.phash`). Hashes use the pixels as stored, without color
+/// management, as Pillow's `Image.open` returns them. `encoding` is base64, hex (matching `str()` of an
+/// `imagehash.ImageHash`) or u64 (list of big-endian words). Failed images yield
+/// an empty string, or an empty list for u64. With `return_status=True`, returns
+/// `(results, statuses)` with one (status, message) tuple per image.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, hash_size=16, algorithm="double_gradient", encoding="base64", preproc_dct=false, return_status=false, limits=None))]
+pub fn image_compute_phash_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    hash_size: u32,
+    algorithm: &str,
+    encoding: &str,
+    preproc_dct: bool,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (algorithm, encoding) = prepare_phash(hash_size, algorithm, encoding)?;
+
+    let hashes = detach_in_pool(py, || {
+        image_compute_phash_all(
+            &image_bytes_list,
+            hash_size,
+            algorithm,
+            preproc_dct,
+            &limits,
+        )
+    });
+    phash_batch_output(py, hashes, encoding, return_status)
+}
+
+/// Non-blocking `image_compute_phash_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, hash_size=16, algorithm="double_gradient", encoding="base64", preproc_dct=false, return_status=false, limits=None))]
+pub fn submit_image_compute_phash_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    hash_size: u32,
+    algorithm: &str,
+    encoding: &str,
+    preproc_dct: bool,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let (algorithm, encoding) = prepare_phash(hash_size, algorithm, encoding)?;
+
+    Ok(BatchFuture::spawn(move || {
+        let hashes = image_compute_phash_all(
+            &image_bytes_list,
+            hash_size,
+            algorithm,
+            preproc_dct,
+            &limits,
+        );
+        finisher(move |py| phash_batch_output(py, hashes, encoding, return_status))
+    }))
+}
+
+/// Arrow variant of `image_compute_phash_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with a `phash` string column (null for failed or null
+/// images). Only the hex and base64 encodings are supported. With
+/// `return_status=True`, `status` and `status_message` columns are added.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (images, hash_size=16, algorithm="double_gradient", encoding="base64", preproc_dct=false, return_status=false, limits=None))]
+pub fn image_compute_phash_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    hash_size: u32,
+    algorithm: &str,
+    encoding: &str,
+    preproc_dct: bool,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let (algorithm, encoding) = prepare_phash(hash_size, algorithm, encoding)?;
+    if matches!(encoding, HashEncoding::U64) {
+        return Err(PyValueError::new_err(
+            "encoding 'u64' is not supported for Arrow output (use 'hex' or 'base64')",
+        ));
+    }
+
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let hashes: Vec<ItemResult<String>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    let bits = image_compute_phash_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        hash_size,
+                        algorithm,
+                        preproc_dct,
+                        &limits,
+                    )?;
+                    Ok(encoding.encode_str(&bits).unwrap_or_default())
+                })
+            })
+            .collect()
+    });
+
+    let columns = vec![ArrowColumn::utf8(
+        "phash",
+        hashes.iter().map(|h| h.as_ref().ok()),
+    )];
+    record_batch_output(py, columns, &hashes, return_status)
+}
+
+// ============================================================================
+// Hamming-Distance Near-Duplicate Search
+// ============================================================================
+
+/// Check that `encoding` names a supported hash string encoding
+fn check_hash_encoding(encoding: &str) -> PyResult<()> {
+    match encoding {
+        "base64" | "hex" => Ok(()),
+        other => Err(PyValueError::new_err(format!(
+            "unknown hash encoding: {other} (expected 'base64' or 'hex')"
+        ))),
+    }
+}
+
+/// Decode a perceptual hash string into raw bits
+///
+/// `encoding` is "base64" (as returned by `image_compute_phash_batch`) or "hex"
+/// (as produced by Python `imagehash`). Hex is decoded digit by digit, so an odd
+/// number of digits (e.g. the 36 bits of `hash_size=6`) is accepted.
+fn decode_phash(hash: &str, encoding: &str) -> PyResult<HashBits> {
+    check_hash_encoding(encoding)?;
+    let decoded = if encoding == "base64" {
+        STANDARD_NO_PAD.decode(hash).ok().map(|bytes| HashBits {
+            bits: bytes.len() * 8,
+            bytes,
+        })
+    } else {
+        hash.chars()
+            .map(|c| c.to_digit(16))
+            .collect::<Option<Vec<u32>>>()
+            .map(|digits| {
+                let bits: Vec<bool> = digits
+                    .iter()
+                    .flat_map(|digit| (0..4).rev().map(move |k| digit >> k & 1 == 1))
+                    .collect();
+                HashBits::from_bools(&bits)
+            })
+    };
+    decoded.ok_or_else(|| PyValueError::new_err(format!("invalid {encoding} hash: {hash}")))
+}
+
+/// Number of differing bits between two equal-length hashes
+fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// A BK-tree node; records with identical hashes share one node
+struct BkNode {
+    hash: Vec<u8>,
+    items: Vec<usize>,
+    children: HashMap<u32, usize>,
+}
+
+/// BK-tree over hash bits under the Hamming metric
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+impl BkTree {
+    /// Insert `item` under `hash`
+    fn insert(&mut self, hash: Vec<u8>, item: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode {
+                hash,
+                items: vec![item],
+                children: HashMap::new(),
+            });
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let dist = hamming_distance(&self.nodes[current].hash, &hash);
+            if dist == 0 {
+                self.nodes[current].items.push(item);
+                return;
+            }
+            match self.nodes[current].children.get(&dist) {
+                Some(&child) => current = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(BkNode {
+                        hash,
+                        items: vec![item],
+                        children: HashMap::new(),
+                    });
+                    self.nodes[current].children.insert(dist, child);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Return (item, distance) for every item within `max_distance` of `hash`
+    fn query(&self, hash: &[u8], max_distance: u32) -> Vec<(usize, u32)> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+
+        while let Some(current) = stack.pop() {
+            let node = &self.nodes[current];
+            let dist = hamming_distance(&node.hash, hash);
+            if dist <= max_distance {
+                found.extend(node.items.iter().map(|&item| (item, dist)));
+            }
+            let lo = dist.saturating_sub(max_distance);
+            let hi = dist + max_distance;
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(&d, _)| d >= lo && d <= hi)
+                    .map(|(_, &child)| child),
+            );
+        }
+        found
+    }
+}
+
+/// Hamming-distance index over perceptual hashes (BK-tree)
+///
+/// All inserted hashes must have the same bit length.
+#[pyclass]
+pub struct PhashIndex {
+    encoding: String,
+    hash_bits: Option<usize>,
+    ids: Vec<String>,
+    tree: BkTree,
+}
+
+impl PhashIndex {
+    /// Decode `hash` and check it matches the length of previously inserted hashes
+    fn decode(&self, hash: &str) -> PyResult<HashBits> {
+        let decoded = decode_phash(hash, &self.encoding)?;
+        match self.hash_bits {
+            Some(bits) if bits != decoded.bits => Err(PyValueError::new_err(format!(
+                "hash has {} bits, index holds {bits}-bit hashes",
+                decoded.bits
+            ))),
+            _ => Ok(decoded),
+        }
+    }
+}
+
+#[pymethods]
+impl PhashIndex {
+    #[new]
+    #[pyo3(signature = (encoding="base64"))]
+    fn new(encoding: &str) -> PyResult<Self> {
+        check_hash_encoding(encoding)?;
+        Ok(Self {
+            encoding: encoding.to_string(),
+            hash_bits: None,
+            ids: Vec::new(),
+            tree: BkTree::default(),
+        })
+    }
+
+    /// Add a record's hash to the index
+    fn insert(&mut self, record_id: String, hash: &str) -> PyResult<()> {
+        let decoded = self.decode(hash)?;
+        self.hash_bits = Some(decoded.bits);
+        self.tree.insert(decoded.bytes, self.ids.len());
+        self.ids.push(record_id);
+        Ok(())
+    }
+
+    /// Add many (record_id, hash) pairs
+    fn insert_batch(&mut self, record_ids: Vec<String>, hashes: Vec<String>) -> PyResult<()> {
+        if record_ids.len() != hashes.len() {
+            return Err(PyValueError::new_err(
+                "record_ids and hashes must have the same length",
+            ));
+        }
+        for (record_id, hash) in record_ids.into_iter().zip(hashes) {
+            self.insert(record_id, &hash)?;
+        }
+        Ok(())
+    }
+
+    /// Return (record_id, distance) for indexed hashes within `max_distance` bits,
+    /// sorted by distance then insertion order
+    fn query(&self, hash: &str, max_distance: u32) -> PyResult<Vec<(String, u32)>> {
+        let decoded = self.decode(hash)?;
+        let mut found = self.tree.query(&decoded.bytes, max_distance);
+        found.sort_unstable_by_key(|&(item, dist)| (dist, item));
+        Ok(found
+            .into_iter()
+            .map(|(item, dist)| (self.ids[item].clone(), dist))
+            .collect())
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// Near-duplicate representatives of a batch of hashes (see `image_phash_find_near_duplicates`)
+fn find_near_duplicates_core(
+    hashes: &[String],
+    max_distance: u32,
+    encoding: &str,
+) -> PyResult<Vec<Option<usize>>> {
+    let decoded: Vec<Option<HashBits>> = hashes
+        .par_iter()
+        .map(|hash| {
+            if hash.is_empty() {
+                Ok(None)
+            } else {
+                decode_phash(hash, encoding).map(Some)
+            }
+        })
+        .collect::<PyResult<_>>()?;
+
+    let mut tree = BkTree::default();
+    let mut hash_bits = None;
+    let mut results = Vec::with_capacity(decoded.len());
+    for (i, hash) in decoded.into_iter().enumerate() {
+        let Some(HashBits { bytes, bits }) = hash else {
+            results.push(None);
+            continue;
+        };
+        if *hash_bits.get_or_insert(bits) != bits {
+            return Err(PyValueError::new_err(format!(
+                "hash {i} has a different bit length from the preceding hashes"
+            )));
+        }
+
+        let representative = tree
+            .query(&bytes, max_distance)
+            .into_iter()
+            .map(|(item, _)| item)
+            .min();
+        if representative.is_none() {
+            tree.insert(bytes, i);
+        }
+        results.push(representative);
+    }
+    Ok(results)
+}
+
+/// Find near-duplicates within a batch of perceptual hashes
+///
+/// Hashes are processed in order; each one is compared against the hashes kept so far.
+/// Returns, per hash, the index of the earliest kept hash within `max_distance` bits,
+/// or None if it is kept itself. Empty strings (failed hashes) are always kept.
+#[pyfunction]
+#[pyo3(signature = (hashes, max_distance, encoding="base64"))]
+pub fn image_phash_find_near_duplicates(
+    py: Python<'_>,
+    hashes: Vec<String>,
+    max_distance: u32,
+    encoding: &str,
+) -> PyResult<Vec<Option<usize>>> {
+    detach_in_pool(py, || {
+        find_near_duplicates_core(&hashes, max_distance, encoding)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn solid_images_have_only_the_dc_bit_set() {
+        for color in [[200, 10, 10], [255, 255, 255], [7, 130, 64]] {
+            let solid = DynamicImage::ImageRgb8(RgbImage::from_pixel(37, 23, Rgb(color)));
+            assert_eq!(imagehash_phash(&solid, 8).to_hex(), "8000000000000000");
+            assert_eq!(
+                imagehash_phash(&solid, 16).to_hex(),
+                format!("8{}", "0".repeat(63))
+            );
+        }
+        let black = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+        assert_eq!(imagehash_phash(&black, 8).to_hex(), "0000000000000000");
+    }
+
+    /// Deterministic hashes in clusters of near-identical members
+    fn clustered_hashes(count: usize, bytes: usize) -> Vec<Vec<u8>> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut hashes: Vec<Vec<u8>> = Vec::with_capacity(count);
+        for i in 0..count {
+            let mut hash: Vec<u8> = match i % 4 {
+                0 => (0..bytes).map(|_| next() as u8).collect(),
+                _ => hashes[i - i % 4].clone(),
+            };
+            for _ in 0..next() % 6 {
+                let bit = next() as usize % (bytes * 8);
+                hash[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            hashes.push(hash);
+        }
+        hashes
+    }
+
+    #[test]
+    fn bk_tree_queries_match_brute_force() {
+        for bytes in [8, 32] {
+            let hashes = clustered_hashes(200, bytes);
+            let mut tree = BkTree::default();
+            for (i, hash) in hashes.iter().enumerate() {
+                tree.insert(hash.clone(), i);
+            }
+            for radius in [0, 1, 4, 10] {
+                for query in hashes.iter().step_by(7) {
+                    let mut found = tree.query(query, radius);
+                    found.sort_unstable();
+                    let expected: Vec<(usize, u32)> = hashes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, hash)| (i, hamming_distance(hash, query)))
+                        .filter(|&(_, dist)| dist <= radius)
+                        .collect();
+                    assert_eq!(found, expected, "{bytes} bytes, radius {radius}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn identical_hashes_share_a_node() {
+        let mut tree = BkTree::default();
+        tree.insert(vec![0xab; 8], 0);
+        tree.insert(vec![0xab; 8], 1);
+        tree.insert(vec![0xaa; 8], 2);
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(tree.nodes[0].items, [0, 1]);
+        assert_eq!(tree.query(&[0xab; 8], 0), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn near_duplicates_point_at_the_earliest_kept_hash() {
+        let hashes = clustered_hashes(120, 8);
+        let hex: Vec<String> = hashes
+            .iter()
+            .map(|hash| hash.iter().map(|b| format!("{b:02x}")).collect())
+            .collect();
+        for radius in [0, 3, 8] {
+            let mut kept: Vec<usize> = Vec::new();
+            let expected: Vec<Option<usize>> = hashes
+                .iter()
+                .enumerate()
+                .map(|(i, hash)| {
+                    let representative = kept
+                        .iter()
+                        .copied()
+                        .find(|&k| hamming_distance(&hashes[k], hash) <= radius);
+                    if representative.is_none() {
+                        kept.push(i);
+                    }
+                    representative
+                })
+                .collect();
+            let found = find_near_duplicates_core(&hex, radius, "hex").unwrap();
+            assert_eq!(found, expected, "radius {radius}");
+        }
+    }
+
+    #[test]
+    fn empty_hashes_are_always_kept() {
+        let hashes = ["ff00ff00ff00ff00", "", "ff00ff00ff00ff01", ""].map(String::from);
+        let found = find_near_duplicates_core(&hashes, 2, "hex").unwrap();
+        assert_eq!(found, [None, None, Some(0), None]);
+    }
+
+    #[test]
+    fn hashes_of_other_lengths_are_rejected() {
+        let hashes = ["ff00ff00ff00ff00", "ff00ff00ff00ff00ff"].map(String::from);
+        assert!(find_near_duplicates_core(&hashes, 2, "hex").is_err());
+        let odd = ["abc", "abcd"].map(String::from);
+        assert!(find_near_duplicates_core(&odd, 2, "hex").is_err());
+        let invalid = ["ff00ff00ff00ff0g"].map(String::from);
+        assert!(find_near_duplicates_core(&invalid, 2, "hex").is_err());
+        assert!(decode_phash("AAAA", "base32").is_err());
+    }
+
+    #[test]
+    fn odd_length_hex_hashes_are_accepted() {
+        let bits: Vec<bool> = (0..36).map(|i| i % 3 == 0).collect();
+        let hash = HashBits::from_bools(&bits);
+        let hex = hash.to_hex();
+        assert_eq!(hex.len(), 9);
+
+        let decoded = decode_phash(&hex, "hex").unwrap();
+        assert_eq!(decoded.bits, 36);
+        assert_eq!(decoded.to_hex(), hex);
+
+        let mut flipped = bits.clone();
+        flipped[0] = !flipped[0];
+        flipped[35] = !flipped[35];
+        let other = HashBits::from_bools(&flipped).to_hex();
+        let found = find_near_duplicates_core(&[hex.clone(), other.clone()], 2, "hex").unwrap();
+        assert_eq!(found, [None, Some(0)]);
+        let found = find_near_duplicates_core(&[hex, other], 1, "hex").unwrap();
+        assert_eq!(found, [None, None]);
+    }
+}
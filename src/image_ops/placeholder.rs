@@ -0,0 +1,400 @@
+//! Blank / solid-color / placeholder classification
+
+use std::collections::HashMap;
+
+use image::{DynamicImage, ImageDecoder, RgbImage};
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels_reduced, open_image_reduced, DecodeLimits};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::quality::check_analysis_max_side;
+
+/// Default minimum side below which an image is classified as tiny (trackers, spacers)
+pub(super) const DEFAULT_PLACEHOLDER_MIN_SIDE: u32 = 8;
+
+/// Side of the thumbnail used for the variance and smoothness tests
+const PLACEHOLDER_THUMBNAIL_SIDE: u32 = 32;
+
+/// Alpha at or above which a pixel counts as visible
+pub(super) const VISIBLE_ALPHA: u8 = 16;
+
+/// Visible-pixel fraction below which an image is transparent-only
+const TRANSPARENT_MAX_COVERAGE: f64 = 0.01;
+
+/// Distinct colors counted before an image is treated as having "many" colors
+const MAX_COUNTED_COLORS: usize = 1024;
+
+/// Thumbnail channel standard deviation at or below which an image is near-solid
+const NEAR_SOLID_MAX_STD: f64 = 4.0;
+
+/// Share of the most common color at or above which an image is near-solid
+const NEAR_SOLID_MIN_DOMINANT: f64 = 0.95;
+
+/// Mean absolute thumbnail Laplacian at or below which an image is a smooth gradient
+const GRADIENT_MAX_LAPLACIAN: f64 = 2.0;
+
+/// Placeholder category of an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum PlaceholderCategory {
+    /// A side is below `min_side` (1x1 trackers, spacer GIFs)
+    Tiny,
+    /// Almost no visible (non-transparent) pixels
+    Transparent,
+    /// A single color
+    Solid,
+    /// Nearly flat, or one color covering almost everything ("image not available" tiles)
+    NearSolid,
+    /// Smooth color ramp without detail
+    Gradient,
+    /// None of the above
+    Content,
+}
+
+impl PlaceholderCategory {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::Tiny => "tiny",
+            Self::Transparent => "transparent",
+            Self::Solid => "solid",
+            Self::NearSolid => "near_solid",
+            Self::Gradient => "gradient",
+            Self::Content => "content",
+        }
+    }
+}
+
+/// Placeholder category of an image with a confidence in [0.5, 1]
+///
+/// Confidence is 1.0 for clear-cut cases and falls to 0.5 at the decision threshold.
+#[derive(Clone, Copy)]
+pub(super) struct PlaceholderClass {
+    pub(super) category: PlaceholderCategory,
+    pub(super) confidence: f64,
+}
+
+impl PlaceholderClass {
+    fn new(category: PlaceholderCategory, confidence: f64) -> Self {
+        Self {
+            category,
+            confidence: confidence.clamp(0.5, 1.0),
+        }
+    }
+}
+
+/// Classify an image as tiny from its declared dimensions
+pub(super) fn placeholder_tiny(dimensions: (u32, u32), min_side: u32) -> Option<PlaceholderClass> {
+    (dimensions.0.min(dimensions.1) < min_side)
+        .then(|| PlaceholderClass::new(PlaceholderCategory::Tiny, 1.0))
+}
+
+/// Largest per-channel standard deviation and mean absolute 4-neighbour Laplacian
+fn thumbnail_variation(thumbnail: &RgbImage) -> (f64, f64) {
+    let (width, height) = thumbnail.dimensions();
+    let n = f64::from(width * height);
+    let mut max_std: f64 = 0.0;
+    for channel in 0..3 {
+        let (sum, sum_sq) = thumbnail.pixels().fold((0.0, 0.0), |(sum, sum_sq), p| {
+            let v = f64::from(p[channel]);
+            (sum + v, sum_sq + v * v)
+        });
+        max_std = max_std.max((sum_sq / n - (sum / n).powi(2)).max(0.0).sqrt());
+    }
+
+    if width < 3 || height < 3 {
+        return (max_std, 0.0);
+    }
+    let at = |x: u32, y: u32, c: usize| i32::from(thumbnail.get_pixel(x, y)[c]);
+    let mut total = 0u64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            for c in 0..3 {
+                let response =
+                    at(x - 1, y, c) + at(x + 1, y, c) + at(x, y - 1, c) + at(x, y + 1, c)
+                        - 4 * at(x, y, c);
+                total += u64::from(response.unsigned_abs());
+            }
+        }
+    }
+    let interior = f64::from((width - 2) * (height - 2) * 3);
+    (max_std, total as f64 / interior)
+}
+
+/// Classify decoded pixels (the image is known not to be tiny)
+///
+/// Tests in order: alpha coverage, unique color count, thumbnail variance and dominant
+/// color share, thumbnail smoothness.
+pub(super) fn classify_placeholder_pixels(image: &DynamicImage) -> PlaceholderClass {
+    let rgba = image.to_rgba8();
+    let total = rgba.pixels().len();
+    if total == 0 {
+        return PlaceholderClass::new(PlaceholderCategory::Tiny, 1.0);
+    }
+
+    let mut visible = 0usize;
+    let mut colors: HashMap<[u8; 3], usize> = HashMap::new();
+    let mut many_colors = false;
+    for pixel in rgba.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < VISIBLE_ALPHA {
+            continue;
+        }
+        visible += 1;
+        if many_colors {
+            continue;
+        }
+        *colors.entry([r, g, b]).or_default() += 1;
+        many_colors = colors.len() > MAX_COUNTED_COLORS;
+    }
+
+    let coverage = visible as f64 / total as f64;
+    if coverage < TRANSPARENT_MAX_COVERAGE {
+        return PlaceholderClass::new(
+            PlaceholderCategory::Transparent,
+            1.0 - 0.5 * coverage / TRANSPARENT_MAX_COVERAGE,
+        );
+    }
+    if !many_colors && colors.len() == 1 {
+        return PlaceholderClass::new(PlaceholderCategory::Solid, 1.0);
+    }
+
+    let dominant = if many_colors {
+        0.0
+    } else {
+        colors.values().copied().max().unwrap_or(0) as f64 / visible as f64
+    };
+    let side = PLACEHOLDER_THUMBNAIL_SIDE;
+    let thumbnail = if image.width() > side || image.height() > side {
+        image.thumbnail(side, side).to_rgb8()
+    } else {
+        image.to_rgb8()
+    };
+    let (std, laplacian) = thumbnail_variation(&thumbnail);
+
+    if std <= NEAR_SOLID_MAX_STD || dominant >= NEAR_SOLID_MIN_DOMINANT {
+        let flatness = 1.0 - 0.5 * std / NEAR_SOLID_MAX_STD;
+        let dominance =
+            0.5 + 0.5 * (dominant - NEAR_SOLID_MIN_DOMINANT) / (1.0 - NEAR_SOLID_MIN_DOMINANT);
+        return PlaceholderClass::new(PlaceholderCategory::NearSolid, flatness.max(dominance));
+    }
+    if laplacian <= GRADIENT_MAX_LAPLACIAN {
+        return PlaceholderClass::new(
+            PlaceholderCategory::Gradient,
+            1.0 - 0.5 * laplacian / GRADIENT_MAX_LAPLACIAN,
+        );
+    }
+
+    // Distance past the nearest threshold, as a ratio (>= 1)
+    let margin = (std / NEAR_SOLID_MAX_STD)
+        .min(laplacian / GRADIENT_MAX_LAPLACIAN)
+        .min((1.0 - dominant) / (1.0 - NEAR_SOLID_MIN_DOMINANT))
+        .min(coverage / TRANSPARENT_MAX_COVERAGE);
+    PlaceholderClass::new(PlaceholderCategory::Content, 1.0 - 0.5 / margin)
+}
+
+/// Classify one image, decoding it only if it is not tiny
+fn image_detect_placeholder_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    min_side: u32,
+) -> ItemResult<PlaceholderClass> {
+    let (_, decoder) = open_image_reduced(image_bytes, limits, analysis_max_side)?;
+    if let Some(tiny) = placeholder_tiny(decoder.dimensions(), min_side) {
+        return Ok(tiny);
+    }
+    let scaled = decode_pixels_reduced(decoder, limits)?;
+    Ok(classify_placeholder_pixels(&scaled.image))
+}
+
+/// Classify every image in parallel
+fn image_detect_placeholder_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    min_side: u32,
+) -> Vec<ItemResult<PlaceholderClass>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_detect_placeholder_core(image_bytes, limits, analysis_max_side, min_side)
+            })
+        })
+        .collect()
+}
+
+/// Build a placeholder batch function's return value: (category, confidence) tuples
+fn placeholder_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<PlaceholderClass>>,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let results = results
+        .into_iter()
+        .map(|r| r.map(|class| (Some(class.category.as_str()), class.confidence)))
+        .collect();
+    batch_output(py, results, || (None, 0.0), return_status)
+}
+
+/// Batch detect blank, solid-color and placeholder images in parallel (GIL released)
+///
+/// Returns a (category, confidence) tuple per image; failed images yield (None, 0.0).
+/// Categories, tested in this order:
+/// - `"tiny"`: A side is below `min_side` (decided from the header, no decode)
+/// - `"transparent"`: Under 1% of pixels are visible (alpha >= 16)
+/// - `"solid"`: A single visible color
+/// - `"near_solid"`: Every channel of a 32px thumbnail has standard deviation <= 4,
+///   or one color covers >= 95% of visible pixels (e.g. "image not available" tiles)
+/// - `"gradient"`: The thumbnail's mean absolute Laplacian is <= 2 (smooth ramps)
+/// - `"content"`: Anything else
+///
+/// Confidence is in [0.5, 1]: 1.0 for clear-cut cases, 0.5 at a decision threshold.
+/// `analysis_max_side` works as in `image_assess_quality_batch`.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, min_side=DEFAULT_PLACEHOLDER_MIN_SIDE))]
+pub fn image_detect_placeholder_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    min_side: u32,
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let results = detach_in_pool(py, || {
+        image_detect_placeholder_all(&image_bytes_list, &limits, analysis_max_side, min_side)
+    });
+    placeholder_batch_output(py, results, return_status)
+}
+
+/// Non-blocking `image_detect_placeholder_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, min_side=DEFAULT_PLACEHOLDER_MIN_SIDE))]
+pub fn submit_image_detect_placeholder_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    min_side: u32,
+) -> PyResult<BatchFuture> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    Ok(BatchFuture::spawn(move || {
+        let results =
+            image_detect_placeholder_all(&image_bytes_list, &limits, analysis_max_side, min_side);
+        finisher(move |py| placeholder_batch_output(py, results, return_status))
+    }))
+}
+
+/// Arrow variant of `image_detect_placeholder_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `placeholder_category` (string) and
+/// `placeholder_confidence` columns (null for failed or null images). With
+/// `return_status=True`, `status` and `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, analysis_max_side=None, min_side=DEFAULT_PLACEHOLDER_MIN_SIDE))]
+pub fn image_detect_placeholder_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    min_side: u32,
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<PlaceholderClass>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_detect_placeholder_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        analysis_max_side,
+                        min_side,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let classes = || results.iter().map(|r| r.as_ref().ok());
+    let columns = vec![
+        ArrowColumn::utf8(
+            "placeholder_category",
+            classes().map(|r| r.map(|class| class.category.as_str())),
+        ),
+        ArrowColumn::primitive(
+            "placeholder_confidence",
+            classes().map(|r| r.map(|class| class.confidence)),
+        ),
+    ];
+    record_batch_output(py, columns, &results, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::test_images::framed;
+    use image::{Rgb, RgbImage, RgbaImage};
+
+    #[test]
+    fn placeholder_categories() {
+        let classify = |image: DynamicImage| classify_placeholder_pixels(&image).category;
+        assert_eq!(
+            classify(DynamicImage::ImageRgba8(RgbaImage::new(40, 40))),
+            PlaceholderCategory::Transparent
+        );
+        assert_eq!(
+            classify(DynamicImage::ImageRgb8(RgbImage::from_pixel(
+                40,
+                40,
+                Rgb([200, 10, 10])
+            ))),
+            PlaceholderCategory::Solid
+        );
+        // A few specks on a flat tile
+        let specks = RgbImage::from_fn(40, 40, |x, y| {
+            Rgb(if (x + y) % 50 == 0 { [0; 3] } else { [230; 3] })
+        });
+        assert_eq!(
+            classify(DynamicImage::ImageRgb8(specks)),
+            PlaceholderCategory::NearSolid
+        );
+        let ramp = RgbImage::from_fn(64, 64, |x, _| Rgb([(x * 4) as u8, 64, 255 - (x * 4) as u8]));
+        assert_eq!(
+            classify(DynamicImage::ImageRgb8(ramp)),
+            PlaceholderCategory::Gradient
+        );
+        assert_eq!(
+            classify(framed(64, 64, (0, 0, 0, 0))),
+            PlaceholderCategory::Content
+        );
+    }
+
+    #[test]
+    fn placeholder_confidence_is_clamped() {
+        let solid = DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 40, Rgb([90; 3])));
+        assert_eq!(classify_placeholder_pixels(&solid).confidence, 1.0);
+        let class = classify_placeholder_pixels(&framed(64, 64, (0, 0, 0, 0)));
+        assert!((0.5..=1.0).contains(&class.confidence));
+    }
+
+    #[test]
+    fn tiny_images_are_classified_from_dimensions() {
+        let class = placeholder_tiny((1, 1), DEFAULT_PLACEHOLDER_MIN_SIDE).unwrap();
+        assert_eq!(class.category, PlaceholderCategory::Tiny);
+        assert!(placeholder_tiny((300, 4), DEFAULT_PLACEHOLDER_MIN_SIDE).is_some());
+        assert!(placeholder_tiny((8, 8), DEFAULT_PLACEHOLDER_MIN_SIDE).is_none());
+    }
+}
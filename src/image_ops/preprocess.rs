@@ -0,0 +1,495 @@
+//! Decoding, resizing and normalizing images into model input tensors
+
+use half::f16;
+use image::imageops::{self, FilterType};
+use image::metadata::Orientation;
+use image::{ImageDecoder, RgbImage};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, ArrowBinaryInput};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::color::{flatten_alpha, DEFAULT_BACKGROUND};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels, open_image, DecodeLimits};
+use crate::status::{catch_panic, status_tuple, ItemResult, StatusTuple};
+use crate::tensor_buffer::{bool_ndarray, TensorBuffer, TensorData, TensorDtype};
+
+/// Per-channel normalization mean of OpenAI CLIP models
+const CLIP_MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+
+/// Per-channel normalization standard deviation of OpenAI CLIP models
+const CLIP_STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+/// Output size given as `size`: an int for square tensors or a (height, width) pair
+#[derive(FromPyObject)]
+pub enum TensorSize {
+    Square(u32),
+    HeightWidth(u32, u32),
+}
+
+/// How an image is fit to the tensor size
+#[derive(Clone, Copy)]
+enum ResizeMode {
+    /// Resize the shorter side to cover the output, then center-crop (torchvision
+    /// `Resize` + `CenterCrop`)
+    CenterCrop,
+    /// Resize to the output size, ignoring the aspect ratio
+    Squash,
+}
+
+impl ResizeMode {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "center_crop" => Ok(Self::CenterCrop),
+            "squash" => Ok(Self::Squash),
+            other => Err(PyValueError::new_err(format!(
+                "unknown resize mode: {other} (expected 'center_crop' or 'squash')"
+            ))),
+        }
+    }
+}
+
+/// Parse a resampling filter name; the filters are antialiased when downscaling
+pub(super) fn parse_resize_filter(name: &str) -> PyResult<FilterType> {
+    match name {
+        "nearest" => Ok(FilterType::Nearest),
+        "bilinear" => Ok(FilterType::Triangle),
+        "bicubic" => Ok(FilterType::CatmullRom),
+        "lanczos" => Ok(FilterType::Lanczos3),
+        other => Err(PyValueError::new_err(format!(
+            "unknown resize filter: {other} (expected nearest, bilinear, bicubic or lanczos)"
+        ))),
+    }
+}
+
+/// How images are turned into tensor rows
+#[derive(Clone, Copy)]
+struct PreprocessParams {
+    height: u32,
+    width: u32,
+    mode: ResizeMode,
+    filter: FilterType,
+    mean: [f32; 3],
+    std: [f32; 3],
+    dtype: TensorDtype,
+    background: [u8; 3],
+}
+
+impl PreprocessParams {
+    /// Elements per image (3 x height x width)
+    fn row_len(&self) -> usize {
+        3 * self.height as usize * self.width as usize
+    }
+}
+
+/// Validate the preprocessing options passed from Python
+fn prepare_preprocess(
+    size: TensorSize,
+    mean: [f32; 3],
+    std: [f32; 3],
+    resize_mode: &str,
+    resize_filter: &str,
+    dtype: &str,
+    background: [u8; 3],
+) -> PyResult<PreprocessParams> {
+    let (height, width) = match size {
+        TensorSize::Square(side) => (side, side),
+        TensorSize::HeightWidth(height, width) => (height, width),
+    };
+    if height == 0 || width == 0 {
+        return Err(PyValueError::new_err("size must be positive"));
+    }
+    if std.iter().any(|&s| !(s.is_finite() && s > 0.0)) || mean.iter().any(|m| !m.is_finite()) {
+        return Err(PyValueError::new_err(
+            "mean must be finite and std must be positive",
+        ));
+    }
+    let params = PreprocessParams {
+        height,
+        width,
+        mode: ResizeMode::parse(resize_mode)?,
+        filter: parse_resize_filter(resize_filter)?,
+        mean,
+        std,
+        dtype: TensorDtype::parse(dtype)?,
+        background,
+    };
+    Ok(params)
+}
+
+/// Aspect-preserving resize that covers an output size, and the centered crop of it
+#[derive(Clone, Copy)]
+pub(super) struct CoverCrop {
+    /// Resize factor (output / source)
+    pub(super) scale: f64,
+    /// Size the image is resized to; at least the output size on both sides
+    pub(super) resized: (u32, u32),
+    /// (left, top, right, bottom) of the output within the resized image
+    pub(super) crop_box: (u32, u32, u32, u32),
+}
+
+/// Plan the torchvision-style `Resize` + `CenterCrop` of a `source` image to `output`
+pub(super) fn cover_crop(source: (u32, u32), output: (u32, u32)) -> CoverCrop {
+    let (width, height) = source;
+    let (out_width, out_height) = output;
+    let scale = f64::max(
+        f64::from(out_width) / f64::from(width),
+        f64::from(out_height) / f64::from(height),
+    );
+    let resized_width = ((f64::from(width) * scale).round() as u32).max(out_width);
+    let resized_height = ((f64::from(height) * scale).round() as u32).max(out_height);
+    let left = (resized_width - out_width) / 2;
+    let top = (resized_height - out_height) / 2;
+    CoverCrop {
+        scale,
+        resized: (resized_width, resized_height),
+        crop_box: (left, top, left + out_width, top + out_height),
+    }
+}
+
+/// Decode one image upright and resize it to the tensor size
+fn image_preprocess_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    params: PreprocessParams,
+) -> ItemResult<RgbImage> {
+    let (_, mut decoder) = open_image(image_bytes, limits)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = decode_pixels(decoder, limits)?;
+    img.apply_orientation(orientation);
+    let rgb = flatten_alpha(&img, params.background);
+
+    let (out_width, out_height) = (params.width, params.height);
+    Ok(match params.mode {
+        ResizeMode::Squash => imageops::resize(&rgb, out_width, out_height, params.filter),
+        ResizeMode::CenterCrop => {
+            let plan = cover_crop(rgb.dimensions(), (out_width, out_height));
+            let (resized_width, resized_height) = plan.resized;
+            let resized = imageops::resize(&rgb, resized_width, resized_height, params.filter);
+            let (left, top, _, _) = plan.crop_box;
+            imageops::crop_imm(&resized, left, top, out_width, out_height).to_image()
+        }
+    })
+}
+
+/// Write an image into a tensor row as normalized channel planes (CHW)
+fn write_tensor_row<T: Copy>(
+    rgb: &RgbImage,
+    row: &mut [T],
+    params: PreprocessParams,
+    convert: fn(f32) -> T,
+) {
+    let lookup: [[T; 256]; 3] = std::array::from_fn(|channel| {
+        std::array::from_fn(|value| {
+            convert((value as f32 / 255.0 - params.mean[channel]) / params.std[channel])
+        })
+    });
+    let plane_len = rgb.width() as usize * rgb.height() as usize;
+    let (red, rest) = row.split_at_mut(plane_len);
+    let (green, blue) = rest.split_at_mut(plane_len);
+    for (i, pixel) in rgb.pixels().enumerate() {
+        red[i] = lookup[0][pixel[0] as usize];
+        green[i] = lookup[1][pixel[1] as usize];
+        blue[i] = lookup[2][pixel[2] as usize];
+    }
+}
+
+/// Preprocess every image in parallel into its row of `rows`
+///
+/// Failed (or null) images leave their row at zero.
+fn fill_tensor_rows<T: Copy + Send>(
+    inputs: &[Option<&[u8]>],
+    rows: &mut [T],
+    limits: &DecodeLimits,
+    params: PreprocessParams,
+    convert: fn(f32) -> T,
+) -> Vec<ItemResult<()>> {
+    rows.par_chunks_mut(params.row_len())
+        .zip(inputs.par_iter())
+        .map(|(row, image_bytes)| {
+            let outcome = catch_panic(|| {
+                let rgb =
+                    image_preprocess_core(image_bytes.ok_or_else(null_input)?, limits, params)?;
+                write_tensor_row(&rgb, row, params, convert);
+                Ok(())
+            });
+            if outcome.is_err() {
+                row.fill(convert(0.0));
+            }
+            outcome
+        })
+        .collect()
+}
+
+/// Preprocess a batch into one (N, 3, H, W) tensor plus per-image outcomes
+fn image_preprocess_all(
+    inputs: &[Option<&[u8]>],
+    limits: &DecodeLimits,
+    params: PreprocessParams,
+) -> (TensorData, Vec<ItemResult<()>>) {
+    let mut data = TensorData::zeros(params.dtype, inputs.len() * params.row_len());
+    let results = match &mut data {
+        TensorData::Float32(rows) => fill_tensor_rows(inputs, rows, limits, params, |v| v),
+        TensorData::Float16(rows) => fill_tensor_rows(inputs, rows, limits, params, f16::from_f32),
+    };
+    (data, results)
+}
+
+/// Build a preprocessing function's return value
+///
+/// Returns `(tensor, valid)` NumPy arrays, plus the per-item statuses with
+/// `return_status`.
+fn tensor_batch_output(
+    py: Python<'_>,
+    data: TensorData,
+    results: Vec<ItemResult<()>>,
+    params: PreprocessParams,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let shape = vec![
+        results.len(),
+        3,
+        params.height as usize,
+        params.width as usize,
+    ];
+    let tensor = TensorBuffer::new(data, shape).into_ndarray(py)?;
+    let valid = bool_ndarray(py, results.iter().map(Result::is_ok).collect())?;
+    if return_status {
+        let statuses: Vec<StatusTuple> = results.iter().map(status_tuple).collect();
+        (tensor, valid, statuses).into_bound_py_any(py)
+    } else {
+        (tensor, valid).into_bound_py_any(py)
+    }
+}
+
+/// Batch decode, resize and normalize images into a model-ready tensor (GIL released)
+///
+/// Each image is decoded, rotated upright according to its EXIF orientation,
+/// converted to sRGB (transparent images are composited onto `background`, white by
+/// default) and fit to `size` (an int, or a (height, width) pair):
+/// - `resize_mode="center_crop"`: Resize so the image covers the output, then crop the
+///   center (torchvision `Resize(size)` + `CenterCrop(size)`)
+/// - `resize_mode="squash"`: Resize to exactly `size`, ignoring the aspect ratio
+///
+/// `resize_filter` is "bicubic" (default), "bilinear", "lanczos" or "nearest"; all but
+/// "nearest" are antialiased when downscaling, like PIL. Pixels are scaled to 0-1 and
+/// normalized per channel with `mean` and `std` (default: OpenAI CLIP).
+///
+/// Returns `(tensor, valid)`: a C-contiguous NumPy array of shape (N, 3, H, W) and
+/// `dtype` ("float32" or "float16"), and a boolean array marking the images that were
+/// preprocessed; rows of failed images are zero. With `return_status=True`, returns
+/// `(tensor, valid, statuses)`. The tensor shares memory allocated in Rust, so it is
+/// not copied on the way to Python (`torch.from_numpy` shares it too). Requires NumPy.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, size, return_status=false, limits=None, mean=CLIP_MEAN, std=CLIP_STD, resize_mode="center_crop", resize_filter="bicubic", dtype="float32", background=DEFAULT_BACKGROUND))]
+pub fn image_preprocess_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    size: TensorSize,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    mean: [f32; 3],
+    std: [f32; 3],
+    resize_mode: &str,
+    resize_filter: &str,
+    dtype: &str,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let params = prepare_preprocess(
+        size,
+        mean,
+        std,
+        resize_mode,
+        resize_filter,
+        dtype,
+        background,
+    )?;
+    let (data, results) = detach_in_pool(py, || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        image_preprocess_all(&inputs, &limits, params)
+    });
+    tensor_batch_output(py, data, results, params, return_status)
+}
+
+/// Non-blocking `image_preprocess_batch`; returns a `BatchFuture`
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, size, return_status=false, limits=None, mean=CLIP_MEAN, std=CLIP_STD, resize_mode="center_crop", resize_filter="bicubic", dtype="float32", background=DEFAULT_BACKGROUND))]
+pub fn submit_image_preprocess_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    size: TensorSize,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    mean: [f32; 3],
+    std: [f32; 3],
+    resize_mode: &str,
+    resize_filter: &str,
+    dtype: &str,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let params = prepare_preprocess(
+        size,
+        mean,
+        std,
+        resize_mode,
+        resize_filter,
+        dtype,
+        background,
+    )?;
+    Ok(BatchFuture::spawn(move || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        let (data, results) = image_preprocess_all(&inputs, &limits, params);
+        finisher(move |py| tensor_batch_output(py, data, results, params, return_status))
+    }))
+}
+
+/// Arrow variant of `image_preprocess_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes; null images are
+/// invalid. Returns the same `(tensor, valid)` (or `(tensor, valid, statuses)`) as
+/// `image_preprocess_batch`, since a tensor does not fit a RecordBatch column.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (images, size, return_status=false, limits=None, mean=CLIP_MEAN, std=CLIP_STD, resize_mode="center_crop", resize_filter="bicubic", dtype="float32", background=DEFAULT_BACKGROUND))]
+pub fn image_preprocess_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    size: TensorSize,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    mean: [f32; 3],
+    std: [f32; 3],
+    resize_mode: &str,
+    resize_filter: &str,
+    dtype: &str,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let params = prepare_preprocess(
+        size,
+        mean,
+        std,
+        resize_mode,
+        resize_filter,
+        dtype,
+        background,
+    )?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let (data, results) = detach_in_pool(py, || image_preprocess_all(&values, &limits, params));
+    tensor_batch_output(py, data, results, params, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::test_images::encode;
+    use crate::jpeg_encode::ChromaSubsampling;
+    use crate::status::ItemStatus;
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+    fn preprocess_params(height: u32, width: u32, dtype: TensorDtype) -> PreprocessParams {
+        PreprocessParams {
+            height,
+            width,
+            mode: ResizeMode::Squash,
+            filter: FilterType::Nearest,
+            mean: [0.5, 0.25, 0.0],
+            std: [0.5, 0.25, 2.0],
+            dtype,
+            background: DEFAULT_BACKGROUND,
+        }
+    }
+
+    #[test]
+    fn preprocessed_rows_hold_normalized_channel_planes() {
+        let pixels = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [10, 20, 30]];
+        let png = encode(
+            DynamicImage::ImageRgb8(RgbImage::from_fn(2, 2, |x, y| {
+                Rgb(pixels[(y * 2 + x) as usize])
+            })),
+            ImageFormat::Png,
+        );
+        let garbage = b"not an image".to_vec();
+        let inputs = [Some(&png[..]), Some(&garbage[..]), None, Some(&png[..])];
+
+        let params = preprocess_params(2, 2, TensorDtype::Float32);
+        let expected: Vec<f32> = (0..3)
+            .flat_map(|c| {
+                pixels.map(|pixel| (pixel[c] as f32 / 255.0 - params.mean[c]) / params.std[c])
+            })
+            .collect();
+        let (data, results) = image_preprocess_all(&inputs, &DecodeLimits::default(), params);
+        let ok: Vec<bool> = results.iter().map(Result::is_ok).collect();
+        assert_eq!(ok, [true, false, false, true]);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().status,
+            ItemStatus::UnsupportedFormat
+        );
+        let TensorData::Float32(rows) = data else {
+            panic!("expected float32 rows");
+        };
+        let rows: Vec<&[f32]> = rows.chunks(params.row_len()).collect();
+        assert_eq!(rows[0], &expected[..]);
+        assert_eq!(rows[1], &[0.0; 12]);
+        assert_eq!(rows[2], &[0.0; 12]);
+        assert_eq!(rows[3], &expected[..]);
+
+        let params = preprocess_params(2, 2, TensorDtype::Float16);
+        let (data, _) = image_preprocess_all(&inputs, &DecodeLimits::default(), params);
+        let TensorData::Float16(rows) = data else {
+            panic!("expected float16 rows");
+        };
+        let expected: Vec<f16> = expected.into_iter().map(f16::from_f32).collect();
+        assert_eq!(&rows[..12], &expected[..]);
+        assert!(rows[12..36].iter().all(|v| *v == f16::ZERO));
+    }
+
+    /// Little-endian EXIF (TIFF) block with only an orientation tag
+    fn exif_orientation(orientation: u16) -> Vec<u8> {
+        let mut exif = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend_from_slice(&0x0112u16.to_le_bytes());
+        exif.extend_from_slice(&3u16.to_le_bytes());
+        exif.extend_from_slice(&1u32.to_le_bytes());
+        exif.extend_from_slice(&orientation.to_le_bytes());
+        exif.extend_from_slice(&[0; 6]);
+        exif
+    }
+
+    #[test]
+    fn preprocessing_rotates_jpegs_upright() {
+        // Left half black, right half white; orientation 6 turns it a quarter clockwise
+        let sideways = RgbImage::from_fn(32, 16, |x, _| Rgb([if x < 16 { 0 } else { 255 }; 3]));
+        let exif = exif_orientation(6);
+        let jpeg = crate::jpeg_encode::encode_jpeg(
+            &sideways,
+            95,
+            ChromaSubsampling::Yuv444,
+            None,
+            Some(&exif),
+        )
+        .unwrap();
+
+        let params = PreprocessParams {
+            mean: [0.0; 3],
+            std: [1.0; 3],
+            ..preprocess_params(32, 16, TensorDtype::Float32)
+        };
+        let (data, results) =
+            image_preprocess_all(&[Some(&jpeg)], &DecodeLimits::default(), params);
+        assert!(results[0].is_ok());
+        let TensorData::Float32(rows) = data else {
+            panic!("expected float32 rows");
+        };
+        // Red plane, rows of 16: the top is black and the bottom white
+        let red = &rows[..32 * 16];
+        assert!(red[..16 * 8].iter().all(|&v| v < 0.1));
+        assert!(red[16 * 24..].iter().all(|&v| v > 0.9));
+    }
+}
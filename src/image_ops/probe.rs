@@ -0,0 +1,478 @@
+//! Header-only metadata probing
+
+use image::metadata::Orientation;
+use image::{ExtendedColorType, ImageDecoder, ImageFormat};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{open_image, DecodeLimits};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::analysis::pil_format_name;
+
+/// Header metadata of one image
+struct ImageMetadata {
+    width: u32,
+    height: u32,
+    format: &'static str,
+    color_type: &'static str,
+    bit_depth: u8,
+    frame_count: u32,
+    orientation: u8,
+    color_space: &'static str,
+}
+
+/// Split a decoder's pixel layout into a Pillow-style mode name and bits per channel
+fn color_type_parts(color: ExtendedColorType) -> (&'static str, u8) {
+    use ExtendedColorType as C;
+    match color {
+        C::A8 => ("A", 8),
+        C::L1 => ("L", 1),
+        C::L2 => ("L", 2),
+        C::L4 => ("L", 4),
+        C::L8 => ("L", 8),
+        C::L16 => ("L", 16),
+        C::La1 => ("LA", 1),
+        C::La2 => ("LA", 2),
+        C::La4 => ("LA", 4),
+        C::La8 => ("LA", 8),
+        C::La16 => ("LA", 16),
+        C::Rgb1 => ("RGB", 1),
+        C::Rgb2 => ("RGB", 2),
+        C::Rgb4 => ("RGB", 4),
+        C::Rgb5x1 => ("RGB", 5),
+        C::Rgb8 | C::Bgr8 => ("RGB", 8),
+        C::Rgb16 => ("RGB", 16),
+        C::Rgb32F => ("RGB", 32),
+        C::Rgba1 => ("RGBA", 1),
+        C::Rgba2 => ("RGBA", 2),
+        C::Rgba4 => ("RGBA", 4),
+        C::Rgba8 | C::Bgra8 => ("RGBA", 8),
+        C::Rgba16 => ("RGBA", 16),
+        C::Rgba32F => ("RGBA", 32),
+        C::Cmyk8 => ("CMYK", 8),
+        C::Cmyk16 => ("CMYK", 16),
+        C::Unknown(bits) => ("P", bits),
+        _ => ("UNKNOWN", 0),
+    }
+}
+
+/// Read a big-endian u32 at `pos`
+fn read_u32_be(bytes: &[u8], pos: usize) -> Option<u32> {
+    let b = bytes.get(pos..pos.checked_add(4)?)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Read a little-endian u32 at `pos`
+fn read_u32_le(bytes: &[u8], pos: usize) -> Option<u32> {
+    let b = bytes.get(pos..pos.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// PNG color type, bit depth and frame count (from the APNG `acTL` chunk)
+///
+/// The `image` decoder expands palette and low bit depth images, so the original
+/// layout is read from IHDR. Only chunks before the first IDAT are visited.
+fn probe_png(bytes: &[u8]) -> Option<(&'static str, u8, u32)> {
+    let bit_depth = *bytes.get(24)?;
+    let color_type = match *bytes.get(25)? {
+        0 => "L",
+        2 => "RGB",
+        3 => "P",
+        4 => "LA",
+        6 => "RGBA",
+        _ => return None,
+    };
+
+    let mut frame_count = 1;
+    let mut pos = 8;
+    while let (Some(length), Some(chunk_type)) =
+        (read_u32_be(bytes, pos), bytes.get(pos + 4..pos + 8))
+    {
+        match chunk_type {
+            b"acTL" => {
+                frame_count = read_u32_be(bytes, pos + 8).unwrap_or(1);
+                break;
+            }
+            b"IDAT" => break,
+            _ => pos += 12 + length as usize,
+        }
+    }
+    Some((color_type, bit_depth, frame_count))
+}
+
+/// JPEG color type and sample precision from the first SOF marker
+///
+/// The `image` decoder converts CMYK/YCCK to RGB, so the component count is read here.
+fn probe_jpeg(bytes: &[u8]) -> Option<(&'static str, u8)> {
+    let mut pos = 2;
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        match marker {
+            // Fill byte before a marker
+            0xFF => pos += 1,
+            // Markers without a payload
+            0x01 | 0xD0..=0xD9 => pos += 2,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let precision = *bytes.get(pos + 4)?;
+                let color_type = match *bytes.get(pos + 9)? {
+                    1 => "L",
+                    3 => "RGB",
+                    4 => "CMYK",
+                    _ => return None,
+                };
+                return Some((color_type, precision));
+            }
+            _ => {
+                let length = u16::from_be_bytes([*bytes.get(pos + 2)?, *bytes.get(pos + 3)?]);
+                pos += 2 + length as usize;
+            }
+        }
+    }
+}
+
+/// Number of frames in a GIF, counted by walking its blocks without LZW decoding
+fn gif_frame_count(bytes: &[u8]) -> u32 {
+    // Size of the color table following a descriptor with these packed flags
+    let color_table_size = |flags: u8| {
+        if flags & 0x80 != 0 {
+            3usize << ((flags & 0x07) + 1)
+        } else {
+            0
+        }
+    };
+    // Position after a chain of data sub-blocks starting at `pos`
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let size = *bytes.get(pos)? as usize;
+            pos += 1 + size;
+            if size == 0 {
+                return Some(pos);
+            }
+        }
+    };
+
+    let Some(&screen_flags) = bytes.get(10) else {
+        return 0;
+    };
+    let mut frames = 0;
+    let mut pos = 13 + color_table_size(screen_flags);
+    while let Some(&block) = bytes.get(pos) {
+        let next = match block {
+            // Extension: label byte, then sub-blocks
+            0x21 => skip_sub_blocks(pos + 2),
+            // Image descriptor: 9 bytes, local color table, LZW code size, sub-blocks
+            0x2C => {
+                frames += 1;
+                bytes
+                    .get(pos + 9)
+                    .and_then(|&flags| skip_sub_blocks(pos + 11 + color_table_size(flags)))
+            }
+            // Trailer or garbage
+            _ => None,
+        };
+        match next {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    frames
+}
+
+/// Number of frames in a WebP file (ANMF chunks of an animated file, otherwise 1)
+fn webp_frame_count(bytes: &[u8]) -> u32 {
+    let mut frames = 0;
+    let mut pos = 12;
+    while let (Some(chunk_type), Some(size)) =
+        (bytes.get(pos..pos + 4), read_u32_le(bytes, pos + 4))
+    {
+        if chunk_type == b"ANMF" {
+            frames += 1;
+        }
+        // Chunks are padded to an even size
+        pos += 8 + size as usize + (size & 1) as usize;
+    }
+    frames.max(1)
+}
+
+/// Read an image's metadata from its header, without decoding pixel data
+fn image_probe_metadata_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+) -> ItemResult<ImageMetadata> {
+    let (format, mut decoder) = open_image(image_bytes, limits)?;
+
+    let (width, height) = decoder.dimensions();
+    let (mut color_type, mut bit_depth) = color_type_parts(decoder.original_color_type());
+    let mut frame_count = 1;
+    match format {
+        Some(ImageFormat::Png) => {
+            if let Some(png) = probe_png(image_bytes) {
+                (color_type, bit_depth, frame_count) = png;
+            }
+        }
+        Some(ImageFormat::Jpeg) => {
+            if let Some(jpeg) = probe_jpeg(image_bytes) {
+                (color_type, bit_depth) = jpeg;
+            }
+        }
+        Some(ImageFormat::Gif) => {
+            (color_type, bit_depth) = ("P", 8);
+            frame_count = gif_frame_count(image_bytes).max(1);
+        }
+        Some(ImageFormat::WebP) => frame_count = webp_frame_count(image_bytes),
+        _ => {}
+    }
+    // A missing or malformed EXIF block means no transform
+    let orientation = decoder.orientation().map_or(1, Orientation::to_exif);
+    let color_space = decoder.color_space();
+
+    Ok(ImageMetadata {
+        width,
+        height,
+        format: format.map_or("UNKNOWN", pil_format_name),
+        color_type,
+        bit_depth,
+        frame_count,
+        orientation,
+        color_space,
+    })
+}
+
+/// Convert image metadata to a dict
+fn metadata_to_dict(py: Python<'_>, metadata: ImageMetadata) -> PyResult<Bound<'_, PyAny>> {
+    let dict = PyDict::new(py);
+    dict.set_item("width", metadata.width)?;
+    dict.set_item("height", metadata.height)?;
+    dict.set_item("format", metadata.format)?;
+    dict.set_item("color_type", metadata.color_type)?;
+    dict.set_item("bit_depth", metadata.bit_depth)?;
+    dict.set_item("frame_count", metadata.frame_count)?;
+    dict.set_item("orientation", metadata.orientation)?;
+    dict.set_item("color_space", metadata.color_space)?;
+    Ok(dict.into_any())
+}
+
+/// Probe every image in parallel
+fn image_probe_metadata_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+) -> Vec<ItemResult<ImageMetadata>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| catch_panic(|| image_probe_metadata_core(image_bytes, limits)))
+        .collect()
+}
+
+/// Convert metadata to dicts and build a probe batch function's return value
+fn probe_batch_output(
+    py: Python<'_>,
+    probes: Vec<ItemResult<ImageMetadata>>,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = probes
+        .into_iter()
+        .map(|probe| match probe {
+            Ok(metadata) => metadata_to_dict(py, metadata).map(Ok),
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Batch read image metadata in parallel from headers only (no pixel decoding, GIL released)
+///
+/// Returns one dict per image with width, height, format (Pillow names such as "JPEG"),
+/// color_type (Pillow-style mode: "L", "LA", "P", "RGB", "RGBA", "CMYK"), bit_depth
+/// (bits per channel, or per palette index), frame_count (GIF/APNG/WebP animations,
+/// otherwise 1), orientation (EXIF value 1-8, 1 when absent) and color_space (the
+/// encoded color space: "srgb", "display_p3", "adobe_rgb", "prophoto_rgb", "rec2020",
+/// "other_rgb", "gray", "cmyk" or "other", from the ICC profile when there is one).
+/// Images whose header cannot be read return None. With `return_status=True`, returns `(results, statuses)`
+/// with one (status, message) tuple per image.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None))]
+pub fn image_probe_metadata_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let probes = detach_in_pool(py, || image_probe_metadata_all(&image_bytes_list, &limits));
+    probe_batch_output(py, probes, return_status)
+}
+
+/// Non-blocking `image_probe_metadata_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None))]
+pub fn submit_image_probe_metadata_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    Ok(BatchFuture::spawn(move || {
+        let probes = image_probe_metadata_all(&image_bytes_list, &limits);
+        finisher(move |py| probe_batch_output(py, probes, return_status))
+    }))
+}
+
+/// Arrow variant of `image_probe_metadata_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with one column per metadata field (null for unreadable
+/// or null images). With `return_status=True`, `status` and `status_message`
+/// columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None))]
+pub fn image_probe_metadata_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let probes: Vec<ItemResult<ImageMetadata>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_probe_metadata_core(image_bytes.ok_or_else(null_input)?, &limits)
+                })
+            })
+            .collect()
+    });
+
+    let field = |get: fn(&ImageMetadata) -> u32| {
+        probes.iter().map(move |probe| probe.as_ref().ok().map(get))
+    };
+    let columns = vec![
+        ArrowColumn::primitive("width", field(|m| m.width)),
+        ArrowColumn::primitive("height", field(|m| m.height)),
+        ArrowColumn::utf8(
+            "format",
+            probes.iter().map(|p| p.as_ref().ok().map(|m| m.format)),
+        ),
+        ArrowColumn::utf8(
+            "color_type",
+            probes.iter().map(|p| p.as_ref().ok().map(|m| m.color_type)),
+        ),
+        ArrowColumn::primitive(
+            "bit_depth",
+            probes.iter().map(|p| p.as_ref().ok().map(|m| m.bit_depth)),
+        ),
+        ArrowColumn::primitive("frame_count", field(|m| m.frame_count)),
+        ArrowColumn::primitive(
+            "orientation",
+            probes
+                .iter()
+                .map(|p| p.as_ref().ok().map(|m| m.orientation)),
+        ),
+        ArrowColumn::utf8(
+            "color_space",
+            probes
+                .iter()
+                .map(|p| p.as_ref().ok().map(|m| m.color_space)),
+        ),
+    ];
+    record_batch_output(py, columns, &probes, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::test_images::{encode, framed};
+    use image::{DynamicImage, ImageBuffer, RgbaImage};
+
+    /// A PNG chunk with a zero CRC (`probe_png` does not check it)
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    #[test]
+    fn probe_png_reads_ihdr_and_actl() {
+        let rgb = encode(framed(8, 8, (0, 0, 0, 0)), ImageFormat::Png);
+        assert_eq!(probe_png(&rgb), Some(("RGB", 8, 1)));
+        let gray = encode(
+            DynamicImage::ImageLuma16(ImageBuffer::new(4, 4)),
+            ImageFormat::Png,
+        );
+        assert_eq!(probe_png(&gray), Some(("L", 16, 1)));
+
+        // Signature (8) + IHDR chunk (25), then an APNG animation control chunk
+        let mut animated = rgb[..33].to_vec();
+        animated.extend(png_chunk(b"acTL", &[0, 0, 0, 3, 0, 0, 0, 0]));
+        animated.extend_from_slice(&rgb[33..]);
+        assert_eq!(probe_png(&animated), Some(("RGB", 8, 3)));
+
+        assert_eq!(probe_png(&rgb[..20]), None);
+    }
+
+    #[test]
+    fn probe_jpeg_reads_component_count() {
+        let rgb = encode(framed(8, 8, (0, 0, 0, 0)), ImageFormat::Jpeg);
+        assert_eq!(probe_jpeg(&rgb), Some(("RGB", 8)));
+        let gray = encode(
+            DynamicImage::ImageLuma8(ImageBuffer::new(8, 8)),
+            ImageFormat::Jpeg,
+        );
+        assert_eq!(probe_jpeg(&gray), Some(("L", 8)));
+
+        // SOI, a fill byte, then a baseline SOF with four components
+        let cmyk = [
+            0xFF, 0xD8, 0xFF, 0xFF, 0xC0, 0x00, 0x14, 8, 0, 1, 0, 1, 4, 1, 0x11, 0, 2, 0x11, 0, 3,
+            0x11, 0, 4, 0x11, 0,
+        ];
+        assert_eq!(probe_jpeg(&cmyk), Some(("CMYK", 8)));
+        assert_eq!(probe_jpeg(&rgb[..4]), None);
+    }
+
+    #[test]
+    fn gif_frames_are_counted() {
+        let frame = || image::Frame::new(RgbaImage::from_pixel(4, 4, image::Rgba([9, 9, 9, 255])));
+        let mut animated = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut animated);
+            encoder.encode_frames([frame(), frame(), frame()]).unwrap();
+        }
+        assert_eq!(gif_frame_count(&animated), 3);
+        let still = encode(framed(4, 4, (0, 0, 0, 0)), ImageFormat::Gif);
+        assert_eq!(gif_frame_count(&still), 1);
+        assert_eq!(gif_frame_count(&animated[..8]), 0);
+    }
+
+    #[test]
+    fn webp_frames_are_counted() {
+        let still = encode(framed(4, 4, (0, 0, 0, 0)), ImageFormat::WebP);
+        assert_eq!(webp_frame_count(&still), 1);
+
+        let chunk = |chunk_type: &[u8; 4], size: u32| {
+            let mut chunk = chunk_type.to_vec();
+            chunk.extend_from_slice(&size.to_le_bytes());
+            chunk.resize(8 + size as usize + (size & 1) as usize, 0);
+            chunk
+        };
+        let mut animated = b"RIFF\0\0\0\0WEBP".to_vec();
+        animated.extend(chunk(b"VP8X", 10));
+        animated.extend(chunk(b"ANIM", 6));
+        // Odd sizes are padded to an even length
+        animated.extend(chunk(b"ANMF", 17));
+        animated.extend(chunk(b"ANMF", 16));
+        assert_eq!(webp_frame_count(&animated), 2);
+    }
+}
@@ -0,0 +1,1250 @@
+//! Technical quality scores: compression artifacts, entropy, sharpness, noise and
+//! exposure statistics
+
+use image::imageops;
+use image::{GrayImage, RgbImage};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::color::{flatten_alpha, DEFAULT_BACKGROUND};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels_reduced, open_image_reduced, DecodeLimits};
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+/// Calculate information entropy directly from RGB image
+pub(super) fn calculate_entropy_from_rgb(rgb_img: &RgbImage) -> f64 {
+    let mut r_counts = [0u32; 256];
+    let mut g_counts = [0u32; 256];
+    let mut b_counts = [0u32; 256];
+    let mut total = 0u32;
+
+    for pixel in rgb_img.pixels() {
+        r_counts[pixel[0] as usize] += 1;
+        g_counts[pixel[1] as usize] += 1;
+        b_counts[pixel[2] as usize] += 1;
+        total += 1;
+    }
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    let inv_total = 1.0 / total as f64;
+
+    let calc_entropy = |counts: &[u32; 256]| -> f64 {
+        let mut entropy = 0.0;
+        for &count in counts.iter() {
+            if count > 0 {
+                let prob = count as f64 * inv_total;
+                entropy -= prob * prob.log2();
+            }
+        }
+        entropy
+    };
+
+    let r_entropy = calc_entropy(&r_counts);
+    let g_entropy = calc_entropy(&g_counts);
+    let b_entropy = calc_entropy(&b_counts);
+
+    (r_entropy + g_entropy + b_entropy) / 3.0
+}
+
+/// Detect compression artifacts using pre-converted RGB image
+///
+/// `source_dimensions` are the encoded image's dimensions, used for the compression
+/// ratio (they differ from the RGB image's when it was reduced for analysis).
+pub(super) fn detect_compression_artifacts_from_rgb(
+    rgb_img: &RgbImage,
+    image_bytes_len: usize,
+    source_dimensions: (u32, u32),
+) -> f64 {
+    let (width, height) = rgb_img.dimensions();
+
+    // Calculate blockiness (JPEG 8x8 block boundaries)
+    let block_size = 8;
+    let max_samples = 64;
+
+    let h_block_count = ((height.saturating_sub(1)) / block_size).min(max_samples as u32);
+    let w_block_count = ((width.saturating_sub(1)) / block_size).min(max_samples as u32);
+
+    let mut v_sum = 0u64;
+    let mut v_count = 0u32;
+    let mut h_sum = 0u64;
+    let mut h_count = 0u32;
+
+    // Vertical boundaries
+    for i in 0..h_block_count {
+        let y = i * block_size;
+        if y + 1 < height {
+            let mut sum = 0u32;
+            for x in 0..width {
+                let p1 = rgb_img.get_pixel(x, y);
+                let p2 = rgb_img.get_pixel(x, y + 1);
+                let gray1 = (p1[0] as u16 + p1[1] as u16 + p1[2] as u16) / 3;
+                let gray2 = (p2[0] as u16 + p2[1] as u16 + p2[2] as u16) / 3;
+                let diff = (gray1 as i16 - gray2 as i16).unsigned_abs() as u32;
+                sum += diff;
+            }
+            v_sum += (sum / width) as u64;
+            v_count += 1;
+        }
+    }
+
+    // Horizontal boundaries
+    for j in 0..w_block_count {
+        let x = j * block_size;
+        if x + 1 < width {
+            let mut sum = 0u32;
+            for y in 0..height {
+                let p1 = rgb_img.get_pixel(x, y);
+                let p2 = rgb_img.get_pixel(x + 1, y);
+                let gray1 = (p1[0] as u16 + p1[1] as u16 + p1[2] as u16) / 3;
+                let gray2 = (p2[0] as u16 + p2[1] as u16 + p2[2] as u16) / 3;
+                let diff = (gray1 as i16 - gray2 as i16).unsigned_abs() as u32;
+                sum += diff;
+            }
+            h_sum += (sum / height) as u64;
+            h_count += 1;
+        }
+    }
+
+    let blockiness = if v_count > 0 && h_count > 0 {
+        let v_avg = v_sum as f64 / v_count as f64 / 255.0;
+        let h_avg = h_sum as f64 / h_count as f64 / 255.0;
+        (v_avg + h_avg) / 2.0
+    } else {
+        0.0
+    };
+
+    // Compression ratio score
+    let (source_width, source_height) = source_dimensions;
+    let uncompressed_size = source_width as u64 * source_height as u64 * 3;
+    let compressed_ratio = if uncompressed_size > 0 {
+        image_bytes_len as f64 / uncompressed_size as f64
+    } else {
+        1.0
+    };
+    let compression_score = 1.0 - (compressed_ratio * 2.0).min(1.0);
+
+    let artifact_score = blockiness * 0.6 + compression_score * 0.4;
+    artifact_score.clamp(0.0, 1.0)
+}
+
+/// Reject a zero `analysis_max_side`
+pub(super) fn check_analysis_max_side(analysis_max_side: Option<u32>) -> PyResult<()> {
+    if analysis_max_side == Some(0) {
+        return Err(PyValueError::new_err("analysis_max_side must be positive"));
+    }
+    Ok(())
+}
+
+/// Quality metrics of one image, plus the analysis scale they were computed at
+#[derive(Clone, Copy)]
+struct QualityScores {
+    compression_artifacts: f64,
+    entropy: f64,
+    scale: f64,
+}
+
+/// Process single image (decode once, compute both metrics)
+///
+/// With `analysis_max_side`, metrics are computed on a reduced copy of the decoded
+/// image (see `decode_pixels_reduced`); the compression ratio still uses the source
+/// dimensions.
+fn image_assess_quality_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> ItemResult<QualityScores> {
+    let (_, decoder) = open_image_reduced(image_bytes, limits, analysis_max_side)?;
+    let scaled = decode_pixels_reduced(decoder, limits)?;
+    let rgb_img = flatten_alpha(&scaled.image, background);
+    Ok(QualityScores {
+        compression_artifacts: detect_compression_artifacts_from_rgb(
+            &rgb_img,
+            image_bytes.len(),
+            scaled.source_dimensions,
+        ),
+        entropy: calculate_entropy_from_rgb(&rgb_img),
+        scale: scaled.scale,
+    })
+}
+
+/// Assess every image in parallel
+fn image_assess_quality_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> Vec<ItemResult<QualityScores>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_assess_quality_core(image_bytes, limits, analysis_max_side, background)
+            })
+        })
+        .collect()
+}
+
+/// Build a quality batch function's return value
+///
+/// Results are (compression_artifacts, entropy) tuples, extended with the decode
+/// scale when `report_scale` is set.
+fn quality_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<QualityScores>>,
+    report_scale: bool,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    if report_scale {
+        let results = results
+            .into_iter()
+            .map(|r| r.map(|q| (q.compression_artifacts, q.entropy, q.scale)))
+            .collect();
+        batch_output(py, results, || (0.0, 0.0, 0.0), return_status)
+    } else {
+        let results = results
+            .into_iter()
+            .map(|r| r.map(|q| (q.compression_artifacts, q.entropy)))
+            .collect();
+        batch_output(py, results, || (0.0, 0.0), return_status)
+    }
+}
+
+/// Batch process multiple images in parallel using rayon (GIL released)
+///
+/// Failed images yield (0.0, 0.0). With `return_status=True`, returns
+/// `(results, statuses)` with one (status, message) tuple per image.
+///
+/// With `analysis_max_side`, images larger than `analysis_max_side` are reduced
+/// (JPEG: decoded at 1/2, 1/4 or 1/8; other formats: resized to fit after decoding)
+/// and scored at that resolution; each result gains a third element, the scale used
+/// (1.0 when not reduced, 0.0 for failed images). This makes metrics comparable across
+/// source resolutions and cheaper to compute; for JPEGs the decode limits apply at the
+/// reduced size (see `decode_pixels_reduced`).
+///
+/// `limits` (a `DecodeLimits`) rejects images before their pixels are decoded, each
+/// limit with its own status; every image function takes the same `limits`.
+///
+/// Decoded pixels are converted to sRGB through the embedded ICC profile (CMYK and
+/// YCCK JPEGs included), and transparent images are composited onto `background`
+/// (an RGB tuple, white by default) before scoring.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_assess_quality_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let results = detach_in_pool(py, || {
+        image_assess_quality_all(&image_bytes_list, &limits, analysis_max_side, background)
+    });
+    quality_batch_output(py, results, analysis_max_side.is_some(), return_status)
+}
+
+/// Non-blocking `image_assess_quality_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_assess_quality_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    Ok(BatchFuture::spawn(move || {
+        let results =
+            image_assess_quality_all(&image_bytes_list, &limits, analysis_max_side, background);
+        finisher(move |py| {
+            quality_batch_output(py, results, analysis_max_side.is_some(), return_status)
+        })
+    }))
+}
+
+/// Arrow variant of `image_assess_quality_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `compression_artifacts` and `entropy` columns (null
+/// for failed or null images), plus `analysis_scale` with `analysis_max_side`. With
+/// `return_status=True`, `status` and `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_assess_quality_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<QualityScores>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_assess_quality_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        analysis_max_side,
+                        background,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let scores = || results.iter().map(|r| r.as_ref().ok());
+    let mut columns = vec![
+        ArrowColumn::primitive(
+            "compression_artifacts",
+            scores().map(|q| q.map(|q| q.compression_artifacts)),
+        ),
+        ArrowColumn::primitive("entropy", scores().map(|q| q.map(|q| q.entropy))),
+    ];
+    if analysis_max_side.is_some() {
+        columns.push(ArrowColumn::primitive(
+            "analysis_scale",
+            scores().map(|q| q.map(|q| q.scale)),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+// ============================================================================
+// Sharpness / Blur
+// ============================================================================
+
+/// Tiles per side of the grid used for the local sharpness summary
+const SHARPNESS_TILE_GRID: u32 = 8;
+
+/// Laplacian variance at or above which a tile counts as sharp
+pub(super) const DEFAULT_SHARP_TILE_THRESHOLD: f64 = 100.0;
+
+/// Blur signals of one image, computed on its luma channel
+#[derive(Clone, Copy, Default)]
+pub(super) struct SharpnessMetrics {
+    /// Variance of the 4-neighbour Laplacian (low = blurry)
+    pub(super) laplacian_variance: f64,
+    /// Mean squared Sobel gradient magnitude
+    pub(super) tenengrad: f64,
+    /// Fraction of the 8x8 tile grid whose Laplacian variance reaches the threshold
+    pub(super) sharp_tile_fraction: f64,
+}
+
+/// Running sum and sum of squares of Laplacian responses
+#[derive(Clone, Copy, Default)]
+struct VarianceAccumulator {
+    sum: f64,
+    sum_sq: f64,
+    count: u64,
+}
+
+impl VarianceAccumulator {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.sum_sq += value * value;
+        self.count += 1;
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let mean = self.sum / self.count as f64;
+        (self.sum_sq / self.count as f64 - mean * mean).max(0.0)
+    }
+}
+
+/// Compute Laplacian variance, Tenengrad and the sharp-tile fraction in one pass
+///
+/// Only interior pixels are used; images smaller than 3x3 have all-zero metrics.
+pub(super) fn sharpness_from_luma(gray: &GrayImage, sharp_tile_threshold: f64) -> SharpnessMetrics {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return SharpnessMetrics::default();
+    }
+
+    let pixels = gray.as_raw();
+    let at = |x: u32, y: u32| pixels[(y * width + x) as usize] as i32;
+    let mut laplacian = VarianceAccumulator::default();
+    let mut tiles = vec![VarianceAccumulator::default(); (SHARPNESS_TILE_GRID.pow(2)) as usize];
+    let mut gradient_energy = 0.0;
+
+    for y in 1..height - 1 {
+        let tile_row = (y * SHARPNESS_TILE_GRID / height) * SHARPNESS_TILE_GRID;
+        for x in 1..width - 1 {
+            let center = at(x, y);
+            let response = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4 * center;
+            laplacian.add(response as f64);
+            tiles[(tile_row + x * SHARPNESS_TILE_GRID / width) as usize].add(response as f64);
+
+            let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            gradient_energy += (gx * gx + gy * gy) as f64;
+        }
+    }
+
+    let populated = tiles.iter().filter(|tile| tile.count > 0).count();
+    let sharp = tiles
+        .iter()
+        .filter(|tile| tile.count > 0 && tile.variance() >= sharp_tile_threshold)
+        .count();
+    SharpnessMetrics {
+        laplacian_variance: laplacian.variance(),
+        tenengrad: gradient_energy / laplacian.count as f64,
+        sharp_tile_fraction: sharp as f64 / populated as f64,
+    }
+}
+
+/// Decode one image and compute its sharpness metrics
+pub(super) fn image_assess_sharpness_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    sharp_tile_threshold: f64,
+    background: [u8; 3],
+) -> ItemResult<(SharpnessMetrics, f64)> {
+    let (_, decoder) = open_image_reduced(image_bytes, limits, analysis_max_side)?;
+    let scaled = decode_pixels_reduced(decoder, limits)?;
+    let luma = imageops::grayscale(&flatten_alpha(&scaled.image, background));
+    let metrics = sharpness_from_luma(&luma, sharp_tile_threshold);
+    Ok((metrics, scaled.scale))
+}
+
+/// Assess the sharpness of every image in parallel
+fn image_assess_sharpness_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    sharp_tile_threshold: f64,
+    background: [u8; 3],
+) -> Vec<ItemResult<(SharpnessMetrics, f64)>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_assess_sharpness_core(
+                    image_bytes,
+                    limits,
+                    analysis_max_side,
+                    sharp_tile_threshold,
+                    background,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Build a sharpness batch function's return value
+///
+/// Results are (laplacian_variance, tenengrad, sharp_tile_fraction) tuples, extended
+/// with the analysis scale when `report_scale` is set.
+fn sharpness_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<(SharpnessMetrics, f64)>>,
+    report_scale: bool,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    if report_scale {
+        let results = results
+            .into_iter()
+            .map(|r| {
+                r.map(|(m, scale)| {
+                    (
+                        m.laplacian_variance,
+                        m.tenengrad,
+                        m.sharp_tile_fraction,
+                        scale,
+                    )
+                })
+            })
+            .collect();
+        batch_output(py, results, || (0.0, 0.0, 0.0, 0.0), return_status)
+    } else {
+        let results = results
+            .into_iter()
+            .map(|r| r.map(|(m, _)| (m.laplacian_variance, m.tenengrad, m.sharp_tile_fraction)))
+            .collect();
+        batch_output(py, results, || (0.0, 0.0, 0.0), return_status)
+    }
+}
+
+/// Reject a negative or NaN tile threshold
+fn check_sharp_tile_threshold(sharp_tile_threshold: f64) -> PyResult<()> {
+    if sharp_tile_threshold.is_nan() || sharp_tile_threshold < 0.0 {
+        return Err(PyValueError::new_err(
+            "sharp_tile_threshold must be a non-negative number",
+        ));
+    }
+    Ok(())
+}
+
+/// Batch compute blur metrics in parallel (GIL released)
+///
+/// Returns (laplacian_variance, tenengrad, sharp_tile_fraction) per image, computed on
+/// the luma of the image composited onto `background`: the variance of the 4-neighbour
+/// Laplacian, the mean squared Sobel gradient magnitude, and the fraction of an 8x8
+/// tile grid whose Laplacian variance is at least `sharp_tile_threshold` (low values
+/// mean blurry or mostly flat images). Failed images yield zeros. `analysis_max_side`
+/// and `background` work as in `image_assess_quality_batch`; with
+/// `analysis_max_side` each result gains the analysis scale.
+/// Note that both variance metrics grow with resolution.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, sharp_tile_threshold=DEFAULT_SHARP_TILE_THRESHOLD, background=DEFAULT_BACKGROUND))]
+pub fn image_assess_sharpness_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    sharp_tile_threshold: f64,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    check_sharp_tile_threshold(sharp_tile_threshold)?;
+    let results = detach_in_pool(py, || {
+        image_assess_sharpness_all(
+            &image_bytes_list,
+            &limits,
+            analysis_max_side,
+            sharp_tile_threshold,
+            background,
+        )
+    });
+    sharpness_batch_output(py, results, analysis_max_side.is_some(), return_status)
+}
+
+/// Non-blocking `image_assess_sharpness_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, sharp_tile_threshold=DEFAULT_SHARP_TILE_THRESHOLD, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_assess_sharpness_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    sharp_tile_threshold: f64,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    check_sharp_tile_threshold(sharp_tile_threshold)?;
+    Ok(BatchFuture::spawn(move || {
+        let results = image_assess_sharpness_all(
+            &image_bytes_list,
+            &limits,
+            analysis_max_side,
+            sharp_tile_threshold,
+            background,
+        );
+        finisher(move |py| {
+            sharpness_batch_output(py, results, analysis_max_side.is_some(), return_status)
+        })
+    }))
+}
+
+/// Arrow variant of `image_assess_sharpness_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `laplacian_variance`, `tenengrad` and
+/// `sharp_tile_fraction` columns (null for failed or null images), plus
+/// `analysis_scale` with `analysis_max_side`. With `return_status=True`, `status` and
+/// `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, analysis_max_side=None, sharp_tile_threshold=DEFAULT_SHARP_TILE_THRESHOLD, background=DEFAULT_BACKGROUND))]
+pub fn image_assess_sharpness_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    sharp_tile_threshold: f64,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    check_sharp_tile_threshold(sharp_tile_threshold)?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<(SharpnessMetrics, f64)>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_assess_sharpness_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        analysis_max_side,
+                        sharp_tile_threshold,
+                        background,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let metrics = || results.iter().map(|r| r.as_ref().ok());
+    let mut columns = vec![
+        ArrowColumn::primitive(
+            "laplacian_variance",
+            metrics().map(|r| r.map(|(m, _)| m.laplacian_variance)),
+        ),
+        ArrowColumn::primitive("tenengrad", metrics().map(|r| r.map(|(m, _)| m.tenengrad))),
+        ArrowColumn::primitive(
+            "sharp_tile_fraction",
+            metrics().map(|r| r.map(|(m, _)| m.sharp_tile_fraction)),
+        ),
+    ];
+    if analysis_max_side.is_some() {
+        columns.push(ArrowColumn::primitive(
+            "analysis_scale",
+            metrics().map(|r| r.map(|&(_, scale)| scale)),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+// ============================================================================
+// Noise Estimation
+// ============================================================================
+
+/// Estimate the standard deviation of additive noise on the luma channel
+///
+/// Immerkær's fast method ("Fast Noise Variance Estimation", 1996): convolve with the
+/// difference of two Laplacians, which cancels smooth image structure, and scale the
+/// mean absolute response to a Gaussian sigma in 8-bit units. Strong edges and fine
+/// texture inflate the estimate. Images smaller than 3x3 yield 0.0.
+pub(super) fn noise_sigma_from_luma(gray: &GrayImage) -> f64 {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let pixels = gray.as_raw();
+    let at = |x: u32, y: u32| pixels[(y * width + x) as usize] as i64;
+    let mut total: u64 = 0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let corners = at(x - 1, y - 1) + at(x + 1, y - 1) + at(x - 1, y + 1) + at(x + 1, y + 1);
+            let edges = at(x, y - 1) + at(x - 1, y) + at(x + 1, y) + at(x, y + 1);
+            total += (corners - 2 * edges + 4 * at(x, y)).unsigned_abs();
+        }
+    }
+
+    let interior = (u64::from(width) - 2) * (u64::from(height) - 2);
+    (std::f64::consts::PI / 2.0).sqrt() * total as f64 / (6.0 * interior as f64)
+}
+
+/// Decode one image and estimate its noise sigma
+pub(super) fn image_estimate_noise_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> ItemResult<(f64, f64)> {
+    let (_, decoder) = open_image_reduced(image_bytes, limits, analysis_max_side)?;
+    let scaled = decode_pixels_reduced(decoder, limits)?;
+    let luma = imageops::grayscale(&flatten_alpha(&scaled.image, background));
+    Ok((noise_sigma_from_luma(&luma), scaled.scale))
+}
+
+/// Estimate the noise of every image in parallel
+fn image_estimate_noise_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> Vec<ItemResult<(f64, f64)>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_estimate_noise_core(image_bytes, limits, analysis_max_side, background)
+            })
+        })
+        .collect()
+}
+
+/// Build a noise batch function's return value
+///
+/// Results are noise sigmas, or (sigma, analysis_scale) tuples when `report_scale` is set.
+fn noise_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<(f64, f64)>>,
+    report_scale: bool,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    if report_scale {
+        batch_output(py, results, || (0.0, 0.0), return_status)
+    } else {
+        let results = results
+            .into_iter()
+            .map(|r| r.map(|(sigma, _)| sigma))
+            .collect();
+        batch_output(py, results, || 0.0, return_status)
+    }
+}
+
+/// Batch estimate image noise in parallel (GIL released)
+///
+/// Returns the estimated noise standard deviation per image, in 8-bit luma units
+/// (Immerkær's method: roughly 0-2 for clean images, 5+ for visibly noisy or heavily
+/// compressed ones), measured on the luma of the image composited onto `background`.
+/// Failed images yield 0.0. `analysis_max_side` and `background` work as in
+/// `image_assess_quality_batch`; with `analysis_max_side` each result becomes
+/// (sigma, analysis_scale). Reducing an image averages out noise, so sigmas are only
+/// comparable at the same `analysis_max_side`.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_estimate_noise_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let results = detach_in_pool(py, || {
+        image_estimate_noise_all(&image_bytes_list, &limits, analysis_max_side, background)
+    });
+    noise_batch_output(py, results, analysis_max_side.is_some(), return_status)
+}
+
+/// Non-blocking `image_estimate_noise_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_estimate_noise_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    Ok(BatchFuture::spawn(move || {
+        let results =
+            image_estimate_noise_all(&image_bytes_list, &limits, analysis_max_side, background);
+        finisher(move |py| {
+            noise_batch_output(py, results, analysis_max_side.is_some(), return_status)
+        })
+    }))
+}
+
+/// Arrow variant of `image_estimate_noise_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with a `noise_sigma` column (null for failed or null images),
+/// plus `analysis_scale` with `analysis_max_side`. With `return_status=True`, `status` and
+/// `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_estimate_noise_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<(f64, f64)>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_estimate_noise_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        analysis_max_side,
+                        background,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let estimates = || results.iter().map(|r| r.as_ref().ok());
+    let mut columns = vec![ArrowColumn::primitive(
+        "noise_sigma",
+        estimates().map(|r| r.map(|&(sigma, _)| sigma)),
+    )];
+    if analysis_max_side.is_some() {
+        columns.push(ArrowColumn::primitive(
+            "analysis_scale",
+            estimates().map(|r| r.map(|&(_, scale)| scale)),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+// ============================================================================
+// Exposure / Color Statistics
+// ============================================================================
+
+/// Rec. 709 luma weights, scaled by `LUMA_SCALE` for exact integer accumulation
+const LUMA_WEIGHTS: [i64; 3] = [2126, 7152, 722];
+const LUMA_SCALE: i64 = 10_000;
+
+/// Luma at or above which a pixel counts as a clipped highlight
+const HIGHLIGHT_CLIP_LUMA: i64 = 250;
+
+/// Luma at or below which a pixel counts as a clipped shadow
+const SHADOW_CLIP_LUMA: i64 = 5;
+
+/// Names of the exposure statistics, in `ExposureStats::values` order
+pub(super) const EXPOSURE_METRICS: [&str; 7] = [
+    "luma_mean",
+    "luma_std",
+    "highlight_clip_fraction",
+    "shadow_clip_fraction",
+    "rms_contrast",
+    "colorfulness",
+    "saturation_mean",
+];
+
+/// Exposure, contrast and color statistics of one image
+#[derive(Clone, Copy, Default)]
+pub(super) struct ExposureStats {
+    /// Mean Rec. 709 luma (0-255)
+    luma_mean: f64,
+    /// Standard deviation of luma (0-255 units)
+    luma_std: f64,
+    /// Fraction of pixels with luma >= 250
+    highlight_clip_fraction: f64,
+    /// Fraction of pixels with luma <= 5
+    shadow_clip_fraction: f64,
+    /// Standard deviation of luma normalized to 0-1
+    rms_contrast: f64,
+    /// Hasler–Süsstrunk colorfulness (0 = grayscale, ~33 = moderately colorful)
+    colorfulness: f64,
+    /// Mean HSV saturation (0-1)
+    saturation_mean: f64,
+}
+
+impl ExposureStats {
+    /// Statistic values in `EXPOSURE_METRICS` order
+    pub(super) fn values(&self) -> [f64; 7] {
+        [
+            self.luma_mean,
+            self.luma_std,
+            self.highlight_clip_fraction,
+            self.shadow_clip_fraction,
+            self.rms_contrast,
+            self.colorfulness,
+            self.saturation_mean,
+        ]
+    }
+}
+
+/// Running sum and sum of squares of integer samples
+#[derive(Default)]
+struct IntMoments {
+    sum: i128,
+    sum_sq: i128,
+}
+
+impl IntMoments {
+    fn add(&mut self, value: i64) {
+        self.sum += i128::from(value);
+        self.sum_sq += i128::from(value) * i128::from(value);
+    }
+
+    fn mean(&self, n: f64) -> f64 {
+        self.sum as f64 / n
+    }
+
+    /// Population standard deviation, computed exactly before the final division
+    fn std(&self, n: i128) -> f64 {
+        let scaled_variance = n * self.sum_sq - self.sum * self.sum;
+        (scaled_variance.max(0) as f64).sqrt() / n as f64
+    }
+}
+
+/// Compute all exposure statistics in one pass over the pixels
+///
+/// Sums are accumulated as exact integers (luma scaled by 10^4, yellow-blue doubled),
+/// so flat images get exactly zero deviation.
+pub(super) fn exposure_stats_from_rgb(rgb_img: &RgbImage) -> ExposureStats {
+    let pixel_count = rgb_img.pixels().len();
+    if pixel_count == 0 {
+        return ExposureStats::default();
+    }
+
+    let mut luma = IntMoments::default();
+    let (mut rg, mut yb) = (IntMoments::default(), IntMoments::default());
+    let (mut highlights, mut shadows) = (0u64, 0u64);
+    let mut saturation_sum = 0.0;
+    for pixel in rgb_img.pixels() {
+        let [r, g, b] = pixel.0.map(i64::from);
+        let scaled_luma = LUMA_WEIGHTS[0] * r + LUMA_WEIGHTS[1] * g + LUMA_WEIGHTS[2] * b;
+        luma.add(scaled_luma);
+        highlights += u64::from(scaled_luma >= HIGHLIGHT_CLIP_LUMA * LUMA_SCALE);
+        shadows += u64::from(scaled_luma <= SHADOW_CLIP_LUMA * LUMA_SCALE);
+        rg.add(r - g);
+        yb.add(r + g - 2 * b);
+
+        let max = r.max(g).max(b);
+        if max > 0 {
+            saturation_sum += (max - r.min(g).min(b)) as f64 / max as f64;
+        }
+    }
+
+    let n = pixel_count as f64;
+    let count = pixel_count as i128;
+    let luma_std = luma.std(count) / LUMA_SCALE as f64;
+    ExposureStats {
+        luma_mean: luma.mean(n) / LUMA_SCALE as f64,
+        luma_std,
+        highlight_clip_fraction: highlights as f64 / n,
+        shadow_clip_fraction: shadows as f64 / n,
+        rms_contrast: luma_std / 255.0,
+        colorfulness: rg.std(count).hypot(yb.std(count) / 2.0)
+            + 0.3 * rg.mean(n).hypot(yb.mean(n) / 2.0),
+        saturation_mean: saturation_sum / n,
+    }
+}
+
+/// Decode one image and compute its exposure statistics
+fn image_exposure_stats_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> ItemResult<(ExposureStats, f64)> {
+    let (_, decoder) = open_image_reduced(image_bytes, limits, analysis_max_side)?;
+    let scaled = decode_pixels_reduced(decoder, limits)?;
+    Ok((
+        exposure_stats_from_rgb(&flatten_alpha(&scaled.image, background)),
+        scaled.scale,
+    ))
+}
+
+/// Compute the exposure statistics of every image in parallel
+fn image_exposure_stats_all(
+    image_bytes_list: &[Vec<u8>],
+    limits: &DecodeLimits,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> Vec<ItemResult<(ExposureStats, f64)>> {
+    image_bytes_list
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_exposure_stats_core(image_bytes, limits, analysis_max_side, background)
+            })
+        })
+        .collect()
+}
+
+/// Convert statistics to dicts and build an exposure batch function's return value
+///
+/// Dicts gain `analysis_scale` when `report_scale` is set; failed images become None.
+fn exposure_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<(ExposureStats, f64)>>,
+    report_scale: bool,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = results
+        .into_iter()
+        .map(|result| match result {
+            Ok((stats, scale)) => {
+                let dict = PyDict::new(py);
+                for (name, value) in EXPOSURE_METRICS.into_iter().zip(stats.values()) {
+                    dict.set_item(name, value)?;
+                }
+                if report_scale {
+                    dict.set_item("analysis_scale", scale)?;
+                }
+                Ok(Ok(dict.into_any()))
+            }
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Batch compute exposure, contrast and color statistics in parallel (GIL released)
+///
+/// Returns one dict per image (None if it fails to decode), computed from a single
+/// decode:
+/// - `luma_mean`, `luma_std`: Rec. 709 luma mean and standard deviation (0-255)
+/// - `highlight_clip_fraction`, `shadow_clip_fraction`: Fraction of pixels with luma
+///   >= 250 / <= 5
+/// - `rms_contrast`: Luma standard deviation normalized to 0-1
+/// - `colorfulness`: Hasler–Süsstrunk colorfulness (0 for grayscale, ~33 moderately
+///   colorful, 60+ extremely colorful)
+/// - `saturation_mean`: Mean HSV saturation (0-1)
+///
+/// `analysis_max_side` and `background` work as in `image_assess_quality_batch`; with
+/// `analysis_max_side` each dict gains `analysis_scale`.
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_exposure_stats_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let results = detach_in_pool(py, || {
+        image_exposure_stats_all(&image_bytes_list, &limits, analysis_max_side, background)
+    });
+    exposure_batch_output(py, results, analysis_max_side.is_some(), return_status)
+}
+
+/// Non-blocking `image_exposure_stats_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_exposure_stats_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    Ok(BatchFuture::spawn(move || {
+        let results =
+            image_exposure_stats_all(&image_bytes_list, &limits, analysis_max_side, background);
+        finisher(move |py| {
+            exposure_batch_output(py, results, analysis_max_side.is_some(), return_status)
+        })
+    }))
+}
+
+/// Arrow variant of `image_exposure_stats_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with one float64 column per statistic (null for failed or
+/// null images), plus `analysis_scale` with `analysis_max_side`. With `return_status=True`,
+/// `status` and `status_message` columns are added.
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, analysis_max_side=None, background=DEFAULT_BACKGROUND))]
+pub fn image_exposure_stats_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    analysis_max_side: Option<u32>,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    check_analysis_max_side(analysis_max_side)?;
+    let limits = limits.unwrap_or_default();
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results: Vec<ItemResult<(ExposureStats, f64)>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|image_bytes| {
+                catch_panic(|| {
+                    image_exposure_stats_core(
+                        image_bytes.ok_or_else(null_input)?,
+                        &limits,
+                        analysis_max_side,
+                        background,
+                    )
+                })
+            })
+            .collect()
+    });
+
+    let stats = || results.iter().map(|r| r.as_ref().ok());
+    let mut columns: Vec<ArrowColumn> = EXPOSURE_METRICS
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            ArrowColumn::primitive(name, stats().map(|r| r.map(|(s, _)| s.values()[i])))
+        })
+        .collect();
+    if analysis_max_side.is_some() {
+        columns.push(ArrowColumn::primitive(
+            "analysis_scale",
+            stats().map(|r| r.map(|&(_, scale)| scale)),
+        ));
+    }
+    record_batch_output(py, columns, &results, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, RgbImage};
+
+    /// Flat gray 128 plus deterministic Gaussian noise of `sigma`, rounded to u8
+    fn gaussian_luma(width: u32, height: u32, sigma: f64) -> GrayImage {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut uniform = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            ((state >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+        };
+        GrayImage::from_fn(width, height, |_, _| {
+            let normal =
+                (-2.0 * uniform().ln()).sqrt() * (2.0 * std::f64::consts::PI * uniform()).cos();
+            Luma([(128.0 + sigma * normal).round().clamp(0.0, 255.0) as u8])
+        })
+    }
+
+    #[test]
+    fn flat_images_have_zero_sharpness() {
+        let flat = GrayImage::from_pixel(64, 48, Luma([90]));
+        let metrics = sharpness_from_luma(&flat, DEFAULT_SHARP_TILE_THRESHOLD);
+        assert_eq!(metrics.laplacian_variance, 0.0);
+        assert_eq!(metrics.tenengrad, 0.0);
+        assert_eq!(metrics.sharp_tile_fraction, 0.0);
+    }
+
+    #[test]
+    fn sharpness_of_gaussian_noise_follows_its_sigma() {
+        // For i.i.d. noise the Laplacian has variance 20 sigma^2 and each Sobel
+        // component 12 sigma^2
+        let sigma = 8.0;
+        let metrics = sharpness_from_luma(&gaussian_luma(256, 256, sigma), 1.0);
+        let variance = sigma * sigma + 1.0 / 12.0;
+        assert!((metrics.laplacian_variance / (20.0 * variance) - 1.0).abs() < 0.05);
+        assert!((metrics.tenengrad / (24.0 * variance) - 1.0).abs() < 0.05);
+        assert_eq!(metrics.sharp_tile_fraction, 1.0);
+    }
+
+    #[test]
+    fn images_under_3_pixels_have_zero_sharpness() {
+        for (width, height) in [(2, 50), (50, 2), (1, 1)] {
+            let metrics = sharpness_from_luma(&gaussian_luma(width, height, 30.0), 1.0);
+            assert_eq!(metrics.laplacian_variance, 0.0);
+            assert_eq!(metrics.tenengrad, 0.0);
+            assert_eq!(metrics.sharp_tile_fraction, 0.0);
+        }
+    }
+
+    #[test]
+    fn images_smaller_than_the_tile_grid_use_populated_tiles() {
+        // 4 interior rows fill only half of the tile rows; empty tiles are not counted
+        let metrics = sharpness_from_luma(&gaussian_luma(40, 6, 20.0), 1.0);
+        assert_eq!(metrics.sharp_tile_fraction, 1.0);
+
+        // A single interior pixel has no variance within its tile
+        let checker = GrayImage::from_fn(3, 3, |x, y| Luma([((x + y) % 2 * 255) as u8]));
+        let metrics = sharpness_from_luma(&checker, DEFAULT_SHARP_TILE_THRESHOLD);
+        assert_eq!(metrics.laplacian_variance, 0.0);
+        assert_eq!(metrics.tenengrad, 0.0);
+        assert_eq!(metrics.sharp_tile_fraction, 0.0);
+    }
+
+    #[test]
+    fn flat_images_have_zero_noise() {
+        assert_eq!(
+            noise_sigma_from_luma(&GrayImage::from_pixel(40, 30, Luma([200]))),
+            0.0
+        );
+        let ramp = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 3 + y * 2) as u8]));
+        assert_eq!(noise_sigma_from_luma(&ramp), 0.0);
+    }
+
+    #[test]
+    fn noise_sigma_recovers_gaussian_sigma() {
+        for sigma in [2.0, 5.0, 10.0] {
+            let estimate = noise_sigma_from_luma(&gaussian_luma(256, 256, sigma));
+            let expected = (sigma * sigma + 1.0 / 12.0).sqrt();
+            assert!(
+                (estimate / expected - 1.0).abs() < 0.05,
+                "sigma {sigma}: estimated {estimate}"
+            );
+        }
+    }
+
+    #[test]
+    fn images_under_3_pixels_have_zero_noise() {
+        for (width, height) in [(2, 50), (50, 2), (1, 1)] {
+            assert_eq!(
+                noise_sigma_from_luma(&gaussian_luma(width, height, 30.0)),
+                0.0
+            );
+        }
+    }
+
+    #[test]
+    fn flat_gray_images_have_no_spread_or_color() {
+        let stats = exposure_stats_from_rgb(&RgbImage::from_pixel(30, 20, Rgb([90, 90, 90])));
+        assert!((stats.luma_mean - 90.0).abs() < 1e-9);
+        assert_eq!(stats.luma_std, 0.0);
+        assert_eq!(stats.rms_contrast, 0.0);
+        assert_eq!(stats.colorfulness, 0.0);
+        assert_eq!(stats.saturation_mean, 0.0);
+        assert_eq!(stats.highlight_clip_fraction, 0.0);
+        assert_eq!(stats.shadow_clip_fraction, 0.0);
+    }
+
+    #[test]
+    fn black_and_white_images_are_fully_clipped() {
+        let black = exposure_stats_from_rgb(&RgbImage::new(16, 16));
+        assert_eq!(black.luma_mean, 0.0);
+        assert_eq!(black.shadow_clip_fraction, 1.0);
+        assert_eq!(black.highlight_clip_fraction, 0.0);
+
+        let white = exposure_stats_from_rgb(&RgbImage::from_pixel(16, 16, Rgb([255; 3])));
+        assert!((white.luma_mean - 255.0).abs() < 1e-9);
+        assert_eq!(white.highlight_clip_fraction, 1.0);
+        assert_eq!(white.shadow_clip_fraction, 0.0);
+    }
+
+    #[test]
+    fn half_black_half_white_has_maximal_contrast() {
+        let split = RgbImage::from_fn(10, 10, |x, _| Rgb([if x < 5 { 0 } else { 255 }; 3]));
+        let stats = exposure_stats_from_rgb(&split);
+        assert!((stats.luma_mean - 127.5).abs() < 1e-9);
+        assert!((stats.luma_std - 127.5).abs() < 1e-9);
+        assert!((stats.rms_contrast - 0.5).abs() < 1e-9);
+        assert_eq!(stats.highlight_clip_fraction, 0.5);
+        assert_eq!(stats.shadow_clip_fraction, 0.5);
+    }
+
+    #[test]
+    fn saturated_colors_are_colorful() {
+        let red = exposure_stats_from_rgb(&RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])));
+        assert_eq!(red.saturation_mean, 1.0);
+        // No spread, so only the mean term: 0.3 * hypot(255, 255 / 2)
+        assert!((red.colorfulness - 0.3 * 255.0f64.hypot(127.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_images_have_default_stats() {
+        let stats = exposure_stats_from_rgb(&RgbImage::new(0, 0));
+        assert_eq!(stats.values(), [0.0; 7]);
+    }
+}
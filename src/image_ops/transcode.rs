@@ -0,0 +1,342 @@
+//! Re-encoding images to a canonical format
+
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use rayon::prelude::*;
+
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::color::{flatten_alpha, DEFAULT_BACKGROUND};
+use crate::executor::detach_in_pool;
+use crate::image_decode::{decode_pixels, open_image, DecodeLimits};
+use crate::image_encode::{
+    check_quality, encode_image_with, parse_chroma_subsampling, EncodeMetadata, OutputFormat,
+    DEFAULT_JPEG_QUALITY,
+};
+use crate::jpeg_encode::ChromaSubsampling;
+use crate::status::{batch_output, catch_panic, ItemResult};
+
+use super::preprocess::parse_resize_filter;
+
+/// Default chroma subsampling of transcoded JPEGs (libjpeg's default)
+const DEFAULT_CHROMA_SUBSAMPLING: &str = "4:2:0";
+
+/// Output settings of `image_transcode_batch`
+#[derive(Clone, Copy)]
+struct TranscodeOptions {
+    /// Target format; `None` picks one with `OutputFormat::for_source`
+    format: Option<OutputFormat>,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    /// Longest output side; larger images are downscaled
+    max_side: Option<u32>,
+    filter: FilterType,
+    strip_metadata: bool,
+    /// Color transparent images are composited onto for JPEG output
+    background: [u8; 3],
+}
+
+/// Re-encoded image and its size
+struct TranscodeResult {
+    image: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: OutputFormat,
+}
+
+/// Decode one image upright, downscale it to `max_side` and re-encode it
+fn image_transcode_core(
+    image_bytes: &[u8],
+    limits: &DecodeLimits,
+    options: TranscodeOptions,
+) -> ItemResult<TranscodeResult> {
+    let (source_format, mut decoder) = open_image(image_bytes, limits)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let metadata = if options.strip_metadata {
+        EncodeMetadata::default()
+    } else {
+        // The pixels are rotated upright, so the EXIF orientation is reset
+        let mut exif = decoder.exif_metadata().ok().flatten();
+        if let Some(exif) = exif.as_mut() {
+            let _ = Orientation::remove_from_exif_chunk(exif);
+        }
+        // Pixels are converted to sRGB, so the source ICC profile no longer applies
+        EncodeMetadata {
+            icc_profile: None,
+            exif,
+        }
+    };
+
+    let mut img = decode_pixels(decoder, limits)?;
+    img.apply_orientation(orientation);
+    if let Some(max_side) = options.max_side {
+        if img.width().max(img.height()) > max_side {
+            img = img.resize(max_side, max_side, options.filter);
+        }
+    }
+    let format = options.format.unwrap_or(OutputFormat::for_source(
+        source_format,
+        img.color().has_alpha(),
+    ));
+    if format == OutputFormat::Jpeg && img.color().has_alpha() {
+        img = DynamicImage::ImageRgb8(flatten_alpha(&img, options.background));
+    }
+    let image = encode_image_with(
+        &img,
+        format,
+        options.quality,
+        options.subsampling,
+        &metadata,
+    )?;
+    Ok(TranscodeResult {
+        image,
+        width: img.width(),
+        height: img.height(),
+        format,
+    })
+}
+
+/// Transcode every image in parallel
+fn image_transcode_all(
+    inputs: &[Option<&[u8]>],
+    limits: &DecodeLimits,
+    options: TranscodeOptions,
+) -> Vec<ItemResult<TranscodeResult>> {
+    inputs
+        .par_iter()
+        .map(|image_bytes| {
+            catch_panic(|| {
+                image_transcode_core(image_bytes.ok_or_else(null_input)?, limits, options)
+            })
+        })
+        .collect()
+}
+
+/// Convert transcode results to dicts and build a transcode batch function's return value
+fn transcode_batch_output(
+    py: Python<'_>,
+    results: Vec<ItemResult<TranscodeResult>>,
+    return_status: bool,
+) -> PyResult<Bound<'_, PyAny>> {
+    let dicts = results
+        .into_iter()
+        .map(|result| match result {
+            Ok(result) => {
+                let dict = PyDict::new(py);
+                dict.set_item("image", PyBytes::new(py, &result.image))?;
+                dict.set_item("width", result.width)?;
+                dict.set_item("height", result.height)?;
+                dict.set_item("format", result.format.name())?;
+                Ok(Ok(dict.into_any()))
+            }
+            Err(err) => Ok(Err(err)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    batch_output(py, dicts, || py.None().into_bound(py), return_status)
+}
+
+/// Validate the transcoding options passed from Python
+fn prepare_transcode(
+    output_format: Option<&str>,
+    quality: Option<u8>,
+    chroma_subsampling: &str,
+    max_side: Option<u32>,
+    resize_filter: &str,
+    strip_metadata: bool,
+    background: [u8; 3],
+) -> PyResult<TranscodeOptions> {
+    let format = OutputFormat::parse_optional(output_format)?;
+    if format == Some(OutputFormat::WebP) && quality.is_some() {
+        return Err(PyValueError::new_err(
+            "quality does not apply to output_format='webp', which is lossless",
+        ));
+    }
+    let quality = quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+    check_quality(quality)?;
+    if max_side == Some(0) {
+        return Err(PyValueError::new_err("max_side must be positive"));
+    }
+    let options = TranscodeOptions {
+        format,
+        quality,
+        subsampling: parse_chroma_subsampling(chroma_subsampling)?,
+        max_side,
+        filter: parse_resize_filter(resize_filter)?,
+        strip_metadata,
+        background,
+    };
+    Ok(options)
+}
+
+/// Batch re-encode images to a canonical format in parallel (GIL released)
+///
+/// Each image is decoded, rotated upright by its EXIF orientation, downscaled with
+/// `resize_filter` ("lanczos", "bicubic", "bilinear" or "nearest") if its longer side
+/// exceeds `max_side`, and encoded as `output_format` ("jpeg", "png" or "webp"; None
+/// writes JPEG and opaque WebP sources as JPEG and everything else as PNG). JPEGs use
+/// `quality` (default 90) and `chroma_subsampling` ("4:2:0", "4:2:2" or "4:4:4"). WebP
+/// output is lossless, so passing `quality` with `output_format="webp"` is an error.
+///
+/// Pixels are converted to sRGB through the embedded ICC profile (CMYK JPEGs included),
+/// so the output carries no ICC profile. Transparent images written as JPEG are
+/// composited onto `background` (an RGB tuple, white by default).
+///
+/// With `strip_metadata=True` the output carries no metadata. Otherwise the EXIF data
+/// is kept (with the EXIF orientation reset); XMP is always dropped.
+///
+/// Returns one dict per image with `image` (bytes), `width`, `height` and `format`,
+/// or None if the image failed.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, output_format=Some("jpeg"), quality=None, chroma_subsampling=DEFAULT_CHROMA_SUBSAMPLING, max_side=None, resize_filter="lanczos", strip_metadata=true, background=DEFAULT_BACKGROUND))]
+pub fn image_transcode_batch<'py>(
+    py: Python<'py>,
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    output_format: Option<&str>,
+    quality: Option<u8>,
+    chroma_subsampling: &str,
+    max_side: Option<u32>,
+    resize_filter: &str,
+    strip_metadata: bool,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let options = prepare_transcode(
+        output_format,
+        quality,
+        chroma_subsampling,
+        max_side,
+        resize_filter,
+        strip_metadata,
+        background,
+    )?;
+    let results = detach_in_pool(py, || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        image_transcode_all(&inputs, &limits, options)
+    });
+    transcode_batch_output(py, results, return_status)
+}
+
+/// Non-blocking `image_transcode_batch`; returns a `BatchFuture`
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (image_bytes_list, return_status=false, limits=None, output_format=Some("jpeg"), quality=None, chroma_subsampling=DEFAULT_CHROMA_SUBSAMPLING, max_side=None, resize_filter="lanczos", strip_metadata=true, background=DEFAULT_BACKGROUND))]
+pub fn submit_image_transcode_batch(
+    image_bytes_list: Vec<Vec<u8>>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    output_format: Option<&str>,
+    quality: Option<u8>,
+    chroma_subsampling: &str,
+    max_side: Option<u32>,
+    resize_filter: &str,
+    strip_metadata: bool,
+    background: [u8; 3],
+) -> PyResult<BatchFuture> {
+    let limits = limits.unwrap_or_default();
+    let options = prepare_transcode(
+        output_format,
+        quality,
+        chroma_subsampling,
+        max_side,
+        resize_filter,
+        strip_metadata,
+        background,
+    )?;
+    Ok(BatchFuture::spawn(move || {
+        let inputs: Vec<Option<&[u8]>> = image_bytes_list.iter().map(|b| Some(&b[..])).collect();
+        let results = image_transcode_all(&inputs, &limits, options);
+        finisher(move |py| transcode_batch_output(py, results, return_status))
+    }))
+}
+
+/// Arrow variant of `image_transcode_batch`
+///
+/// Takes a pyarrow binary array without copying the image bytes and returns a
+/// `pyarrow.RecordBatch` with `image` (binary), `width`, `height` and `format` columns.
+/// Failed or null images are null throughout. With `return_status=True`, `status` and
+/// `status_message` columns are added.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (images, return_status=false, limits=None, output_format=Some("jpeg"), quality=None, chroma_subsampling=DEFAULT_CHROMA_SUBSAMPLING, max_side=None, resize_filter="lanczos", strip_metadata=true, background=DEFAULT_BACKGROUND))]
+pub fn image_transcode_arrow<'py>(
+    py: Python<'py>,
+    images: &Bound<'py, PyAny>,
+    return_status: bool,
+    limits: Option<DecodeLimits>,
+    output_format: Option<&str>,
+    quality: Option<u8>,
+    chroma_subsampling: &str,
+    max_side: Option<u32>,
+    resize_filter: &str,
+    strip_metadata: bool,
+    background: [u8; 3],
+) -> PyResult<Bound<'py, PyAny>> {
+    let limits = limits.unwrap_or_default();
+    let options = prepare_transcode(
+        output_format,
+        quality,
+        chroma_subsampling,
+        max_side,
+        resize_filter,
+        strip_metadata,
+        background,
+    )?;
+    let images = ArrowBinaryInput::import(images)?;
+    let values = images.values();
+    let results = detach_in_pool(py, || image_transcode_all(&values, &limits, options));
+
+    let transcoded = || results.iter().map(|r| r.as_ref().ok());
+    let columns = vec![
+        ArrowColumn::binary("image", transcoded().map(|r| r.map(|r| &r.image))),
+        ArrowColumn::primitive("width", transcoded().map(|r| r.map(|r| r.width))),
+        ArrowColumn::primitive("height", transcoded().map(|r| r.map(|r| r.height))),
+        ArrowColumn::utf8("format", transcoded().map(|r| r.map(|r| r.format.name()))),
+    ];
+    record_batch_output(py, columns, &results, return_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::test_images::{encode, framed};
+    use image::{ImageFormat, RgbaImage};
+
+    #[test]
+    fn webp_sources_are_not_transcoded_to_lossless_webp() {
+        let options = TranscodeOptions {
+            format: None,
+            quality: DEFAULT_JPEG_QUALITY,
+            subsampling: ChromaSubsampling::Yuv420,
+            max_side: None,
+            filter: FilterType::Lanczos3,
+            strip_metadata: true,
+            background: DEFAULT_BACKGROUND,
+        };
+        let transcode = |image: DynamicImage, source: ImageFormat| {
+            let bytes = encode(image, source);
+            image_transcode_core(&bytes, &DecodeLimits::default(), options)
+                .unwrap()
+                .format
+        };
+        let opaque = framed(16, 16, (2, 2, 2, 2));
+        assert_eq!(
+            transcode(opaque.clone(), ImageFormat::WebP),
+            OutputFormat::Jpeg
+        );
+        assert_eq!(
+            transcode(opaque.clone(), ImageFormat::Jpeg),
+            OutputFormat::Jpeg
+        );
+        assert_eq!(transcode(opaque, ImageFormat::Gif), OutputFormat::Png);
+        let transparent = DynamicImage::ImageRgba8(RgbaImage::new(16, 16));
+        assert_eq!(transcode(transparent, ImageFormat::WebP), OutputFormat::Png);
+    }
+}
@@ -0,0 +1,1548 @@
+//! JPEG decoding at 1/2, 1/4 or 1/8 scale with a scaled IDCT
+//!
+//! The `zune-jpeg` decoder `image` uses only produces full-resolution pixels. This
+//! decoder reads baseline and progressive Huffman-coded JPEGs (8-bit gray, YCbCr or
+//! RGB) straight to a reduced size in the DCT domain, as libjpeg's `scale_denom` does:
+//! each 8x8 block is inverse transformed from its low-frequency coefficients to 4x4,
+//! 2x2 or 1x1 samples (subsampled chroma blocks to correspondingly more), so no
+//! full-resolution plane is allocated and no resize is needed afterwards.
+//!
+//! Other JPEGs (CMYK/YCCK, arithmetic-coded, 12-bit, lossless or hierarchical) are
+//! rejected by `JpegFrame::read` and left to the full-resolution decoder. Corrupt
+//! entropy-coded data is decoded leniently (missing bits read as zeros).
+
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+
+use crate::jpeg_encode::ZIGZAG;
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+// Markers
+const SOF0: u8 = 0xC0;
+const SOF1: u8 = 0xC1;
+const SOF2: u8 = 0xC2;
+const DHT: u8 = 0xC4;
+const RST0: u8 = 0xD0;
+const RST7: u8 = 0xD7;
+const SOI: u8 = 0xD8;
+const EOI: u8 = 0xD9;
+const SOS: u8 = 0xDA;
+const DQT: u8 = 0xDB;
+const DRI: u8 = 0xDD;
+const APP0: u8 = 0xE0;
+const APP14: u8 = 0xEE;
+const TEM: u8 = 0x01;
+
+/// Code length resolved by a single lookup in a Huffman table; longer codes are
+/// searched length by length
+const LOOKUP_BITS: u32 = 9;
+
+/// Scale factors the decoder supports
+pub(crate) const SCALE_FACTORS: [usize; 3] = [2, 4, 8];
+
+/// Offset after the next marker at or after `pos`, skipping fill bytes and any
+/// garbage before it, and the marker itself
+fn next_marker(data: &[u8], pos: usize) -> Option<(u8, usize)> {
+    let mut pos = pos;
+    loop {
+        let at = pos + data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+        let mut marker_at = at + 1;
+        while data.get(marker_at) == Some(&0xFF) {
+            marker_at += 1;
+        }
+        match *data.get(marker_at)? {
+            // Stuffed zero inside entropy-coded data
+            0 => pos = marker_at + 1,
+            marker => return Some((marker, marker_at + 1)),
+        }
+    }
+}
+
+/// Payload of the marker segment whose length field is at `at`
+fn segment(data: &[u8], at: usize) -> Option<&[u8]> {
+    let length = usize::from(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]));
+    data.get(at + 2..at.checked_add(length)?)
+        .filter(|_| length >= 2)
+}
+
+fn corrupt(message: &str) -> ItemError {
+    ItemError::new(ItemStatus::DecodeError, format!("corrupt JPEG: {message}"))
+}
+
+// ============================================================================
+// Frame Header
+// ============================================================================
+
+/// How the components of a frame map to output pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorModel {
+    Gray,
+    YCbCr,
+    Rgb,
+}
+
+/// One component of a frame
+struct FrameComponent {
+    id: u8,
+    /// Sampling factors (horizontal, vertical)
+    sampling: (usize, usize),
+    qtable: usize,
+    /// Blocks per row and column, padded to whole MCUs
+    blocks: (usize, usize),
+    /// Blocks per row and column that cover the image; the MCUs of scans that
+    /// contain only this component
+    coded_blocks: (usize, usize),
+}
+
+/// Frame header of a JPEG that `decode_jpeg_scaled` supports
+pub(crate) struct JpegFrame {
+    width: usize,
+    height: usize,
+    progressive: bool,
+    color: ColorModel,
+    components: Vec<FrameComponent>,
+    /// Largest sampling factors (horizontal, vertical)
+    max_sampling: (usize, usize),
+    /// MCUs per row and column of scans with several components
+    mcus: (usize, usize),
+}
+
+impl JpegFrame {
+    /// Read the frame header; `None` if the scaled decoder does not support the JPEG
+    pub(crate) fn read(data: &[u8]) -> Option<Self> {
+        if !data.starts_with(&[0xFF, SOI]) {
+            return None;
+        }
+        let mut pos = 2;
+        let mut jfif = false;
+        let mut adobe_transform = None;
+        loop {
+            let (marker, at) = next_marker(data, pos)?;
+            if matches!(marker, RST0..=RST7 | TEM) {
+                pos = at;
+                continue;
+            }
+            let payload = segment(data, at)?;
+            pos = at + 2 + payload.len();
+            match marker {
+                APP0 if payload.starts_with(b"JFIF\0") => jfif = true,
+                APP14 if payload.starts_with(b"Adobe") => {
+                    adobe_transform = payload.get(11).copied()
+                }
+                SOF0 | SOF1 | SOF2 => {
+                    return Self::parse(payload, marker == SOF2, jfif, adobe_transform)
+                }
+                // Lossless, hierarchical and arithmetic-coded frames, or no frame at all
+                0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | SOS | EOI => return None,
+                _ => {}
+            }
+        }
+    }
+
+    fn parse(
+        payload: &[u8],
+        progressive: bool,
+        jfif: bool,
+        adobe_transform: Option<u8>,
+    ) -> Option<Self> {
+        let (&[precision, h0, h1, w0, w1, count], rest) = payload.split_first_chunk::<6>()?;
+        let height = usize::from(u16::from_be_bytes([h0, h1]));
+        let width = usize::from(u16::from_be_bytes([w0, w1]));
+        let count = usize::from(count);
+        if precision != 8 || width == 0 || height == 0 || !matches!(count, 1 | 3) {
+            return None;
+        }
+
+        let mut specs = Vec::with_capacity(count);
+        for spec in rest.get(..3 * count)?.chunks_exact(3) {
+            let sampling = (usize::from(spec[1] >> 4), usize::from(spec[1] & 15));
+            let qtable = usize::from(spec[2]);
+            if !(1..=4).contains(&sampling.0) || !(1..=4).contains(&sampling.1) || qtable > 3 {
+                return None;
+            }
+            specs.push((spec[0], sampling, qtable));
+        }
+        // A single component is never interleaved, so its sampling factors do not matter
+        if count == 1 {
+            specs[0].1 = (1, 1);
+        }
+        let max_h = specs.iter().map(|s| s.1 .0).max()?;
+        let max_v = specs.iter().map(|s| s.1 .1).max()?;
+        if specs
+            .iter()
+            .any(|&(_, (h, v), _)| max_h % h != 0 || max_v % v != 0)
+        {
+            return None;
+        }
+
+        let mcus = (width.div_ceil(8 * max_h), height.div_ceil(8 * max_v));
+        let components = specs
+            .into_iter()
+            .map(|(id, (h, v), qtable)| FrameComponent {
+                id,
+                sampling: (h, v),
+                qtable,
+                blocks: (mcus.0 * h, mcus.1 * v),
+                coded_blocks: (
+                    (width * h).div_ceil(max_h).div_ceil(8),
+                    (height * v).div_ceil(max_v).div_ceil(8),
+                ),
+            })
+            .collect::<Vec<_>>();
+        let ids: Vec<u8> = components.iter().map(|c| c.id).collect();
+        let color = match (count, adobe_transform) {
+            (1, _) => ColorModel::Gray,
+            (_, Some(0)) => ColorModel::Rgb,
+            (_, None) if !jfif && ids == b"RGB" => ColorModel::Rgb,
+            _ => ColorModel::YCbCr,
+        };
+        Some(Self {
+            width,
+            height,
+            progressive,
+            color,
+            components,
+            max_sampling: (max_h, max_v),
+            mcus,
+        })
+    }
+
+    /// Output size at 1/`factor` scale (rounded up, as libjpeg does)
+    pub(crate) fn scaled_size(&self, factor: usize) -> (u32, u32) {
+        (
+            self.width.div_ceil(factor) as u32,
+            self.height.div_ceil(factor) as u32,
+        )
+    }
+
+    /// Size of the sample planes at 1/`factor` scale (padded to whole MCUs)
+    fn plane_size(&self, factor: usize) -> (usize, usize) {
+        let samples = 8 / factor;
+        (
+            self.mcus.0 * self.max_sampling.0 * samples,
+            self.mcus.1 * self.max_sampling.1 * samples,
+        )
+    }
+
+    /// Bytes allocated to decode at 1/`factor` scale: the sample planes, the output
+    /// pixels and, for progressive JPEGs, the coefficients of every block
+    pub(crate) fn decode_bytes(&self, factor: usize) -> u64 {
+        let (plane_width, plane_height) = self.plane_size(factor);
+        let planes = (plane_width * plane_height * self.components.len()) as u64;
+        let (width, height) = self.scaled_size(factor);
+        let output = u64::from(width) * u64::from(height) * self.components.len() as u64;
+        let coefficients = if self.progressive {
+            let blocks: usize = self
+                .components
+                .iter()
+                .map(|c| c.blocks.0 * c.blocks.1)
+                .sum();
+            blocks as u64 * 128
+        } else {
+            0
+        };
+        planes + output + coefficients
+    }
+}
+
+// ============================================================================
+// Entropy Decoding
+// ============================================================================
+
+/// Reader of entropy-coded data; stuffed zero bytes are removed, and once a marker
+/// or the end of the data is reached it reads zeros
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+    at_marker: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self {
+            data,
+            pos,
+            bits: 0,
+            count: 0,
+            at_marker: false,
+        }
+    }
+
+    /// Top up the buffer to at least 57 bits
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let mut byte = 0;
+            if !self.at_marker {
+                match self.data.get(self.pos) {
+                    Some(&0xFF) if self.data.get(self.pos + 1) == Some(&0) => {
+                        byte = 0xFF;
+                        self.pos += 2;
+                    }
+                    Some(&0xFF) | None => self.at_marker = true,
+                    Some(&b) => {
+                        byte = b;
+                        self.pos += 1;
+                    }
+                }
+            }
+            self.bits |= u64::from(byte) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    /// Next `n` (at most 32) bits without consuming them
+    fn peek(&mut self, n: u32) -> u32 {
+        self.fill();
+        (self.bits >> (64 - n)) as u32
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.bits <<= n;
+        self.count -= n;
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let value = self.peek(n);
+        self.consume(n);
+        value
+    }
+
+    fn bit(&mut self) -> bool {
+        self.bits(1) == 1
+    }
+
+    /// Read an `s`-bit magnitude and extend its sign (T.81 F.2.2.1)
+    fn receive_extend(&mut self, s: u32) -> i32 {
+        if s == 0 {
+            return 0;
+        }
+        let value = self.bits(s) as i32;
+        if value < 1 << (s - 1) {
+            value - (1 << s) + 1
+        } else {
+            value
+        }
+    }
+
+    /// Discard buffered bits and skip the restart marker that ends an interval
+    fn restart(&mut self) {
+        self.bits = 0;
+        self.count = 0;
+        if let Some((marker, after)) = next_marker(self.data, self.pos) {
+            self.pos = after - 2;
+            if (RST0..=RST7).contains(&marker) {
+                self.pos = after;
+                self.at_marker = false;
+                return;
+            }
+        }
+        self.at_marker = true;
+    }
+}
+
+/// Huffman decoding table (T.81 F.2.2.3) with a lookup for short codes
+struct HuffmanTable {
+    /// (symbol, code length) of each `LOOKUP_BITS`-bit prefix; length 0 if longer
+    lookup: Vec<(u8, u8)>,
+    /// Largest code of each length (-1 if none)
+    max_code: [i32; 17],
+    /// Index into `values` of each length's first code, minus that code
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    /// Build from the code counts per length (1-16 bits) and the symbols; `None` if
+    /// the counts do not describe a valid prefix code
+    fn new(counts: &[u8; 16], values: &[u8]) -> Option<Self> {
+        let mut lookup = vec![(0, 0); 1 << LOOKUP_BITS];
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut code = 0i32;
+        let mut index = 0usize;
+        for length in 1..=16 {
+            let count = usize::from(counts[length - 1]);
+            offset[length] = index as i32 - code;
+            for _ in 0..count {
+                let symbol = *values.get(index)?;
+                if length as u32 <= LOOKUP_BITS {
+                    let shift = LOOKUP_BITS - length as u32;
+                    let first = (code as usize) << shift;
+                    lookup[first..first + (1 << shift)].fill((symbol, length as u8));
+                }
+                code += 1;
+                index += 1;
+            }
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+            if code > 1 << length {
+                return None;
+            }
+            code <<= 1;
+        }
+        Some(Self {
+            lookup,
+            max_code,
+            offset,
+            values: values[..index].to_vec(),
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> ItemResult<u8> {
+        let (symbol, length) = self.lookup[reader.peek(LOOKUP_BITS) as usize];
+        if length > 0 {
+            reader.consume(u32::from(length));
+            return Ok(symbol);
+        }
+        for length in LOOKUP_BITS as usize + 1..=16 {
+            let code = reader.peek(length as u32) as i32;
+            if code <= self.max_code[length] {
+                reader.consume(length as u32);
+                let index = (self.offset[length] + code) as usize;
+                return self
+                    .values
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| corrupt("bad Huffman code"));
+            }
+        }
+        Err(corrupt("bad Huffman code"))
+    }
+}
+
+/// Per-scan decoding state, reset at every restart marker
+#[derive(Default)]
+struct ScanState {
+    /// DC predictor of each component
+    predictors: [i32; 3],
+    /// Remaining blocks of an end-of-band run (progressive AC scans)
+    eobrun: u32,
+}
+
+/// Decode one block of a sequential scan (T.81 F.2.2)
+fn decode_sequential(
+    reader: &mut BitReader,
+    dc: &HuffmanTable,
+    ac: &HuffmanTable,
+    predictor: &mut i32,
+    block: &mut [i16; 64],
+) -> ItemResult<()> {
+    let s = u32::from(dc.decode(reader)?);
+    if s > 16 {
+        return Err(corrupt("bad DC magnitude"));
+    }
+    *predictor = predictor.wrapping_add(reader.receive_extend(s));
+    block[0] = *predictor as i16;
+    let mut k = 1;
+    while k < 64 {
+        let rs = ac.decode(reader)?;
+        let (run, s) = (usize::from(rs >> 4), u32::from(rs & 15));
+        if s == 0 {
+            if run != 15 {
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += run;
+        if k > 63 {
+            break;
+        }
+        block[ZIGZAG[k]] = reader.receive_extend(s) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// First DC scan of a progressive JPEG (T.81 G.1.2.1)
+fn decode_dc_first(
+    reader: &mut BitReader,
+    dc: &HuffmanTable,
+    predictor: &mut i32,
+    block: &mut [i16; 64],
+    al: u32,
+) -> ItemResult<()> {
+    let s = u32::from(dc.decode(reader)?);
+    if s > 16 {
+        return Err(corrupt("bad DC magnitude"));
+    }
+    *predictor = predictor.wrapping_add(reader.receive_extend(s));
+    block[0] = predictor.wrapping_shl(al) as i16;
+    Ok(())
+}
+
+/// First AC scan of a band of a progressive JPEG (T.81 G.1.2.2)
+fn decode_ac_first(
+    reader: &mut BitReader,
+    ac: &HuffmanTable,
+    state: &mut ScanState,
+    block: &mut [i16; 64],
+    (ss, se): (usize, usize),
+    al: u32,
+) -> ItemResult<()> {
+    if state.eobrun > 0 {
+        state.eobrun -= 1;
+        return Ok(());
+    }
+    let mut k = ss;
+    while k <= se {
+        let rs = ac.decode(reader)?;
+        let (run, s) = (u32::from(rs >> 4), u32::from(rs & 15));
+        if s == 0 {
+            if run != 15 {
+                state.eobrun = (1 << run) + reader.bits(run) - 1;
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += run as usize;
+        if k > se {
+            break;
+        }
+        block[ZIGZAG[k]] = reader.receive_extend(s).wrapping_shl(al) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// Refining AC scan of a band of a progressive JPEG (T.81 G.1.2.3), as libjpeg's
+/// `decode_mcu_AC_refine`
+fn decode_ac_refine(
+    reader: &mut BitReader,
+    ac: &HuffmanTable,
+    state: &mut ScanState,
+    block: &mut [i16; 64],
+    (ss, se): (usize, usize),
+    al: u32,
+) -> ItemResult<()> {
+    let p1 = 1i16 << al;
+    let m1 = -1i16 << al;
+    // Correction bit of a coefficient that is already non-zero
+    let refine = |reader: &mut BitReader, coefficient: &mut i16| {
+        if reader.bit() && *coefficient & p1 == 0 {
+            *coefficient = coefficient.wrapping_add(if *coefficient >= 0 { p1 } else { m1 });
+        }
+    };
+
+    let mut k = ss;
+    if state.eobrun == 0 {
+        while k <= se {
+            let rs = ac.decode(reader)?;
+            let (mut run, s) = (u32::from(rs >> 4), rs & 15);
+            let mut value = 0;
+            if s != 0 {
+                value = if reader.bit() { p1 } else { m1 };
+            } else if run != 15 {
+                state.eobrun = (1 << run) + reader.bits(run);
+                break;
+            }
+            // Skip `run` zero coefficients, refining the non-zero ones passed on the way
+            while k <= se {
+                let coefficient = &mut block[ZIGZAG[k]];
+                if *coefficient != 0 {
+                    refine(reader, coefficient);
+                } else if run == 0 {
+                    break;
+                } else {
+                    run -= 1;
+                }
+                k += 1;
+            }
+            if value != 0 && k <= se {
+                block[ZIGZAG[k]] = value;
+            }
+            k += 1;
+        }
+    }
+    if state.eobrun > 0 {
+        for &index in ZIGZAG.get(k..=se).unwrap_or_default() {
+            if block[index] != 0 {
+                refine(reader, &mut block[index]);
+            }
+        }
+        state.eobrun -= 1;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Scaled Inverse DCT
+// ============================================================================
+
+/// 1-D inverse DCT of an 8-point block evaluated at the centers of `size` equal cells
+///
+/// `basis[n][u] = c(u) / 2 * cos((2n + 1) u pi / (2 size))`; frequencies the output
+/// cannot represent (`u >= size`) are dropped, so 1 sample is the block average.
+struct IdctBasis {
+    size: usize,
+    /// Coefficients used per axis
+    terms: usize,
+    basis: Vec<[f32; 8]>,
+}
+
+impl IdctBasis {
+    fn new(size: usize) -> Self {
+        let terms = size.min(8);
+        let basis = (0..size)
+            .map(|n| {
+                let mut row = [0.0f32; 8];
+                for (u, value) in row.iter_mut().enumerate().take(terms) {
+                    let c = if u == 0 {
+                        std::f32::consts::FRAC_1_SQRT_2
+                    } else {
+                        1.0
+                    };
+                    let angle = ((2 * n + 1) * u) as f32 * std::f32::consts::PI / (2 * size) as f32;
+                    *value = c / 2.0 * angle.cos();
+                }
+                row
+            })
+            .collect();
+        Self { size, terms, basis }
+    }
+}
+
+/// Dequantize a block and write its scaled inverse DCT into `plane` at (`x0`, `y0`)
+fn idct_block(
+    block: &[i16; 64],
+    qtable: &[u16; 64],
+    (columns, rows): (&IdctBasis, &IdctBasis),
+    plane: &mut [u8],
+    plane_width: usize,
+    (x0, y0): (usize, usize),
+) {
+    // Rows first: partial[v][x] for the vertical frequencies in use
+    let mut partial = [[0.0f32; 16]; 8];
+    for (v, line) in partial.iter_mut().enumerate().take(rows.terms) {
+        let mut coefficients = [0.0f32; 8];
+        for (u, value) in coefficients.iter_mut().enumerate().take(columns.terms) {
+            *value = f32::from(block[v * 8 + u]) * f32::from(qtable[v * 8 + u]);
+        }
+        for (x, out) in line.iter_mut().enumerate().take(columns.size) {
+            *out = (0..columns.terms)
+                .map(|u| columns.basis[x][u] * coefficients[u])
+                .sum();
+        }
+    }
+    for (y, basis) in rows.basis.iter().enumerate() {
+        let start = (y0 + y) * plane_width + x0;
+        for (x, sample) in plane[start..start + columns.size].iter_mut().enumerate() {
+            let value: f32 = (0..rows.terms).map(|v| basis[v] * partial[v][x]).sum();
+            *sample = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// ============================================================================
+// Decoder
+// ============================================================================
+
+/// Scan header (T.81 B.2.3)
+struct Scan {
+    /// (component index, DC table, AC table)
+    components: Vec<(usize, usize, usize)>,
+    /// Spectral selection (first and last zig-zag index)
+    spectral: (usize, usize),
+    /// Successive approximation: previous and current bit position
+    ah: u8,
+    al: u8,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    frame: &'a JpegFrame,
+    /// Quantization tables in natural order
+    qtables: [Option<[u16; 64]>; 4],
+    dc_tables: [Option<HuffmanTable>; 4],
+    ac_tables: [Option<HuffmanTable>; 4],
+    restart_interval: usize,
+    /// Inverse DCT (horizontal, vertical) of each component
+    idct: Vec<(IdctBasis, IdctBasis)>,
+    /// Samples of each component at the output scale, padded to whole MCUs
+    planes: Vec<Vec<u8>>,
+    plane_width: usize,
+    /// Coefficients of every block, kept across the scans of progressive JPEGs
+    coefficients: Option<Vec<Vec<[i16; 64]>>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8], frame: &'a JpegFrame, factor: usize) -> Self {
+        let samples = 8 / factor;
+        let (max_h, max_v) = frame.max_sampling;
+        let idct = frame
+            .components
+            .iter()
+            .map(|c| {
+                (
+                    IdctBasis::new(samples * max_h / c.sampling.0),
+                    IdctBasis::new(samples * max_v / c.sampling.1),
+                )
+            })
+            .collect();
+        let (plane_width, plane_height) = frame.plane_size(factor);
+        let coefficients = frame.progressive.then(|| {
+            frame
+                .components
+                .iter()
+                .map(|c| vec![[0i16; 64]; c.blocks.0 * c.blocks.1])
+                .collect()
+        });
+        Self {
+            data,
+            frame,
+            qtables: [None; 4],
+            dc_tables: [None, None, None, None],
+            ac_tables: [None, None, None, None],
+            restart_interval: 0,
+            idct,
+            planes: vec![vec![0; plane_width * plane_height]; frame.components.len()],
+            plane_width,
+            coefficients,
+        }
+    }
+
+    /// Decode every scan up to EOI (or the end of the data)
+    fn run(&mut self) -> ItemResult<()> {
+        let mut pos = 2;
+        let mut scans = 0;
+        let mut frames = 0;
+        while let Some((marker, at)) = next_marker(self.data, pos) {
+            match marker {
+                EOI => break,
+                RST0..=RST7 | TEM => {
+                    pos = at;
+                    continue;
+                }
+                _ => {}
+            }
+            let Some(payload) = segment(self.data, at) else {
+                break;
+            };
+            pos = at + 2 + payload.len();
+            match marker {
+                DQT => self.read_qtables(payload)?,
+                DHT => self.read_huffman_tables(payload)?,
+                DRI => {
+                    let interval = payload.get(..2).ok_or_else(|| corrupt("short DRI"))?;
+                    self.restart_interval =
+                        usize::from(u16::from_be_bytes([interval[0], interval[1]]));
+                }
+                SOF0 | SOF1 | SOF2 => {
+                    frames += 1;
+                    if frames > 1 {
+                        break;
+                    }
+                }
+                SOS => {
+                    let scan = self.read_scan(payload)?;
+                    pos = self.decode_scan(&scan, pos)?;
+                    scans += 1;
+                }
+                _ => {}
+            }
+        }
+        if scans == 0 {
+            return Err(corrupt("no scan"));
+        }
+        if let Some(coefficients) = self.coefficients.take() {
+            for (index, blocks) in coefficients.iter().enumerate() {
+                let component = &self.frame.components[index];
+                for (i, block) in blocks.iter().enumerate() {
+                    let position = (i % component.blocks.0, i / component.blocks.0);
+                    self.transform(index, block, position)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_qtables(&mut self, mut payload: &[u8]) -> ItemResult<()> {
+        while let Some((&spec, rest)) = payload.split_first() {
+            let (precision, id) = (spec >> 4, usize::from(spec & 15));
+            let size = if precision == 0 { 64 } else { 128 };
+            let values = rest.get(..size).filter(|_| id < 4 && precision < 2);
+            let values = values.ok_or_else(|| corrupt("bad DQT"))?;
+            let mut table = [0u16; 64];
+            for (k, &index) in ZIGZAG.iter().enumerate() {
+                table[index] = if precision == 0 {
+                    u16::from(values[k])
+                } else {
+                    u16::from_be_bytes([values[2 * k], values[2 * k + 1]])
+                };
+            }
+            self.qtables[id] = Some(table);
+            payload = &rest[size..];
+        }
+        Ok(())
+    }
+
+    fn read_huffman_tables(&mut self, mut payload: &[u8]) -> ItemResult<()> {
+        while let Some((&spec, rest)) = payload.split_first() {
+            let (class, id) = (spec >> 4, usize::from(spec & 15));
+            let counts: &[u8; 16] = rest
+                .first_chunk()
+                .filter(|_| class < 2 && id < 4)
+                .ok_or_else(|| corrupt("bad DHT"))?;
+            let total: usize = counts.iter().map(|&n| usize::from(n)).sum();
+            let values = rest.get(16..16 + total).ok_or_else(|| corrupt("bad DHT"))?;
+            let table = HuffmanTable::new(counts, values).ok_or_else(|| corrupt("bad DHT"))?;
+            let tables = if class == 0 {
+                &mut self.dc_tables
+            } else {
+                &mut self.ac_tables
+            };
+            tables[id] = Some(table);
+            payload = &rest[16 + total..];
+        }
+        Ok(())
+    }
+
+    fn read_scan(&self, payload: &[u8]) -> ItemResult<Scan> {
+        let count = usize::from(*payload.first().ok_or_else(|| corrupt("bad SOS"))?);
+        let specs = payload
+            .get(1..1 + 2 * count)
+            .ok_or_else(|| corrupt("bad SOS"))?;
+        let &[ss, se, approximation] = payload
+            .get(1 + 2 * count..)
+            .and_then(<[u8]>::first_chunk::<3>)
+            .ok_or_else(|| corrupt("bad SOS"))?;
+        let mut components = Vec::with_capacity(count);
+        for spec in specs.chunks_exact(2) {
+            let index = self
+                .frame
+                .components
+                .iter()
+                .position(|c| c.id == spec[0])
+                .ok_or_else(|| corrupt("unknown scan component"))?;
+            components.push((index, usize::from(spec[1] >> 4), usize::from(spec[1] & 15)));
+        }
+        let scan = Scan {
+            components,
+            spectral: (usize::from(ss), usize::from(se)),
+            ah: approximation >> 4,
+            al: approximation & 15,
+        };
+
+        let (ss, se) = scan.spectral;
+        let valid = if self.frame.progressive {
+            let bands_ok = if ss == 0 {
+                se == 0
+            } else {
+                se >= ss && se < 64 && count == 1
+            };
+            bands_ok && scan.al < 14
+        } else {
+            true
+        };
+        if !valid || count == 0 || count > 3 {
+            return Err(corrupt("bad scan parameters"));
+        }
+        // Tables this scan decodes with must already be defined
+        let progressive = self.frame.progressive;
+        let needs_dc = !progressive || (ss == 0 && scan.ah == 0);
+        let needs_ac = !progressive || ss > 0;
+        for &(index, dc, ac) in &scan.components {
+            let qtable = self.frame.components[index].qtable;
+            let missing = (needs_dc && self.dc_tables.get(dc).is_none_or(Option::is_none))
+                || (needs_ac && self.ac_tables.get(ac).is_none_or(Option::is_none))
+                || (!progressive && self.qtables[qtable].is_none());
+            if missing {
+                return Err(corrupt("scan uses an undefined table"));
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Decode the entropy-coded data of a scan starting at `start`, returning the
+    /// offset reached
+    fn decode_scan(&mut self, scan: &Scan, start: usize) -> ItemResult<usize> {
+        let frame = self.frame;
+        let single = scan.components.len() == 1;
+        let (mcus_x, mcus_y) = if single {
+            frame.components[scan.components[0].0].coded_blocks
+        } else {
+            frame.mcus
+        };
+        let mut reader = BitReader::new(self.data, start);
+        let mut state = ScanState::default();
+        for mcu in 0..mcus_x * mcus_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                reader.restart();
+                state = ScanState::default();
+            }
+            let (mcu_x, mcu_y) = (mcu % mcus_x, mcu / mcus_x);
+            for (slot, &(index, dc, ac)) in scan.components.iter().enumerate() {
+                let (h, v) = if single {
+                    (1, 1)
+                } else {
+                    frame.components[index].sampling
+                };
+                for block_y in 0..v {
+                    for block_x in 0..h {
+                        let position = (mcu_x * h + block_x, mcu_y * v + block_y);
+                        let tables = (dc, ac);
+                        self.decode_block(&mut reader, &mut state, scan, slot, tables, position)?;
+                    }
+                }
+            }
+        }
+        Ok(reader.pos)
+    }
+
+    fn decode_block(
+        &mut self,
+        reader: &mut BitReader,
+        state: &mut ScanState,
+        scan: &Scan,
+        slot: usize,
+        (dc, ac): (usize, usize),
+        (x, y): (usize, usize),
+    ) -> ItemResult<()> {
+        let index = scan.components[slot].0;
+        let dc_table = self.dc_tables.get(dc).and_then(Option::as_ref);
+        let ac_table = self.ac_tables.get(ac).and_then(Option::as_ref);
+        let Some(coefficients) = self.coefficients.as_mut() else {
+            let mut block = [0i16; 64];
+            let (Some(dc_table), Some(ac_table)) = (dc_table, ac_table) else {
+                return Err(corrupt("scan uses an undefined table"));
+            };
+            decode_sequential(
+                reader,
+                dc_table,
+                ac_table,
+                &mut state.predictors[slot],
+                &mut block,
+            )?;
+            return self.transform(index, &block, (x, y));
+        };
+
+        let blocks_per_row = self.frame.components[index].blocks.0;
+        let block = &mut coefficients[index][y * blocks_per_row + x];
+        let al = u32::from(scan.al);
+        let missing = || corrupt("scan uses an undefined table");
+        match (scan.spectral.0, scan.ah) {
+            (0, 0) => {
+                let dc_table = dc_table.ok_or_else(missing)?;
+                decode_dc_first(reader, dc_table, &mut state.predictors[slot], block, al)
+            }
+            (0, _) => {
+                if reader.bit() {
+                    block[0] |= 1 << al;
+                }
+                Ok(())
+            }
+            (_, 0) => {
+                let ac_table = ac_table.ok_or_else(missing)?;
+                decode_ac_first(reader, ac_table, state, block, scan.spectral, al)
+            }
+            _ => {
+                let ac_table = ac_table.ok_or_else(missing)?;
+                decode_ac_refine(reader, ac_table, state, block, scan.spectral, al)
+            }
+        }
+    }
+
+    /// Inverse transform one block of a component into its plane
+    fn transform(
+        &mut self,
+        index: usize,
+        block: &[i16; 64],
+        (x, y): (usize, usize),
+    ) -> ItemResult<()> {
+        let qtable = self.frame.components[index].qtable;
+        let qtable = self.qtables[qtable]
+            .as_ref()
+            .ok_or_else(|| corrupt("undefined quantization table"))?;
+        let (columns, rows) = &self.idct[index];
+        let origin = (x * columns.size, y * rows.size);
+        idct_block(
+            block,
+            qtable,
+            (columns, rows),
+            &mut self.planes[index],
+            self.plane_width,
+            origin,
+        );
+        Ok(())
+    }
+
+    /// Convert the planes to output pixels of the scaled size
+    fn into_image(self, factor: usize) -> DynamicImage {
+        let (width, height) = self.frame.scaled_size(factor);
+        let stride = self.plane_width;
+        let sample =
+            |plane: usize, x: u32, y: u32| self.planes[plane][y as usize * stride + x as usize];
+        match self.frame.color {
+            ColorModel::Gray => {
+                DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                    Luma([sample(0, x, y)])
+                }))
+            }
+            ColorModel::Rgb => DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+                Rgb([sample(0, x, y), sample(1, x, y), sample(2, x, y)])
+            })),
+            ColorModel::YCbCr => {
+                DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+                    let luma = f32::from(sample(0, x, y));
+                    let cb = f32::from(sample(1, x, y)) - 128.0;
+                    let cr = f32::from(sample(2, x, y)) - 128.0;
+                    Rgb([
+                        luma + 1.402 * cr,
+                        luma - 0.344_136 * cb - 0.714_136 * cr,
+                        luma + 1.772 * cb,
+                    ]
+                    .map(|value| value.round().clamp(0.0, 255.0) as u8))
+                }))
+            }
+        }
+    }
+}
+
+/// Decode a JPEG whose frame header is `frame` at 1/`factor` of its size
+///
+/// `factor` is one of `SCALE_FACTORS`. Gray JPEGs decode to `Luma8`, others to `Rgb8`.
+pub(crate) fn decode_jpeg_scaled(
+    data: &[u8],
+    frame: &JpegFrame,
+    factor: usize,
+) -> ItemResult<DynamicImage> {
+    if !SCALE_FACTORS.contains(&factor) {
+        return Err(ItemError::new(
+            ItemStatus::DecodeError,
+            format!("unsupported JPEG scale 1/{factor}"),
+        ));
+    }
+    let mut decoder = Decoder::new(data, frame, factor);
+    decoder.run()?;
+    Ok(decoder.into_image(factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg_encode::{encode_jpeg, ChromaSubsampling};
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{GenericImageView, ImageFormat};
+
+    /// Entropy-coded data writer for the test JPEGs; every Huffman code is the symbol
+    /// itself, 4 bits long for DC (`DC_COUNTS`) and 8 for AC (`AC_COUNTS`)
+    #[derive(Default)]
+    struct ScanWriter {
+        bytes: Vec<u8>,
+        accumulator: u32,
+        count: u32,
+        eobrun: u32,
+        /// Correction bits of the blocks in the pending EOB run
+        corrections: Vec<u32>,
+    }
+
+    const DC_COUNTS: [u8; 16] = [0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    const AC_COUNTS: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    impl ScanWriter {
+        fn put(&mut self, value: u32, bits: u32) {
+            for i in (0..bits).rev() {
+                self.accumulator = self.accumulator << 1 | (value >> i & 1);
+                self.count += 1;
+                if self.count == 8 {
+                    let byte = self.accumulator as u8;
+                    self.bytes.push(byte);
+                    if byte == 0xFF {
+                        self.bytes.push(0);
+                    }
+                    (self.accumulator, self.count) = (0, 0);
+                }
+            }
+        }
+
+        fn symbol(&mut self, symbol: u32) {
+            self.put(symbol, 8);
+        }
+
+        /// Size category of `value` and its magnitude bits
+        fn magnitude(value: i32) -> (u32, u32) {
+            let size = 32 - value.unsigned_abs().leading_zeros();
+            let bits = if value < 0 { value - 1 } else { value };
+            (size, bits as u32 & ((1 << size) - 1))
+        }
+
+        fn dc(&mut self, difference: i32) {
+            let (size, bits) = Self::magnitude(difference);
+            self.put(size, 4);
+            self.put(bits, size);
+        }
+
+        fn ac(&mut self, run: u32, value: i32) {
+            let (size, bits) = Self::magnitude(value);
+            self.symbol(run << 4 | size);
+            self.put(bits, size);
+        }
+
+        fn flush_eobrun(&mut self) {
+            if self.eobrun > 0 {
+                let size = 31 - self.eobrun.leading_zeros();
+                self.symbol(size << 4);
+                self.put(self.eobrun, size);
+                self.eobrun = 0;
+            }
+            for bit in std::mem::take(&mut self.corrections) {
+                self.put(bit, 1);
+            }
+        }
+
+        fn end_eob(&mut self) {
+            self.eobrun += 1;
+            if self.eobrun == 0x7FFF {
+                self.flush_eobrun();
+            }
+        }
+
+        fn finish(&mut self) {
+            self.flush_eobrun();
+            while self.count != 0 {
+                self.put(1, 1);
+            }
+        }
+
+        fn sequential(&mut self, block: &[i16; 64], predictor: &mut i32) {
+            let dc = i32::from(block[0]);
+            self.dc(dc - *predictor);
+            *predictor = dc;
+            let mut run = 0;
+            for &index in &ZIGZAG[1..] {
+                let value = i32::from(block[index]);
+                if value == 0 {
+                    run += 1;
+                    continue;
+                }
+                while run > 15 {
+                    self.symbol(0xF0);
+                    run -= 16;
+                }
+                self.ac(run, value);
+                run = 0;
+            }
+            if run > 0 {
+                self.symbol(0);
+            }
+        }
+
+        fn dc_first(&mut self, block: &[i16; 64], predictor: &mut i32, al: u32) {
+            let dc = i32::from(block[0]) >> al;
+            self.dc(dc - *predictor);
+            *predictor = dc;
+        }
+
+        fn ac_first(&mut self, block: &[i16; 64], (ss, se): (usize, usize), al: u32) {
+            let mut run = 0;
+            for &index in &ZIGZAG[ss..=se] {
+                let value = i32::from(block[index]);
+                let value = if value < 0 {
+                    -(-value >> al)
+                } else {
+                    value >> al
+                };
+                if value == 0 {
+                    run += 1;
+                    continue;
+                }
+                self.flush_eobrun();
+                while run > 15 {
+                    self.symbol(0xF0);
+                    run -= 16;
+                }
+                self.ac(run, value);
+                run = 0;
+            }
+            if run > 0 {
+                self.end_eob();
+            }
+        }
+
+        /// libjpeg's `encode_mcu_AC_refine`
+        fn ac_refine(&mut self, block: &[i16; 64], (ss, se): (usize, usize), al: u32) {
+            let absolute: Vec<u32> = ZIGZAG[ss..=se]
+                .iter()
+                .map(|&index| block[index].unsigned_abs() as u32 >> al)
+                .collect();
+            let last_new = absolute.iter().rposition(|&value| value == 1);
+            let mut run = 0;
+            let mut pending = Vec::new();
+            for (k, &value) in absolute.iter().enumerate() {
+                if value == 0 {
+                    run += 1;
+                    continue;
+                }
+                while run > 15 && last_new.is_some_and(|last| k <= last) {
+                    self.flush_eobrun();
+                    self.symbol(0xF0);
+                    run -= 16;
+                    for bit in pending.drain(..) {
+                        self.put(bit, 1);
+                    }
+                }
+                if value > 1 {
+                    pending.push(value & 1);
+                    continue;
+                }
+                self.flush_eobrun();
+                self.symbol(run << 4 | 1);
+                self.put(u32::from(block[ZIGZAG[ss + k]] > 0), 1);
+                for bit in pending.drain(..) {
+                    self.put(bit, 1);
+                }
+                run = 0;
+            }
+            if run > 0 || !pending.is_empty() {
+                self.corrections.append(&mut pending);
+                self.end_eob();
+            }
+        }
+    }
+
+    /// Components, spectral selection, Ah and Al of a scan
+    type ScanSpec = (Vec<usize>, (usize, usize), u32, u32);
+
+    /// Quantized coefficients of a test JPEG, in natural order
+    struct Coefficients {
+        width: usize,
+        height: usize,
+        sampling: Vec<(usize, usize)>,
+        blocks: Vec<Vec<[i16; 64]>>,
+    }
+
+    impl Coefficients {
+        /// Pseudo-random coefficients with decaying AC, long zero runs and empty blocks
+        fn new(width: usize, height: usize, sampling: &[(usize, usize)]) -> Self {
+            let frame = Self::frame(width, height, sampling);
+            let mut seed = 0x2545_F491_u32;
+            let mut random = |range: i32| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 8) as i32 % (2 * range + 1) - range
+            };
+            let blocks = frame
+                .components
+                .iter()
+                .map(|c| {
+                    let (columns, rows) = c.blocks;
+                    (0..columns * rows)
+                        .map(|i| {
+                            let mut block = [0i16; 64];
+                            block[0] = random(150) as i16;
+                            let coded =
+                                i % columns < c.coded_blocks.0 && i / columns < c.coded_blocks.1;
+                            if !coded || i % 3 == 2 {
+                                return block;
+                            }
+                            for k in (1..20).chain([37, 63]) {
+                                if random(1) != 0 {
+                                    block[ZIGZAG[k]] = random(240 / (k as i32 + 2) + 1) as i16;
+                                }
+                            }
+                            block
+                        })
+                        .collect()
+                })
+                .collect();
+            Self {
+                width,
+                height,
+                sampling: sampling.to_vec(),
+                blocks,
+            }
+        }
+
+        fn sof(width: usize, height: usize, sampling: &[(usize, usize)]) -> Vec<u8> {
+            let mut sof = vec![8];
+            sof.extend_from_slice(&(height as u16).to_be_bytes());
+            sof.extend_from_slice(&(width as u16).to_be_bytes());
+            sof.push(sampling.len() as u8);
+            for (id, &(h, v)) in (1u8..).zip(sampling) {
+                sof.extend_from_slice(&[id, (h << 4 | v) as u8, 0]);
+            }
+            sof
+        }
+
+        fn frame(width: usize, height: usize, sampling: &[(usize, usize)]) -> JpegFrame {
+            JpegFrame::parse(&Self::sof(width, height, sampling), false, false, None).unwrap()
+        }
+
+        /// Encode as a JPEG with a restart every `restart_interval` MCUs (0 for none);
+        /// progressive JPEGs use libjpeg's default scan script
+        fn encode(&self, progressive: bool, restart_interval: usize) -> Vec<u8> {
+            let frame = Self::frame(self.width, self.height, &self.sampling);
+            let mut bytes = vec![0xFF, SOI];
+            let segment = |bytes: &mut Vec<u8>, marker: u8, payload: &[u8]| {
+                bytes.extend_from_slice(&[0xFF, marker]);
+                bytes.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+                bytes.extend_from_slice(payload);
+            };
+            segment(&mut bytes, DQT, &[[0].as_slice(), &[2; 64]].concat());
+            let sof = Self::sof(self.width, self.height, &self.sampling);
+            segment(&mut bytes, if progressive { SOF2 } else { SOF0 }, &sof);
+            let symbols: Vec<u8> = (0..255).collect();
+            segment(
+                &mut bytes,
+                DHT,
+                &[&[0x00], &DC_COUNTS[..], &symbols[..12]].concat(),
+            );
+            segment(
+                &mut bytes,
+                DHT,
+                &[&[0x10], &AC_COUNTS[..], &symbols].concat(),
+            );
+            if restart_interval > 0 {
+                segment(&mut bytes, DRI, &(restart_interval as u16).to_be_bytes());
+            }
+
+            let all: Vec<usize> = (0..self.sampling.len()).collect();
+            let script: Vec<ScanSpec> = if !progressive {
+                vec![(all, (0, 63), 0, 0)]
+            } else {
+                let mut script = vec![(all.clone(), (0, 0), 0, 1)];
+                script.push((vec![0], (1, 5), 0, 2));
+                for &chroma in &all[1..] {
+                    script.push((vec![chroma], (1, 63), 0, 1));
+                }
+                script.push((vec![0], (6, 63), 0, 2));
+                script.push((vec![0], (1, 63), 2, 1));
+                script.push((all.clone(), (0, 0), 1, 0));
+                for &component in all[1..].iter().chain([&0]) {
+                    script.push((vec![component], (1, 63), 1, 0));
+                }
+                script
+            };
+            for (components, spectral, ah, al) in script {
+                let mut sos = vec![components.len() as u8];
+                for &c in &components {
+                    sos.extend_from_slice(&[c as u8 + 1, 0]);
+                }
+                sos.extend_from_slice(&[spectral.0 as u8, spectral.1 as u8, (ah << 4 | al) as u8]);
+                segment(&mut bytes, SOS, &sos);
+                self.write_scan(
+                    &mut bytes,
+                    &frame,
+                    progressive,
+                    (components, spectral, ah, al),
+                    restart_interval,
+                );
+            }
+            bytes.extend_from_slice(&[0xFF, EOI]);
+            bytes
+        }
+
+        fn write_scan(
+            &self,
+            bytes: &mut Vec<u8>,
+            frame: &JpegFrame,
+            progressive: bool,
+            (components, spectral, ah, al): ScanSpec,
+            restart_interval: usize,
+        ) {
+            let single = components.len() == 1;
+            let (mcus_x, mcus_y) = if single {
+                frame.components[components[0]].coded_blocks
+            } else {
+                frame.mcus
+            };
+            let mut writer = ScanWriter::default();
+            let mut predictors = [0i32; 3];
+            for mcu in 0..mcus_x * mcus_y {
+                if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+                    writer.finish();
+                    let marker = RST0 + ((mcu / restart_interval - 1) % 8) as u8;
+                    writer.bytes.extend_from_slice(&[0xFF, marker]);
+                    predictors = [0; 3];
+                }
+                for (slot, &index) in components.iter().enumerate() {
+                    let component = &frame.components[index];
+                    let (h, v) = if single { (1, 1) } else { component.sampling };
+                    for y in 0..v {
+                        for x in 0..h {
+                            let column = mcu % mcus_x * h + x;
+                            let row = mcu / mcus_x * v + y;
+                            let block = &self.blocks[index][row * component.blocks.0 + column];
+                            match (progressive, spectral.0, ah) {
+                                (false, ..) => writer.sequential(block, &mut predictors[slot]),
+                                (_, 0, 0) => writer.dc_first(block, &mut predictors[slot], al),
+                                (_, 0, _) => writer.put((block[0] >> al) as u32 & 1, 1),
+                                (_, _, 0) => writer.ac_first(block, spectral, al),
+                                _ => writer.ac_refine(block, spectral, al),
+                            }
+                        }
+                    }
+                }
+            }
+            writer.finish();
+            bytes.extend_from_slice(&writer.bytes);
+        }
+    }
+
+    fn scaled(data: &[u8], factor: usize) -> DynamicImage {
+        let frame = JpegFrame::read(data).unwrap();
+        decode_jpeg_scaled(data, &frame, factor).unwrap()
+    }
+
+    fn box_reduce(full: &RgbImage, factor: u32) -> RgbImage {
+        let (width, height) = (
+            full.width().div_ceil(factor),
+            full.height().div_ceil(factor),
+        );
+        RgbImage::from_fn(width, height, |x, y| {
+            let (mut sum, mut count) = ([0u32; 3], 0);
+            for source_y in y * factor..((y + 1) * factor).min(full.height()) {
+                for source_x in x * factor..((x + 1) * factor).min(full.width()) {
+                    let pixel = full.get_pixel(source_x, source_y);
+                    for (total, &value) in sum.iter_mut().zip(&pixel.0) {
+                        *total += u32::from(value);
+                    }
+                    count += 1;
+                }
+            }
+            Rgb(sum.map(|total| ((total + count / 2) / count) as u8))
+        })
+    }
+
+    fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+        let squared: f64 = a
+            .as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+            .sum();
+        let mse = squared / a.as_raw().len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-9)).log10()
+    }
+
+    fn smooth_image(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as f32, y as f32);
+            Rgb([
+                128.0 + 90.0 * (x / 9.0).sin(),
+                128.0 + 90.0 * (y / 7.0).cos(),
+                (x + y) * 1.5,
+            ]
+            .map(|value| value as u8))
+        })
+    }
+
+    #[test]
+    fn progressive_and_restart_scans_decode_like_baseline() {
+        let layouts: [&[(usize, usize)]; 4] = [
+            &[(1, 1)],
+            &[(1, 1), (1, 1), (1, 1)],
+            &[(2, 1), (1, 1), (1, 1)],
+            &[(2, 2), (1, 1), (1, 1)],
+        ];
+        for sampling in layouts {
+            let coefficients = Coefficients::new(70, 40, sampling);
+            let baseline = coefficients.encode(false, 0);
+            let full = image::load_from_memory(&baseline).unwrap();
+            for (progressive, restart_interval) in [(false, 3), (true, 0), (true, 2)] {
+                let variant = coefficients.encode(progressive, restart_interval);
+                // The test encoder agrees with `image`'s decoder
+                assert_eq!(image::load_from_memory(&variant).unwrap(), full);
+                for factor in SCALE_FACTORS {
+                    assert_eq!(
+                        scaled(&variant, factor),
+                        scaled(&baseline, factor),
+                        "{sampling:?} progressive={progressive} restart={restart_interval} 1/{factor}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_decode_approximates_reduced_full_decode() {
+        for (width, height) in [(70, 40), (33, 65), (17, 9)] {
+            let image = smooth_image(width, height);
+            for subsampling in [
+                ChromaSubsampling::Yuv444,
+                ChromaSubsampling::Yuv422,
+                ChromaSubsampling::Yuv420,
+            ] {
+                let data = encode_jpeg(&image, 95, subsampling, None, None).unwrap();
+                let full = image::load_from_memory(&data).unwrap().to_rgb8();
+                for factor in SCALE_FACTORS {
+                    let pixels = scaled(&data, factor);
+                    assert!(matches!(pixels, DynamicImage::ImageRgb8(_)));
+                    let reference = box_reduce(&full, factor as u32);
+                    assert_eq!(pixels.dimensions(), reference.dimensions());
+                    let quality = psnr(&pixels.to_rgb8(), &reference);
+                    assert!(
+                        quality > 28.0,
+                        "{subsampling:?} {width}x{height} 1/{factor}: {quality}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gray_jpegs_decode_to_luma() {
+        let image = DynamicImage::ImageRgb8(smooth_image(70, 40)).to_luma8();
+        let mut data = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, 95)
+            .encode_image(&image)
+            .unwrap();
+        let full = DynamicImage::ImageLuma8(image::load_from_memory(&data).unwrap().to_luma8());
+        for factor in SCALE_FACTORS {
+            let pixels = scaled(&data, factor);
+            assert!(matches!(pixels, DynamicImage::ImageLuma8(_)));
+            let reference = box_reduce(&full.to_rgb8(), factor as u32);
+            assert!(psnr(&pixels.to_rgb8(), &reference) > 30.0);
+        }
+    }
+
+    #[test]
+    fn unsupported_jpegs_are_not_read() {
+        let data = encode_jpeg(
+            &smooth_image(16, 16),
+            90,
+            ChromaSubsampling::Yuv420,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(JpegFrame::read(&data).is_some());
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        smooth_image(16, 16)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        assert!(JpegFrame::read(png.get_ref()).is_none());
+
+        let sof = data.windows(2).position(|w| w == [0xFF, SOF0]).unwrap();
+        for (offset, value) in [(1, 0xC3), (4, 12), (9, 4)] {
+            let mut patched = data.clone();
+            patched[sof + offset] = value;
+            assert!(JpegFrame::read(&patched).is_none(), "offset {offset}");
+        }
+    }
+
+    #[test]
+    fn corrupt_jpegs_do_not_panic() {
+        let coefficients = Coefficients::new(70, 40, &[(2, 2), (1, 1), (1, 1)]);
+        for data in [coefficients.encode(false, 3), coefficients.encode(true, 2)] {
+            for length in (0..data.len()).step_by(7) {
+                if let Some(frame) = JpegFrame::read(&data[..length]) {
+                    let _ = decode_jpeg_scaled(&data[..length], &frame, 2);
+                }
+            }
+            for position in (0..data.len()).step_by(5) {
+                let mut corrupted = data.clone();
+                corrupted[position] ^= 0x5A;
+                if let Some(frame) = JpegFrame::read(&corrupted) {
+                    let _ = decode_jpeg_scaled(&corrupted, &frame, 4);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,623 @@
+//! Baseline JPEG encoding with chroma subsampling
+//!
+//! The `image` crate's JPEG encoder always stores chroma at full resolution (4:4:4).
+//! This encoder writes baseline sequential JPEGs with horizontally (4:2:2) or
+//! horizontally and vertically (4:2:0) halved chroma, which is what libjpeg produces
+//! by default and is noticeably smaller at the same quality. It uses the standard
+//! tables of ITU-T T.81 Annex K, with quantization scaled by quality as in libjpeg.
+
+use image::RgbImage;
+
+use crate::status::{ItemError, ItemResult, ItemStatus};
+
+// Markers
+const SOI: u8 = 0xD8;
+const EOI: u8 = 0xD9;
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const DQT: u8 = 0xDB;
+const SOF0: u8 = 0xC0;
+const DHT: u8 = 0xC4;
+const SOS: u8 = 0xDA;
+
+/// Largest payload of a marker segment (the 16-bit length counts itself)
+const MAX_SEGMENT_PAYLOAD: usize = 65533;
+
+/// Natural-order index of each zig-zag position
+#[rustfmt::skip]
+pub(crate) const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Table K.1
+#[rustfmt::skip]
+const LUMA_QTABLE: [u8; 64] = [
+    16, 11, 10, 16,  24,  40,  51,  61,
+    12, 12, 14, 19,  26,  58,  60,  55,
+    14, 13, 16, 24,  40,  57,  69,  56,
+    14, 17, 22, 29,  51,  87,  80,  62,
+    18, 22, 37, 56,  68, 109, 103,  77,
+    24, 35, 55, 64,  81, 104, 113,  92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103,  99,
+];
+
+// Table K.2
+#[rustfmt::skip]
+const CHROMA_QTABLE: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+// Tables K.3 - K.6: code counts per length (1-16 bits) and symbols
+const LUMA_DC_LENGTHS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const CHROMA_DC_LENGTHS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const LUMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+#[rustfmt::skip]
+const LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+const CHROMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+#[rustfmt::skip]
+const CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+/// Chroma resolution of a JPEG relative to luma
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ChromaSubsampling {
+    /// Full-resolution chroma
+    Yuv444,
+    /// Chroma halved horizontally
+    Yuv422,
+    /// Chroma halved horizontally and vertically
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    /// Luma samples per chroma sample, horizontally and vertically
+    fn factors(self) -> (usize, usize) {
+        match self {
+            Self::Yuv444 => (1, 1),
+            Self::Yuv422 => (2, 1),
+            Self::Yuv420 => (2, 2),
+        }
+    }
+}
+
+/// Huffman code (value, bit length) of each symbol
+struct HuffmanTable {
+    lengths: &'static [u8; 16],
+    values: &'static [u8],
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanTable {
+    /// Assign canonical codes to the symbols, shortest first (T.81 Annex C)
+    fn new(lengths: &'static [u8; 16], values: &'static [u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut symbols = values.iter();
+        for (length, &count) in (1u8..).zip(lengths.iter()) {
+            for &symbol in symbols.by_ref().take(usize::from(count)) {
+                codes[usize::from(symbol)] = (code, length);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Self {
+            lengths,
+            values,
+            codes,
+        }
+    }
+}
+
+/// Quantization table in natural order, scaled for `quality` (1-100) as libjpeg does
+fn scaled_qtable(base: &[u8; 64], quality: u8) -> [u16; 64] {
+    let quality = u32::from(quality.clamp(1, 100));
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    base.map(|q| ((u32::from(q) * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+/// Entropy-coded segment writer with 0xFF byte stuffing
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, bits: u16, count: u8) {
+        if count == 0 {
+            return;
+        }
+        let mask = (1u32 << count) - 1;
+        self.accumulator = (self.accumulator << count) | (u32::from(bits) & mask);
+        self.bit_count += u32::from(count);
+        while self.bit_count >= 8 {
+            self.bit_count -= 8;
+            let byte = (self.accumulator >> self.bit_count) as u8;
+            self.bytes.push(byte);
+            if byte == 0xFF {
+                self.bytes.push(0x00);
+            }
+        }
+        self.accumulator &= (1 << self.bit_count) - 1;
+    }
+
+    /// Pad the last byte with 1 bits
+    fn flush(&mut self) {
+        if self.bit_count > 0 {
+            let padding = (8 - self.bit_count) as u8;
+            self.write_bits(0xFF, padding);
+        }
+    }
+}
+
+/// Magnitude category of a coefficient and its appended bits (T.81 F.1.2.1)
+fn magnitude(value: i32) -> (u8, u16) {
+    let category = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (category, bits as u16)
+}
+
+/// One image component: its sample plane and the tables it is coded with
+struct Component {
+    id: u8,
+    /// Sampling factors (horizontal, vertical) in the frame header
+    sampling: (usize, usize),
+    qtable_id: u8,
+    table_id: u8,
+    plane: Vec<u8>,
+    plane_width: usize,
+    previous_dc: i32,
+}
+
+/// 8x8 forward DCT basis, `basis[u][x] = c(u) / 2 * cos((2x + 1) u pi / 16)`
+fn dct_basis() -> [[f32; 8]; 8] {
+    let mut basis = [[0.0; 8]; 8];
+    for (u, row) in basis.iter_mut().enumerate() {
+        let c = if u == 0 {
+            std::f32::consts::FRAC_1_SQRT_2
+        } else {
+            1.0
+        };
+        for (x, value) in row.iter_mut().enumerate() {
+            let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+            *value = c / 2.0 * angle.cos();
+        }
+    }
+    basis
+}
+
+/// Baseline encoder state for one image
+struct Encoder {
+    writer: BitWriter,
+    basis: [[f32; 8]; 8],
+    qtables: [[u16; 64]; 2],
+    dc_tables: [HuffmanTable; 2],
+    ac_tables: [HuffmanTable; 2],
+}
+
+impl Encoder {
+    /// DCT, quantize and entropy-code the 8x8 block at (`x`, `y`) of a component
+    fn encode_block(&mut self, component: &mut Component, x: usize, y: usize) {
+        let mut samples = [[0.0f32; 8]; 8];
+        for (row, line) in samples.iter_mut().enumerate() {
+            let start = (y + row) * component.plane_width + x;
+            for (value, &sample) in line.iter_mut().zip(&component.plane[start..start + 8]) {
+                *value = f32::from(sample) - 128.0;
+            }
+        }
+
+        // Separable 2-D DCT: rows, then columns
+        let mut rows = [[0.0f32; 8]; 8];
+        for (line, out) in samples.iter().zip(rows.iter_mut()) {
+            for (u, value) in out.iter_mut().enumerate() {
+                *value = (0..8).map(|x| self.basis[u][x] * line[x]).sum();
+            }
+        }
+        let qtable = &self.qtables[usize::from(component.qtable_id)];
+        let mut coefficients = [0i32; 64];
+        for (index, coefficient) in coefficients.iter_mut().enumerate() {
+            let (v, u) = (index / 8, index % 8);
+            let value: f32 = (0..8).map(|y| self.basis[v][y] * rows[y][u]).sum();
+            *coefficient = (value / f32::from(qtable[index])).round() as i32;
+        }
+
+        let table = usize::from(component.table_id);
+        let dc = coefficients[0];
+        let (category, bits) = magnitude(dc - component.previous_dc);
+        component.previous_dc = dc;
+        let (code, length) = self.dc_tables[table].codes[usize::from(category)];
+        self.writer.write_bits(code, length);
+        self.writer.write_bits(bits, category);
+
+        let mut run = 0u8;
+        for &index in &ZIGZAG[1..] {
+            let value = coefficients[index];
+            if value == 0 {
+                run += 1;
+                continue;
+            }
+            while run >= 16 {
+                let (code, length) = self.ac_tables[table].codes[0xF0];
+                self.writer.write_bits(code, length);
+                run -= 16;
+            }
+            let (category, bits) = magnitude(value);
+            let (code, length) = self.ac_tables[table].codes[usize::from((run << 4) | category)];
+            self.writer.write_bits(code, length);
+            self.writer.write_bits(bits, category);
+            run = 0;
+        }
+        if run > 0 {
+            let (code, length) = self.ac_tables[table].codes[0x00];
+            self.writer.write_bits(code, length);
+        }
+    }
+}
+
+/// Convert to YCbCr planes padded to whole MCUs, averaging chroma over each
+/// subsampled area; edges are extended by repeating the last row and column
+fn ycbcr_planes(image: &RgbImage, padded: (usize, usize), factors: (usize, usize)) -> [Vec<u8>; 3] {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let (padded_width, padded_height) = padded;
+    let (fx, fy) = factors;
+    let pixel = |x: usize, y: usize| {
+        let p = image.get_pixel(x.min(width - 1) as u32, y.min(height - 1) as u32);
+        (f32::from(p[0]), f32::from(p[1]), f32::from(p[2]))
+    };
+
+    let mut luma = Vec::with_capacity(padded_width * padded_height);
+    for y in 0..padded_height {
+        for x in 0..padded_width {
+            let (r, g, b) = pixel(x, y);
+            luma.push((0.299 * r + 0.587 * g + 0.114 * b).round() as u8);
+        }
+    }
+
+    let chroma_len = (padded_width / fx) * (padded_height / fy);
+    let mut cb = Vec::with_capacity(chroma_len);
+    let mut cr = Vec::with_capacity(chroma_len);
+    let area = (fx * fy) as f32;
+    for cy in 0..padded_height / fy {
+        for cx in 0..padded_width / fx {
+            let (mut cb_sum, mut cr_sum) = (0.0f32, 0.0f32);
+            for y in cy * fy..(cy + 1) * fy {
+                for x in cx * fx..(cx + 1) * fx {
+                    let (r, g, b) = pixel(x, y);
+                    cb_sum += -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0;
+                    cr_sum += 0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0;
+                }
+            }
+            cb.push((cb_sum / area).round().clamp(0.0, 255.0) as u8);
+            cr.push((cr_sum / area).round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    [luma, cb, cr]
+}
+
+fn write_segment(bytes: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    bytes.extend_from_slice(&[0xFF, marker]);
+    bytes.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    bytes.extend_from_slice(payload);
+}
+
+/// Write EXIF (APP1) and ICC profile (APP2, split into numbered chunks) segments
+fn write_metadata(
+    bytes: &mut Vec<u8>,
+    icc_profile: Option<&[u8]>,
+    exif: Option<&[u8]>,
+) -> ItemResult<()> {
+    let too_large =
+        |what: &str| ItemError::new(ItemStatus::EncodeError, format!("{what} too large"));
+    if let Some(exif) = exif {
+        if exif.len() + 6 > MAX_SEGMENT_PAYLOAD {
+            return Err(too_large("EXIF data"));
+        }
+        write_segment(bytes, APP1, &[b"Exif\0\0", exif].concat());
+    }
+    if let Some(icc_profile) = icc_profile {
+        let chunks: Vec<&[u8]> = icc_profile.chunks(MAX_SEGMENT_PAYLOAD - 14).collect();
+        let count = u8::try_from(chunks.len()).map_err(|_| too_large("ICC profile"))?;
+        for (number, chunk) in (1u8..).zip(&chunks) {
+            write_segment(
+                bytes,
+                APP2,
+                &[b"ICC_PROFILE\0", &[number, count][..], chunk].concat(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Encode an RGB image as a baseline JPEG with the given chroma subsampling
+///
+/// `quality` is 1-100. The ICC profile and EXIF data, if given, are embedded as is.
+pub(crate) fn encode_jpeg(
+    image: &RgbImage,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    icc_profile: Option<&[u8]>,
+    exif: Option<&[u8]>,
+) -> ItemResult<Vec<u8>> {
+    let (width, height) = (image.width(), image.height());
+    let (Ok(frame_width @ 1..), Ok(frame_height @ 1..)) =
+        (u16::try_from(width), u16::try_from(height))
+    else {
+        return Err(ItemError::new(
+            ItemStatus::EncodeError,
+            format!("invalid JPEG dimensions {width}x{height} (sides must be 1-65535)"),
+        ));
+    };
+
+    let (fx, fy) = subsampling.factors();
+    let (mcu_width, mcu_height) = (8 * fx, 8 * fy);
+    let padded = (
+        usize::from(frame_width).div_ceil(mcu_width) * mcu_width,
+        usize::from(frame_height).div_ceil(mcu_height) * mcu_height,
+    );
+    let [luma, cb, cr] = ycbcr_planes(image, padded, (fx, fy));
+    let component = |id, sampling, table, plane, plane_width| Component {
+        id,
+        sampling,
+        qtable_id: table,
+        table_id: table,
+        plane,
+        plane_width,
+        previous_dc: 0,
+    };
+    let mut components = [
+        component(1, (fx, fy), 0, luma, padded.0),
+        component(2, (1, 1), 1, cb, padded.0 / fx),
+        component(3, (1, 1), 1, cr, padded.0 / fx),
+    ];
+
+    let mut encoder = Encoder {
+        writer: BitWriter {
+            bytes: Vec::new(),
+            accumulator: 0,
+            bit_count: 0,
+        },
+        basis: dct_basis(),
+        qtables: [
+            scaled_qtable(&LUMA_QTABLE, quality),
+            scaled_qtable(&CHROMA_QTABLE, quality),
+        ],
+        dc_tables: [
+            HuffmanTable::new(&LUMA_DC_LENGTHS, &DC_VALUES),
+            HuffmanTable::new(&CHROMA_DC_LENGTHS, &DC_VALUES),
+        ],
+        ac_tables: [
+            HuffmanTable::new(&LUMA_AC_LENGTHS, &LUMA_AC_VALUES),
+            HuffmanTable::new(&CHROMA_AC_LENGTHS, &CHROMA_AC_VALUES),
+        ],
+    };
+
+    let mut bytes = vec![0xFF, SOI];
+    write_segment(
+        &mut bytes,
+        APP0,
+        &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0],
+    );
+    write_metadata(&mut bytes, icc_profile, exif)?;
+
+    let mut dqt = Vec::with_capacity(130);
+    for (id, table) in (0u8..).zip(&encoder.qtables) {
+        dqt.push(id);
+        dqt.extend(ZIGZAG.iter().map(|&index| table[index] as u8));
+    }
+    write_segment(&mut bytes, DQT, &dqt);
+
+    let mut sof = vec![8];
+    sof.extend_from_slice(&frame_height.to_be_bytes());
+    sof.extend_from_slice(&frame_width.to_be_bytes());
+    sof.push(components.len() as u8);
+    for c in &components {
+        sof.extend_from_slice(&[
+            c.id,
+            ((c.sampling.0 << 4) | c.sampling.1) as u8,
+            c.qtable_id,
+        ]);
+    }
+    write_segment(&mut bytes, SOF0, &sof);
+
+    let mut dht = Vec::new();
+    for (class, tables) in [(0u8, &encoder.dc_tables), (1, &encoder.ac_tables)] {
+        for (id, table) in (0u8..).zip(tables.iter()) {
+            dht.push((class << 4) | id);
+            dht.extend_from_slice(table.lengths);
+            dht.extend_from_slice(table.values);
+        }
+    }
+    write_segment(&mut bytes, DHT, &dht);
+
+    let mut sos = vec![components.len() as u8];
+    for c in &components {
+        sos.extend_from_slice(&[c.id, (c.table_id << 4) | c.table_id]);
+    }
+    sos.extend_from_slice(&[0, 63, 0]);
+    write_segment(&mut bytes, SOS, &sos);
+
+    // Interleaved MCUs: fx * fy luma blocks followed by one block of each chroma plane
+    for mcu_y in (0..padded.1).step_by(mcu_height) {
+        for mcu_x in (0..padded.0).step_by(mcu_width) {
+            for component in components.iter_mut() {
+                let (h, v) = component.sampling;
+                let (x0, y0) = (mcu_x / fx * h, mcu_y / fy * v);
+                for block_y in 0..v {
+                    for block_x in 0..h {
+                        encoder.encode_block(component, x0 + block_x * 8, y0 + block_y * 8);
+                    }
+                }
+            }
+        }
+    }
+    encoder.writer.flush();
+
+    bytes.extend_from_slice(&encoder.writer.bytes);
+    bytes.extend_from_slice(&[0xFF, EOI]);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegDecoder;
+    use image::{ImageDecoder, Rgb};
+    use std::io::Cursor;
+
+    const SUBSAMPLINGS: [ChromaSubsampling; 3] = [
+        ChromaSubsampling::Yuv444,
+        ChromaSubsampling::Yuv422,
+        ChromaSubsampling::Yuv420,
+    ];
+
+    fn smooth_image(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as f32, y as f32);
+            Rgb([
+                128.0 + 80.0 * (x / 6.0).sin(),
+                128.0 + 80.0 * (y / 5.0).cos(),
+                40.0 + 3.0 * (x + y),
+            ]
+            .map(|value| value.clamp(0.0, 255.0) as u8))
+        })
+    }
+
+    fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+        let squared: f64 = a
+            .as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+            .sum();
+        let mse = squared / a.as_raw().len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-9)).log10()
+    }
+
+    /// Component sampling factors (h << 4 | v) in the SOF0 segment
+    fn sof_sampling(bytes: &[u8]) -> Vec<u8> {
+        let sof = bytes.windows(2).position(|w| w == [0xFF, SOF0]).unwrap();
+        let count = usize::from(bytes[sof + 9]);
+        (0..count).map(|i| bytes[sof + 11 + 3 * i]).collect()
+    }
+
+    #[test]
+    fn round_trips_through_image_decoder() {
+        for subsampling in SUBSAMPLINGS {
+            let (fx, fy) = subsampling.factors();
+            for (width, height) in [(1, 1), (17, 9), (33, 65)] {
+                let image = smooth_image(width, height);
+                for (quality, min_psnr) in [(1, 14.0), (90, 30.0), (100, 34.0)] {
+                    let bytes = encode_jpeg(&image, quality, subsampling, None, None).unwrap();
+                    assert_eq!(sof_sampling(&bytes), [(fx << 4 | fy) as u8, 0x11, 0x11]);
+                    let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+                    assert_eq!(decoded.dimensions(), (width, height));
+                    let quality_db = psnr(&decoded, &image);
+                    assert!(
+                        quality_db > min_psnr,
+                        "{subsampling:?} {width}x{height} q{quality}: {quality_db:.1} dB"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn higher_quality_is_larger_and_closer() {
+        let image = smooth_image(64, 48);
+        for subsampling in SUBSAMPLINGS {
+            let encode = |quality| encode_jpeg(&image, quality, subsampling, None, None).unwrap();
+            let (low, high) = (encode(30), encode(95));
+            assert!(low.len() < high.len());
+            let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().to_rgb8();
+            assert!(psnr(&decode(&low), &image) < psnr(&decode(&high), &image));
+        }
+    }
+
+    #[test]
+    fn large_icc_profile_is_split_into_app2_chunks() {
+        let profile: Vec<u8> = (0..150_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let exif = b"MM\0\x2a\0\0\0\x08\0\0".to_vec();
+        let bytes = encode_jpeg(
+            &smooth_image(8, 8),
+            90,
+            ChromaSubsampling::Yuv420,
+            Some(&profile),
+            Some(&exif),
+        )
+        .unwrap();
+        let chunks = bytes
+            .windows(16)
+            .filter(|w| w[..2] == [0xFF, APP2] && &w[4..] == b"ICC_PROFILE\0")
+            .count();
+        assert_eq!(chunks, 3);
+
+        let mut decoder = JpegDecoder::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(decoder.icc_profile().unwrap(), Some(profile));
+        assert_eq!(decoder.exif_metadata().unwrap(), Some(exif));
+    }
+
+    #[test]
+    fn oversized_inputs_are_rejected() {
+        let image = smooth_image(8, 8);
+        let profile = vec![0; 256 * (MAX_SEGMENT_PAYLOAD - 14)];
+        let result = encode_jpeg(&image, 90, ChromaSubsampling::Yuv444, Some(&profile), None);
+        assert_eq!(result.err().unwrap().status, ItemStatus::EncodeError);
+        let exif = vec![0; MAX_SEGMENT_PAYLOAD];
+        let result = encode_jpeg(&image, 90, ChromaSubsampling::Yuv444, None, Some(&exif));
+        assert_eq!(result.err().unwrap().status, ItemStatus::EncodeError);
+        let result = encode_jpeg(
+            &RgbImage::new(0, 4),
+            90,
+            ChromaSubsampling::Yuv444,
+            None,
+            None,
+        );
+        assert_eq!(result.err().unwrap().status, ItemStatus::EncodeError);
+    }
+}
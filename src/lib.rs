@@ -4,31 +4,237 @@
 //!
 //! ## Image Operations (`image_ops`)
 //! - `image_assess_quality_batch`: Compression artifacts + entropy calculation
+//! - `image_assess_sharpness_batch`: Blur metrics (Laplacian variance, Tenengrad, sharp-tile fraction)
+//! - `image_estimate_noise_batch`: Noise sigma estimation (Immerkær's method)
+//! - `image_exposure_stats_batch`: Exposure, contrast and colorfulness statistics
+//! - `image_detect_placeholder_batch`: Blank / solid-color / placeholder classification
+//! - `image_detect_borders_batch`: Uniform border / letterbox detection with optional auto-crop
+//! - `image_preprocess_batch`: Decode, resize, crop and normalize into an (N, 3, H, W) NumPy tensor
+//! - `image_plan_buckets` / `image_bucket_batch`: Aspect-ratio bucket assignment and crop
+//!   planning, optionally producing the resized and cropped image
+//! - `image_transcode_batch`: Re-encode to a canonical format (max side, quality, chroma
+//!   subsampling, metadata stripping)
+//! - `image_embedded_metadata_batch`: EXIF/XMP/ICC/C2PA metadata (camera, software, capture
+//!   time, GPS, AI-generation declarations), optionally stripping GPS or all metadata
 //! - `image_compute_phash_batch`: Perceptual hash computation
+//! - `image_analyze_batch`: Single-decode multi-metric analysis (size, format, quality, phash)
+//! - `image_probe_metadata_batch`: Header-only metadata (size, format, color type, frames,
+//!   orientation, color space)
+//! - `image_assess_quality_arrow`, `image_assess_sharpness_arrow`, `image_estimate_noise_arrow`,
+//!   `image_exposure_stats_arrow`, `image_detect_placeholder_arrow`,
+//!   `image_detect_borders_arrow`, `image_bucket_arrow`, `image_transcode_arrow`,
+//!   `image_embedded_metadata_arrow`, `image_compute_phash_arrow`,
+//!   `image_probe_metadata_arrow`:
+//!   Zero-copy variants taking a pyarrow binary array and returning a RecordBatch
+//! - `image_preprocess_arrow`: Zero-copy input variant of `image_preprocess_batch`
+//! - `PhashIndex`: Hamming-radius search over perceptual hashes (BK-tree)
+//! - `image_phash_find_near_duplicates`: Batch perceptual near-duplicate detection
+//! - `DecodeLimits`: Pixel, allocation, input-size and format limits, passed as `limits=`
+//!   to every image function
 //!
 //! ## Text Operations (`text_ops`)
 //! - `html_extract_text`: Extract readable text from a single HTML string
 //! - `html_extract_text_batch`: Extract readable text from multiple HTML strings (parallel)
+//! - `html_extract_text_arrow`: Zero-copy variant taking a pyarrow string/binary array
+//! - `text_minhash_batch`: MinHash signatures + LSH band keys for near-duplicate detection (parallel)
+//! - `MinHashLshIndex`: In-memory LSH index for candidate pairs and near-duplicate clusters
+//!
+//! ## WARC Operations (`warc_ops`)
+//! - `WarcHtmlReader`: Stream (url, warc_date, html) batches from a local WARC file
+//! - `WarcTextExtractor`: Stream extracted-text record dicts from a local WARC file (parallel)
+//!
+//! ## Batch Submission (`batch_future`)
+//! - `submit_*`: Non-blocking variants of the list-based `*_batch` functions
+//! - `BatchFuture`: Handle with `done()` / `result(timeout=None)`
+//!
+//! ## Thread Pools (`executor`)
+//! - `RustExecutor`: Dedicated rayon pool (thread count, optional core pinning);
+//!   batch functions called inside `with executor:` or via `executor.run()` use it
+//!
+//! All batch functions release the GIL while they compute, and a panic while
+//! processing one item fails only that item (status `"panic"`).
+//!
+//! Shared infrastructure: `status` (per-item status reporting), `arrow_ffi`
+//! (Arrow C data interface for the `*_arrow` variants), `image_decode`
+//! (decode limits shared by all image operators), `color` (ICC-aware conversion
+//! of decoded pixels to sRGB, CMYK decoding and alpha compositing), `image_encode` (re-encoding
+//! for operators that return image bytes, with `jpeg_encode` for chroma-subsampled
+//! JPEGs), `image_metadata` (EXIF/XMP/ICC/C2PA parsing and lossless metadata
+//! stripping) and `tensor_buffer` (NumPy arrays backed by Rust allocations).
 
+mod arrow_ffi;
+mod batch_future;
+mod color;
+mod executor;
+mod image_decode;
+mod image_encode;
+mod image_metadata;
 mod image_ops;
+mod jpeg_decode;
+mod jpeg_encode;
+mod status;
+mod tensor_buffer;
 mod text_ops;
+mod warc_ops;
 
 use pyo3::prelude::*;
 
 // Re-export all public functions
-pub use image_ops::{image_assess_quality_batch, image_compute_phash_batch};
-pub use text_ops::{html_extract_text, html_extract_text_batch};
+pub use batch_future::BatchFuture;
+pub use executor::RustExecutor;
+pub use image_decode::DecodeLimits;
+pub use image_ops::{
+    image_analyze_batch, image_assess_quality_arrow, image_assess_quality_batch,
+    image_assess_sharpness_arrow, image_assess_sharpness_batch, image_bucket_arrow,
+    image_bucket_batch, image_compute_phash_arrow, image_compute_phash_batch,
+    image_detect_borders_arrow, image_detect_borders_batch, image_detect_placeholder_arrow,
+    image_detect_placeholder_batch, image_embedded_metadata_arrow, image_embedded_metadata_batch,
+    image_estimate_noise_arrow, image_estimate_noise_batch, image_exposure_stats_arrow,
+    image_exposure_stats_batch, image_phash_find_near_duplicates, image_plan_buckets,
+    image_preprocess_arrow, image_preprocess_batch, image_probe_metadata_arrow,
+    image_probe_metadata_batch, image_transcode_arrow, image_transcode_batch,
+    submit_image_analyze_batch, submit_image_assess_quality_batch,
+    submit_image_assess_sharpness_batch, submit_image_bucket_batch,
+    submit_image_compute_phash_batch, submit_image_detect_borders_batch,
+    submit_image_detect_placeholder_batch, submit_image_embedded_metadata_batch,
+    submit_image_estimate_noise_batch, submit_image_exposure_stats_batch,
+    submit_image_preprocess_batch, submit_image_probe_metadata_batch, submit_image_transcode_batch,
+    PhashIndex,
+};
+pub use tensor_buffer::TensorBuffer;
+pub use text_ops::{
+    html_extract_text, html_extract_text_arrow, html_extract_text_batch,
+    submit_html_extract_text_batch, submit_text_minhash_batch, text_minhash_batch, MinHashLshIndex,
+};
+pub use warc_ops::{WarcHtmlReader, WarcTextExtractor};
 
 /// Python module definition
 #[pymodule]
 fn rust_operators(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // Image operations
     m.add_function(wrap_pyfunction!(image_ops::image_assess_quality_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_assess_sharpness_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_estimate_noise_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_exposure_stats_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_detect_placeholder_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_detect_borders_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_preprocess_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_plan_buckets, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_bucket_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_transcode_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_embedded_metadata_batch,
+        m
+    )?)?;
     m.add_function(wrap_pyfunction!(image_ops::image_compute_phash_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_analyze_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_probe_metadata_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_phash_find_near_duplicates,
+        m
+    )?)?;
+    m.add_class::<image_ops::PhashIndex>()?;
+    m.add_class::<image_decode::DecodeLimits>()?;
+    m.add_class::<tensor_buffer::TensorBuffer>()?;
+
+    // Image operations - Arrow (zero-copy) variants
+    m.add_function(wrap_pyfunction!(image_ops::image_assess_quality_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_assess_sharpness_arrow,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_estimate_noise_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_exposure_stats_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_detect_placeholder_arrow,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_detect_borders_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_preprocess_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_bucket_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_transcode_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::image_embedded_metadata_arrow,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_compute_phash_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(image_ops::image_probe_metadata_arrow, m)?)?;
 
     // Text operations - HTML extraction
     m.add_function(wrap_pyfunction!(text_ops::html_extract_text, m)?)?;
     m.add_function(wrap_pyfunction!(text_ops::html_extract_text_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(text_ops::html_extract_text_arrow, m)?)?;
+
+    // Text operations - near-duplicate detection
+    m.add_function(wrap_pyfunction!(text_ops::text_minhash_batch, m)?)?;
+    m.add_class::<text_ops::MinHashLshIndex>()?;
+
+    // Non-blocking batch submission
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_assess_quality_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_assess_sharpness_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_estimate_noise_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_exposure_stats_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_detect_placeholder_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_detect_borders_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_preprocess_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::submit_image_bucket_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_transcode_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_embedded_metadata_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_compute_phash_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(image_ops::submit_image_analyze_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        image_ops::submit_image_probe_metadata_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(
+        text_ops::submit_html_extract_text_batch,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(text_ops::submit_text_minhash_batch, m)?)?;
+    m.add_class::<batch_future::BatchFuture>()?;
+
+    // Thread pools
+    m.add_class::<executor::RustExecutor>()?;
+
+    // WARC operations
+    m.add_class::<warc_ops::WarcHtmlReader>()?;
+    m.add_class::<warc_ops::WarcTextExtractor>()?;
 
     Ok(())
 }
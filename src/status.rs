@@ -0,0 +1,332 @@
+//! Per-item status reporting for batch operators
+//!
+//! Batch functions accept `return_status=True` to return `(results, statuses)`,
+//! where each status is a `(status, message)` tuple:
+//! - `"ok"`: Item processed (the result may still be legitimately empty)
+//! - `"decode_error"`: Input could not be decoded or parsed
+//! - `"unsupported_format"`: Input format is not recognised or not supported
+//! - `"too_large"`: Input exceeds a size or resource limit
+//! - `"input_too_large"`, `"too_many_pixels"`, `"alloc_limit"`, `"format_not_allowed"`:
+//!   Image rejected by a decode limit (see `image_decode`)
+//! - `"encode_error"`: Output image could not be encoded
+//! - `"timeout"`: Item exceeded its wall-clock budget
+//! - `"resource_error"`: No worker thread could be started for the item
+//! - `"panic"`: Processing the item panicked (the rest of the batch is unaffected)
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use dom_smoothie::ReadabilityError;
+use image::error::LimitErrorKind;
+use image::ImageError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use crate::executor::{budget_pool, BudgetPool};
+
+/// Failure category of a single batch item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ItemStatus {
+    DecodeError,
+    UnsupportedFormat,
+    TooLarge,
+    InputTooLarge,
+    TooManyPixels,
+    AllocLimit,
+    FormatNotAllowed,
+    EncodeError,
+    Timeout,
+    ResourceError,
+    Panic,
+}
+
+impl ItemStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DecodeError => "decode_error",
+            Self::UnsupportedFormat => "unsupported_format",
+            Self::TooLarge => "too_large",
+            Self::InputTooLarge => "input_too_large",
+            Self::TooManyPixels => "too_many_pixels",
+            Self::AllocLimit => "alloc_limit",
+            Self::FormatNotAllowed => "format_not_allowed",
+            Self::EncodeError => "encode_error",
+            Self::Timeout => "timeout",
+            Self::ResourceError => "resource_error",
+            Self::Panic => "panic",
+        }
+    }
+}
+
+/// A failed batch item: its status plus a human-readable message
+#[derive(Clone, Debug)]
+pub(crate) struct ItemError {
+    pub status: ItemStatus,
+    pub message: String,
+}
+
+impl ItemError {
+    pub fn new(status: ItemStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ImageError> for ItemError {
+    fn from(err: ImageError) -> Self {
+        let status = match err {
+            ImageError::Unsupported(_) => ItemStatus::UnsupportedFormat,
+            ImageError::Encoding(_) => ItemStatus::EncodeError,
+            ImageError::Limits(ref limit) => match limit.kind() {
+                LimitErrorKind::DimensionError => ItemStatus::TooManyPixels,
+                LimitErrorKind::InsufficientMemory => ItemStatus::AllocLimit,
+                _ => ItemStatus::TooLarge,
+            },
+            _ => ItemStatus::DecodeError,
+        };
+        Self::new(status, err.to_string())
+    }
+}
+
+impl From<ReadabilityError> for ItemError {
+    fn from(err: ReadabilityError) -> Self {
+        let status = match err {
+            ReadabilityError::TooManyElements(..) => ItemStatus::TooLarge,
+            _ => ItemStatus::DecodeError,
+        };
+        Self::new(status, err.to_string())
+    }
+}
+
+// ============================================================================
+// Item Isolation
+// ============================================================================
+
+/// Message carried by a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let detail = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("panicked: {detail}")
+}
+
+/// Process one item, reporting a panic as a `Panic` failure of that item
+pub(crate) fn catch_panic<T>(process: impl FnOnce() -> ItemResult<T>) -> ItemResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(process))
+        .unwrap_or_else(|payload| Err(ItemError::new(ItemStatus::Panic, panic_message(&*payload))))
+}
+
+/// Job state shared between a budgeted item and the caller waiting for it
+const QUEUED: u8 = 0;
+const RUNNING: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Process one item within a wall-clock budget
+///
+/// With a timeout the item runs on the fixed-size budget pool of the active executor
+/// (see `executor::budget_pool`) and is reported as `Timeout` once the budget
+/// (counted from submission) runs out. An overrunning item keeps its worker until it
+/// finishes (Rust code cannot be interrupted); an item still queued when its budget
+/// runs out is dropped without running. Panics are caught either way.
+pub(crate) fn run_with_timeout<T, F>(timeout: Option<Duration>, process: F) -> ItemResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> ItemResult<T> + Send + 'static,
+{
+    match timeout {
+        Some(timeout) => run_in_budget_pool(&budget_pool(), timeout, process),
+        None => catch_panic(process),
+    }
+}
+
+/// Process one item on `budget` within `timeout`
+fn run_in_budget_pool<T, F>(budget: &BudgetPool, timeout: Duration, process: F) -> ItemResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> ItemResult<T> + Send + 'static,
+{
+    let pool = budget.get().map_err(|err| {
+        ItemError::new(
+            ItemStatus::ResourceError,
+            format!("failed to start item workers: {err}"),
+        )
+    })?;
+
+    let state = Arc::new(AtomicU8::new(QUEUED));
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let job_state = Arc::clone(&state);
+    pool.spawn(move || {
+        if job_state
+            .compare_exchange(QUEUED, RUNNING, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            let _ = sender.send(catch_panic(process));
+        }
+    });
+    receiver
+        .recv_timeout(timeout)
+        .unwrap_or_else(|err| match err {
+            mpsc::RecvTimeoutError::Timeout => {
+                let started = state
+                    .compare_exchange(QUEUED, ABANDONED, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err();
+                let message = if started {
+                    format!("exceeded {:.3}s budget", timeout.as_secs_f64())
+                } else {
+                    format!(
+                        "no item worker free within {:.3}s budget",
+                        timeout.as_secs_f64()
+                    )
+                };
+                Err(ItemError::new(ItemStatus::Timeout, message))
+            }
+            mpsc::RecvTimeoutError::Disconnected => Err(ItemError::new(
+                ItemStatus::Panic,
+                "item worker exited without a result",
+            )),
+        })
+}
+
+/// Parse an optional timeout in seconds
+pub(crate) fn parse_timeout(timeout: Option<f64>) -> PyResult<Option<Duration>> {
+    timeout
+        .map(|secs| {
+            Duration::try_from_secs_f64(secs)
+                .map_err(|_| PyValueError::new_err("timeout must be a non-negative number"))
+        })
+        .transpose()
+}
+
+/// (status, message) tuple as handed to Python
+pub(crate) type StatusTuple = (&'static str, Option<String>);
+
+/// Result of processing one batch item
+pub(crate) type ItemResult<T> = Result<T, ItemError>;
+
+/// Status tuple of one item's outcome
+pub(crate) fn status_tuple<T>(outcome: &ItemResult<T>) -> StatusTuple {
+    match outcome {
+        Ok(_) => ("ok", None),
+        Err(err) => (err.status.as_str(), Some(err.message.clone())),
+    }
+}
+
+/// Build a batch function's return value
+///
+/// Failed items are replaced by `fallback()` in the results. With `return_status`
+/// the value is `(results, statuses)`, otherwise just `results`.
+pub(crate) fn batch_output<'py, T>(
+    py: Python<'py>,
+    outcomes: Vec<ItemResult<T>>,
+    fallback: impl Fn() -> T,
+    return_status: bool,
+) -> PyResult<Bound<'py, PyAny>>
+where
+    T: IntoPyObject<'py>,
+{
+    if !return_status {
+        let results: Vec<T> = outcomes
+            .into_iter()
+            .map(|outcome| outcome.unwrap_or_else(|_| fallback()))
+            .collect();
+        return results.into_bound_py_any(py);
+    }
+
+    let (results, statuses): (Vec<T>, Vec<StatusTuple>) = outcomes
+        .into_iter()
+        .map(|outcome| match outcome {
+            Ok(value) => (value, ("ok", None)),
+            Err(err) => (fallback(), (err.status.as_str(), Some(err.message))),
+        })
+        .unzip();
+    (results, statuses).into_bound_py_any(py)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn items_without_timeout_run_inline() {
+        assert_eq!(run_with_timeout(None, || Ok(7)).unwrap(), 7);
+        let err = run_with_timeout::<(), _>(None, || panic!("boom")).unwrap_err();
+        assert_eq!(err.status, ItemStatus::Panic);
+        assert!(err.message.contains("boom"));
+    }
+
+    #[test]
+    fn items_within_budget_return_their_result() {
+        let budget = Some(Duration::from_secs(5));
+        assert_eq!(run_with_timeout(budget, || Ok("done")).unwrap(), "done");
+        let err = run_with_timeout::<(), _>(budget, || panic!("boom")).unwrap_err();
+        assert_eq!(err.status, ItemStatus::Panic);
+    }
+
+    #[test]
+    fn overrunning_items_time_out() {
+        let start = Instant::now();
+        let err = run_with_timeout(Some(Duration::from_millis(20)), || {
+            thread::sleep(Duration::from_millis(300));
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(err.status, ItemStatus::Timeout);
+        assert!(start.elapsed() < Duration::from_millis(300));
+    }
+
+    #[test]
+    fn items_queued_past_their_budget_never_run() {
+        let budget = BudgetPool::new(1, None);
+        let timeout = Duration::from_millis(50);
+        let running = run_in_budget_pool(&budget, timeout, || {
+            thread::sleep(Duration::from_millis(300));
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(running.status, ItemStatus::Timeout);
+        assert!(running.message.starts_with("exceeded"));
+
+        // The only worker is still busy with the overrunning item
+        let ran = Arc::new(AtomicBool::new(false));
+        let queued_ran = Arc::clone(&ran);
+        let queued = run_in_budget_pool(&budget, timeout, move || {
+            queued_ran.store(true, Ordering::Release);
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(queued.status, ItemStatus::Timeout);
+        assert!(queued.message.starts_with("no item worker free"));
+
+        // Once the worker is free, later items run and the dropped one does not
+        let later = run_in_budget_pool(&budget, Duration::from_secs(5), || Ok(3));
+        assert_eq!(later.unwrap(), 3);
+        assert!(!ran.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn panicking_items_on_the_budget_pool_fail_alone() {
+        let budget = BudgetPool::new(1, None);
+        let timeout = Duration::from_secs(5);
+        let err = run_in_budget_pool::<(), _>(&budget, timeout, || panic!("boom")).unwrap_err();
+        assert_eq!(err.status, ItemStatus::Panic);
+        assert!(err.message.contains("boom"));
+        assert_eq!(run_in_budget_pool(&budget, timeout, || Ok(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn item_statuses_have_stable_names() {
+        assert_eq!(ItemStatus::ResourceError.as_str(), "resource_error");
+        assert_eq!(status_tuple::<()>(&Ok(())), ("ok", None));
+    }
+}
@@ -0,0 +1,119 @@
+//! NumPy-compatible tensor output for batch operators
+//!
+//! `TensorBuffer` owns a contiguous C-order array and exposes it through NumPy's
+//! `__array_interface__`, so `numpy.asarray` wraps the Rust allocation without copying
+//! and keeps the buffer alive as the array's base. The crate does not link against
+//! NumPy; it is imported only when a tensor is handed to Python.
+
+use half::f16;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+/// Element type of a tensor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TensorDtype {
+    Float32,
+    Float16,
+}
+
+impl TensorDtype {
+    /// Parse a dtype name ("float32" or "float16")
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "float32" => Ok(Self::Float32),
+            "float16" => Ok(Self::Float16),
+            other => Err(PyValueError::new_err(format!(
+                "unsupported dtype: {other} (expected 'float32' or 'float16')"
+            ))),
+        }
+    }
+}
+
+/// Owned tensor elements
+pub(crate) enum TensorData {
+    Float32(Vec<f32>),
+    Float16(Vec<f16>),
+}
+
+impl TensorData {
+    /// Zero-filled tensor of `len` elements
+    pub(crate) fn zeros(dtype: TensorDtype, len: usize) -> Self {
+        match dtype {
+            TensorDtype::Float32 => Self::Float32(vec![0.0; len]),
+            TensorDtype::Float16 => Self::Float16(vec![f16::ZERO; len]),
+        }
+    }
+
+    /// NumPy array-interface type string (native byte order)
+    fn typestr(&self) -> &'static str {
+        let little = cfg!(target_endian = "little");
+        match (self, little) {
+            (Self::Float32(_), true) => "<f4",
+            (Self::Float32(_), false) => ">f4",
+            (Self::Float16(_), true) => "<f2",
+            (Self::Float16(_), false) => ">f2",
+        }
+    }
+
+    /// Address of the first element, derived from a mutable borrow so NumPy may write
+    fn address(&mut self) -> usize {
+        match self {
+            Self::Float32(values) => values.as_mut_ptr() as usize,
+            Self::Float16(values) => values.as_mut_ptr() as usize,
+        }
+    }
+}
+
+/// Contiguous C-order tensor handed to NumPy without copying
+///
+/// Rust never reads the elements after construction; from then on they are only
+/// accessed through the array interface, and the allocation lives as long as the
+/// NumPy arrays that reference this object.
+#[pyclass(frozen)]
+pub struct TensorBuffer {
+    data: TensorData,
+    shape: Vec<usize>,
+    address: usize,
+}
+
+impl TensorBuffer {
+    /// Wrap `data`, whose length must equal the product of `shape`
+    pub(crate) fn new(mut data: TensorData, shape: Vec<usize>) -> Self {
+        let address = data.address();
+        Self {
+            data,
+            shape,
+            address,
+        }
+    }
+
+    /// Convert to a `numpy.ndarray` that shares this buffer
+    pub(crate) fn into_ndarray(self, py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+        let buffer = Bound::new(py, self)?;
+        py.import("numpy")?.call_method1("asarray", (buffer,))
+    }
+}
+
+/// Convert flags to a boolean `numpy.ndarray`
+pub(crate) fn bool_ndarray(py: Python<'_>, values: Vec<bool>) -> PyResult<Bound<'_, PyAny>> {
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("dtype", "bool")?;
+    py.import("numpy")?
+        .call_method("array", (values,), Some(&kwargs))
+}
+
+#[pymethods]
+impl TensorBuffer {
+    /// NumPy array interface (version 3) describing the owned buffer
+    #[getter]
+    fn __array_interface__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let interface = PyDict::new(py);
+        interface.set_item("version", 3)?;
+        interface.set_item("shape", PyTuple::new(py, &self.shape)?)?;
+        interface.set_item("typestr", self.data.typestr())?;
+        interface.set_item("data", (self.address, false))?;
+        interface.set_item("strides", py.None())?;
+        Ok(interface)
+    }
+}
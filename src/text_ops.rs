@@ -3,29 +3,94 @@
 //! Provides Rust-accelerated text operations:
 //! - `html_extract_text`: Extract readable text from a single HTML string
 //! - `html_extract_text_batch`: Extract readable text from multiple HTML strings (parallel)
+//! - `html_extract_text_arrow`: Same on a pyarrow string/binary array, returning a RecordBatch
+//! - `submit_html_extract_text_batch`, `submit_text_minhash_batch`: Non-blocking variants
+//! - `text_minhash_batch`: MinHash signatures + LSH band keys for near-duplicate detection (parallel)
+//! - `MinHashLshIndex`: In-memory LSH index for candidate pairs and near-duplicate clusters
 
-use dom_smoothie::Readability;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use dom_smoothie::{Config, Readability, ReadabilityError};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
 use rayon::prelude::*;
 
+use crate::arrow_ffi::{null_input, record_batch_output, ArrowBinaryInput, ArrowColumn};
+use crate::batch_future::{finisher, BatchFuture};
+use crate::executor::detach_in_pool;
+use crate::status::{batch_output, catch_panic, parse_timeout, run_with_timeout, ItemResult};
+
 // ============================================================================
 // HTML Text Extraction
 // ============================================================================
 
+/// Per-document work and wall-clock budget for readability extraction
+#[derive(Clone, Copy, Default)]
+pub(crate) struct HtmlBudget {
+    /// Maximum number of elements to parse (documents with more fail as "too_large")
+    max_elements: Option<usize>,
+    /// Wall-clock budget per document (overruns fail as "timeout")
+    timeout: Option<Duration>,
+}
+
+impl HtmlBudget {
+    pub(crate) fn new(max_elements: Option<usize>, timeout: Option<f64>) -> PyResult<Self> {
+        if max_elements == Some(0) {
+            return Err(PyValueError::new_err("max_elements must be positive"));
+        }
+        Ok(Self {
+            max_elements,
+            timeout: parse_timeout(timeout)?,
+        })
+    }
+}
+
 /// Extract readable text from HTML using dom_smoothie (Rust port of readability.js)
-fn html_extract_text_core(html: &str) -> Option<(String, String)> {
-    let mut readability = Readability::new(html, None, None).ok()?;
-    let article = readability.parse().ok()?;
+///
+/// Returns `Ok(None)` if no article was found or its text is too short.
+fn html_extract_text_checked(
+    html: &str,
+    max_elements: Option<usize>,
+) -> ItemResult<Option<(String, String)>> {
+    let config = max_elements.map(|max_elements| Config {
+        max_elements_to_parse: max_elements,
+        ..Config::default()
+    });
+    let mut readability = Readability::new(html, None, config)?;
+    let article = match readability.parse() {
+        Ok(article) => article,
+        Err(ReadabilityError::GrabFailed) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
 
     let title = article.title;
     let content = article.text_content.to_string();
 
     // Skip if content is empty or too short
     if content.trim().is_empty() || content.len() < 50 {
-        return None;
+        return Ok(None);
     }
 
-    Some((title, content))
+    Ok(Some((title, content)))
+}
+
+/// Extract readable text within `budget`, isolating panics
+pub(crate) fn html_extract_text_budgeted(
+    html: String,
+    budget: HtmlBudget,
+) -> ItemResult<Option<(String, String)>> {
+    run_with_timeout(budget.timeout, move || {
+        html_extract_text_checked(&html, budget.max_elements)
+    })
+}
+
+/// Extract readable text from HTML, treating failures as no content
+pub(crate) fn html_extract_text_core(html: &str) -> Option<(String, String)> {
+    catch_panic(|| html_extract_text_checked(html, None))
+        .ok()
+        .flatten()
 }
 
 /// Extract readable text from a single HTML string
@@ -41,18 +106,556 @@ pub fn html_extract_text(html: String) -> PyResult<Option<(String, String, usize
     }
 }
 
-/// Batch extract readable text from multiple HTML strings (parallel)
-/// Returns Vec of (title, text, text_length) for successful extractions
-#[pyfunction]
-pub fn html_extract_text_batch(htmls: Vec<String>) -> PyResult<Vec<Option<(String, String, usize)>>> {
-    let results: Vec<_> = htmls
+/// (title, text, text_length) of an extracted document
+type ExtractedText = (String, String, usize);
+
+/// Extract text from every HTML string in parallel
+fn html_extract_text_all(
+    htmls: Vec<String>,
+    budget: HtmlBudget,
+) -> Vec<ItemResult<Option<ExtractedText>>> {
+    htmls
         .into_par_iter()
         .map(|html| {
-            html_extract_text_core(&html).map(|(title, text)| {
-                let text_len = text.len();
-                (title, text, text_len)
+            html_extract_text_budgeted(html, budget).map(|extracted| {
+                extracted.map(|(title, text)| {
+                    let text_len = text.len();
+                    (title, text, text_len)
+                })
             })
         })
-        .collect();
-    Ok(results)
+        .collect()
+}
+
+/// Batch extract readable text from multiple HTML strings (parallel, GIL released)
+/// Returns Vec of (title, text, text_length) for successful extractions
+///
+/// With `return_status=True`, returns `(results, statuses)` with one (status, message)
+/// tuple per document; a None result with status "ok" means no readable content.
+///
+/// Each document is isolated: a panic fails only that document ("panic"). `timeout`
+/// (seconds) caps the wall-clock time per document ("timeout") and `max_elements`
+/// caps the parsed DOM size ("too_large"). Documents with a timeout run on a shared
+/// pool of one thread per core; one that overruns keeps its thread until it finishes,
+/// so set `max_elements` too to bound the work itself.
+#[pyfunction]
+#[pyo3(signature = (htmls, return_status=false, timeout=None, max_elements=None))]
+pub fn html_extract_text_batch<'py>(
+    py: Python<'py>,
+    htmls: Vec<String>,
+    return_status: bool,
+    timeout: Option<f64>,
+    max_elements: Option<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let budget = HtmlBudget::new(max_elements, timeout)?;
+    let results = detach_in_pool(py, || html_extract_text_all(htmls, budget));
+    batch_output(py, results, || None, return_status)
+}
+
+/// Non-blocking `html_extract_text_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (htmls, return_status=false, timeout=None, max_elements=None))]
+pub fn submit_html_extract_text_batch(
+    htmls: Vec<String>,
+    return_status: bool,
+    timeout: Option<f64>,
+    max_elements: Option<usize>,
+) -> PyResult<BatchFuture> {
+    let budget = HtmlBudget::new(max_elements, timeout)?;
+    Ok(BatchFuture::spawn(move || {
+        let results = html_extract_text_all(htmls, budget);
+        finisher(move |py| batch_output(py, results, || None, return_status))
+    }))
+}
+
+/// Arrow variant of `html_extract_text_batch`
+///
+/// Takes a pyarrow string or binary array (binary is decoded as lossy UTF-8) without
+/// copying it and returns a `pyarrow.RecordBatch` with `title`, `text` and
+/// `text_length` columns, null where no readable content was extracted. With
+/// `return_status=True`, `status` and `status_message` columns are added.
+/// `timeout` and `max_elements` work as in `html_extract_text_batch`.
+#[pyfunction]
+#[pyo3(signature = (htmls, return_status=false, timeout=None, max_elements=None))]
+pub fn html_extract_text_arrow<'py>(
+    py: Python<'py>,
+    htmls: &Bound<'py, PyAny>,
+    return_status: bool,
+    timeout: Option<f64>,
+    max_elements: Option<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let budget = HtmlBudget::new(max_elements, timeout)?;
+    let htmls = ArrowBinaryInput::import(htmls)?;
+    let values = htmls.values();
+    let results: Vec<ItemResult<Option<(String, String)>>> = detach_in_pool(py, || {
+        values
+            .par_iter()
+            .map(|html| {
+                let html = String::from_utf8_lossy(html.ok_or_else(null_input)?).into_owned();
+                html_extract_text_budgeted(html, budget)
+            })
+            .collect()
+    });
+
+    let extracted = || {
+        results
+            .iter()
+            .map(|r| r.as_ref().ok().and_then(Option::as_ref))
+    };
+    let columns = vec![
+        ArrowColumn::utf8("title", extracted().map(|e| e.map(|(title, _)| title))),
+        ArrowColumn::utf8("text", extracted().map(|e| e.map(|(_, text)| text))),
+        ArrowColumn::primitive(
+            "text_length",
+            extracted().map(|e| e.map(|(_, text)| text.len() as u64)),
+        ),
+    ];
+    record_batch_output(py, columns, &results, return_status)
+}
+
+// ============================================================================
+// MinHash / LSH Signatures
+// ============================================================================
+
+/// Mersenne prime 2^61 - 1 used as the modulus of the permutation family
+const MERSENNE_PRIME: u64 = (1 << 61) - 1;
+
+/// FNV-1a 64-bit hash (stable across platforms and Rust versions)
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// SplitMix64 step, used to derive permutation coefficients from the seed
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Generate `num_perm` (a, b) coefficients for h(x) = (a * x + b) mod p
+fn minhash_permutations(num_perm: usize, seed: u64) -> Vec<(u64, u64)> {
+    let mut state = seed;
+    (0..num_perm)
+        .map(|_| {
+            let a = splitmix64(&mut state) % (MERSENNE_PRIME - 1) + 1;
+            let b = splitmix64(&mut state) % MERSENNE_PRIME;
+            (a, b)
+        })
+        .collect()
+}
+
+/// Hash the word n-gram shingles of a text to 32-bit values
+///
+/// Texts with fewer than `ngram_size` words yield a single shingle of all words.
+fn shingle_hashes(text: &str, ngram_size: usize, lowercase: bool) -> Vec<u32> {
+    let normalized = if lowercase {
+        text.to_lowercase()
+    } else {
+        text.to_string()
+    };
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    words
+        .windows(ngram_size.min(words.len()))
+        .map(|gram| fnv1a_64(gram.join(" ").as_bytes()) as u32)
+        .collect()
+}
+
+/// Compute the MinHash signature of a text (empty if the text has no words)
+fn minhash_signature_core(
+    text: &str,
+    ngram_size: usize,
+    lowercase: bool,
+    permutations: &[(u64, u64)],
+) -> Vec<u32> {
+    let hashes = shingle_hashes(text, ngram_size, lowercase);
+    if hashes.is_empty() {
+        return Vec::new();
+    }
+
+    permutations
+        .iter()
+        .map(|&(a, b)| {
+            hashes
+                .iter()
+                .map(|&h| ((a as u128 * h as u128 + b as u128) % MERSENNE_PRIME as u128) as u32)
+                .min()
+                .unwrap_or(u32::MAX)
+        })
+        .collect()
+}
+
+/// Split a signature into `num_bands` bands and hash each one
+fn lsh_band_hashes(signature: &[u32], num_bands: usize) -> Vec<u64> {
+    if signature.is_empty() {
+        return Vec::new();
+    }
+
+    let rows = signature.len() / num_bands;
+    signature
+        .chunks_exact(rows)
+        .map(|values| {
+            let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            fnv1a_64(&bytes)
+        })
+        .collect()
+}
+
+/// Split a signature into `num_bands` bands and hash each one to a key
+///
+/// Keys are formatted as `"{band}:{hash:016x}"` so that equal rows in different
+/// bands never collide when all keys share one dedup backend.
+fn lsh_band_keys(signature: &[u32], num_bands: usize) -> Vec<String> {
+    lsh_band_hashes(signature, num_bands)
+        .into_iter()
+        .enumerate()
+        .map(|(band, hash)| format!("{band}:{hash:016x}"))
+        .collect()
+}
+
+/// Validate the (num_perm, num_bands) LSH configuration
+fn check_lsh_params(num_perm: usize, num_bands: usize) -> PyResult<()> {
+    if num_perm == 0 || num_bands == 0 || !num_perm.is_multiple_of(num_bands) {
+        return Err(PyValueError::new_err(
+            "num_perm must be a positive multiple of num_bands",
+        ));
+    }
+    Ok(())
+}
+
+/// (signature, band_keys) of one text
+type MinHashResult = (Vec<u32>, Vec<String>);
+
+/// Check `text_minhash_batch` parameters
+fn check_minhash_params(num_perm: usize, num_bands: usize, ngram_size: usize) -> PyResult<()> {
+    check_lsh_params(num_perm, num_bands)?;
+    if ngram_size == 0 {
+        return Err(PyValueError::new_err("ngram_size must be positive"));
+    }
+    Ok(())
+}
+
+/// Compute MinHash signatures and band keys of every text in parallel
+fn text_minhash_all(
+    texts: &[String],
+    num_perm: usize,
+    num_bands: usize,
+    ngram_size: usize,
+    seed: u64,
+    lowercase: bool,
+) -> Vec<MinHashResult> {
+    let permutations = minhash_permutations(num_perm, seed);
+    texts
+        .par_iter()
+        .map(|text| {
+            let signature = minhash_signature_core(text, ngram_size, lowercase, &permutations);
+            let band_keys = lsh_band_keys(&signature, num_bands);
+            (signature, band_keys)
+        })
+        .collect()
+}
+
+/// Batch compute MinHash signatures and LSH band keys (parallel, GIL released)
+///
+/// Shingles are word n-grams of `ngram_size` words. `num_perm` must be divisible by
+/// `num_bands`. Returns Vec of (signature, band_keys); both are empty for texts
+/// without any words.
+#[pyfunction]
+#[pyo3(signature = (texts, num_perm=128, num_bands=16, ngram_size=5, seed=42, lowercase=true))]
+pub fn text_minhash_batch(
+    py: Python<'_>,
+    texts: Vec<String>,
+    num_perm: usize,
+    num_bands: usize,
+    ngram_size: usize,
+    seed: u64,
+    lowercase: bool,
+) -> PyResult<Vec<MinHashResult>> {
+    check_minhash_params(num_perm, num_bands, ngram_size)?;
+    Ok(detach_in_pool(py, || {
+        text_minhash_all(&texts, num_perm, num_bands, ngram_size, seed, lowercase)
+    }))
+}
+
+/// Non-blocking `text_minhash_batch`; returns a `BatchFuture`
+#[pyfunction]
+#[pyo3(signature = (texts, num_perm=128, num_bands=16, ngram_size=5, seed=42, lowercase=true))]
+pub fn submit_text_minhash_batch(
+    texts: Vec<String>,
+    num_perm: usize,
+    num_bands: usize,
+    ngram_size: usize,
+    seed: u64,
+    lowercase: bool,
+) -> PyResult<BatchFuture> {
+    check_minhash_params(num_perm, num_bands, ngram_size)?;
+    Ok(BatchFuture::spawn(move || {
+        let results = text_minhash_all(&texts, num_perm, num_bands, ngram_size, seed, lowercase);
+        finisher(move |py| results.into_bound_py_any(py))
+    }))
+}
+
+// ============================================================================
+// MinHash LSH Index
+// ============================================================================
+
+/// Estimate Jaccard similarity as the fraction of equal signature slots
+fn estimated_jaccard(a: &[u32], b: &[u32]) -> f64 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+/// Find the root of `i` with path halving
+fn union_find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// In-memory MinHash LSH index over `text_minhash_batch` signatures
+///
+/// Records sharing at least one band bucket are candidate duplicates. Candidates can
+/// be verified against an estimated Jaccard `threshold` and merged into connected
+/// components with union-find, so a worker can near-dedup a whole shard locally.
+#[pyclass]
+pub struct MinHashLshIndex {
+    num_perm: usize,
+    num_bands: usize,
+    ids: Vec<String>,
+    positions: HashMap<String, usize>,
+    signatures: Vec<Vec<u32>>,
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+impl MinHashLshIndex {
+    /// Check that a non-empty signature has `num_perm` values
+    fn check_signature(&self, signature: &[u32]) -> PyResult<()> {
+        if !signature.is_empty() && signature.len() != self.num_perm {
+            return Err(PyValueError::new_err(format!(
+                "signature has {} values, expected {}",
+                signature.len(),
+                self.num_perm
+            )));
+        }
+        Ok(())
+    }
+
+    /// Candidate pairs (i < j) of record positions sharing a bucket, optionally verified
+    fn candidate_pairs_core(&self, threshold: Option<f64>) -> Vec<(usize, usize)> {
+        let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+        for band in &self.buckets {
+            for members in band.values().filter(|m| m.len() > 1) {
+                for (k, &i) in members.iter().enumerate() {
+                    pairs.extend(members[k + 1..].iter().map(|&j| (i.min(j), i.max(j))));
+                }
+            }
+        }
+
+        let mut pairs: Vec<(usize, usize)> = match threshold {
+            Some(t) => pairs
+                .into_par_iter()
+                .filter(|&(i, j)| estimated_jaccard(&self.signatures[i], &self.signatures[j]) >= t)
+                .collect(),
+            None => pairs.into_iter().collect(),
+        };
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+#[pymethods]
+impl MinHashLshIndex {
+    #[new]
+    #[pyo3(signature = (num_perm=128, num_bands=16))]
+    fn new(num_perm: usize, num_bands: usize) -> PyResult<Self> {
+        check_lsh_params(num_perm, num_bands)?;
+        Ok(Self {
+            num_perm,
+            num_bands,
+            ids: Vec::new(),
+            positions: HashMap::new(),
+            signatures: Vec::new(),
+            buckets: vec![HashMap::new(); num_bands],
+        })
+    }
+
+    /// Add a record's signature; empty signatures are stored but never match
+    fn insert(&mut self, record_id: String, signature: Vec<u32>) -> PyResult<()> {
+        self.check_signature(&signature)?;
+        if self.positions.contains_key(&record_id) {
+            return Err(PyValueError::new_err(format!(
+                "duplicate record id: {record_id}"
+            )));
+        }
+
+        let pos = self.ids.len();
+        for (band, hash) in lsh_band_hashes(&signature, self.num_bands)
+            .into_iter()
+            .enumerate()
+        {
+            self.buckets[band].entry(hash).or_default().push(pos);
+        }
+        self.positions.insert(record_id.clone(), pos);
+        self.ids.push(record_id);
+        self.signatures.push(signature);
+        Ok(())
+    }
+
+    /// Add many (record_id, signature) pairs
+    fn insert_batch(&mut self, record_ids: Vec<String>, signatures: Vec<Vec<u32>>) -> PyResult<()> {
+        if record_ids.len() != signatures.len() {
+            return Err(PyValueError::new_err(
+                "record_ids and signatures must have the same length",
+            ));
+        }
+        for (record_id, signature) in record_ids.into_iter().zip(signatures) {
+            self.insert(record_id, signature)?;
+        }
+        Ok(())
+    }
+
+    /// Return ids of indexed records sharing a bucket with `signature`
+    ///
+    /// If `threshold` is set, only ids whose estimated Jaccard is at least `threshold`.
+    /// Raises ValueError if `signature` does not have `num_perm` values; an empty
+    /// signature matches nothing.
+    #[pyo3(signature = (signature, threshold=None))]
+    fn query(&self, signature: Vec<u32>, threshold: Option<f64>) -> PyResult<Vec<String>> {
+        self.check_signature(&signature)?;
+        let mut hits: Vec<usize> = lsh_band_hashes(&signature, self.num_bands)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(band, hash)| self.buckets[band].get(&hash))
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|&i| {
+                threshold.is_none_or(|t| estimated_jaccard(&signature, &self.signatures[i]) >= t)
+            })
+            .collect();
+        hits.sort_unstable();
+        Ok(hits.into_iter().map(|i| self.ids[i].clone()).collect())
+    }
+
+    /// Return candidate duplicate (id_a, id_b) pairs, in insertion order
+    ///
+    /// If `threshold` is set, pairs are verified by estimated Jaccard similarity.
+    #[pyo3(signature = (threshold=None))]
+    fn candidate_pairs(&self, py: Python<'_>, threshold: Option<f64>) -> Vec<(String, String)> {
+        detach_in_pool(py, || self.candidate_pairs_core(threshold))
+            .into_iter()
+            .map(|(i, j)| (self.ids[i].clone(), self.ids[j].clone()))
+            .collect()
+    }
+
+    /// Assign a cluster id to every record via union-find over candidate pairs
+    ///
+    /// Returns a dict of record_id -> cluster id. The cluster id is the insertion
+    /// position of the cluster's first record, so that record is the one to keep.
+    #[pyo3(signature = (threshold=None))]
+    fn clusters(&self, py: Python<'_>, threshold: Option<f64>) -> HashMap<String, usize> {
+        let mut parent: Vec<usize> = (0..self.ids.len()).collect();
+        for (i, j) in detach_in_pool(py, || self.candidate_pairs_core(threshold)) {
+            let (ri, rj) = (
+                union_find_root(&mut parent, i),
+                union_find_root(&mut parent, j),
+            );
+            if ri != rj {
+                parent[ri.max(rj)] = ri.min(rj);
+            }
+        }
+
+        (0..self.ids.len())
+            .map(|i| (self.ids[i].clone(), union_find_root(&mut parent, i)))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_with(signatures: &[(&str, Vec<u32>)]) -> MinHashLshIndex {
+        let mut index = MinHashLshIndex::new(8, 4).unwrap();
+        for (id, signature) in signatures {
+            index.insert(id.to_string(), signature.clone()).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn band_hashes_split_signature_into_bands() {
+        let signature: Vec<u32> = (0..8).collect();
+        let hashes = lsh_band_hashes(&signature, 4);
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(hashes, lsh_band_hashes(&signature, 4));
+        assert!(lsh_band_hashes(&[], 4).is_empty());
+
+        let mut changed = signature.clone();
+        changed[7] = 100;
+        let changed_hashes = lsh_band_hashes(&changed, 4);
+        assert_eq!(hashes[..3], changed_hashes[..3]);
+        assert_ne!(hashes[3], changed_hashes[3]);
+    }
+
+    #[test]
+    fn band_keys_are_prefixed_by_band() {
+        let keys = lsh_band_keys(&[7; 8], 4);
+        assert_eq!(keys.len(), 4);
+        assert!(keys[0].starts_with("0:"));
+        assert!(keys[3].starts_with("3:"));
+        assert_ne!(keys[0], keys[1]);
+    }
+
+    #[test]
+    fn index_finds_records_sharing_a_band() {
+        let index = index_with(&[
+            ("a", vec![1, 2, 3, 4, 5, 6, 7, 8]),
+            ("b", vec![1, 2, 30, 40, 50, 60, 70, 80]),
+            ("c", vec![9; 8]),
+        ]);
+        assert_eq!(
+            index.query(vec![1, 2, 0, 0, 0, 0, 0, 0], None).unwrap(),
+            ["a", "b"]
+        );
+        assert_eq!(
+            index
+                .query(vec![1, 2, 3, 4, 5, 6, 0, 0], Some(0.7))
+                .unwrap(),
+            ["a"]
+        );
+        assert!(index.query(Vec::new(), None).unwrap().is_empty());
+        assert_eq!(index.candidate_pairs_core(None), [(0, 1)]);
+        assert!(index.candidate_pairs_core(Some(0.5)).is_empty());
+    }
+
+    #[test]
+    fn index_rejects_mismatched_signatures() {
+        let mut index = index_with(&[("a", vec![1; 8])]);
+        assert!(index.query(vec![1; 2], None).is_err());
+        assert!(index.query(vec![1; 9], None).is_err());
+        assert!(index.insert("b".into(), vec![1; 4]).is_err());
+        assert!(index.insert("a".into(), vec![1; 8]).is_err());
+        assert!(MinHashLshIndex::new(10, 4).is_err());
+    }
 }
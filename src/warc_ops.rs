@@ -0,0 +1,475 @@
+//! WARC processing operators: streaming HTML record extraction
+//!
+//! Provides Rust-accelerated WARC operations:
+//! - `WarcHtmlReader`: Stream (url, warc_date, html) batches from a local WARC file
+//! - `WarcTextExtractor`: Stream extracted-text record dicts from a local WARC file (parallel)
+
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::executor::detach_in_pool;
+use crate::text_ops::{html_extract_text_budgeted, HtmlBudget};
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+use warc::{BufferedBody, Record, RecordType, WarcHeader, WarcReader};
+
+type RecordResult = Result<Record<BufferedBody>, warc::Error>;
+type RecordIter = Box<dyn Iterator<Item = RecordResult> + Send + Sync>;
+
+/// (url, warc_date, html_bytes) as handed to Python
+type HtmlRecordTuple = (String, String, Vec<u8>);
+
+// ============================================================================
+// Record Decoding
+// ============================================================================
+
+/// A `response` record whose HTTP payload is HTML
+pub(crate) struct HtmlRecord {
+    pub url: String,
+    pub warc_date: String,
+    pub html: Vec<u8>,
+}
+
+/// Split an HTTP response block into its header section and payload
+fn split_http_response(block: &[u8]) -> Option<(&[u8], &[u8])> {
+    let pos = block.windows(4).position(|w| w == b"\r\n\r\n")?;
+    Some((&block[..pos], &block[pos + 4..]))
+}
+
+/// Case-insensitive lookup of a header value in a raw HTTP header section
+fn http_header<'a>(headers: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    headers.split(|&b| b == b'\n').skip(1).find_map(|line| {
+        let colon = line.iter().position(|&b| b == b':')?;
+        let (key, value) = line.split_at(colon);
+        if key.trim_ascii().eq_ignore_ascii_case(name.as_bytes()) {
+            Some(value[1..].trim_ascii())
+        } else {
+            None
+        }
+    })
+}
+
+/// Decode a WARC record into an `HtmlRecord` if it is an HTML response
+fn decode_html_record(record: Record<BufferedBody>) -> Option<HtmlRecord> {
+    if *record.warc_type() != RecordType::Response {
+        return None;
+    }
+
+    let (headers, payload) = split_http_response(record.body())?;
+    let content_type = http_header(headers, "content-type")?;
+    if !content_type
+        .to_ascii_lowercase()
+        .windows(9)
+        .any(|w| w == b"text/html")
+    {
+        return None;
+    }
+
+    let url = record
+        .header(WarcHeader::TargetURI)
+        .unwrap_or_default()
+        .into_owned();
+    let warc_date = record
+        .header(WarcHeader::Date)
+        .unwrap_or_default()
+        .into_owned();
+    let html = payload.to_vec();
+
+    Some(HtmlRecord {
+        url,
+        warc_date,
+        html,
+    })
+}
+
+/// Decode HTML bytes as UTF-8, dropping invalid sequences like Python's `errors="ignore"`
+fn decode_utf8_ignore(bytes: &[u8]) -> String {
+    bytes.utf8_chunks().map(|chunk| chunk.valid()).collect()
+}
+
+/// Streaming iterator over the HTML response records of a WARC file
+pub(crate) struct HtmlRecordStream {
+    records: RecordIter,
+}
+
+impl HtmlRecordStream {
+    /// Open a local WARC file, decompressing it if the path ends in `.gz`
+    pub fn open(path: &str) -> PyResult<Self> {
+        let records: RecordIter = if path.ends_with(".gz") {
+            let reader = WarcReader::from_path_gzip(path)
+                .map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+            Box::new(reader.iter_records())
+        } else {
+            let file = File::open(path).map_err(|e| PyIOError::new_err(format!("{path}: {e}")))?;
+            Box::new(WarcReader::new(BufReader::new(file)).iter_records())
+        };
+        Ok(Self { records })
+    }
+
+    /// Read up to `batch_size` HTML records; an empty batch means the file is exhausted
+    pub fn next_batch(&mut self, batch_size: usize) -> PyResult<Vec<HtmlRecord>> {
+        let mut batch = Vec::with_capacity(batch_size);
+        while batch.len() < batch_size {
+            match self.records.next() {
+                None => break,
+                Some(Ok(record)) => batch.extend(decode_html_record(record)),
+                // The record framing was intact, only its WARC headers were invalid
+                Some(Err(warc::Error::MissingHeader(_) | warc::Error::MalformedHeader(..))) => {}
+                Some(Err(warc::Error::ReadData(e))) => {
+                    return Err(PyIOError::new_err(e.to_string()))
+                }
+                Some(Err(e)) => {
+                    return Err(PyValueError::new_err(format!("corrupt WARC record: {e}")))
+                }
+            }
+        }
+        Ok(batch)
+    }
+}
+
+// ============================================================================
+// Python Iterator
+// ============================================================================
+
+/// Stream HTML `response` records from a local WARC (or `.warc.gz`) file
+///
+/// Iterating yields lists of up to `batch_size` (url, warc_date, html_bytes) tuples.
+/// Parsing and decompression run without holding the GIL.
+#[pyclass]
+pub struct WarcHtmlReader {
+    stream: HtmlRecordStream,
+    batch_size: usize,
+}
+
+#[pymethods]
+impl WarcHtmlReader {
+    #[new]
+    #[pyo3(signature = (path, batch_size=256))]
+    fn new(path: &str, batch_size: usize) -> PyResult<Self> {
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size must be positive"));
+        }
+        Ok(Self {
+            stream: HtmlRecordStream::open(path)?,
+            batch_size,
+        })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(
+        mut slf: PyRefMut<'_, Self>,
+        py: Python<'_>,
+    ) -> PyResult<Option<Vec<HtmlRecordTuple>>> {
+        let batch_size = slf.batch_size;
+        let stream = &mut slf.stream;
+        let batch = detach_in_pool(py, || stream.next_batch(batch_size))?;
+
+        if batch.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            batch
+                .into_iter()
+                .map(|r| (r.url, r.warc_date, r.html))
+                .collect(),
+        ))
+    }
+}
+
+/// A WARC HTML record after readability extraction
+struct TextRecord {
+    url: String,
+    warc_date: String,
+    title: String,
+    text: String,
+}
+
+/// Stream extracted-text records from a local WARC (or `.warc.gz`) file
+///
+/// Iterating yields lists of dicts with keys url, warc_date, title, text and text_length.
+/// Each step reads up to `batch_size` HTML responses, decodes them as UTF-8 (dropping
+/// invalid bytes), drops those shorter than `min_html_length` characters, skips the first `skip_records` remaining ones (for
+/// checkpoint resume) and runs readability extraction on the rest in parallel.
+/// Records whose extraction fails, panics, exceeds `timeout` seconds or parses more
+/// than `max_elements` elements are dropped. The whole step runs without the GIL.
+#[pyclass]
+pub struct WarcTextExtractor {
+    stream: HtmlRecordStream,
+    batch_size: usize,
+    min_html_length: usize,
+    skip_records: usize,
+    budget: HtmlBudget,
+    records_parsed: usize,
+}
+
+impl WarcTextExtractor {
+    /// Read and extract the next non-empty batch; `None` once the file is exhausted
+    fn next_batch(&mut self) -> PyResult<Option<Vec<TextRecord>>> {
+        loop {
+            let batch = self.stream.next_batch(self.batch_size)?;
+            if batch.is_empty() {
+                return Ok(None);
+            }
+
+            let decoded: Vec<(HtmlRecord, String)> = batch
+                .into_par_iter()
+                .filter_map(|record| {
+                    let html = decode_utf8_ignore(&record.html);
+                    (html.chars().count() >= self.min_html_length).then_some((record, html))
+                })
+                .collect();
+
+            let start = decoded.len().min(self.skip_records);
+            self.skip_records -= start;
+            self.records_parsed += decoded.len();
+
+            let extracted: Vec<TextRecord> = decoded
+                .into_par_iter()
+                .skip(start)
+                .filter_map(|(record, html)| {
+                    html_extract_text_budgeted(html, self.budget)
+                        .ok()
+                        .flatten()
+                        .map(|(title, text)| TextRecord {
+                            url: record.url,
+                            warc_date: record.warc_date,
+                            title,
+                            text,
+                        })
+                })
+                .collect();
+
+            if !extracted.is_empty() {
+                return Ok(Some(extracted));
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl WarcTextExtractor {
+    #[new]
+    #[pyo3(signature = (path, batch_size=256, min_html_length=100, skip_records=0, timeout=None, max_elements=None))]
+    fn new(
+        path: &str,
+        batch_size: usize,
+        min_html_length: usize,
+        skip_records: usize,
+        timeout: Option<f64>,
+        max_elements: Option<usize>,
+    ) -> PyResult<Self> {
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size must be positive"));
+        }
+        Ok(Self {
+            stream: HtmlRecordStream::open(path)?,
+            batch_size,
+            min_html_length,
+            skip_records,
+            budget: HtmlBudget::new(max_elements, timeout)?,
+            records_parsed: 0,
+        })
+    }
+
+    /// Number of HTML records read so far that passed `min_html_length` (including skipped ones)
+    #[getter]
+    fn records_parsed(&self) -> usize {
+        self.records_parsed
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        py: Python<'py>,
+    ) -> PyResult<Option<Vec<Bound<'py, PyDict>>>> {
+        let extractor = &mut *slf;
+        let Some(batch) = detach_in_pool(py, || extractor.next_batch())? else {
+            return Ok(None);
+        };
+
+        batch
+            .into_iter()
+            .map(|record| {
+                let text_length = record.text.len();
+                let dict = PyDict::new(py);
+                dict.set_item("url", record.url)?;
+                dict.set_item("warc_date", record.warc_date)?;
+                dict.set_item("title", record.title)?;
+                dict.set_item("text", record.text)?;
+                dict.set_item("text_length", text_length)?;
+                Ok(dict)
+            })
+            .collect::<PyResult<_>>()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// One WARC record with the given type, target URI and block
+    fn warc_record(warc_type: &str, uri: &str, block: &[u8]) -> Vec<u8> {
+        let mut record = format!(
+            "WARC/1.0\r\n\
+             WARC-Type: {warc_type}\r\n\
+             WARC-Target-URI: {uri}\r\n\
+             WARC-Date: 2024-05-01T12:00:00Z\r\n\
+             WARC-Record-ID: <urn:uuid:{uri}>\r\n\
+             Content-Type: application/http; msgtype={warc_type}\r\n\
+             Content-Length: {}\r\n\r\n",
+            block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        record
+    }
+
+    /// HTTP response block with the given content-type header line and payload
+    fn http_response(content_type: &str, payload: &str) -> Vec<u8> {
+        format!("HTTP/1.1 200 OK\r\nServer: test\r\n{content_type}\r\n\r\n{payload}").into_bytes()
+    }
+
+    /// An article page readability extracts `text` from
+    fn article(title: &str, text: &str) -> String {
+        format!(
+            "<html><head><title>{title}</title></head><body>\
+             <article><h1>{title}</h1><p>{text}</p><p>{text}</p></article></body></html>"
+        )
+    }
+
+    /// A WARC file in the temp directory, removed on drop
+    struct WarcFile(PathBuf);
+
+    impl WarcFile {
+        fn new(name: &str, records: &[Vec<u8>]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("warc_ops_{}_{name}.warc", std::process::id()));
+            std::fs::write(&path, records.concat()).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for WarcFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn only_html_responses_are_read() {
+        let html = "<html><body>hello</body></html>";
+        let file = WarcFile::new(
+            "filter",
+            &[
+                warc_record("request", "http://a.test/", b"GET / HTTP/1.1\r\n\r\n"),
+                warc_record(
+                    "response",
+                    "http://b.test/",
+                    &http_response("Content-Type: text/html; charset=utf-8", html),
+                ),
+                warc_record(
+                    "response",
+                    "http://c.test/",
+                    &http_response("Content-Type: image/png", "PNG"),
+                ),
+                warc_record(
+                    "response",
+                    "http://d.test/",
+                    &http_response("content-type:TEXT/HTML", html),
+                ),
+                warc_record(
+                    "response",
+                    "http://e.test/",
+                    b"HTTP/1.1 200 OK\r\n\r\nno type",
+                ),
+            ],
+        );
+
+        let mut stream = HtmlRecordStream::open(file.path()).unwrap();
+        let first = stream.next_batch(1).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].url, "http://b.test/");
+        assert_eq!(first[0].warc_date, "2024-05-01T12:00:00Z");
+        assert_eq!(first[0].html, html.as_bytes());
+
+        let rest = stream.next_batch(10).unwrap();
+        let urls: Vec<&str> = rest.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(urls, ["http://d.test/"]);
+        assert!(stream.next_batch(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn header_lookup_tolerates_missing_spaces_and_case() {
+        let headers = b"HTTP/1.1 200 OK\r\nX-Other: 1\r\nCONTENT-TYPE:text/html\r\n";
+        assert_eq!(
+            http_header(headers, "content-type"),
+            Some(&b"text/html"[..])
+        );
+        assert_eq!(http_header(headers, "content-length"), None);
+        // The status line is not a header
+        assert_eq!(http_header(b"HTTP/1.1: 200", "HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn invalid_utf8_is_dropped() {
+        assert_eq!(
+            decode_utf8_ignore(b"caf\xc3\xa9 \xff\xfeok\xe2\x82"),
+            "caf\u{e9} ok"
+        );
+        // Replacement characters in the source are kept
+        assert_eq!(decode_utf8_ignore("a\u{fffd}b".as_bytes()), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn extractor_skips_records_across_batches() {
+        let text = "This paragraph is long enough for readability to keep it as the main \
+                    content of the page, with several sentences of ordinary prose.";
+        let mut records: Vec<Vec<u8>> = (0..5)
+            .map(|i| {
+                warc_record(
+                    "response",
+                    &format!("http://page{i}.test/"),
+                    &http_response(
+                        "Content-Type: text/html",
+                        &article(&format!("Page {i}"), text),
+                    ),
+                )
+            })
+            .collect();
+        records.insert(
+            2,
+            warc_record(
+                "response",
+                "http://short.test/",
+                &http_response("Content-Type: text/html", "<p>short</p>"),
+            ),
+        );
+        let file = WarcFile::new("skip", &records);
+
+        let mut extractor = WarcTextExtractor::new(file.path(), 2, 100, 3, None, None).unwrap();
+        let mut urls = Vec::new();
+        while let Some(batch) = extractor.next_batch().unwrap() {
+            assert!(batch.iter().all(|r| r.text.contains("main content")));
+            urls.extend(batch.into_iter().map(|r| r.url));
+        }
+        assert_eq!(urls, ["http://page3.test/", "http://page4.test/"]);
+        assert_eq!(extractor.records_parsed, 5);
+        assert_eq!(extractor.skip_records, 0);
+    }
+}
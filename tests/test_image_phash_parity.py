@@ -0,0 +1,72 @@
+"""
+Parity tests for the Rust pHash and Python imagehash
+
+Hashes the same images with `image_compute_phash_batch(algorithm="phash")` and with
+`imagehash.phash` and checks that the hex strings are identical, so dedup keys do not
+depend on which backend computed them.
+"""
+
+from io import BytesIO
+
+import numpy as np
+import pytest
+from PIL import Image
+
+imagehash = pytest.importorskip("imagehash")
+
+try:
+    from mega_data_factory import rust_operators  # type: ignore
+except ImportError:
+    rust_operators = None
+
+requires_rust = pytest.mark.skipif(rust_operators is None, reason="Rust backend not installed")
+
+
+def _noise(height: int, width: int) -> np.ndarray:
+    """Deterministic per-channel hash noise."""
+    y, x = np.mgrid[0:height, 0:width].astype(np.int64)
+    return np.stack([((x * 73856093) ^ (y * 19349663) ^ (c * 83492791)) % 256 for c in range(3)], axis=-1)
+
+
+def _test_images() -> dict[str, Image.Image]:
+    y, x = np.mgrid[0:120, 0:90]
+    texture = np.stack([(x * 37) % 200 + 40, (y * 53) % 200 + 40, np.full_like(x, 128)], axis=-1)
+    ramp = np.arange(200) * 255 // 199
+    gradient = np.broadcast_to(np.stack([ramp, np.full(200, 64), 255 - ramp], axis=-1), (50, 200, 3))
+    arrays = {
+        "noise": _noise(64, 96),
+        "texture": texture,
+        "gradient": gradient,
+        "solid": np.broadcast_to(np.array([200, 10, 10]), (48, 48, 3)),
+        "white": np.full((33, 17, 3), 255),
+        "tiny": _noise(3, 5),
+    }
+    images = {name: Image.fromarray(np.ascontiguousarray(array, dtype=np.uint8)) for name, array in arrays.items()}
+    images["gray"] = images["noise"].convert("L")
+    rgba = np.concatenate([_noise(40, 40), _noise(40, 40)[..., :1]], axis=-1)
+    images["rgba"] = Image.fromarray(rgba.astype(np.uint8))
+    return images
+
+
+def _png(image: Image.Image) -> bytes:
+    buf = BytesIO()
+    image.save(buf, format="PNG")
+    return buf.getvalue()
+
+
+@requires_rust
+@pytest.mark.parametrize("hash_size", [8, 16])
+def test_phash_matches_imagehash(hash_size):
+    images = _test_images()
+    hashes = rust_operators.image_compute_phash_batch(
+        [_png(image) for image in images.values()], hash_size, algorithm="phash", encoding="hex"
+    )
+    for (name, image), rust_hash in zip(images.items(), hashes, strict=True):
+        assert rust_hash == str(imagehash.phash(image, hash_size=hash_size)), name
+
+
+@requires_rust
+def test_solid_color_hash_has_only_the_dc_bit():
+    solid = _png(Image.new("RGB", (64, 64), (30, 140, 220)))
+    hashes = rust_operators.image_compute_phash_batch([solid], 8, algorithm="phash", encoding="hex")
+    assert hashes == ["8000000000000000"]
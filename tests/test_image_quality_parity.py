@@ -0,0 +1,180 @@
+"""
+Parity tests for the Rust and Python backends of the image quality operators
+
+Refines the same images with both backends of ImageTechnicalQualityRefiner and checks
+that the sharpness, noise, exposure, placeholder and border fields agree, and that
+ImageQualityFilter makes the same decisions on either backend's records.
+"""
+
+from io import BytesIO
+
+import numpy as np
+import pytest
+from PIL import Image
+
+import mega_data_factory.operators.refiners.image_technical_quality as tq_module
+from mega_data_factory.operators.filters.image_quality_filter import ImageQualityFilter
+from mega_data_factory.operators.refiners.image_metadata import FIELD_HEIGHT, FIELD_WIDTH
+from mega_data_factory.operators.refiners.image_technical_quality import (
+    EXPOSURE_FIELDS,
+    FIELD_BORDER_FRACTION,
+    FIELD_COMPRESSION_ARTIFACTS,
+    FIELD_INFORMATION_ENTROPY,
+    FIELD_NOISE_SIGMA,
+    FIELD_PLACEHOLDER_CATEGORY,
+    RUST_BACKEND_AVAILABLE,
+    SHARPNESS_FIELDS,
+    ImageTechnicalQualityRefiner,
+)
+
+requires_rust = pytest.mark.skipif(not RUST_BACKEND_AVAILABLE, reason="Rust backend not installed")
+
+FLOAT_FIELDS = [*SHARPNESS_FIELDS, FIELD_NOISE_SIGMA, *EXPOSURE_FIELDS, FIELD_BORDER_FRACTION]
+
+
+def _noise(height: int, width: int) -> np.ndarray:
+    """Deterministic per-channel hash noise."""
+    y, x = np.mgrid[0:height, 0:width].astype(np.int64)
+    return np.stack([((x * 73856093) ^ (y * 19349663) ^ (c * 83492791)) % 256 for c in range(3)], axis=-1)
+
+
+def _hidden_color(hidden: tuple[int, int, int, int]) -> np.ndarray:
+    """Opaque noise with a center square of `hidden` RGBA."""
+    image = np.concatenate([_noise(48, 48), np.full((48, 48, 1), 255)], axis=-1)
+    image[16:32, 16:32] = hidden
+    return image
+
+
+def _test_images() -> dict[str, np.ndarray]:
+    y, x = np.mgrid[0:64, 0:96]
+    texture = np.stack([(x * 37) % 200 + 40, (y * 53) % 200 + 40, np.full_like(x, 128)], axis=-1)
+    ramp = np.arange(64) * 4
+    gradient = np.broadcast_to(np.stack([ramp, np.full(64, 64), 255 - ramp], axis=-1), (64, 64, 3))
+    specks_y, specks_x = np.mgrid[0:40, 0:40]
+    near_solid = np.where(((specks_x + specks_y) % 50 == 0)[..., None], 0, 230).repeat(3, axis=-1)
+    letterbox = _noise(60, 80)
+    letterbox[:12] = 0
+    letterbox[48:] = 0
+    return {
+        "noise": _noise(64, 96),
+        "texture": texture,
+        "gradient": gradient,
+        "solid": np.broadcast_to(np.array([200, 10, 10]), (48, 48, 3)),
+        "near_solid": near_solid,
+        "letterbox": letterbox,
+        "transparent": np.zeros((40, 40, 4)),
+        "tiny": _noise(4, 4),
+        "hidden_color": _hidden_color((200, 30, 90, 0)),
+    }
+
+
+def _records(images: dict[str, np.ndarray] | None = None) -> list[dict]:
+    records = []
+    for array in (images or _test_images()).values():
+        buf = BytesIO()
+        Image.fromarray(np.ascontiguousarray(array, dtype=np.uint8)).save(buf, format="PNG")
+        height, width = array.shape[:2]
+        records.append({"image": {"bytes": buf.getvalue()}, FIELD_WIDTH: width, FIELD_HEIGHT: height})
+    return records
+
+
+def _refine(monkeypatch: pytest.MonkeyPatch, use_rust: bool, images: dict[str, np.ndarray] | None = None) -> list[dict]:
+    refiner = ImageTechnicalQualityRefiner(
+        compute_sharpness=True,
+        compute_noise=True,
+        compute_exposure=True,
+        compute_placeholder=True,
+        compute_border=True,
+    )
+    records = _records(images)
+    with monkeypatch.context() as patch:
+        patch.setattr(tq_module, "RUST_BACKEND_AVAILABLE", use_rust)
+        refiner.refine_batch(records)
+    return records
+
+
+@requires_rust
+class TestRefinerParity:
+    """The new refiner fields agree between backends."""
+
+    def test_float_fields_match(self, monkeypatch):
+        rust = _refine(monkeypatch, use_rust=True)
+        python = _refine(monkeypatch, use_rust=False)
+        for name, rust_record, python_record in zip(_test_images(), rust, python, strict=True):
+            for field in FLOAT_FIELDS:
+                assert rust_record[field] == pytest.approx(python_record[field], rel=1e-6, abs=1e-6), (name, field)
+
+    def test_placeholder_categories_match(self, monkeypatch):
+        rust = _refine(monkeypatch, use_rust=True)
+        python = _refine(monkeypatch, use_rust=False)
+        categories = [record[FIELD_PLACEHOLDER_CATEGORY] for record in rust]
+        assert categories == [record[FIELD_PLACEHOLDER_CATEGORY] for record in python]
+        assert categories == [
+            "content",
+            "content",
+            "gradient",
+            "solid",
+            "near_solid",
+            "content",
+            "transparent",
+            "tiny",
+            "content",
+        ]
+
+
+@requires_rust
+class TestFilterParity:
+    """ImageQualityFilter thresholds give the same decisions on either backend's fields."""
+
+    def test_thresholds_match(self, monkeypatch):
+        quality_filter = ImageQualityFilter(
+            min_width=8,
+            min_height=8,
+            max_compression_artifacts=1.0,
+            min_information_entropy=0.0,
+            min_laplacian_variance=100.0,
+            max_noise=60.0,
+            min_rms_contrast=0.05,
+            max_highlight_clip_fraction=0.5,
+            max_shadow_clip_fraction=0.3,
+            reject_placeholder_categories=["solid", "near_solid", "transparent"],
+            max_border_fraction=0.3,
+        )
+        rust = quality_filter.should_keep_batch(_refine(monkeypatch, use_rust=True))
+        python = quality_filter.should_keep_batch(_refine(monkeypatch, use_rust=False))
+        assert rust == python
+        assert rust == [True, True, False, False, False, False, False, False, True]
+
+
+@pytest.mark.parametrize("use_rust", [False, pytest.param(True, marks=requires_rust)])
+def test_color_under_transparent_pixels_is_ignored(monkeypatch, use_rust):
+    """Sharpness, noise and exposure see the image composited onto the background."""
+    images = {
+        "hidden": _hidden_color((200, 30, 90, 0)),
+        "composited": _hidden_color((255, 255, 255, 255)),
+    }
+    hidden, composited = _refine(monkeypatch, use_rust, images)
+    for field in [*SHARPNESS_FIELDS, FIELD_NOISE_SIGMA, *EXPOSURE_FIELDS]:
+        assert hidden[field] == pytest.approx(composited[field], rel=1e-9, abs=1e-9), field
+
+
+class TestRequireQualityFields:
+    """Records without quality fields are rejected unless require_quality_fields=False."""
+
+    def test_missing_quality_fields(self):
+        record = {FIELD_WIDTH: 512, FIELD_HEIGHT: 512}
+        assert ImageQualityFilter().should_keep_batch([record]) == [False]
+        assert ImageQualityFilter(require_quality_fields=False).should_keep_batch([record]) == [True]
+
+    def test_size_is_checked_first(self):
+        record = {FIELD_WIDTH: 100, FIELD_HEIGHT: 512}
+        assert ImageQualityFilter(require_quality_fields=False).should_keep_batch([record]) == [False]
+
+    def test_failed_decode_is_rejected(self):
+        record = {
+            FIELD_WIDTH: 512,
+            FIELD_HEIGHT: 512,
+            FIELD_COMPRESSION_ARTIFACTS: None,
+            FIELD_INFORMATION_ENTROPY: None,
+        }
+        assert ImageQualityFilter(require_quality_fields=False).should_keep_batch([record]) == [False]